@@ -0,0 +1,126 @@
+use crate::encryption::KdfParams;
+use crate::error::FerropassError;
+
+use aes::Aes256;
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use ctr::cipher::{KeyIvInit, StreamCipher};
+
+// Ciphers are named in the vault header; files from before the header had
+// a cipher field are AES-256-GCM.
+pub const DEFAULT_CIPHER: &str = "aes-256-gcm";
+
+// Turns a passkey and salt into a key. `secret` is the pepper, if the vault
+// uses one.
+pub trait Kdf {
+    fn derive(&self, passkey: &[u8], salt: &[u8], secret: Option<&[u8]>) -> Result<[u8; 32], FerropassError>;
+}
+
+pub trait AeadCipher {
+    fn nonce_len(&self) -> usize;
+
+    fn tag_len(&self) -> usize;
+
+    // The returned ciphertext ends with the tag.
+    fn encrypt(&self, key: &[u8; 32], nonce: &[u8], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, FerropassError>;
+
+    fn decrypt(&self, key: &[u8; 32], nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>, FerropassError>;
+
+    // Decrypts `data` in place without checking any tag, for salvaging
+    // damaged files. `data` must not include the tag.
+    fn decrypt_unauthenticated(&self, _key: &[u8; 32], _nonce: &[u8], _data: &mut [u8]) -> Result<(), FerropassError> {
+        Err(FerropassError::Crypto("This cipher can't decrypt damaged data".to_string()))
+    }
+}
+
+// Picks the implementation for a header's key derivation parameters.
+pub fn kdf(params: &KdfParams) -> Result<Box<dyn Kdf>, FerropassError> {
+    match params.algorithm.as_str() {
+        "argon2id" => Ok(Box::new(Argon2id::new(params.m_cost, params.t_cost, params.p_cost)?)),
+        other => Err(FerropassError::Format(format!("Unsupported key derivation algorithm '{}'", other))),
+    }
+}
+
+pub fn cipher(name: &str) -> Result<Box<dyn AeadCipher>, FerropassError> {
+    match name {
+        DEFAULT_CIPHER => Ok(Box::new(Aes256GcmCipher)),
+        other => Err(FerropassError::Format(format!("Unsupported cipher '{}'", other))),
+    }
+}
+
+pub struct Argon2id {
+    params: Params,
+}
+
+impl Argon2id {
+    pub fn new(m_cost: u32, t_cost: u32, p_cost: u32) -> Result<Self, FerropassError> {
+        let params = Params::new(m_cost, t_cost, p_cost, Some(32))
+            .map_err(|e| FerropassError::Crypto(format!("Invalid key derivation parameters: {}", e)))?;
+
+        Ok(Argon2id { params })
+    }
+}
+
+impl Kdf for Argon2id {
+    fn derive(&self, passkey: &[u8], salt: &[u8], secret: Option<&[u8]>) -> Result<[u8; 32], FerropassError> {
+        let argon2 = match secret {
+            Some(secret) => Argon2::new_with_secret(secret, Algorithm::Argon2id, Version::V0x13, self.params.clone())
+                .map_err(|e| FerropassError::Crypto(format!("Invalid pepper: {}", e)))?,
+            None => Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone()),
+        };
+
+        let mut key = [0u8; 32];
+        argon2.hash_password_into(passkey, salt, &mut key)
+            .map_err(|e| FerropassError::Crypto(format!("Error deriving key: {}", e)))?;
+
+        Ok(key)
+    }
+}
+
+pub struct Aes256GcmCipher;
+
+impl AeadCipher for Aes256GcmCipher {
+    fn nonce_len(&self) -> usize {
+        12
+    }
+
+    fn tag_len(&self) -> usize {
+        16
+    }
+
+    fn encrypt(&self, key: &[u8; 32], nonce: &[u8], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, FerropassError> {
+        let cipher = Aes256Gcm::new_from_slice(key)
+            .map_err(|e| FerropassError::Crypto(format!("Error creating cipher: {}", e)))?;
+
+        cipher.encrypt(Nonce::from_slice(nonce), Payload { msg: plaintext, aad })
+            .map_err(|e| FerropassError::Crypto(format!("Error encrypting data: {}", e)))
+    }
+
+    fn decrypt(&self, key: &[u8; 32], nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>, FerropassError> {
+        let cipher = Aes256Gcm::new_from_slice(key)
+            .map_err(|e| FerropassError::Crypto(format!("Error creating cipher: {}", e)))?;
+
+        cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(|e| FerropassError::Crypto(format!("Error decrypting data: {}", e)))
+    }
+
+    // Runs the GCM keystream: CTR mode with a 32-bit counter that starts at
+    // 2 after the nonce.
+    fn decrypt_unauthenticated(&self, key: &[u8; 32], nonce: &[u8], data: &mut [u8]) -> Result<(), FerropassError> {
+        if nonce.len() != self.nonce_len() {
+            return Err(FerropassError::Crypto(format!("Nonce is {} bytes, expected {}", nonce.len(), self.nonce_len())));
+        }
+
+        let mut iv = [0u8; 16];
+        iv[..nonce.len()].copy_from_slice(nonce);
+        iv[15] = 2;
+
+        let mut cipher = ctr::Ctr32BE::<Aes256>::new(key.into(), &iv.into());
+        cipher.apply_keystream(data);
+
+        Ok(())
+    }
+}
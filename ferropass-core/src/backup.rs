@@ -0,0 +1,56 @@
+use crate::error::FerropassError;
+
+use chrono::Local;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// Backups sit next to the vault as `<file name>.<timestamp>.bak`, e.g.
+// `personal.fp.20240101-120000.bak`.
+pub fn snapshot(filepath: &Path) -> Result<PathBuf, FerropassError> {
+    let file_name = filepath.file_name()
+        .ok_or_else(|| FerropassError::Validation("Database path has no file name".to_string()))?
+        .to_string_lossy();
+    let timestamp = Local::now().format("%Y%m%d-%H%M%S");
+
+    let mut backup_path = filepath.with_file_name(format!("{}.{}.bak", file_name, timestamp));
+    let mut attempt = 1;
+    while backup_path.exists() {
+        backup_path = filepath.with_file_name(format!("{}.{}-{}.bak", file_name, timestamp, attempt));
+        attempt += 1;
+    }
+
+    fs::copy(filepath, &backup_path)
+        .map_err(|e| FerropassError::Io(format!("Failed to back up {}: {}", filepath.display(), e)))?;
+
+    Ok(backup_path)
+}
+
+pub fn list(filepath: &Path) -> Vec<(PathBuf, u64)> {
+    let Some(file_name) = filepath.file_name().and_then(|name| name.to_str()) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.", file_name);
+    let dir = match filepath.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with(&prefix) && name.ends_with(".bak")
+        })
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            let modified = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+            Some((entry.path(), modified))
+        })
+        .collect()
+}
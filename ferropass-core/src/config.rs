@@ -0,0 +1,343 @@
+use crate::error::FerropassError;
+use crate::notice;
+use crate::password::Preset;
+#[cfg(feature = "remote")]
+use crate::s3::S3Profile;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// User preferences that apply to every vault, read once from
+// `$XDG_CONFIG_HOME/ferropass/config.json` (or `~/.config/ferropass/config.json`).
+// Per-vault settings live inside the encrypted database instead.
+#[derive(Deserialize, Default)]
+pub struct Config {
+    #[serde(default)]
+    no_clear: bool,
+    #[serde(default)]
+    reduced_motion: bool,
+    #[serde(default)]
+    pepper_file: Option<PathBuf>,
+    #[serde(default)]
+    signing_key_file: Option<PathBuf>,
+    #[serde(default)]
+    hibp_url: Option<String>,
+    #[serde(default)]
+    presets: BTreeMap<String, Preset>,
+    #[serde(default)]
+    alias_base: Option<String>,
+    #[serde(default)]
+    wordlist_dir: Option<PathBuf>,
+    #[serde(default)]
+    clipboard_clear_seconds: Option<u64>,
+    #[serde(default)]
+    clipboard_backend: ClipboardBackend,
+    #[serde(default)]
+    clipboard_selection: Selection,
+    #[serde(default)]
+    autotype_delay_seconds: Option<u64>,
+    #[serde(default)]
+    git_autocommit: bool,
+    #[cfg(feature = "remote")]
+    #[serde(default)]
+    s3_profiles: BTreeMap<String, S3Profile>,
+    #[serde(default)]
+    ssh_command: Option<String>,
+}
+
+impl Config {
+    // Never clear the screen or switch to full-screen views; output is only
+    // ever appended. Reduced motion implies this too, and will also cover
+    // spinners and other animations.
+    pub fn linear_output(&self) -> bool {
+        self.no_clear || self.reduced_motion
+    }
+
+    // Where this machine's pepper is kept, `pepper` in the config directory
+    // unless the config names another file.
+    pub fn pepper_file(&self) -> Option<PathBuf> {
+        self.pepper_file.clone().or_else(|| Some(config_dir()?.join("pepper")))
+    }
+
+    pub fn signing_key_file(&self) -> Option<PathBuf> {
+        self.signing_key_file.clone().or_else(|| Some(config_dir()?.join("signing.key")))
+    }
+
+    // The Pwned Passwords range API, or a mirror of it; a five-character hash
+    // prefix is appended to it.
+    pub fn hibp_url(&self) -> &str {
+        self.hibp_url.as_deref().unwrap_or("https://api.pwnedpasswords.com/range/")
+    }
+
+    // The email address unique aliases are made from, e.g. "me@example.com".
+    pub fn alias_base(&self) -> Option<&str> {
+        self.alias_base.as_deref()
+    }
+
+    // Where passphrase wordlists of your own are kept, `wordlists` in the
+    // config directory unless the config names another.
+    pub fn wordlist_dir(&self) -> Option<PathBuf> {
+        self.wordlist_dir.clone().or_else(|| Some(config_dir()?.join("wordlists")))
+    }
+
+    // How long a copied password stays in the clipboard, 30 seconds unless
+    // the config says otherwise; 0 leaves it there.
+    pub fn clipboard_clear_after(&self) -> Option<Duration> {
+        match self.clipboard_clear_seconds.unwrap_or(30) {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds)),
+        }
+    }
+
+    // How long auto-type waits before typing, to switch to the login form.
+    pub fn autotype_delay(&self) -> Duration {
+        Duration::from_secs(self.autotype_delay_seconds.unwrap_or(3))
+    }
+
+    // Commit a vault that is in a git repository after every save.
+    pub fn git_autocommit(&self) -> bool {
+        self.git_autocommit
+    }
+
+    // How sftp:// vaults are reached, `ssh` unless the config gives another
+    // command, e.g. "ssh -i ~/.ssh/vaults".
+    pub fn ssh_command(&self) -> &str {
+        self.ssh_command.as_deref().unwrap_or("ssh")
+    }
+
+    pub fn clipboard_backend(&self) -> ClipboardBackend {
+        self.clipboard_backend
+    }
+
+    pub fn clipboard_selection(&self) -> Selection {
+        self.clipboard_selection
+    }
+
+    pub fn presets(&self) -> &BTreeMap<String, Preset> {
+        &self.presets
+    }
+
+    pub fn preset(&self, name: &str) -> Result<&Preset, FerropassError> {
+        self.presets.get(name).ok_or_else(|| FerropassError::Validation(match self.presets.len() {
+            0 => format!("No preset named '{}'; presets are defined under \"presets\" in the config", name),
+            _ => format!(
+                "No preset named '{}'; the config defines {}",
+                name, self.presets.keys().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        }))
+    }
+
+    #[cfg(feature = "remote")]
+    pub fn s3_profile(&self, name: &str) -> Result<&S3Profile, FerropassError> {
+        self.s3_profiles.get(name)
+            .ok_or_else(|| FerropassError::Validation(format!("No S3 profile named '{}'; profiles are defined under \"s3_profiles\" in the config", name)))
+    }
+}
+
+// Where copies go, `clipboard_selection` in the config: the clipboard, the
+// primary selection that middle-click pastes from, or both. The primary
+// selection only exists on X11 and Wayland, and through OSC 52 in terminals
+// that support it.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Selection {
+    #[default]
+    Clipboard,
+    Primary,
+    Both,
+}
+
+impl Selection {
+    pub fn clipboard(self) -> bool {
+        self != Selection::Primary
+    }
+
+    pub fn primary(self) -> bool {
+        self != Selection::Clipboard
+    }
+}
+
+// Which clipboard to use, `clipboard_backend` in the config. `auto` tries
+// Wayland's clipboard in a Wayland session, then the system one, and falls
+// back to OSC 52, such as in an SSH session without X forwarding.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardBackend {
+    #[default]
+    Auto,
+    System,
+    Wayland,
+    Osc52,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+pub fn current() -> &'static Config {
+    CONFIG.get_or_init(load)
+}
+
+fn load() -> Config {
+    let Some(path) = config_path() else {
+        return Config::default();
+    };
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return Config::default(),
+    };
+
+    match serde_json::from_str(&content) {
+        Ok(config) => config,
+        Err(e) => {
+            notice::warn(format!("ignoring {}: {}", path.display(), e));
+            Config::default()
+        }
+    }
+}
+
+// Creates a file only the current user can read, for secrets such as the
+// pepper. Fails if the file already exists.
+pub fn write_private_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), FerropassError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| FerropassError::Io(format!("Failed to create {}: {}", dir.display(), e)))?;
+    }
+
+    let mut file = private_options().write(true).create_new(true).open(path)
+        .map_err(|e| FerropassError::Io(format!("Failed to create {}: {}", path.display(), e)))?;
+    file.write_all(contents.as_ref())
+        .and_then(|()| file.sync_all())
+        .map_err(|e| FerropassError::Io(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+fn config_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.json"))
+}
+
+// Where ferropass keeps what it records about this machine's use of vaults,
+// `$XDG_STATE_HOME/ferropass` (or `~/.local/state/ferropass`).
+pub fn state_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local").join("state"),
+    };
+
+    Some(base.join("ferropass"))
+}
+
+// Reads a JSON file from the state directory; a file that isn't there yet
+// is the default state. One that exists but can't be read or parsed is an
+// error rather than a fresh start, since some state, such as the journal
+// lengths seen and wrong passkeys tried, is only worth anything kept.
+pub fn load_state<T: DeserializeOwned + Default>(name: &str) -> Result<T, FerropassError> {
+    let Some(dir) = state_dir() else {
+        return Ok(T::default());
+    };
+    let path = dir.join(name);
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(FerropassError::Io(format!("Failed to read {}: {}", path.display(), e))),
+    };
+    serde_json::from_str(&content)
+        .map_err(|e| FerropassError::Format(format!("{} is damaged ({}); fix or remove it", path.display(), e)))
+}
+
+// Reads a state file, lets `update` change it and writes it back if it did,
+// holding `<name>.lock` meanwhile so that processes updating the same state
+// take turns instead of losing each other's changes.
+pub fn update_state<T, R>(name: &str, update: impl FnOnce(&mut T) -> R) -> Result<R, FerropassError>
+where
+    T: Serialize + DeserializeOwned + Default + Clone + PartialEq,
+{
+    let Some(dir) = state_dir() else {
+        return Ok(update(&mut T::default()));
+    };
+    create_private_dir(&dir)?;
+
+    let lock_path = dir.join(format!("{}.lock", name));
+    let lock = private_options().read(true).write(true).create(true).truncate(false).open(&lock_path)
+        .and_then(|file| file.lock().map(|()| file))
+        .map_err(|e| FerropassError::Io(format!("Failed to lock {}: {}", lock_path.display(), e)))?;
+
+    let mut state: T = load_state(name)?;
+    let before = state.clone();
+    let result = update(&mut state);
+    if state != before {
+        write_state(&dir, name, &state)?;
+    }
+
+    drop(lock);
+    Ok(result)
+}
+
+// Writes to a temporary file and renames it over the state, so a crash
+// mid-write leaves the old state rather than half of the new one.
+fn write_state<T: Serialize>(dir: &Path, name: &str, state: &T) -> Result<(), FerropassError> {
+    let path = dir.join(name);
+    let temp_path = dir.join(format!(".{}.tmp", name));
+    let json = serde_json::to_string(state).map_err(|e| FerropassError::Io(format!("Failed to update {}: {}", path.display(), e)))?;
+
+    // Only the update holding the lock writes the temporary file, so one left
+    // there is from a crash.
+    let _ = fs::remove_file(&temp_path);
+    let written = private_options().write(true).create_new(true).open(&temp_path)
+        .and_then(|mut file| file.write_all(json.as_bytes()).and_then(|()| file.sync_all()))
+        .and_then(|()| fs::rename(&temp_path, &path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(FerropassError::Io(format!("Failed to update {}: {}", path.display(), e)));
+    }
+
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+fn private_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+fn create_private_dir(dir: &Path) -> Result<(), FerropassError> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir).map_err(|e| FerropassError::Io(format!("Failed to create {}: {}", dir.display(), e)))
+}
+
+// What state is keyed by for a vault: its canonical path where it has one.
+pub fn vault_id(filepath: &Path) -> String {
+    fs::canonicalize(filepath)
+        .unwrap_or_else(|_| filepath.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(base.join("ferropass"))
+}
@@ -0,0 +1,1440 @@
+use crate::algorithms::{self, AeadCipher, DEFAULT_CIPHER};
+use crate::entropy;
+use crate::error::FerropassError;
+use crate::events::{self, Event};
+use crate::memlock::{self, SecretBuffer, SecretKey};
+use crate::pepper;
+use crate::signing;
+#[cfg(feature = "fs")]
+use crate::config;
+#[cfg(feature = "fs")]
+use crate::throttle;
+use crate::stream::{self, ChunkReader, ChunkWriter, CHUNK_SIZE};
+use crate::models::{current_timestamp, Change, Database, StoredPassword};
+use crate::storage::{Save, VaultStorage};
+use argon2::Params;
+use argon2::password_hash::{SaltString, rand_core::OsRng};
+use rand_core::RngCore;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use base64::{Engine as _, engine::general_purpose};
+use base64::read::DecoderReader;
+use base64::write::EncoderStringWriter;
+use sha2::{Digest, Sha256};
+use ed25519_dalek::SigningKey;
+
+// The journal is folded back into the base snapshot once it holds this many
+// records or grows larger than the snapshot itself.
+const MAX_JOURNAL_RECORDS: usize = 64;
+
+const ENTRY_ASSOCIATED_DATA: &[u8] = b"ferropass:entry";
+
+// How each journal record binds the one before it.
+const JOURNAL_CHAIN: &str = "sha256";
+
+// How long each vault's journal was when this machine last read or wrote
+// it, by the nonce of its base snapshot. Appends leave the header alone, so a
+// file cut short after one of its records looks whole; only having seen more
+// of it shows that records are missing.
+#[cfg(feature = "fs")]
+const JOURNAL_STATE_FILE: &str = "journals.json";
+
+// Version 1 files have no header and are encrypted without associated data.
+// Version 2 adds the header below and binds it to the ciphertext as AAD.
+// Version 3 encrypts the data under a random data key, which the header
+// carries wrapped under the passkey-derived key; the key derivation fields
+// are bound to the wrap instead of the data, so only the header changes with
+// the passkey.
+const LEGACY_FORMAT_VERSION: u32 = 1;
+pub const HEADER_FORMAT_VERSION: u32 = 2;
+pub const CURRENT_FORMAT_VERSION: u32 = 3;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    // ID of the machine-local pepper mixed into derivation, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pepper: Option<String>,
+}
+
+impl KdfParams {
+    pub fn current() -> Self {
+        KdfParams {
+            algorithm: "argon2id".to_string(),
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+            pepper: None,
+        }
+    }
+    
+    // Whether these parameters cost an attacker less than `other` does.
+    fn is_weaker_than(&self, other: &KdfParams) -> bool {
+        self.algorithm != other.algorithm || self.m_cost < other.m_cost || self.t_cost < other.t_cost
+    }
+}
+
+// The key the vault's data is encrypted under, kept for the rest of the
+// session together with how it is wrapped: the salt and parameters of the
+// passkey-derived key and the data key sealed under it. Saves don't need the
+// passkey again, and changing the passkey only re-wraps the data key.
+#[derive(Clone)]
+pub struct SessionKey {
+    key: SecretKey,
+    salt: String,
+    kdf: KdfParams,
+    cipher: String,
+    wrapped_key: String,
+    recovery: Option<RecoverySlot>,
+    signing_key: Option<String>,
+    vault_id: String,
+    // The revision of each file this session opened or saved, shared by its
+    // clones and rekeyed copies, which save to the same files.
+    revisions: Arc<Mutex<HashMap<PathBuf, Revision>>>,
+}
+
+impl SessionKey {
+    // Makes a random data key for a new vault, wrapped under the passkey.
+    pub fn generate(passkey: &str) -> Result<Self, FerropassError> {
+        Self::wrap(generate_key()?, passkey, KdfParams::current(), DEFAULT_CIPHER.to_string(), None)
+    }
+    
+    fn wrap(key: SecretKey, passkey: &str, kdf: KdfParams, cipher: String, recovery: Option<RecoverySlot>) -> Result<Self, FerropassError> {
+        entropy::ensure()?;
+        let salt = SaltString::generate(&mut OsRng).as_str().to_string();
+        let wrapping_key = derive_key_with_salt(passkey, &salt, &kdf)?;
+        let wrapped_key = seal(algorithms::cipher(&cipher)?.as_ref(), &wrapping_key, &key[..], &wrap_associated_data(&kdf, &salt))?;
+        
+        Ok(SessionKey { key, salt, kdf, cipher, wrapped_key, recovery, signing_key: None, vault_id: generate_vault_id(), revisions: Arc::default() })
+    }
+    
+    // Files from before version 3 are encrypted under the passkey-derived key
+    // itself. Their session gets a new data key, wrapped under that same
+    // derived key, so the next save re-encrypts them in the current format.
+    fn from_legacy(derived_key: &[u8; 32], encrypted_data: &EncryptedData, kdf: KdfParams) -> Result<Self, FerropassError> {
+        let key = generate_key()?;
+        let cipher = encrypted_data.cipher()?;
+        let salt = encrypted_data.salt.clone();
+        let wrapped_key = seal(cipher.as_ref(), derived_key, &key[..], &wrap_associated_data(&kdf, &salt))?;
+        
+        let recovery = match &encrypted_data.recovery {
+            Some(slot) => {
+                let recovery_key = slot.open_legacy_recovery_key(cipher.as_ref(), derived_key, &salt)?;
+                Some(RecoverySlot::seal(cipher.as_ref(), &key, &recovery_key, slot.threshold, slot.shares)?)
+            },
+            None => None,
+        };
+        
+        Ok(SessionKey { key, salt, kdf, cipher: encrypted_data.cipher.clone(), wrapped_key, recovery, signing_key: None, vault_id: encrypted_data.vault_id(), revisions: Arc::default() })
+    }
+    
+    // Wraps the same data key under a new passkey (or the same passkey with a
+    // new salt and current parameters), keeping the pepper.
+    pub fn rekey(&self, passkey: &str) -> Result<Self, FerropassError> {
+        self.rekey_with_pepper(passkey, self.kdf.pepper.clone())
+    }
+    
+    pub fn get_pepper(&self) -> Option<&str> {
+        self.kdf.pepper.as_deref()
+    }
+    
+    pub fn rekey_with_pepper(&self, passkey: &str, pepper: Option<String>) -> Result<Self, FerropassError> {
+        let mut key = Self::wrap(self.key.clone(), passkey, KdfParams { pepper, ..KdfParams::current() }, self.cipher.clone(), self.recovery.clone())?;
+        key.signing_key = self.signing_key.clone();
+        key.vault_id = self.vault_id.clone();
+        key.revisions = self.revisions.clone();
+        
+        Ok(key)
+    }
+    
+    // The public key the vault is signed with, if it is.
+    pub fn get_signing_key(&self) -> Option<&str> {
+        self.signing_key.as_deref()
+    }
+
+    // Written to the header with each save that rewrites it; files from
+    // before vault IDs get theirs on the first.
+    pub fn get_vault_id(&self) -> &str {
+        &self.vault_id
+    }
+    
+    // Takes effect with the next full save, which binds the key to the data.
+    pub fn set_signing_key(&mut self, signing_key: Option<String>) {
+        self.signing_key = signing_key;
+    }
+    
+    fn signer(&self) -> Result<Option<SigningKey>, FerropassError> {
+        self.signing_key.as_deref().map(signing::load).transpose()
+    }
+    
+    // Makes a new recovery key that can unlock the vault in place of the
+    // passkey, replacing any earlier one, and returns it for splitting.
+    pub fn set_up_recovery(&mut self, threshold: u8, shares: u8) -> Result<[u8; 32], FerropassError> {
+        let recovery_key = generate_key()?;
+        self.recovery = Some(RecoverySlot::seal(self.aead()?.as_ref(), &self.key, &recovery_key, threshold, shares)?);
+        
+        Ok(*recovery_key)
+    }
+    
+    pub fn entry_key(&self) -> EntryKey {
+        EntryKey::new(&self.key, &self.cipher)
+    }
+    
+    pub fn fingerprint(&self) -> String {
+        key_fingerprint(&self.key)
+    }
+    
+    fn aead(&self) -> Result<Box<dyn AeadCipher>, FerropassError> {
+        algorithms::cipher(&self.cipher)
+    }
+    
+    fn matches(&self, encrypted_data: &EncryptedData) -> bool {
+        encrypted_data.salt == self.salt
+            && encrypted_data.cipher == self.cipher
+            && encrypted_data.kdf_params().is_ok_and(|kdf| kdf == self.kdf)
+    }
+    
+    fn seen_revision(&self, filepath: &Path) -> Option<Revision> {
+        self.revisions.lock().ok()?.get(filepath).cloned()
+    }
+    
+    fn remember_revision(&self, filepath: &Path, revision: Revision) {
+        if let Ok(mut revisions) = self.revisions.lock() {
+            revisions.insert(filepath.to_path_buf(), revision);
+        }
+    }
+}
+
+// Where a file stood when a session last read or wrote it: the count of full
+// saves and re-wraps in its header, the nonce of its base snapshot, and how
+// many records were appended after it. Another save anywhere changes one of
+// them, even one that ends up at the same revision number.
+#[derive(Clone, PartialEq)]
+struct Revision {
+    header: u64,
+    nonce: String,
+    journal_len: usize,
+}
+
+impl Revision {
+    fn of(encrypted_data: &EncryptedData) -> Self {
+        Revision {
+            header: encrypted_data.revision,
+            nonce: encrypted_data.nonce.clone(),
+            journal_len: encrypted_data.journal.len(),
+        }
+    }
+    
+    fn number(&self) -> u64 {
+        self.header + self.journal_len as u64
+    }
+}
+
+// Seals each account's password on its own inside the database, under a key
+// derived from the data key. The decrypted database then holds only sealed
+// passwords, and each one is opened when it is needed and dropped after.
+#[derive(Clone)]
+pub struct EntryKey {
+    key: SecretKey,
+    cipher: String,
+}
+
+impl EntryKey {
+    fn new(data_key: &[u8; 32], cipher: &str) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(b"ferropass:entry-key:");
+        hasher.update(data_key);
+        
+        let mut key: [u8; 32] = hasher.finalize().into();
+        
+        EntryKey { key: SecretKey::new(&mut key), cipher: cipher.to_string() }
+    }
+    
+    pub fn seal(&self, password: &str) -> Result<StoredPassword, FerropassError> {
+        let sealed = seal(algorithms::cipher(&self.cipher)?.as_ref(), &self.key, password.as_bytes(), ENTRY_ASSOCIATED_DATA)?;
+        
+        Ok(StoredPassword::Sealed { sealed })
+    }
+    
+    pub fn open(&self, password: &StoredPassword) -> Result<String, FerropassError> {
+        let sealed = match password {
+            StoredPassword::Sealed { sealed } => sealed,
+            StoredPassword::Plain(password) => return Ok(password.clone()),
+        };
+        
+        let cipher = algorithms::cipher(&self.cipher)?;
+        let sealed = general_purpose::STANDARD.decode(sealed)
+            .map_err(|e| FerropassError::Format(format!("Error decoding sealed password: {}", e)))?;
+        if sealed.len() < cipher.nonce_len() {
+            return Err(FerropassError::Format("Sealed password is too short".to_string()));
+        }
+        
+        let (nonce, ciphertext) = sealed.split_at(cipher.nonce_len());
+        let plaintext = cipher.decrypt(&self.key, nonce, ciphertext, ENTRY_ASSOCIATED_DATA)
+            .map_err(|_| FerropassError::Crypto("The password can't be opened with this vault's key".to_string()))?;
+        
+        String::from_utf8(plaintext).map_err(|_| FerropassError::Format("Sealed password is not valid UTF-8".to_string()))
+    }
+    
+    // Moves a password sealed under `from` (another vault's key) over to this
+    // key.
+    pub fn reseal(&self, password: &StoredPassword, from: &EntryKey) -> Result<StoredPassword, FerropassError> {
+        self.seal(&from.open(password)?)
+    }
+    
+    pub fn reseal_all(&self, database: &mut Database, from: &EntryKey) -> Result<(), FerropassError> {
+        for account in database.get_accounts_mut() {
+            let password = self.reseal(account.get_password(), from)
+                .map_err(|e| e.context(format!("Account {}", account.get_id())))?;
+            account.reseal_password(password);
+            
+            if let Some(totp) = account.get_totp() {
+                let totp = self.reseal(totp, from)
+                    .map_err(|e| e.context(format!("Account {}'s TOTP secret", account.get_id())))?;
+                account.reseal_totp(totp);
+            }
+            
+            if let Some(ssh_key) = account.get_ssh_key() {
+                let ssh_key = self.reseal(ssh_key, from)
+                    .map_err(|e| e.context(format!("Account {}'s SSH key", account.get_id())))?;
+                account.reseal_ssh_key(ssh_key);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    pub fn seal_plaintext(&self, database: &mut Database) -> Result<(), FerropassError> {
+        for account in database.get_accounts_mut() {
+            if let StoredPassword::Plain(password) = account.get_password() {
+                let sealed = self.seal(password)?;
+                account.reseal_password(sealed);
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+// Lets a recovery key stand in for the passkey by sealing the data key
+// under it. Re-wrapping the data key for a new passkey leaves the slot as it
+// is. Version 2 files sealed the passkey-derived key instead, bound to the
+// salt, and the recovery key under the derived key so it could be carried
+// over.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RecoverySlot {
+    threshold: u8,
+    shares: u8,
+    sealed_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed_recovery_key: Option<String>,
+}
+
+impl RecoverySlot {
+    fn seal(cipher: &dyn AeadCipher, key: &[u8; 32], recovery_key: &[u8; 32], threshold: u8, shares: u8) -> Result<Self, FerropassError> {
+        Ok(RecoverySlot {
+            threshold,
+            shares,
+            sealed_key: seal(cipher, recovery_key, key, &Self::associated_data(None))?,
+            sealed_recovery_key: None,
+        })
+    }
+    
+    fn open_key(&self, cipher: &dyn AeadCipher, recovery_key: &[u8; 32], legacy_salt: Option<&str>) -> Result<SecretKey, FerropassError> {
+        unseal_key(cipher, recovery_key, &self.sealed_key, &Self::associated_data(legacy_salt))
+            .map_err(|_| FerropassError::Crypto("The shares don't rebuild this vault's recovery key".to_string()))
+    }
+    
+    fn open_legacy_recovery_key(&self, cipher: &dyn AeadCipher, derived_key: &[u8; 32], salt: &str) -> Result<SecretKey, FerropassError> {
+        let sealed = self.sealed_recovery_key.as_deref()
+            .ok_or_else(|| FerropassError::Format("The vault's recovery slot is incomplete".to_string()))?;
+        
+        unseal_key(cipher, derived_key, sealed, &Self::associated_data(Some(salt)))
+            .map_err(|_| FerropassError::Crypto("The vault's recovery key can't be opened".to_string()))
+    }
+    
+    fn associated_data(legacy_salt: Option<&str>) -> Vec<u8> {
+        match legacy_salt {
+            Some(salt) => format!("ferropass:recovery:{}", salt).into_bytes(),
+            None => b"ferropass:recovery".to_vec(),
+        }
+    }
+    
+    pub fn get_threshold(&self) -> u8 {
+        self.threshold
+    }
+    
+    pub fn get_shares(&self) -> u8 {
+        self.shares
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EncryptedData {
+    #[serde(default = "legacy_format_version")]
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kdf: Option<KdfParams>,
+    #[serde(default = "default_cipher")]
+    cipher: String,
+    // Plaintext bytes per chunk of the base snapshot; files without it are
+    // encrypted in one piece.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chunk_size: Option<u32>,
+    salt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wrapped_key: Option<String>,
+    nonce: String,
+    data: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recovery: Option<RecoverySlot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signing_key: Option<String>,
+    // Counts the saves that rewrote the header, so copies of the file on a
+    // shared folder can tell which is newer. It isn't part of the associated
+    // data: it only stops stale saves, and a signature still covers it.
+    #[serde(default)]
+    revision: u64,
+    // Random and kept for the vault's lifetime, across passkey changes and
+    // wherever it is copied, so tools can tell vaults apart without the
+    // passkey. Like the revision, it isn't part of the associated data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vault_id: Option<String>,
+    // Set on files whose journal records each bind a hash of the record
+    // before them, or of the base snapshot for the first, so records can't be
+    // swapped for those of another copy of the vault. Being part of the
+    // associated data, it also keeps versions of ferropass from before it off
+    // the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    journal_chain: Option<String>,
+    #[serde(skip)]
+    journal: Vec<JournalRecord>,
+}
+
+#[cfg(feature = "fs")]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct SeenJournal {
+    nonce: String,
+    records: usize,
+}
+
+// What a vault's header says about it, read without the passkey.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub version: u32,
+    pub kdf: Option<KdfParams>,
+    pub cipher: String,
+    pub vault_id: Option<String>,
+    pub revision: u64,
+    pub journal_len: usize,
+    pub signing_key: Option<String>,
+    // The threshold and number of recovery shares, if there are any.
+    pub recovery_shares: Option<(u8, u8)>,
+}
+
+// One appended changeset, stored on its own line after the base snapshot.
+#[derive(Serialize, Deserialize)]
+pub struct JournalRecord {
+    seq: u64,
+    nonce: String,
+    data: String,
+}
+
+// What a journal record decrypts to. Records from before they were
+// timestamped hold the bare list of changes.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RecordBody {
+    Timed { at: u64, changes: Vec<Change> },
+    Changes(Vec<Change>),
+}
+
+// A record's changes and when they were saved, if it says.
+pub type TimedChanges = (Option<u64>, Vec<Change>);
+
+fn legacy_format_version() -> u32 {
+    LEGACY_FORMAT_VERSION
+}
+
+fn default_cipher() -> String {
+    DEFAULT_CIPHER.to_string()
+}
+
+impl EncryptedData {
+    // Reassembles a file from fields salvaged out of a damaged one.
+    pub fn from_parts(version: u32, kdf: Option<KdfParams>, cipher: String, salt: String, nonce: String, data: &[u8], journal: Vec<JournalRecord>) -> Self {
+        EncryptedData {
+            version,
+            kdf,
+            cipher,
+            chunk_size: None,
+            salt,
+            wrapped_key: None,
+            nonce,
+            data: general_purpose::STANDARD.encode(data),
+            recovery: None,
+            signing_key: None,
+            revision: 0,
+            vault_id: None,
+            journal_chain: None,
+            journal,
+        }
+    }
+    
+    pub fn set_chunk_size(&mut self, chunk_size: Option<u32>) {
+        self.chunk_size = chunk_size;
+    }
+    
+    pub fn set_wrapped_key(&mut self, wrapped_key: Option<String>) {
+        self.wrapped_key = wrapped_key;
+    }
+    
+    pub fn set_signing_key(&mut self, signing_key: Option<String>) {
+        self.signing_key = signing_key;
+    }
+    
+    pub fn read(storage: &dyn VaultStorage, filepath: &Path) -> Result<Self, FerropassError> {
+        let file_content = storage.read_text(filepath)?;
+        
+        Self::parse(&file_content)
+    }
+    
+    // Like `read`, but first checks the signature of a signed vault, and that
+    // no records are missing from a journal this machine has seen more of, so
+    // a modified or replaced file is caught before anything is decrypted.
+    pub fn read_signed(storage: &dyn VaultStorage, filepath: &Path) -> Result<Self, FerropassError> {
+        let file_content = storage.read_text(filepath)?;
+        let encrypted_data = Self::parse(&file_content)?;
+        
+        encrypted_data.check_signature(storage, filepath, &file_content)?;
+        check_journal(storage, filepath, &encrypted_data)?;
+        Ok(encrypted_data)
+    }
+    
+    pub fn check_signature(&self, storage: &dyn VaultStorage, filepath: &Path, file_content: &str) -> Result<(), FerropassError> {
+        match &self.signing_key {
+            Some(public_key) => signing::verify(storage, filepath, public_key, file_content.as_bytes()),
+            None => Ok(()),
+        }
+    }
+    
+    pub fn get_signing_key(&self) -> Option<&str> {
+        self.signing_key.as_deref()
+    }
+    
+    // The base snapshot is the first line; every further line is a journal
+    // record. Records are written newline-terminated, so an unterminated last
+    // line is an append that never completed and is ignored.
+    pub fn parse(file_content: &str) -> Result<Self, FerropassError> {
+        let mut lines: Vec<&str> = file_content.split('\n').collect();
+        if lines.len() > 1 {
+            lines.pop();
+        }
+        
+        let mut lines = lines.into_iter().filter(|line| !line.trim().is_empty());
+        let base = lines.next().ok_or_else(|| FerropassError::Format("Error parsing file content: file is empty".to_string()))?;
+        
+        let mut encrypted_data: EncryptedData = serde_json::from_str(base)
+            .map_err(|e| FerropassError::Format(format!("Error parsing file content: {}", e)))?;
+        
+        for (index, line) in lines.enumerate() {
+            let record: JournalRecord = serde_json::from_str(line)
+                .map_err(|e| FerropassError::Format(format!("Error parsing journal record {}: {}", index + 1, e)))?;
+            encrypted_data.journal.push(record);
+        }
+        
+        if encrypted_data.version == LEGACY_FORMAT_VERSION && !encrypted_data.journal.is_empty() {
+            return Err(FerropassError::Format("Legacy database files cannot carry a journal".to_string()));
+        }
+        if let Some(chain) = encrypted_data.journal_chain.as_deref().filter(|chain| *chain != JOURNAL_CHAIN) {
+            return Err(FerropassError::Format(format!("Unsupported journal chain '{}'", chain)));
+        }
+        
+        Ok(encrypted_data)
+    }
+    
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn header(&self) -> Header {
+        Header {
+            version: self.version,
+            kdf: self.kdf_params().ok(),
+            cipher: self.cipher.clone(),
+            vault_id: self.vault_id.clone(),
+            revision: self.revision(),
+            journal_len: self.journal.len(),
+            signing_key: self.signing_key.clone(),
+            recovery_shares: self.recovery.as_ref().map(|slot| (slot.threshold, slot.shares)),
+        }
+    }
+
+    // The file's own ID, or a new one for a file from before vault IDs.
+    fn vault_id(&self) -> String {
+        self.vault_id.clone().unwrap_or_else(generate_vault_id)
+    }
+    
+    pub fn journal_len(&self) -> usize {
+        self.journal.len()
+    }
+    
+    // Every save counts, appends included.
+    pub fn revision(&self) -> u64 {
+        Revision::of(self).number()
+    }
+    
+    // Records can only follow a terminated line of a file in the current
+    // format; anything else is rewritten in full.
+    fn takes_appends(&self, file_content: &str) -> bool {
+        file_content.ends_with('\n') && self.version == CURRENT_FORMAT_VERSION
+    }
+    
+    fn needs_compaction(&self) -> bool {
+        let journal_bytes: usize = self.journal.iter().map(|record| record.data.len()).sum();
+        self.journal.len() >= MAX_JOURNAL_RECORDS || journal_bytes > self.data.len()
+    }
+    
+    pub fn kdf_params(&self) -> Result<KdfParams, FerropassError> {
+        match (self.version, &self.kdf) {
+            (LEGACY_FORMAT_VERSION, None) => Ok(KdfParams::current()),
+            (HEADER_FORMAT_VERSION | CURRENT_FORMAT_VERSION, Some(kdf)) => {
+                algorithms::kdf(kdf)?;
+                Ok(kdf.clone())
+            },
+            (HEADER_FORMAT_VERSION | CURRENT_FORMAT_VERSION, None) => Err(FerropassError::Format("Header is missing key derivation parameters".to_string())),
+            (LEGACY_FORMAT_VERSION, Some(_)) => Err(FerropassError::Format("Legacy header must not carry key derivation parameters".to_string())),
+            (version, _) => Err(FerropassError::Format(format!("Unsupported format version {}", version))),
+        }
+    }
+    
+    pub fn cipher(&self) -> Result<Box<dyn AeadCipher>, FerropassError> {
+        algorithms::cipher(&self.cipher)
+    }
+    
+    pub fn recovery(&self) -> Option<&RecoverySlot> {
+        self.recovery.as_ref()
+    }
+    
+    pub fn salt(&self) -> Result<SaltString, FerropassError> {
+        SaltString::from_b64(&self.salt)
+            .map_err(|e| FerropassError::Format(format!("Error parsing salt: {}", e)))
+    }
+    
+    pub fn nonce(&self) -> Result<Vec<u8>, FerropassError> {
+        let nonce = general_purpose::STANDARD.decode(&self.nonce)
+            .map_err(|e| FerropassError::Format(format!("Error decoding nonce: {}", e)))?;
+        let nonce_len = self.cipher()?.nonce_len();
+        
+        if nonce.len() != nonce_len {
+            return Err(FerropassError::Format(format!("Nonce is {} bytes, expected {}", nonce.len(), nonce_len)));
+        }
+        
+        Ok(nonce)
+    }
+    
+    pub fn ciphertext(&self) -> Result<Vec<u8>, FerropassError> {
+        let ciphertext = general_purpose::STANDARD.decode(&self.data)
+            .map_err(|e| FerropassError::Format(format!("Error decoding data: {}", e)))?;
+        let tag_len = self.cipher()?.tag_len();
+        
+        if ciphertext.len() < tag_len {
+            return Err(FerropassError::Format(format!("Ciphertext is {} bytes, shorter than the {}-byte authentication tag", ciphertext.len(), tag_len)));
+        }
+        
+        Ok(ciphertext)
+    }
+    
+    // The header fields the ciphertext depends on, in a fixed textual form.
+    // Version 2 binds everything, so swapping the salt or weakening the KDF
+    // parameters breaks authentication; version 3 leaves those to the wrap.
+    fn associated_data(&self) -> Vec<u8> {
+        match &self.kdf {
+            _ if self.version == CURRENT_FORMAT_VERSION => {
+                let mut aad = format!("ferropass:v{}:{}:{}", self.version, self.cipher, self.nonce);
+                if let Some(chunk_size) = self.chunk_size {
+                    aad.push_str(&format!(":chunks={}", chunk_size));
+                }
+                if let Some(signing_key) = &self.signing_key {
+                    aad.push_str(&format!(":signer={}", signing_key));
+                }
+                if let Some(chain) = &self.journal_chain {
+                    aad.push_str(&format!(":journal={}", chain));
+                }
+                aad.into_bytes()
+            },
+            Some(kdf) if self.version == HEADER_FORMAT_VERSION => {
+                let mut aad = format!(
+                    "ferropass:v{}:{}:m={},t={},p={}:{}:{}",
+                    self.version, kdf.algorithm, kdf.m_cost, kdf.t_cost, kdf.p_cost, self.salt, self.nonce
+                );
+                if let Some(pepper) = &kdf.pepper {
+                    aad.push_str(&format!(":pepper={}", pepper));
+                }
+                if self.cipher != DEFAULT_CIPHER {
+                    aad.push_str(&format!(":cipher={}", self.cipher));
+                }
+                aad.into_bytes()
+            },
+            _ => Vec::new(),
+        }
+    }
+    
+    fn journal_associated_data(&self, seq: u64) -> Vec<u8> {
+        let mut aad = self.associated_data();
+        aad.extend_from_slice(format!(":journal:{}", seq).as_bytes());
+        if self.journal_chain.is_some() {
+            let previous = match seq.checked_sub(1) {
+                Some(previous) => usize::try_from(previous).ok()
+                    .and_then(|previous| self.journal.get(previous))
+                    .map_or("", |record| record.data.as_str()),
+                None => self.data.as_str(),
+            };
+            aad.extend_from_slice(format!(":after={:x}", Sha256::digest(previous.as_bytes())).as_bytes());
+        }
+        aad
+    }
+    
+    pub fn derive_key(&self, passkey: &str) -> Result<SecretKey, FerropassError> {
+        let kdf = self.kdf_params()?;
+        derive_key_with_salt(passkey, &self.salt, &kdf)
+    }
+    
+    // Opens the data key with the passkey-derived key. Files from before
+    // version 3 are encrypted under the derived key itself.
+    pub fn unwrap_key(&self, derived_key: &[u8; 32]) -> Result<SecretKey, FerropassError> {
+        match (self.version, &self.wrapped_key) {
+            (CURRENT_FORMAT_VERSION, Some(wrapped_key)) => {
+                let kdf = self.kdf_params()?;
+                unseal_key(self.cipher()?.as_ref(), derived_key, wrapped_key, &wrap_associated_data(&kdf, &self.salt))
+                    .map_err(|_| FerropassError::InvalidPasskey)
+            },
+            (CURRENT_FORMAT_VERSION, None) => Err(FerropassError::Format("Header is missing the wrapped data key".to_string())),
+            (_, None) => Ok(SecretKey::new(&mut derived_key.clone())),
+            (version, Some(_)) => Err(FerropassError::Format(format!("Format version {} doesn't carry a wrapped data key", version))),
+        }
+    }
+    
+    pub fn unlock_key(&self, passkey: &str) -> Result<SecretKey, FerropassError> {
+        let derived_key = self.derive_key(passkey)?;
+        self.unwrap_key(&derived_key)
+    }
+    
+    // The key the passwords inside are sealed under, given the data key.
+    pub fn entry_key(&self, key: &[u8; 32]) -> EntryKey {
+        EntryKey::new(key, &self.cipher)
+    }
+    
+    pub fn decrypt_base(&self, key: &[u8; 32]) -> Result<SecretBuffer, FerropassError> {
+        if self.chunk_size.is_some() {
+            // The plaintext is shorter than the ciphertext, which is at most
+            // three quarters of its base64.
+            let mut plaintext = SecretBuffer::new(self.data.len() / 4 * 3);
+            let len = self.with_base_reader(key, |reader| read_all(reader, &mut plaintext).map_err(read_error))?;
+            plaintext.truncate(len);
+            return Ok(plaintext);
+        }
+        
+        let nonce_bytes = self.nonce()?;
+        let ciphertext = self.ciphertext()?;
+        let aad = self.associated_data();
+        
+        self.cipher()?.decrypt(key, &nonce_bytes, &ciphertext, &aad)
+            .map(SecretBuffer::take)
+            .map_err(|_| FerropassError::InvalidPasskey)
+    }
+    
+    // Hands `read` the plaintext of a chunked base snapshot as a stream,
+    // decrypting one chunk at a time.
+    fn with_base_reader<T>(&self, key: &[u8; 32], read: impl FnOnce(&mut dyn Read) -> Result<T, FerropassError>) -> Result<T, FerropassError> {
+        let chunk_size = self.chunk_size.ok_or_else(|| FerropassError::Format("The data is not chunked".to_string()))? as usize;
+        if chunk_size == 0 {
+            return Err(FerropassError::Format("Header has a chunk size of 0".to_string()));
+        }
+        
+        let cipher = self.cipher()?;
+        let nonce = self.nonce()?;
+        let aad = self.associated_data();
+        let mut decoder = DecoderReader::new(self.data.as_bytes(), &general_purpose::STANDARD);
+        
+        read(&mut ChunkReader::new(cipher.as_ref(), key, &nonce, &aad, chunk_size, &mut decoder))
+    }
+    
+    pub fn decrypt_journal(&self, key: &[u8; 32]) -> Result<Vec<Vec<Change>>, FerropassError> {
+        (0..self.journal.len())
+            .map(|index| self.decrypt_journal_record(key, index))
+            .collect()
+    }
+    
+    pub fn decrypt_journal_record(&self, key: &[u8; 32], index: usize) -> Result<Vec<Change>, FerropassError> {
+        self.decrypt_timed_record(key, index).map(|(_, changes)| changes)
+    }
+    
+    // Each record's changes with when they were saved, oldest first.
+    pub fn journal_history(&self, key: &[u8; 32]) -> Result<Vec<TimedChanges>, FerropassError> {
+        (0..self.journal.len())
+            .map(|index| self.decrypt_timed_record(key, index))
+            .collect()
+    }
+    
+    fn decrypt_timed_record(&self, key: &[u8; 32], index: usize) -> Result<TimedChanges, FerropassError> {
+        let record = self.journal.get(index)
+            .ok_or_else(|| FerropassError::Format(format!("Journal record {} does not exist", index + 1)))?;
+        
+        if record.seq != index as u64 {
+            return Err(FerropassError::Format(format!("Journal record {} is out of sequence", index + 1)));
+        }
+        
+        let plaintext = record.decrypt(self.cipher()?.as_ref(), key, &self.journal_associated_data(record.seq))
+            .map_err(|e| e.context(format!("Journal record {}", index + 1)))?;
+        
+        match serde_json::from_slice(&plaintext) {
+            Ok(RecordBody::Timed { at, changes }) => Ok((Some(at), changes)),
+            Ok(RecordBody::Changes(changes)) => Ok((None, changes)),
+            Err(e) => Err(FerropassError::Format(format!("Error parsing journal record {}: {}", index + 1, e))),
+        }
+    }
+    
+    // Decrypts the base ciphertext without checking the tag. Only for
+    // salvaging a damaged file: the result may be corrupted or tampered with.
+    // A truncated file has lost its tag, so `has_tag` tells whether the tag
+    // at the end must be skipped.
+    pub fn decrypt_base_unauthenticated(&self, key: &[u8; 32], has_tag: bool) -> Result<Vec<u8>, FerropassError> {
+        let cipher = self.cipher()?;
+        let nonce = self.nonce()?;
+        let mut data = general_purpose::STANDARD.decode(&self.data)
+            .map_err(|e| FerropassError::Format(format!("Error decoding data: {}", e)))?;
+        
+        let Some(chunk_size) = self.chunk_size else {
+            if has_tag {
+                data.truncate(data.len().saturating_sub(cipher.tag_len()));
+            }
+            
+            cipher.decrypt_unauthenticated(key, &nonce, &mut data)?;
+            return Ok(data);
+        };
+        
+        // Every complete chunk carries a tag; only the last one may have lost it.
+        let full_len = chunk_size as usize + cipher.tag_len();
+        let chunk_count = data.len().div_ceil(full_len);
+        let mut plaintext = Vec::with_capacity(data.len());
+        for (index, chunk) in data.chunks_mut(full_len).enumerate() {
+            let tag_len = if chunk.len() == full_len || (has_tag && index + 1 == chunk_count) { cipher.tag_len() } else { 0 };
+            let end = chunk.len().saturating_sub(tag_len);
+            
+            cipher.decrypt_unauthenticated(key, &stream::chunk_nonce(&nonce, index as u64), &mut chunk[..end])?;
+            plaintext.extend_from_slice(&chunk[..end]);
+        }
+        
+        Ok(plaintext)
+    }
+    
+    pub fn open(&self, key: &[u8; 32]) -> Result<Database, FerropassError> {
+        let mut database = self.open_base(key)?;
+        for change in self.decrypt_journal(key)?.into_iter().flatten() {
+            database.apply(change);
+        }
+        
+        Ok(database)
+    }
+    
+    // The database as of the last full save, without the journal.
+    pub fn open_base(&self, key: &[u8; 32]) -> Result<Database, FerropassError> {
+        match self.chunk_size {
+            Some(_) => self.with_base_reader(key, |reader| serde_json::from_reader(reader).map_err(|e| {
+                if e.is_io() { read_error(e.into()) } else { FerropassError::Format(format!("Error parsing database: {}", e)) }
+            })),
+            None => serde_json::from_slice(&self.decrypt_base(key)?)
+                .map_err(|e| FerropassError::Format(format!("Error parsing database: {}", e))),
+        }
+    }
+    
+    // Checks the key against the newest record in the file so a wrong key
+    // never gets a record appended under it.
+    fn authenticate(&self, key: &[u8; 32]) -> Result<(), FerropassError> {
+        match self.journal.last() {
+            Some(record) => record.decrypt(self.cipher()?.as_ref(), key, &self.journal_associated_data(record.seq)).map(|_| ()),
+            None => self.decrypt_base(key).map(|_| ()),
+        }
+    }
+}
+
+impl JournalRecord {
+    fn decrypt(&self, cipher: &dyn AeadCipher, key: &[u8; 32], aad: &[u8]) -> Result<SecretBuffer, FerropassError> {
+        let nonce = general_purpose::STANDARD.decode(&self.nonce)
+            .map_err(|e| FerropassError::Format(format!("Error decoding nonce: {}", e)))?;
+        let ciphertext = general_purpose::STANDARD.decode(&self.data)
+            .map_err(|e| FerropassError::Format(format!("Error decoding data: {}", e)))?;
+        
+        if nonce.len() != cipher.nonce_len() || ciphertext.len() < cipher.tag_len() {
+            return Err(FerropassError::Format("Record is truncated".to_string()));
+        }
+        
+        cipher.decrypt(key, &nonce, &ciphertext, aad)
+            .map(SecretBuffer::take)
+            .map_err(|_| FerropassError::Crypto("Invalid passkey or corrupted journal record".to_string()))
+    }
+}
+
+// Encrypts a small secret under `key`, keeping the nonce with it.
+fn seal(cipher: &dyn AeadCipher, key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Result<String, FerropassError> {
+    let nonce = generate_nonce(cipher)?;
+    let mut sealed = nonce.clone();
+    sealed.extend(cipher.encrypt(key, &nonce, plaintext, aad)?);
+    
+    Ok(general_purpose::STANDARD.encode(sealed))
+}
+
+fn unseal_key(cipher: &dyn AeadCipher, key: &[u8; 32], sealed: &str, aad: &[u8]) -> Result<SecretKey, FerropassError> {
+    let sealed = general_purpose::STANDARD.decode(sealed)
+        .map_err(|e| FerropassError::Format(format!("Error decoding sealed key: {}", e)))?;
+    if sealed.len() < cipher.nonce_len() {
+        return Err(FerropassError::Format("Sealed key is too short".to_string()));
+    }
+    
+    let (nonce, ciphertext) = sealed.split_at(cipher.nonce_len());
+    let mut plaintext = cipher.decrypt(key, nonce, ciphertext, aad)?;
+    let key = <&mut [u8; 32]>::try_from(plaintext.as_mut_slice())
+        .map(SecretKey::new)
+        .map_err(|_| FerropassError::Format("Sealed key has the wrong length".to_string()));
+    memlock::wipe(&mut plaintext);
+    
+    key
+}
+
+// A short, printable tag of the data key: the same vault always gives the
+// same fingerprint, whatever its passkey, without revealing the key itself.
+pub fn key_fingerprint(key: &[u8; 32]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"ferropass:fingerprint:");
+    hasher.update(key);
+    
+    let digest = format!("{:X}", hasher.finalize());
+    digest.as_bytes()[..16]
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk).to_string())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn derive_key_with_salt(passkey: &str, salt_str: &str, kdf: &KdfParams) -> Result<SecretKey, FerropassError> {
+    let salt = SaltString::from_b64(salt_str)
+        .map_err(|e| FerropassError::Format(format!("Error parsing salt: {}", e)))?;
+    
+    let pepper = kdf.pepper.as_deref().map(pepper::load).transpose()?;
+    
+    let mut key = algorithms::kdf(kdf)?.derive(
+        passkey.as_bytes(),
+        salt.as_str().as_bytes(),
+        pepper.as_ref().map(|pepper| pepper.get_bytes())
+    )?;
+    
+    Ok(SecretKey::new(&mut key))
+}
+
+// Reads until `buffer` is full or the reader ends, returning how much was read.
+fn read_all(reader: &mut dyn Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    
+    Ok(filled)
+}
+
+// Decryption errors come out of the chunk reader inside an I/O error.
+fn read_error(e: std::io::Error) -> FerropassError {
+    match e.into_inner().map(|inner| inner.downcast::<FerropassError>()) {
+        Some(Ok(error)) => *error,
+        Some(Err(inner)) => FerropassError::Io(inner.to_string()),
+        None => FerropassError::Io("Error reading the database".to_string()),
+    }
+}
+
+// The wrapped data key is bound to everything that feeds key derivation, so
+// swapping the salt or weakening the parameters makes unwrapping fail.
+fn wrap_associated_data(kdf: &KdfParams, salt: &str) -> Vec<u8> {
+    let mut aad = format!(
+        "ferropass:wrap:{}:m={},t={},p={}:{}",
+        kdf.algorithm, kdf.m_cost, kdf.t_cost, kdf.p_cost, salt
+    );
+    if let Some(pepper) = &kdf.pepper {
+        aad.push_str(&format!(":pepper={}", pepper));
+    }
+    aad.into_bytes()
+}
+
+// Writes a fresh base snapshot with no journal, compacting any records
+// appended since the last full save.
+pub fn encrypt_and_save_database(storage: &dyn VaultStorage, database: &Database, filepath: &Path, key: &SessionKey) -> Result<(), FerropassError> {
+    let previous = if events::is_listened() { saved_before(storage, filepath, key) } else { None };
+    write_database(storage, database, filepath, key)?;
+    events::emit_saved(filepath, previous.as_ref(), database);
+    Ok(())
+}
+
+// What the file holds before a full save, for the entry events: nothing for
+// a new file, and unknown when the key the save uses doesn't open it.
+fn saved_before(storage: &dyn VaultStorage, filepath: &Path, key: &SessionKey) -> Option<Database> {
+    if !storage.exists(filepath) {
+        return Some(Database::new());
+    }
+    EncryptedData::parse(&storage.read_text(filepath).ok()?).ok()?.open(&key.key).ok()
+}
+
+fn write_database(storage: &dyn VaultStorage, database: &Database, filepath: &Path, key: &SessionKey) -> Result<(), FerropassError> {
+    let file_content = storage.read_text(filepath).ok();
+    let current = file_content.as_deref().and_then(|content| EncryptedData::parse(content).ok());
+    guard_revision(storage, database, filepath, key, current.as_ref())?;
+    
+    // A vault that keeps its history is appended to whenever its file can
+    // take it, so not even a full save drops the edits before it.
+    if database.get_settings().get_keep_history()
+        && let (Some(file_content), Some(current)) = (&file_content, &current)
+        && current.takes_appends(file_content)
+        && key.matches(current)
+        && current.signing_key == key.signing_key
+    {
+        let previous = current.open(&key.key)?;
+        let changes = previous.diff(database);
+        if changes.is_empty() {
+            return Ok(());
+        }
+        return append_changes(storage, &previous, changes, filepath, key, current, file_content);
+    }
+    
+    // Past both the revision the session knows and the file's own, in case
+    // the file was removed or is new to the session.
+    let revision = current.as_ref().map(EncryptedData::revision)
+        .max(key.seen_revision(filepath).map(|seen| seen.number()))
+        .unwrap_or(0) + 1;
+    
+    let signer = key.signer()?;
+    let encrypted_data = write_snapshot(storage, database, filepath, key, revision)?;
+    key.remember_revision(filepath, Revision::of(&encrypted_data));
+    after_write(storage, filepath, key, signer.as_ref(), Save::Full { entries: database.get_accounts().len() })
+}
+
+fn write_snapshot(storage: &dyn VaultStorage, database: &Database, filepath: &Path, key: &SessionKey, revision: u64) -> Result<EncryptedData, FerropassError> {
+    let cipher = key.aead()?;
+    let nonce = generate_nonce(cipher.as_ref())?;
+    
+    let mut encrypted_data = EncryptedData {
+        version: CURRENT_FORMAT_VERSION,
+        kdf: Some(key.kdf.clone()),
+        cipher: key.cipher.clone(),
+        chunk_size: Some(CHUNK_SIZE as u32),
+        salt: key.salt.clone(),
+        wrapped_key: Some(key.wrapped_key.clone()),
+        nonce: general_purpose::STANDARD.encode(&nonce),
+        data: String::new(),
+        recovery: key.recovery.clone(),
+        signing_key: key.signing_key.clone(),
+        revision,
+        vault_id: Some(key.vault_id.clone()),
+        journal_chain: Some(JOURNAL_CHAIN.to_string()),
+        journal: Vec::new(),
+    };
+    let aad = encrypted_data.associated_data();
+    
+    // The database is serialized straight into the encryption, so its
+    // plaintext is only ever held a chunk at a time.
+    let encoder = EncoderStringWriter::new(&general_purpose::STANDARD);
+    let mut writer = ChunkWriter::new(cipher.as_ref(), &key.key, &nonce, &aad, CHUNK_SIZE, encoder);
+    serde_json::to_writer(&mut writer, database)
+        .map_err(|e| FerropassError::Format(format!("Error serializing database: {}", e)))?;
+    
+    encrypted_data.data = writer.finish()
+        .map_err(|e| FerropassError::Crypto(format!("Error encrypting data: {}", e)))?
+        .into_inner();
+    
+    let mut encrypted_json = serde_json::to_string(&encrypted_data)
+        .map_err(|e| FerropassError::Format(format!("Error serializing encrypted data: {}", e)))?;
+    encrypted_json.push('\n');
+    
+    storage.write_blob(filepath, encrypted_json.as_bytes())?;
+    Ok(encrypted_data)
+}
+
+// Refuses to save over a file that changed since the session last read or
+// wrote it, as happens when another machine saves to a synced or shared
+// folder. The session's database is written next to it instead, to be merged
+// in, so neither side's changes are lost.
+fn guard_revision(storage: &dyn VaultStorage, database: &Database, filepath: &Path, key: &SessionKey, current: Option<&EncryptedData>) -> Result<(), FerropassError> {
+    let (Some(seen), Some(current)) = (key.seen_revision(filepath), current.map(Revision::of)) else {
+        return Ok(());
+    };
+    if seen == current {
+        return Ok(());
+    }
+    
+    let what = if current.number() > seen.number() {
+        format!("revision {} was saved there since this session had revision {}", current.number(), seen.number())
+    } else {
+        format!("it was replaced since this session had revision {}", seen.number())
+    };
+    let copy = conflict_copy_path(filepath);
+    let saved = key.signer().and_then(|signer| {
+        write_snapshot(storage, database, &copy, key, seen.number() + 1)?;
+        signer.map_or(Ok(()), |signer| signing::sign(storage, &copy, &signer))
+    });
+    
+    Err(FerropassError::Io(match saved {
+        Ok(()) => format!(
+            "Not saving over {}: {}. Your changes were saved to {} instead; close the vault, then merge them in with `ferropass merge {} {}`",
+            filepath.display(), what, copy.display(), filepath.display(), copy.display()
+        ),
+        Err(e) => format!("Not saving over {}: {}, and your changes couldn't be saved aside: {}", filepath.display(), what, e),
+    }))
+}
+
+// "personal.fp" becomes "personal.conflict.fp".
+fn conflict_copy_path(filepath: &Path) -> PathBuf {
+    let stem = filepath.file_stem().map_or_else(|| "vault".into(), |stem| stem.to_string_lossy().to_string());
+    let extension = filepath.extension().map_or_else(|| "fp".into(), |extension| extension.to_string_lossy().to_string());
+    filepath.with_file_name(format!("{}.conflict.{}", stem, extension))
+}
+
+// Appends only what changed between `previous` (the state last written to
+// the file) and `database`, falling back to a full save for legacy files or
+// once the journal is due for compaction, which a vault keeping its history
+// never is.
+pub fn save_changes(storage: &dyn VaultStorage, previous: &Database, database: &Database, filepath: &Path, key: &SessionKey) -> Result<(), FerropassError> {
+    let changes = previous.diff(database);
+    if changes.is_empty() {
+        return Ok(());
+    }
+    
+    // Appending after an unterminated line (a file from before the journal
+    // existed, or an interrupted append) would corrupt it, so compact instead.
+    let file_content = storage.read_text(filepath).unwrap_or_default();
+    let encrypted_data = match EncryptedData::parse(&file_content) {
+        Ok(data) if data.takes_appends(&file_content)
+            && (database.get_settings().get_keep_history() || !data.needs_compaction()) => data,
+        _ => {
+            write_database(storage, database, filepath, key)?;
+            events::emit_saved(filepath, Some(previous), database);
+            return Ok(());
+        },
+    };
+    
+    guard_revision(storage, database, filepath, key, Some(&encrypted_data))?;
+    append_changes(storage, previous, changes, filepath, key, &encrypted_data, &file_content)?;
+    events::emit_saved(filepath, Some(previous), database);
+    Ok(())
+}
+
+fn append_changes(storage: &dyn VaultStorage, previous: &Database, changes: Vec<Change>, filepath: &Path, key: &SessionKey, encrypted_data: &EncryptedData, file_content: &str) -> Result<(), FerropassError> {
+    if !key.matches(encrypted_data) {
+        return Err(FerropassError::Io("The database file was re-encrypted since it was unlocked, reopen it to save changes".to_string()));
+    }
+    encrypted_data.check_signature(storage, filepath, file_content)?;
+    encrypted_data.authenticate(&key.key)?;
+    
+    let save = Save::of_changes(previous, &changes);
+    let json = serde_json::to_string(&RecordBody::Timed { at: current_timestamp(), changes })
+        .map_err(|e| FerropassError::Format(format!("Error serializing changes: {}", e)))?;
+    
+    let seq = encrypted_data.journal.len() as u64;
+    let cipher = key.aead()?;
+    let nonce = generate_nonce(cipher.as_ref())?;
+    let ciphertext = cipher.encrypt(&key.key, &nonce, json.as_bytes(), &encrypted_data.journal_associated_data(seq))?;
+    
+    let record = JournalRecord {
+        seq,
+        nonce: general_purpose::STANDARD.encode(&nonce),
+        data: general_purpose::STANDARD.encode(ciphertext),
+    };
+    let mut record_json = serde_json::to_string(&record)
+        .map_err(|e| FerropassError::Format(format!("Error serializing journal record: {}", e)))?;
+    record_json.push('\n');
+    
+    let signer = key.signer()?;
+    storage.append_blob(filepath, record_json.as_bytes())?;
+    key.remember_revision(filepath, Revision { journal_len: encrypted_data.journal.len() + 1, ..Revision::of(encrypted_data) });
+    after_write(storage, filepath, key, signer.as_ref(), save)
+}
+
+// Rewrites only the header with the session's wrapped data key, keeping the
+// encrypted data and journal as they are, for a new passkey or pepper.
+// Files from before version 3 are re-encrypted in full instead.
+pub fn rewrap_and_save(storage: &dyn VaultStorage, database: &Database, filepath: &Path, key: &SessionKey) -> Result<(), FerropassError> {
+    let file_content = storage.read_text(filepath).unwrap_or_default();
+    let mut encrypted_data = match EncryptedData::parse(&file_content) {
+        Ok(data) if data.takes_appends(&file_content)
+            && data.cipher == key.cipher
+            && data.signing_key == key.signing_key => data,
+        _ => return encrypt_and_save_database(storage, database, filepath, key),
+    };
+    
+    guard_revision(storage, database, filepath, key, Some(&encrypted_data))?;
+    encrypted_data.check_signature(storage, filepath, &file_content)?;
+    encrypted_data.authenticate(&key.key)
+        .map_err(|_| FerropassError::Io("The database file was re-encrypted since it was unlocked, reopen it to change how it is unlocked".to_string()))?;
+    
+    encrypted_data.revision += 1;
+    encrypted_data.kdf = Some(key.kdf.clone());
+    encrypted_data.salt = key.salt.clone();
+    encrypted_data.wrapped_key = Some(key.wrapped_key.clone());
+    encrypted_data.recovery = key.recovery.clone();
+    encrypted_data.vault_id = Some(key.vault_id.clone());
+    
+    let mut contents = serde_json::to_string(&encrypted_data)
+        .map_err(|e| FerropassError::Format(format!("Error serializing encrypted data: {}", e)))?;
+    contents.push('\n');
+    for record in &encrypted_data.journal {
+        contents.push_str(&serde_json::to_string(record).map_err(|e| FerropassError::Format(format!("Error serializing journal record: {}", e)))?);
+        contents.push('\n');
+    }
+    
+    let signer = key.signer()?;
+    storage.write_blob(filepath, contents.as_bytes())?;
+    key.remember_revision(filepath, Revision::of(&encrypted_data));
+    after_write(storage, filepath, key, signer.as_ref(), Save::Rekeyed)?;
+    events::emit(&Event::Saved { path: filepath.to_path_buf() });
+    Ok(())
+}
+
+// Signs the vault if it is signed and tells the storage it was saved, and
+// what the save wrote.
+fn after_write(storage: &dyn VaultStorage, filepath: &Path, key: &SessionKey, signer: Option<&SigningKey>, save: Save) -> Result<(), FerropassError> {
+    if let Some(revision) = key.seen_revision(filepath) {
+        remember_journal(storage, filepath, &revision)?;
+    }
+    if let Some(signer) = signer {
+        signing::sign(storage, filepath, signer)?;
+    }
+    storage.saved(filepath, save);
+    Ok(())
+}
+
+// Opens the file and hands back the data key for the session.
+pub fn unlock_database(storage: &dyn VaultStorage, filepath: &Path, passkey: &str) -> Result<(Database, SessionKey), FerropassError> {
+    let encrypted_data = EncryptedData::read_signed(storage, filepath)?;
+    let revision = Revision::of(&encrypted_data);
+    let unlocked = throttled(storage, filepath, || unlock(filepath, encrypted_data, passkey))?;
+    remember_journal(storage, filepath, &revision)?;
+    events::emit(&Event::Unlocked { path: filepath.to_path_buf() });
+    Ok(unlocked)
+}
+
+// For re-signing a vault whose signature no longer matches: decrypting with
+// the passkey authenticates the contents instead.
+pub fn unlock_ignoring_signature(storage: &dyn VaultStorage, filepath: &Path, passkey: &str) -> Result<(Database, SessionKey), FerropassError> {
+    let encrypted_data = EncryptedData::read(storage, filepath)?;
+    let unlocked = throttled(storage, filepath, || unlock(filepath, encrypted_data, passkey))?;
+    events::emit(&Event::Unlocked { path: filepath.to_path_buf() });
+    Ok(unlocked)
+}
+
+// Wrong passkeys are only counted for vaults that outlive the process.
+#[cfg(feature = "fs")]
+fn throttled<T>(storage: &dyn VaultStorage, filepath: &Path, attempt: impl FnOnce() -> Result<T, FerropassError>) -> Result<T, FerropassError> {
+    if storage.is_persistent() {
+        throttle::guard(filepath, attempt)
+    } else {
+        attempt()
+    }
+}
+
+// Without local files there is nowhere to count them.
+#[cfg(not(feature = "fs"))]
+fn throttled<T>(_storage: &dyn VaultStorage, _filepath: &Path, attempt: impl FnOnce() -> Result<T, FerropassError>) -> Result<T, FerropassError> {
+    attempt()
+}
+
+// Refuses a file whose journal is shorter than when this machine last saw
+// the same base snapshot.
+#[cfg(feature = "fs")]
+fn check_journal(storage: &dyn VaultStorage, filepath: &Path, encrypted_data: &EncryptedData) -> Result<(), FerropassError> {
+    if !storage.is_persistent() {
+        return Ok(());
+    }
+    let seen: HashMap<String, SeenJournal> = config::load_state(JOURNAL_STATE_FILE)?;
+    match seen.get(&config::vault_id(filepath)) {
+        Some(seen) if seen.nonce == encrypted_data.nonce && seen.records > encrypted_data.journal.len() => Err(FerropassError::Crypto(format!(
+            "{} has {} journal record(s), but {} were seen here; saves may have been removed from it. `ferropass restore-backup` puts back an earlier version on purpose",
+            filepath.display(), encrypted_data.journal.len(), seen.records
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(feature = "fs")]
+fn remember_journal(storage: &dyn VaultStorage, filepath: &Path, revision: &Revision) -> Result<(), FerropassError> {
+    if !storage.is_persistent() {
+        return Ok(());
+    }
+    let journal = SeenJournal { nonce: revision.nonce.clone(), records: revision.journal_len };
+    config::update_state(JOURNAL_STATE_FILE, |seen: &mut HashMap<String, SeenJournal>| {
+        seen.insert(config::vault_id(filepath), journal);
+    })
+}
+
+// Lets the vault now at `filepath` be opened with however many records it
+// has, after an earlier version was put back on purpose.
+#[cfg(feature = "fs")]
+pub fn forget_journal(filepath: &Path) -> Result<(), FerropassError> {
+    config::update_state(JOURNAL_STATE_FILE, |seen: &mut HashMap<String, SeenJournal>| {
+        seen.remove(&config::vault_id(filepath));
+    })
+}
+
+#[cfg(not(feature = "fs"))]
+fn check_journal(_storage: &dyn VaultStorage, _filepath: &Path, _encrypted_data: &EncryptedData) -> Result<(), FerropassError> {
+    Ok(())
+}
+
+#[cfg(not(feature = "fs"))]
+fn remember_journal(_storage: &dyn VaultStorage, _filepath: &Path, _revision: &Revision) -> Result<(), FerropassError> {
+    Ok(())
+}
+
+fn unlock(filepath: &Path, encrypted_data: EncryptedData, passkey: &str) -> Result<(Database, SessionKey), FerropassError> {
+    let kdf = encrypted_data.kdf_params()?;
+    let derived_key = derive_key_with_salt(passkey, &encrypted_data.salt, &kdf)?;
+    let key = encrypted_data.unwrap_key(&derived_key)?;
+    
+    let mut database = encrypted_data.open(&key)?;
+    
+    let session_key = match &encrypted_data.wrapped_key {
+        Some(wrapped_key) => SessionKey {
+            key,
+            salt: encrypted_data.salt.clone(),
+            kdf,
+            cipher: encrypted_data.cipher.clone(),
+            wrapped_key: wrapped_key.clone(),
+            recovery: encrypted_data.recovery.clone(),
+            signing_key: encrypted_data.signing_key.clone(),
+            vault_id: encrypted_data.vault_id(),
+            revisions: Arc::default(),
+        },
+        None => SessionKey::from_legacy(&derived_key, &encrypted_data, kdf)?,
+    };
+    session_key.entry_key().seal_plaintext(&mut database)?;
+    session_key.remember_revision(filepath, Revision::of(&encrypted_data));
+    
+    Ok((database, session_key))
+}
+
+// Opens the file with a recovery key rebuilt from shares instead of the
+// passkey.
+pub fn unlock_with_recovery_key(storage: &dyn VaultStorage, filepath: &Path, recovery_key: &[u8; 32]) -> Result<(Database, SessionKey), FerropassError> {
+    let encrypted_data = EncryptedData::read_signed(storage, filepath)?;
+    let kdf = encrypted_data.kdf_params()?;
+    let slot = encrypted_data.recovery.clone()
+        .ok_or_else(|| FerropassError::Validation("Recovery shares are not set up for this vault".to_string()))?;
+    let cipher = encrypted_data.cipher()?;
+    
+    let Some(wrapped_key) = encrypted_data.wrapped_key.clone() else {
+        let derived_key = slot.open_key(cipher.as_ref(), recovery_key, Some(&encrypted_data.salt))?;
+        let mut database = encrypted_data.open(&derived_key)?;
+        let session_key = SessionKey::from_legacy(&derived_key, &encrypted_data, kdf)?;
+        session_key.entry_key().seal_plaintext(&mut database)?;
+        session_key.remember_revision(filepath, Revision::of(&encrypted_data));
+        events::emit(&Event::Unlocked { path: filepath.to_path_buf() });
+        return Ok((database, session_key));
+    };
+    
+    let key = slot.open_key(cipher.as_ref(), recovery_key, None)?;
+    let mut database = encrypted_data.open(&key)?;
+    encrypted_data.entry_key(&key).seal_plaintext(&mut database)?;
+    
+    let session_key = SessionKey {
+        key,
+        salt: encrypted_data.salt.clone(),
+        kdf,
+        cipher: encrypted_data.cipher.clone(),
+        wrapped_key,
+        recovery: Some(slot),
+        signing_key: encrypted_data.signing_key.clone(),
+        vault_id: encrypted_data.vault_id(),
+        revisions: Arc::default(),
+    };
+    session_key.remember_revision(filepath, Revision::of(&encrypted_data));
+    events::emit(&Event::Unlocked { path: filepath.to_path_buf() });
+    Ok((database, session_key))
+}
+
+// Reads the header alone, without the passkey or checking the signature, so
+// a vault can be identified and inspected before it is unlocked. `kdf` is
+// None when the parameters aren't ones this version supports.
+pub fn peek_header(storage: &dyn VaultStorage, filepath: &Path) -> Result<Header, FerropassError> {
+    Ok(EncryptedData::read(storage, filepath)?.header())
+}
+
+// Describes what is outdated about the file's header, if anything, so it can
+// be offered a re-encryption with the current format and parameters.
+pub fn upgrade_reason(storage: &dyn VaultStorage, filepath: &Path) -> Option<String> {
+    let encrypted_data = EncryptedData::read(storage, filepath).ok()?;
+    let kdf = encrypted_data.kdf_params().ok()?;
+    let current = KdfParams::current();
+    
+    if encrypted_data.version < CURRENT_FORMAT_VERSION {
+        Some(format!(
+            "it uses format version {}, the current version is {}",
+            encrypted_data.version, CURRENT_FORMAT_VERSION
+        ))
+    } else if kdf.is_weaker_than(&current) {
+        Some(format!(
+            "its key derivation parameters ({} m={} t={}) are weaker than the current ones ({} m={} t={})",
+            kdf.algorithm, kdf.m_cost, kdf.t_cost, current.algorithm, current.m_cost, current.t_cost
+        ))
+    } else {
+        None
+    }
+}
+
+// Opens a file read-only, handing back the key its passwords are sealed
+// under instead of a session.
+pub fn load_and_decrypt_database(storage: &dyn VaultStorage, filepath: &Path, passkey: &str) -> Result<(Database, EntryKey), FerropassError> {
+    let encrypted_data = EncryptedData::read_signed(storage, filepath)?;
+    let (key, mut database) = throttled(storage, filepath, || {
+        let key = encrypted_data.unlock_key(passkey)?;
+        let database = encrypted_data.open(&key)?;
+        Ok((key, database))
+    })?;
+    remember_journal(storage, filepath, &Revision::of(&encrypted_data))?;
+    
+    let entry_key = encrypted_data.entry_key(&key);
+    entry_key.seal_plaintext(&mut database)?;
+    
+    events::emit(&Event::Unlocked { path: filepath.to_path_buf() });
+    Ok((database, entry_key))
+}
+
+fn generate_nonce(cipher: &dyn AeadCipher) -> Result<Vec<u8>, FerropassError> {
+    entropy::ensure()?;
+    
+    let mut nonce = vec![0u8; cipher.nonce_len()];
+    OsRng.fill_bytes(&mut nonce);
+    Ok(nonce)
+}
+
+// A random version 4 UUID.
+fn generate_vault_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+fn generate_key() -> Result<SecretKey, FerropassError> {
+    entropy::ensure()?;
+    
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    Ok(SecretKey::new(&mut key))
+}
\ No newline at end of file
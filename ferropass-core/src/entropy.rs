@@ -0,0 +1,62 @@
+use crate::error::FerropassError;
+
+use std::sync::OnceLock;
+
+const PROBE_LEN: usize = 32;
+
+static STATUS: OnceLock<Result<(), FerropassError>> = OnceLock::new();
+
+// Probes the OS random source once; later calls return the same result.
+// Anything that generates keys, salts, nonces or passwords calls this first,
+// so a broken source is refused instead of silently used.
+pub fn ensure() -> Result<(), FerropassError> {
+    STATUS.get_or_init(probe).clone()
+}
+
+fn probe() -> Result<(), FerropassError> {
+    let mut buffer = [0u8; PROBE_LEN];
+    fill(&mut buffer).map_err(|e| FerropassError::Crypto(format!("The system's random number source is unavailable ({})", e)))?;
+
+    // 32 zero bytes from a working source is not a realistic outcome.
+    if buffer.iter().all(|&byte| byte == 0) {
+        return Err(FerropassError::Crypto("The system's random number source returned no randomness".to_string()));
+    }
+
+    Ok(())
+}
+
+// On Linux, ask without blocking so an entropy pool that hasn't been
+// initialised yet (common early in boot, in containers and on embedded
+// boards) is reported instead of hanging.
+#[cfg(target_os = "linux")]
+fn fill(buffer: &mut [u8]) -> Result<(), FerropassError> {
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        let remaining = &mut buffer[filled..];
+        let result = unsafe {
+            libc::getrandom(remaining.as_mut_ptr().cast(), remaining.len(), libc::GRND_NONBLOCK)
+        };
+
+        if result < 0 {
+            let error = std::io::Error::last_os_error();
+            match error.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                Some(libc::EAGAIN) => return Err(FerropassError::Crypto("the entropy pool is not initialised yet".to_string())),
+                _ => return Err(FerropassError::Crypto(error.to_string())),
+            }
+        }
+
+        filled += result as usize;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn fill(buffer: &mut [u8]) -> Result<(), FerropassError> {
+    use rand::RngCore;
+    use rand::rngs::OsRng;
+
+    OsRng.try_fill_bytes(buffer).map_err(|e| FerropassError::Crypto(e.to_string()))
+}
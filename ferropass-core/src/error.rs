@@ -0,0 +1,46 @@
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+// Every error the library returns. The message is written for the user, as
+// the command line shows it; the variant tells callers what kind of failure
+// it was.
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "message", rename_all = "kebab-case")]
+pub enum FerropassError {
+    // A file couldn't be read or written, or a server or command couldn't be
+    // reached.
+    #[error("{0}")]
+    Io(String),
+    // Encryption, key derivation, signing or the random number source failed.
+    #[error("{0}")]
+    Crypto(String),
+    // The passkey is wrong, or the data it opens was changed.
+    #[error("Invalid passkey or corrupted database file")]
+    InvalidPasskey,
+    // A vault, export or other input isn't in the format it should be.
+    #[error("{0}")]
+    Format(String),
+    // The caller asked for something that isn't allowed, or doesn't exist.
+    #[error("{0}")]
+    Validation(String),
+}
+
+impl FerropassError {
+    // The same kind of error, with `context` in front of its message.
+    pub fn context(self, context: impl std::fmt::Display) -> Self {
+        match self {
+            FerropassError::Io(message) => FerropassError::Io(format!("{}: {}", context, message)),
+            FerropassError::Crypto(message) => FerropassError::Crypto(format!("{}: {}", context, message)),
+            FerropassError::InvalidPasskey => FerropassError::InvalidPasskey,
+            FerropassError::Format(message) => FerropassError::Format(format!("{}: {}", context, message)),
+            FerropassError::Validation(message) => FerropassError::Validation(format!("{}: {}", context, message)),
+        }
+    }
+}
+
+// The command line reports errors as text.
+impl From<FerropassError> for String {
+    fn from(error: FerropassError) -> Self {
+        error.to_string()
+    }
+}
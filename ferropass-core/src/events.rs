@@ -0,0 +1,131 @@
+use crate::models::Database;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+
+// Something that happened to a vault, for audit logs, notifications or
+// caches kept outside it. Each names the vault, so one set of hooks can
+// watch several.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Unlocked { path: PathBuf },
+    EntryAdded { path: PathBuf, id: String },
+    EntryEdited { path: PathBuf, id: String },
+    EntryDeleted { path: PathBuf, id: String },
+    Saved { path: PathBuf },
+}
+
+impl Event {
+    pub fn get_path(&self) -> &Path {
+        match self {
+            Event::Unlocked { path }
+            | Event::EntryAdded { path, .. }
+            | Event::EntryEdited { path, .. }
+            | Event::EntryDeleted { path, .. }
+            | Event::Saved { path } => path,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Unlocked { path } => write!(f, "Unlocked {}", path.display()),
+            Event::EntryAdded { path, id } => write!(f, "Added entry {} to {}", id, path.display()),
+            Event::EntryEdited { path, id } => write!(f, "Edited entry {} in {}", id, path.display()),
+            Event::EntryDeleted { path, id } => write!(f, "Deleted entry {} from {}", id, path.display()),
+            Event::Saved { path } => write!(f, "Saved {}", path.display()),
+        }
+    }
+}
+
+type Listener = Arc<dyn Fn(&Event) + Send + Sync>;
+
+// The callbacks a vault calls as things happen to it, in the order they
+// were added. Cloning shares the callbacks rather than copying them.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    listeners: Vec<Listener>,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Hooks::default()
+    }
+
+    pub fn add(&mut self, listener: impl Fn(&Event) + Send + Sync + 'static) {
+        self.listeners.push(Arc::new(listener));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    pub fn emit(&self, event: &Event) {
+        for listener in &self.listeners {
+            listener(event);
+        }
+    }
+
+    // Reports the entries `database` adds, edits and deletes since `saved`,
+    // then the save itself.
+    pub fn emit_saved(&self, path: &Path, saved: &Database, database: &Database) {
+        if self.is_empty() {
+            return;
+        }
+
+        for account in database.get_accounts() {
+            let id = account.get_id().to_string();
+            match saved.get_account_by_id(&id) {
+                None => self.emit(&Event::EntryAdded { path: path.to_path_buf(), id }),
+                Some(previous) if previous != account => self.emit(&Event::EntryEdited { path: path.to_path_buf(), id }),
+                Some(_) => {},
+            }
+        }
+        for account in saved.get_accounts() {
+            if database.get_account_by_id(account.get_id()).is_none() {
+                self.emit(&Event::EntryDeleted { path: path.to_path_buf(), id: account.get_id().to_string() });
+            }
+        }
+        self.emit(&Event::Saved { path: path.to_path_buf() });
+    }
+}
+
+// Hooks for every vault the process unlocks or saves, however it does: the
+// shared unlock and save functions emit to them, so callers that don't go
+// through `Vault`, such as the interactive session and the agent, are heard
+// as well.
+static GLOBAL: RwLock<Hooks> = RwLock::new(Hooks { listeners: Vec::new() });
+
+pub fn listen(listener: impl Fn(&Event) + Send + Sync + 'static) {
+    GLOBAL.write().unwrap_or_else(PoisonError::into_inner).add(listener);
+}
+
+pub fn clear_listeners() {
+    *GLOBAL.write().unwrap_or_else(PoisonError::into_inner) = Hooks::new();
+}
+
+// A copy, so a listener may add another without waiting on itself.
+fn global() -> Hooks {
+    GLOBAL.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+pub(crate) fn is_listened() -> bool {
+    !global().is_empty()
+}
+
+// For code that opens vaults its own way, so its listeners hear of it too.
+pub fn emit(event: &Event) {
+    global().emit(event);
+}
+
+// A save, with the entries it changed when what the file held before is
+// known.
+pub(crate) fn emit_saved(path: &Path, previous: Option<&Database>, database: &Database) {
+    let hooks = global();
+    match previous {
+        Some(previous) => hooks.emit_saved(path, previous, database),
+        None => hooks.emit(&Event::Saved { path: path.to_path_buf() }),
+    }
+}
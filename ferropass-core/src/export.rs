@@ -0,0 +1,148 @@
+use crate::config;
+use crate::encryption::EntryKey;
+use crate::error::FerropassError;
+use crate::models::{format_timestamp, Account, Database};
+use crate::query::Query;
+
+use serde::Serialize;
+use std::path::Path;
+
+// Writes entries out in one unencrypted format, for leaving ferropass or
+// handing a vault to an auditor. The built-in formats are below; a program
+// adds its own to a `formats::Registry`.
+pub trait Exporter: Send + Sync {
+    // What `--plaintext` takes, e.g. "csv".
+    fn name(&self) -> &str;
+
+    // What `--list-formats` shows next to the name.
+    fn description(&self) -> &str;
+
+    // The file's contents, with every password in the clear.
+    fn write(&self, entries: &[PlainEntry]) -> Result<String, FerropassError>;
+}
+
+// One entry opened for export. The CSV columns are named so the file imports
+// back into ferropass, and into most other password managers, as a
+// 1Password-style CSV.
+#[derive(Serialize)]
+pub struct PlainEntry {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub group: String,
+    pub tags: String,
+    pub username: String,
+    pub password: String,
+    pub totp: String,
+    pub notes: String,
+    pub created: String,
+    pub updated: String,
+}
+
+pub fn built_in() -> Vec<Box<dyn Exporter>> {
+    vec![Box::new(Csv), Box::new(Json)]
+}
+
+struct Csv;
+
+impl Exporter for Csv {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    fn description(&self) -> &str {
+        "CSV in 1Password's columns"
+    }
+
+    fn write(&self, entries: &[PlainEntry]) -> Result<String, FerropassError> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for entry in entries {
+            writer.serialize(entry).map_err(|e| FerropassError::Io(format!("Failed to write CSV: {}", e)))?;
+        }
+        let bytes = writer.into_inner().map_err(|e| FerropassError::Io(format!("Failed to write CSV: {}", e)))?;
+        String::from_utf8(bytes).map_err(|e| FerropassError::Io(format!("Failed to write CSV: {}", e)))
+    }
+}
+
+struct Json;
+
+impl Exporter for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn description(&self) -> &str {
+        "JSON array of entries"
+    }
+
+    fn write(&self, entries: &[PlainEntry]) -> Result<String, FerropassError> {
+        serde_json::to_string_pretty(entries)
+            .map_err(|e| FerropassError::Io(format!("Failed to write JSON: {}", e)))
+    }
+}
+
+// Which entries to export. Each kind of criterion given narrows the
+// selection further, and with none every entry is exported.
+pub struct Selection {
+    pub ids: Vec<String>,
+    pub tags: Vec<String>,
+    pub groups: Vec<String>,
+    pub search: Option<String>,
+}
+
+impl Selection {
+    pub fn query<'a>(&self, database: &'a Database) -> Query<'a> {
+        let mut query = database.query();
+        for id in &self.ids {
+            query = query.id(id);
+        }
+        for tag in &self.tags {
+            query = query.tag(tag);
+        }
+        for group in &self.groups {
+            query = query.group(group);
+        }
+        if let Some(search) = &self.search {
+            query = query.search(search);
+        }
+        query
+    }
+}
+
+// The accounts to export, at least one.
+pub fn select<'a>(database: &'a Database, selection: &Selection, filepath: &Path) -> Result<Vec<&'a Account>, FerropassError> {
+    if let Some(id) = selection.ids.iter().find(|id| database.get_account_by_id(id).is_none()) {
+        return Err(FerropassError::Validation(format!("No account with ID {} in {}", id, filepath.display())));
+    }
+
+    let accounts: Vec<&Account> = selection.query(database).into_iter().collect();
+    if accounts.is_empty() {
+        return Err(FerropassError::Validation(format!("No accounts in {} match the selection", filepath.display())));
+    }
+    Ok(accounts)
+}
+
+// Writes the accounts out unencrypted into a new file only the user can
+// read, returning how many there were.
+pub fn write_plaintext(accounts: &[&Account], output: &Path, exporter: &dyn Exporter, entry_key: &EntryKey) -> Result<usize, FerropassError> {
+    let mut entries = Vec::new();
+    for account in accounts {
+        entries.push(PlainEntry {
+            id: account.get_id().to_string(),
+            title: account.get_description().clone().unwrap_or_default(),
+            url: account.get_url().unwrap_or_default().to_string(),
+            group: account.get_group().unwrap_or_default().to_string(),
+            tags: account.get_tags().join(", "),
+            username: account.get_username_or_email().to_string(),
+            password: entry_key.open(account.get_password())?,
+            totp: account.get_totp().map(|totp| entry_key.open(totp)).transpose()?.unwrap_or_default(),
+            notes: account.get_notes().clone().unwrap_or_default(),
+            created: format_timestamp(account.get_created_at()),
+            updated: format_timestamp(account.get_updated_at()),
+        });
+    }
+
+    let contents = exporter.write(&entries)?;
+    config::write_private_file(output, &contents)?;
+    Ok(entries.len())
+}
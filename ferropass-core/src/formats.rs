@@ -0,0 +1,74 @@
+use crate::error::FerropassError;
+use crate::export::{self, Exporter};
+use crate::importers::{self, Importer};
+
+use std::path::Path;
+
+// The formats a program can import from and export to: the built-in ones,
+// and any it adds. A format added under the name of one already there
+// replaces it.
+pub struct Registry {
+    importers: Vec<Box<dyn Importer>>,
+    exporters: Vec<Box<dyn Exporter>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry { importers: importers::built_in(), exporters: export::built_in() }
+    }
+
+    // Detection tries importers in the order they were added, so one added
+    // later only gets the files the built-in ones don't recognise.
+    pub fn add_importer(&mut self, importer: impl Importer + 'static) {
+        match self.importers.iter().position(|known| known.name() == importer.name()) {
+            Some(index) => self.importers[index] = Box::new(importer),
+            None => self.importers.push(Box::new(importer)),
+        }
+    }
+
+    pub fn add_exporter(&mut self, exporter: impl Exporter + 'static) {
+        match self.exporters.iter().position(|known| known.name() == exporter.name()) {
+            Some(index) => self.exporters[index] = Box::new(exporter),
+            None => self.exporters.push(Box::new(exporter)),
+        }
+    }
+
+    pub fn get_importers(&self) -> &[Box<dyn Importer>] {
+        &self.importers
+    }
+
+    pub fn get_exporters(&self) -> &[Box<dyn Exporter>] {
+        &self.exporters
+    }
+
+    pub fn importer(&self, name: &str) -> Result<&dyn Importer, FerropassError> {
+        self.importers.iter()
+            .find(|importer| importer.name().eq_ignore_ascii_case(name))
+            .map(Box::as_ref)
+            .ok_or_else(|| FerropassError::Validation(format!("There is no import format named '{}'; the formats are {}", name, names(&self.importers, |importer| importer.name()))))
+    }
+
+    pub fn exporter(&self, name: &str) -> Result<&dyn Exporter, FerropassError> {
+        self.exporters.iter()
+            .find(|exporter| exporter.name().eq_ignore_ascii_case(name))
+            .map(Box::as_ref)
+            .ok_or_else(|| FerropassError::Validation(format!("There is no export format named '{}'; the formats are {}", name, names(&self.exporters, |exporter| exporter.name()))))
+    }
+
+    pub fn detect(&self, source: &Path) -> Result<&dyn Importer, FerropassError> {
+        self.importers.iter()
+            .find(|importer| importer.detect(source))
+            .map(Box::as_ref)
+            .ok_or_else(|| FerropassError::Format(format!("Can't tell the format of {}, use --format; `ferropass import --list-formats` lists them", source.display())))
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::new()
+    }
+}
+
+fn names<T: ?Sized>(formats: &[Box<T>], name: impl Fn(&T) -> &str) -> String {
+    formats.iter().map(|format| name(format)).collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,254 @@
+use crate::config;
+use crate::encryption::{load_and_decrypt_database, EntryKey};
+use crate::error::FerropassError;
+use crate::models::Database;
+use crate::notice;
+use crate::paranoid;
+use crate::signing;
+#[cfg(feature = "fs")]
+use crate::storage::FileStorage;
+use crate::storage::{MemoryStorage, Save, VaultStorage};
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The vault's directory, where git is run, and the names of the vault file
+// and its signature relative to it.
+pub struct VaultPaths {
+    pub dir: PathBuf,
+    pub file: String,
+    pub signature: String,
+}
+
+impl VaultPaths {
+    pub fn of(filepath: &Path) -> Result<Self, FerropassError> {
+        let file = filepath.file_name()
+            .ok_or_else(|| FerropassError::Validation("Database path has no file name".to_string()))?
+            .to_string_lossy()
+            .to_string();
+        let dir = match filepath.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let signature = signing::signature_path(Path::new(&file)).to_string_lossy().to_string();
+
+        Ok(VaultPaths { dir, file, signature })
+    }
+
+    // The vault and, for a signed vault, its signature, as they are to be
+    // committed together.
+    pub fn existing(&self) -> Vec<&str> {
+        [self.file.as_str(), self.signature.as_str()].into_iter()
+            .filter(|name| self.dir.join(name).exists())
+            .collect()
+    }
+
+    pub fn git(&self, args: &[&str]) -> Result<String, FerropassError> {
+        let output = self.git_bytes(args)?;
+        Ok(String::from_utf8_lossy(&output).trim_end().to_string())
+    }
+
+    // Output exactly as git gave it, for file contents.
+    pub fn git_bytes(&self, args: &[&str]) -> Result<Vec<u8>, FerropassError> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()
+            .map_err(|e| FerropassError::Io(format!("Failed to run git: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(FerropassError::Io(format!("git {} failed: {}", args[0], stderr.trim())));
+        }
+        Ok(output.stdout)
+    }
+
+    // For commands like `git diff --quiet` that answer through their status.
+    pub fn git_succeeds(&self, args: &[&str]) -> bool {
+        Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+
+    pub fn in_work_tree(&self) -> bool {
+        self.git_succeeds(&["rev-parse", "--is-inside-work-tree"])
+    }
+
+    pub fn merging(&self) -> bool {
+        self.git_succeeds(&["rev-parse", "-q", "--verify", "MERGE_HEAD"])
+    }
+
+    // Commits the vault's files alone, whatever else is staged, if they
+    // changed since the last commit.
+    pub fn commit(&self, message: &str) -> Result<bool, FerropassError> {
+        let paths = self.existing();
+        let mut add = vec!["add", "--"];
+        add.extend(&paths);
+        self.git(&add)?;
+
+        let mut unchanged = vec!["diff", "--cached", "--quiet", "--"];
+        unchanged.extend(&paths);
+        if self.git_succeeds(&unchanged) {
+            return Ok(false);
+        }
+
+        let mut commit = vec!["commit", "-q", "-m", message, "--"];
+        commit.extend(&paths);
+        self.git(&commit)?;
+        Ok(true)
+    }
+
+    // Decrypts the vault as it was at `commit`. The outer error is for a
+    // version that can't be read from git, the inner one for one that doesn't
+    // open with `passkey`.
+    pub fn load_version(&self, commit: &str, passkey: &str) -> Result<Result<(Database, EntryKey), FerropassError>, FerropassError> {
+        let storage = MemoryStorage::new();
+        storage.write_blob(Path::new(&self.file), &self.git_bytes(&["show", &format!("{}:./{}", commit, self.file)])?)?;
+        if let Ok(signature) = self.git_bytes(&["show", &format!("{}:./{}", commit, self.signature)]) {
+            storage.write_blob(Path::new(&self.signature), &signature)?;
+        }
+
+        Ok(load_and_decrypt_database(&storage, Path::new(&self.file), passkey))
+    }
+
+    pub fn commit_count(&self, range: &str) -> Result<usize, FerropassError> {
+        self.git(&["rev-list", "--count", range])?
+            .parse()
+            .map_err(|_| FerropassError::Io("git rev-list gave an unexpected answer".to_string()))
+    }
+}
+
+// What a sync brought in from upstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pulled {
+    Nothing,
+    FastForwarded(usize),
+    // Merged by git, without the vault changing on both sides.
+    Merged(usize),
+    // Merged entry by entry by the caller's `merge_vault`.
+    VaultMerged,
+}
+
+// What `sync` did, for the caller to report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncReport {
+    pub upstream: String,
+    pub committed: bool,
+    pub pulled: Pulled,
+    pub pushed: bool,
+}
+
+// Commits the vault if needed, pulls and pushes, without asking anything. A
+// vault changed on both sides can't be merged by git; this refuses to sync it,
+// and `sync_with` hands it to a merge of the caller's.
+#[cfg(feature = "fs")]
+pub fn sync(filepath: &Path) -> Result<SyncReport, FerropassError> {
+    sync_with(filepath, |paths, upstream, _| {
+        Err(FerropassError::Io(format!("{} was changed both here and on {}; `ferropass sync` can merge it", paths.file, upstream)))
+    })
+}
+
+// Like `sync`, with `merge_vault` given the upstream and the commit both sides
+// have in common when both changed the vault. It is left to merge the vault
+// with git's own merge, and commit the result.
+#[cfg(feature = "fs")]
+pub fn sync_with(
+    filepath: &Path,
+    merge_vault: impl FnOnce(&VaultPaths, &str, &str) -> Result<(), FerropassError>,
+) -> Result<SyncReport, FerropassError> {
+    paranoid::ensure_allowed("Git sync")?;
+
+    let paths = VaultPaths::of(filepath)?;
+    if !paths.in_work_tree() {
+        return Err(FerropassError::Io(format!("{} is not in a git repository", filepath.display())));
+    }
+    if paths.merging() {
+        return Err(FerropassError::Io("A git merge is in progress; finish or abort it first".to_string()));
+    }
+    let upstream = paths.git(&["rev-parse", "--abbrev-ref", "--symbolic-full-name", "@{u}"])
+        .map_err(|_| FerropassError::Io("The current branch has no upstream to sync with; set one with `git push -u`".to_string()))?;
+
+    let _vault_lock = FileStorage.lock(filepath)?;
+    let committed = paths.commit(&format!("Save {} before syncing", paths.file))?;
+    paths.git(&["fetch", "-q"])?;
+
+    let behind = paths.commit_count("HEAD..@{u}")?;
+    let ahead = paths.commit_count("@{u}..HEAD")?;
+
+    let pulled = if behind == 0 {
+        Pulled::Nothing
+    } else if ahead == 0 {
+        paths.git(&["merge", "-q", "--ff-only", "@{u}"])?;
+        Pulled::FastForwarded(behind)
+    } else {
+        let base = paths.git(&["merge-base", "HEAD", "@{u}"])?;
+        let changed_on = |commit: &str| !paths.git_succeeds(&["diff", "--quiet", &base, commit, "--", &paths.file]);
+
+        if changed_on("HEAD") && changed_on("@{u}") {
+            merge_vault(&paths, &upstream, &base)?;
+            Pulled::VaultMerged
+        } else {
+            paths.git(&["merge", "-q", "--no-edit", "@{u}"])?;
+            Pulled::Merged(behind)
+        }
+    };
+
+    let pushed = paths.commit_count("@{u}..HEAD")? > 0;
+    if pushed {
+        paths.git(&["push", "-q"])?;
+    }
+
+    Ok(SyncReport { upstream, committed, pulled, pushed })
+}
+
+// With `git_autocommit` set, commits the vault after each save when it is in
+// a git work tree. Messages only count entries, never name them, since
+// commit messages are not encrypted. A failed commit doesn't undo the save.
+// Paranoid mode skips it, since committing runs the repository's hooks.
+pub fn commit_saved(filepath: &Path, save: Save) {
+    if paranoid::is_enabled() || !config::current().git_autocommit() {
+        return;
+    }
+
+    let result = VaultPaths::of(filepath).and_then(|paths| {
+        // A sync that is merging commits its result itself.
+        if !paths.in_work_tree() || paths.merging() {
+            return Ok(false);
+        }
+        paths.commit(&describe(filepath, save))
+    });
+    if let Err(e) = result {
+        notice::warn(format!("{} was saved but not committed: {}", filepath.display(), e));
+    }
+}
+
+// A commit message for `save`, which names no entries.
+pub fn describe(filepath: &Path, save: Save) -> String {
+    let file = file_name(filepath);
+    let (added, changed, removed, settings) = match save {
+        Save::Full { entries } => return format!("Save {} ({} entries)", file, entries),
+        Save::Rekeyed => return format!("Change how {} is unlocked", file),
+        Save::Changes { added, changed, removed, settings } => (added, changed, removed, settings),
+    };
+
+    let mut parts = Vec::new();
+    for (count, what) in [(added, "added"), (changed, "changed"), (removed, "removed")] {
+        if count > 0 {
+            parts.push(format!("{} {} {}", count, if count == 1 { "entry" } else { "entries" }, what));
+        }
+    }
+    if settings {
+        parts.push("settings changed".to_string());
+    }
+
+    format!("Update {}: {}", file, parts.join(", "))
+}
+
+pub fn file_name(filepath: &Path) -> String {
+    filepath.file_name().map_or_else(|| filepath.display().to_string(), |name| name.to_string_lossy().to_string())
+}
+
@@ -0,0 +1,561 @@
+use crate::error::FerropassError;
+use crate::models::parse_tags;
+
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// Reads one format of export into entries. The built-in formats are below;
+// a program adds its own to a `formats::Registry`.
+pub trait Importer: Send + Sync {
+    // What `--format` takes, e.g. "lastpass".
+    fn name(&self) -> &str;
+
+    // What `--list-formats` shows next to the name.
+    fn description(&self) -> &str;
+
+    // Whether `source` looks like this format, for imports without
+    // `--format`.
+    fn detect(&self, source: &Path) -> bool;
+
+    fn read(&self, source: &Path) -> Result<Vec<ImportedEntry>, FerropassError>;
+
+    // Exports of other password managers are plain text, unlike a ferropass
+    // database or a password store.
+    fn is_plain_text(&self) -> bool {
+        true
+    }
+}
+
+// One login as another password manager exported it, before it is sealed.
+pub struct ImportedEntry {
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub group: Option<String>,
+    pub tags: Vec<String>,
+    pub username: String,
+    pub password: String,
+    pub notes: Option<String>,
+    pub totp: Option<String>,
+}
+
+pub const FERROPASS: &str = "ferropass";
+
+// The built-in formats, in the order they are detected: LastPass and
+// 1Password CSV exports have columns a browser's doesn't.
+pub fn built_in() -> Vec<Box<dyn Importer>> {
+    vec![
+        Box::new(Ferropass),
+        Box::new(Csv { name: "lastpass", description: "LastPass CSV export", columns: &[&["grouping", "extra"]] }),
+        Box::new(Csv { name: "1password-csv", description: "1Password CSV export", columns: &[&["title"]] }),
+        Box::new(OnePux),
+        Box::new(Csv { name: "browser", description: "browser saved-passwords CSV (Chrome, Edge, Firefox)", columns: &[&["url"], &["username"], &["password"]] }),
+        Box::new(Pass),
+    ]
+}
+
+// Another ferropass database, imported as a database rather than as entries.
+struct Ferropass;
+
+impl Importer for Ferropass {
+    fn name(&self) -> &str {
+        FERROPASS
+    }
+
+    fn description(&self) -> &str {
+        "ferropass database (.fp)"
+    }
+
+    fn detect(&self, source: &Path) -> bool {
+        has_extension(source, "fp")
+    }
+
+    fn read(&self, _source: &Path) -> Result<Vec<ImportedEntry>, FerropassError> {
+        Err(FerropassError::Validation("A ferropass database is imported as a database, not as entries".to_string()))
+    }
+
+    fn is_plain_text(&self) -> bool {
+        false
+    }
+}
+
+// A CSV export, told apart by its header row: it has one of each group of
+// `columns`.
+struct Csv {
+    name: &'static str,
+    description: &'static str,
+    columns: &'static [&'static [&'static str]],
+}
+
+impl Importer for Csv {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    fn detect(&self, source: &Path) -> bool {
+        if !has_extension(source, "csv") {
+            return false;
+        }
+        let Ok(mut reader) = csv_reader(source) else {
+            return false;
+        };
+        let Ok(headers) = reader.headers() else {
+            return false;
+        };
+        self.columns.iter().all(|names| headers.iter().any(|header| names.iter().any(|name| header.trim().eq_ignore_ascii_case(name))))
+    }
+
+    fn read(&self, source: &Path) -> Result<Vec<ImportedEntry>, FerropassError> {
+        read_csv(source)
+    }
+}
+
+struct OnePux;
+
+impl Importer for OnePux {
+    fn name(&self) -> &str {
+        "1pux"
+    }
+
+    fn description(&self) -> &str {
+        "1Password 1PUX export"
+    }
+
+    fn detect(&self, source: &Path) -> bool {
+        has_extension(source, "1pux")
+    }
+
+    fn read(&self, source: &Path) -> Result<Vec<ImportedEntry>, FerropassError> {
+        read_1pux(source)
+    }
+}
+
+// A password store is a directory with a .gpg-id file at its top.
+struct Pass;
+
+impl Importer for Pass {
+    fn name(&self) -> &str {
+        "pass"
+    }
+
+    fn description(&self) -> &str {
+        "pass (password-store) directory"
+    }
+
+    fn detect(&self, source: &Path) -> bool {
+        source.is_dir() && source.join(".gpg-id").exists()
+    }
+
+    fn read(&self, source: &Path) -> Result<Vec<ImportedEntry>, FerropassError> {
+        read_password_store(source)
+    }
+
+    fn is_plain_text(&self) -> bool {
+        false
+    }
+}
+
+fn has_extension(source: &Path, wanted: &str) -> bool {
+    source.extension().is_some_and(|extension| extension.eq_ignore_ascii_case(wanted))
+}
+
+fn csv_reader(source: &Path) -> Result<csv::Reader<File>, FerropassError> {
+    csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(source)
+        .map_err(|e| FerropassError::Io(format!("Failed to open {}: {}", source.display(), e)))
+}
+
+// Columns are found by name rather than position, so every CSV format shares
+// one reader: LastPass exports "url,username,password,totp,extra,name,grouping,fav",
+// 1Password "Title,Url,Username,Password,OTPAuth,Favorite,Archived,Tags,Notes",
+// Chrome and Edge "name,url,username,password,note" and Firefox
+// "url,username,password,httpRealm,formActionOrigin,guid,..." with no title,
+// and older versions name a few columns differently.
+fn read_csv(source: &Path) -> Result<Vec<ImportedEntry>, FerropassError> {
+    let mut reader = csv_reader(source)?;
+    let headers = reader.headers()
+        .map_err(|e| FerropassError::Io(format!("Failed to read {}: {}", source.display(), e)))?
+        .clone();
+    let column = |names: &[&str]| headers.iter()
+        .position(|header| names.iter().any(|name| header.trim().eq_ignore_ascii_case(name)));
+
+    let title = column(&["name", "title"]);
+    let url = column(&["url", "website", "login_uri"]);
+    let username = column(&["username", "login", "login_username"]);
+    let password = column(&["password", "login_password"]);
+    let notes = column(&["extra", "notes", "note"]);
+    let totp = column(&["totp", "otpauth", "login_totp"]);
+    let group = column(&["grouping", "group", "folder"]);
+    let tags = column(&["tags"]);
+    let archived = column(&["archived"]);
+
+    if password.is_none() {
+        return Err(FerropassError::Format(format!("{} has no password column", source.display())));
+    }
+
+    let mut entries = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| FerropassError::Io(format!("Failed to read {}: {}", source.display(), e)))?;
+        let field = |index: Option<usize>| index
+            .and_then(|index| record.get(index))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string);
+
+        if field(archived).is_some_and(|value| value.eq_ignore_ascii_case("true")) {
+            continue;
+        }
+
+        // LastPass gives secure notes the address "http://sn".
+        let url = field(url).filter(|url| url != "http://sn");
+        entries.push(ImportedEntry {
+            title: field(title).or_else(|| url.as_deref().and_then(host)),
+            url,
+            // LastPass nests folders with backslashes.
+            group: field(group).map(|group| group.replace('\\', "/")),
+            tags: field(tags).map_or_else(Vec::new, |tags| parse_tags(&tags)),
+            username: field(username).unwrap_or_default(),
+            password: field(password).unwrap_or_default(),
+            notes: field(notes),
+            totp: field(totp),
+        });
+    }
+
+    Ok(entries)
+}
+
+// A 1PUX file is a zip archive whose export.data holds every account, vault
+// and item as JSON. Archived and deleted items are left behind.
+fn read_1pux(source: &Path) -> Result<Vec<ImportedEntry>, FerropassError> {
+    let file = File::open(source).map_err(|e| FerropassError::Io(format!("Failed to open {}: {}", source.display(), e)))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| FerropassError::Format(format!("{} is not a 1PUX export: {}", source.display(), e)))?;
+
+    let mut data = String::new();
+    archive.by_name("export.data")
+        .map_err(|e| FerropassError::Format(format!("{} is not a 1PUX export: {}", source.display(), e)))?
+        .read_to_string(&mut data)
+        .map_err(|e| FerropassError::Io(format!("Failed to read {}: {}", source.display(), e)))?;
+    let export: Value = serde_json::from_str(&data)
+        .map_err(|e| FerropassError::Format(format!("Failed to parse {}: {}", source.display(), e)))?;
+
+    // Each item's vault becomes its group.
+    let items = export["accounts"].as_array().into_iter().flatten()
+        .flat_map(|account| account["vaults"].as_array().into_iter().flatten())
+        .flat_map(|vault| vault["items"].as_array().into_iter().flatten()
+            .map(move |item| (text(&vault["attrs"]["name"]), item)));
+
+    let mut entries = Vec::new();
+    for (vault, item) in items {
+        if item["state"].as_str().is_some_and(|state| state != "active") {
+            continue;
+        }
+
+        let details = &item["details"];
+        let login_field = |designation: &str| details["loginFields"].as_array().into_iter().flatten()
+            .find(|field| field["designation"] == designation)
+            .and_then(|field| text(&field["value"]));
+
+        entries.push(ImportedEntry {
+            title: text(&item["overview"]["title"]),
+            url: text(&item["overview"]["url"]),
+            group: vault,
+            tags: item["overview"]["tags"].as_array().into_iter().flatten().filter_map(text).collect(),
+            username: login_field("username").unwrap_or_default(),
+            // Password items keep theirs outside the login fields.
+            password: login_field("password").or_else(|| text(&details["password"])).unwrap_or_default(),
+            notes: text(&details["notesPlain"]),
+            totp: details["sections"].as_array().into_iter().flatten()
+                .flat_map(|section| section["fields"].as_array().into_iter().flatten())
+                .find_map(|field| text(&field["value"]["totp"])),
+        });
+    }
+
+    Ok(entries)
+}
+
+// Decrypts every entry of a pass (password-store) directory with gpg, which
+// asks for the key's passphrase through its agent as pass itself would.
+fn read_password_store(source: &Path) -> Result<Vec<ImportedEntry>, FerropassError> {
+    let mut files = Vec::new();
+    find_gpg_files(source, &mut files)?;
+    files.sort();
+
+    let mut entries = Vec::new();
+    for file in files {
+        let name = file.strip_prefix(source).unwrap_or(&file).with_extension("");
+        let output = Command::new("gpg")
+            .args(["--quiet", "--yes", "--decrypt"])
+            .arg(&file)
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|e| FerropassError::Io(format!("Failed to run gpg: {}", e)))?;
+        if !output.status.success() {
+            return Err(FerropassError::Crypto(format!("gpg failed to decrypt {} ({})", file.display(), output.status)));
+        }
+
+        let contents = String::from_utf8(output.stdout)
+            .map_err(|_| FerropassError::Format(format!("{} doesn't decrypt to text", file.display())))?;
+        entries.push(pass_entry(&name.to_string_lossy(), &contents));
+    }
+
+    Ok(entries)
+}
+
+// Hidden directories, such as the store's .git, are not entries.
+fn find_gpg_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), FerropassError> {
+    let entries = fs::read_dir(dir).map_err(|e| FerropassError::Io(format!("Failed to read {}: {}", dir.display(), e)))?;
+
+    for entry in entries {
+        let path = entry.map_err(|e| FerropassError::Io(format!("Failed to read {}: {}", dir.display(), e)))?.path();
+        if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+            continue;
+        }
+
+        if path.is_dir() {
+            find_gpg_files(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "gpg") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+// By pass's convention the first line is the password and the rest is free
+// text. "login:", "url:" and similar lines and an otpauth:// line (as
+// written by pass-otp) fill in those fields; without a login line the entry's
+// file name is taken as the username, as browserpass does.
+fn pass_entry(name: &str, contents: &str) -> ImportedEntry {
+    let mut lines = contents.lines();
+    let password = lines.next().unwrap_or("").to_string();
+
+    let (mut username, mut url, mut totp) = (None, None, None);
+    let mut notes = Vec::new();
+    for line in lines {
+        let annotation = line.split_once(':')
+            .map(|(key, value)| (key.trim().to_lowercase(), value.trim()))
+            .filter(|(_, value)| !value.is_empty());
+
+        match annotation {
+            _ if line.trim().starts_with("otpauth://") && totp.is_none() => totp = Some(line.trim().to_string()),
+            Some((key, value)) if matches!(key.as_str(), "login" | "username" | "user" | "email") && username.is_none() => {
+                username = Some(value.to_string());
+            },
+            Some((key, value)) if matches!(key.as_str(), "url" | "website" | "site") && url.is_none() => {
+                url = Some(value.to_string());
+            },
+            _ => notes.push(line),
+        }
+    }
+
+    let notes = notes.join("\n");
+    let (group, file_name) = name.rsplit_once(['/', '\\']).map_or((None, name), |(group, file_name)| (Some(group), file_name));
+    ImportedEntry {
+        title: Some(name.to_string()),
+        url,
+        group: group.map(|group| group.replace('\\', "/")),
+        tags: Vec::new(),
+        username: username.unwrap_or_else(|| file_name.to_string()),
+        password,
+        notes: (!notes.trim().is_empty()).then(|| notes.trim().to_string()),
+        totp,
+    }
+}
+
+// The site a URL points to, standing in for the title in exports without one.
+fn host(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next()?;
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    let host = host.split(':').next()?;
+
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+fn text(value: &Value) -> Option<String> {
+    value.as_str()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    fn file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ferropass-importers-{}", std::process::id())).join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn detected(source: &Path) -> Option<String> {
+        built_in().into_iter().find(|importer| importer.detect(source)).map(|importer| importer.name().to_string())
+    }
+
+    // A 1PUX archive holding `data` as its export.data.
+    fn onepux(name: &str, data: &Value) -> PathBuf {
+        let path = file(name, b"");
+        let mut archive = zip::ZipWriter::new(File::create(&path).unwrap());
+        archive.start_file("export.data", zip::write::SimpleFileOptions::default()).unwrap();
+        archive.write_all(data.to_string().as_bytes()).unwrap();
+        archive.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn quoted_fields_keep_their_commas_and_newlines() {
+        let source = file("quoted.csv", concat!(
+            "url,username,password,totp,extra,name,grouping,fav\n",
+            "https://mail.example.com/login,alice,\"pa,ss\"\"word\",,\"first line\nsecond, line\",\"Mail, personal\",Work\\Mail,0\n",
+        ).as_bytes());
+
+        let entries = read_csv(&source).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].password, "pa,ss\"word");
+        assert_eq!(entries[0].notes.as_deref(), Some("first line\nsecond, line"));
+        assert_eq!(entries[0].title.as_deref(), Some("Mail, personal"));
+        assert_eq!(entries[0].group.as_deref(), Some("Work/Mail"));
+        assert_eq!(entries[0].totp, None);
+    }
+
+    #[test]
+    fn missing_password_column_is_refused() {
+        let source = file("no-password.csv", b"url,username,note\nhttps://example.com,alice,hello\n");
+
+        assert!(matches!(read_csv(&source), Err(FerropassError::Format(_))));
+        assert_eq!(detected(&source), None);
+    }
+
+    #[test]
+    fn duplicate_rows_are_all_read() {
+        let source = file("duplicates.csv", concat!(
+            "name,url,username,password,note\n",
+            "Forum,https://forum.example.com,bob,hunter2,\n",
+            "Forum,https://forum.example.com,bob,hunter2,\n",
+            "Forum,https://forum.example.com,bob,hunter3,changed\n",
+        ).as_bytes());
+
+        let entries = read_csv(&source).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(|entry| entry.username == "bob" && entry.title.as_deref() == Some("Forum")));
+        assert_eq!(entries.iter().map(|entry| entry.password.as_str()).collect::<Vec<_>>(), ["hunter2", "hunter2", "hunter3"]);
+    }
+
+    #[test]
+    fn csv_formats_are_told_apart_by_their_header() {
+        let lastpass = file("lastpass.csv", b"url,username,password,totp,extra,name,grouping,fav\nhttp://sn,,,,a secure note,Note,,0\n");
+        let onepassword = file("1password.csv", b"Title,Url,Username,Password,OTPAuth,Favorite,Archived,Tags,Notes\nOld,,carol,pw,,false,true,,\nBank,https://bank.example.com,carol,pw,,false,false,\"money, important\",\n");
+        let chrome = file("chrome.csv", b"name,url,username,password,note\nShop,https://shop.example.com,dave,pw,\n");
+        let firefox = file("firefox.csv", b"\"url\",\"username\",\"password\",\"httpRealm\",\"formActionOrigin\",\"guid\"\n\"https://user@www.example.com:8443/login\",\"erin\",\"pw\",,\"\",\"{1}\"\n");
+
+        assert_eq!(detected(&lastpass).as_deref(), Some("lastpass"));
+        assert_eq!(detected(&onepassword).as_deref(), Some("1password-csv"));
+        assert_eq!(detected(&chrome).as_deref(), Some("browser"));
+        assert_eq!(detected(&firefox).as_deref(), Some("browser"));
+
+        let note = &read_csv(&lastpass).unwrap()[0];
+        assert_eq!((note.url.as_deref(), note.notes.as_deref()), (None, Some("a secure note")));
+
+        let bank = read_csv(&onepassword).unwrap();
+        assert_eq!(bank.len(), 1);
+        assert_eq!(bank[0].tags, ["money", "important"]);
+
+        assert_eq!(read_csv(&firefox).unwrap()[0].title.as_deref(), Some("www.example.com"));
+    }
+
+    #[test]
+    fn onepux_items_are_read_from_their_vaults() {
+        let source = onepux("export.1pux", &serde_json::json!({
+            "accounts": [{
+                "vaults": [{
+                    "attrs": { "name": "Personal" },
+                    "items": [
+                        {
+                            "state": "active",
+                            "overview": { "title": "Mail", "url": "https://mail.example.com", "tags": ["email"] },
+                            "details": {
+                                "loginFields": [
+                                    { "designation": "username", "value": "alice" },
+                                    { "designation": "password", "value": "pa,ss\nword" },
+                                ],
+                                "notesPlain": "recovery codes in the safe",
+                                "sections": [{ "fields": [{ "value": { "totp": "otpauth://totp/Mail?secret=JBSWY3DPEHPK3PXP" } }] }],
+                            },
+                        },
+                        {
+                            "state": "archived",
+                            "overview": { "title": "Old" },
+                            "details": { "password": "gone" },
+                        },
+                        {
+                            "state": "active",
+                            "overview": { "title": "Wi-Fi" },
+                            "details": { "password": "router-password" },
+                        },
+                    ],
+                }],
+            }],
+        }));
+
+        assert_eq!(detected(&source).as_deref(), Some("1pux"));
+        let entries = read_1pux(&source).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let mail = &entries[0];
+        assert_eq!((mail.username.as_str(), mail.password.as_str()), ("alice", "pa,ss\nword"));
+        assert_eq!(mail.group.as_deref(), Some("Personal"));
+        assert_eq!(mail.tags, ["email"]);
+        assert_eq!(mail.notes.as_deref(), Some("recovery codes in the safe"));
+        assert!(mail.totp.as_deref().is_some_and(|totp| totp.starts_with("otpauth://")));
+
+        assert_eq!((entries[1].username.as_str(), entries[1].password.as_str()), ("", "router-password"));
+    }
+
+    #[test]
+    fn onepux_without_export_data_is_refused() {
+        let source = file("empty.1pux", b"not a zip archive");
+        assert!(matches!(read_1pux(&source), Err(FerropassError::Format(_))));
+    }
+
+    #[test]
+    fn pass_entries_follow_pass_conventions() {
+        let entry = pass_entry("web/github", "gh-password\nlogin: alice\nurl: https://github.com\notpauth://totp/GitHub?secret=JBSWY3DPEHPK3PXP\nrecovery: in the safe\n");
+
+        assert_eq!(entry.password, "gh-password");
+        assert_eq!(entry.username, "alice");
+        assert_eq!(entry.url.as_deref(), Some("https://github.com"));
+        assert_eq!(entry.group.as_deref(), Some("web"));
+        assert_eq!(entry.title.as_deref(), Some("web/github"));
+        assert!(entry.totp.is_some());
+        assert_eq!(entry.notes.as_deref(), Some("recovery: in the safe"));
+
+        let bare = pass_entry("bank", "only-a-password");
+        assert_eq!((bare.username.as_str(), bare.group, bare.notes), ("bank", None, None));
+    }
+
+    #[test]
+    fn pass_store_skips_hidden_directories() {
+        let store = file("store/.gpg-id", b"ABCDEF\n").parent().unwrap().to_path_buf();
+        file("store/web/github.gpg", b"");
+        file("store/.git/objects.gpg", b"");
+        file("store/README.txt", b"");
+
+        let mut files = Vec::new();
+        find_gpg_files(&store, &mut files).unwrap();
+        assert_eq!(files, [store.join("web/github.gpg")]);
+        assert_eq!(detected(&store).as_deref(), Some("pass"));
+    }
+}
@@ -0,0 +1,134 @@
+//! The vault format behind FerroPass, without its terminal interface: the
+//! database model, its encryption, the places a vault can be stored and the
+//! password generators, for tools that read or write `.fp` files themselves.
+//!
+//! A vault is a [`models::Database`] kept encrypted at a path in some
+//! [`storage::VaultStorage`]. [`vault::Vault`] opens one with its passkey and
+//! keeps its key for saving; each password in it stays sealed until it is
+//! opened. The functions in [`encryption`] underneath it unlock and save
+//! databases directly.
+//!
+//! ```no_run
+//! use ferropass_core::error::FerropassError;
+//! use ferropass_core::storage::FileStorage;
+//! use ferropass_core::vault::Vault;
+//! use std::sync::Arc;
+//!
+//! # fn main() -> Result<(), FerropassError> {
+//! let mut vault = Vault::open(Arc::new(FileStorage), "personal.fp", "correct horse battery staple")?;
+//!
+//! for account in vault.entries() {
+//!     println!("{}: {}", account.get_username_or_email(), vault.password(account)?);
+//! }
+//!
+//! vault.add_entry("alice@example.com", Some("Mail"), "hunter2")?;
+//! vault.save()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`vault::Vault::in_memory`] and [`vault::Vault::from_bytes`] keep a vault
+//! in a [`storage::MemoryStorage`] instead, touching no files, and
+//! [`vault::Vault::to_bytes`] hands back the file to store wherever the
+//! program keeps it:
+//!
+//! ```
+//! use ferropass_core::error::FerropassError;
+//! use ferropass_core::vault::Vault;
+//!
+//! # fn main() -> Result<(), FerropassError> {
+//! let mut vault = Vault::in_memory("correct horse battery staple")?;
+//! vault.add_entry("alice@example.com", Some("Mail"), "hunter2")?;
+//! vault.save()?;
+//!
+//! let reopened = Vault::from_bytes(&vault.to_bytes()?, "correct horse battery staple")?;
+//! assert_eq!(reopened.password(reopened.find("alice")?)?, "hunter2");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Errors are [`error::FerropassError`]s, whose variant tells the kind of
+//! failure and whose message is written for the user.
+//!
+//! Features:
+//!
+//! - `fs` (default): vault files on this machine, with their backups and the
+//!   wrong-passkey delays, and importing and exporting other formats through
+//!   a [`formats::Registry`]
+//! - `remote` (default): vaults on WebDAV, S3 and SFTP servers
+//! - `clap`: derives `clap::ValueEnum` for the enums a command line takes as
+//!   options
+//! - `tokio`: runs the [`nonblocking`] functions on a tokio runtime's
+//!   blocking thread pool when called inside one
+//!
+//! Without the default features the crate builds for
+//! `wasm32-unknown-unknown`, keeping vaults in a [`storage::MemoryStorage`]
+//! or a [`storage::VaultStorage`] of the caller's own.
+
+/// The error every fallible function returns.
+pub mod error;
+/// Accounts, the database that holds them and its history.
+pub mod models;
+/// Filtering and sorting a database's accounts.
+pub mod query;
+/// Password, passphrase and PIN generators, and strength ratings.
+pub mod password;
+/// Opening, editing and saving a vault.
+pub mod vault;
+/// Callbacks for a vault being unlocked and saved, and its entries changing.
+pub mod events;
+/// The vault file format: key derivation, sealing and saving.
+pub mod encryption;
+/// Async versions of unlocking, saving and syncing, which run on a thread of their own.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod nonblocking;
+/// The ciphers and key derivation functions a vault can use.
+pub mod algorithms;
+/// Encryption of the vault's plaintext in chunks.
+pub mod stream;
+/// Keys kept in memory that isn't swapped out.
+pub mod memlock;
+/// Checks that the system's random number generator is ready.
+pub mod entropy;
+/// Where vaults are kept: local files, memory, or a remote server.
+pub mod storage;
+/// Rotating backups taken before each save.
+#[cfg(feature = "fs")]
+pub mod backup;
+/// Vaults on a WebDAV server.
+#[cfg(feature = "remote")]
+pub mod webdav;
+/// Vaults in S3-compatible object storage.
+#[cfg(feature = "remote")]
+pub mod s3;
+/// Vaults on an SSH server.
+#[cfg(feature = "remote")]
+pub mod sftp;
+/// Paranoid mode, which turns off anything that leaves the machine.
+pub mod paranoid;
+/// Warnings and other messages for the user, passed to a handler of the program's.
+pub mod notice;
+/// The user's configuration and state files.
+pub mod config;
+/// Delays after wrong passkeys.
+#[cfg(feature = "fs")]
+pub mod throttle;
+/// Reading other password managers' exports.
+#[cfg(feature = "fs")]
+pub mod importers;
+/// Choosing accounts to export, and writing them out in plain text.
+#[cfg(feature = "fs")]
+pub mod export;
+/// The import and export formats a program knows, and adding its own.
+#[cfg(feature = "fs")]
+pub mod formats;
+/// TOTP codes.
+pub mod totp;
+/// Shamir's secret sharing, for recovery shares.
+pub mod shamir;
+/// The pepper file mixed into the key derivation.
+pub mod pepper;
+/// Signatures on saved vaults.
+pub mod signing;
+/// Vaults kept in a git repository.
+pub mod git;
@@ -0,0 +1,179 @@
+use crate::error::FerropassError;
+use crate::notice;
+
+use std::alloc::{self, Layout};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::Once;
+
+static WARNING: Once = Once::new();
+
+// A fixed-size heap buffer for keys and decrypted data. It gets pages of its
+// own, which are locked into RAM where the platform allows it so they are
+// never written to swap, and it is zeroed before it is freed. When locking is
+// refused (typically by RLIMIT_MEMLOCK) a warning is printed once and the
+// buffer works unlocked.
+pub struct SecretBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+    locked: bool,
+}
+
+// The buffer owns its allocation outright, like a Vec.
+unsafe impl Send for SecretBuffer {}
+unsafe impl Sync for SecretBuffer {}
+
+impl SecretBuffer {
+    pub fn new(len: usize) -> Self {
+        let page = page_size();
+        let size = len.max(1).div_ceil(page) * page;
+        let layout = Layout::from_size_align(size, page).expect("secret buffer layout");
+
+        let Some(ptr) = NonNull::new(unsafe { alloc::alloc_zeroed(layout) }) else {
+            alloc::handle_alloc_error(layout);
+        };
+
+        let locked = match lock(ptr.as_ptr(), size) {
+            Ok(()) => true,
+            Err(e) => {
+                WARNING.call_once(|| notice::warn(format!(
+                    "memory for keys and decrypted data can't be locked ({}); it may be written to swap", e
+                )));
+                false
+            }
+        };
+
+        SecretBuffer { ptr, len, layout, locked }
+    }
+
+    pub fn from_slice(data: &[u8]) -> Self {
+        let mut buffer = Self::new(data.len());
+        buffer.copy_from_slice(data);
+        buffer
+    }
+
+    // Shortens the usable part of the buffer; the rest stays allocated and
+    // locked until it is dropped.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    // Moves plaintext that a library handed back as a Vec into a locked
+    // buffer, wiping the Vec.
+    pub fn take(mut data: Vec<u8>) -> Self {
+        let buffer = Self::from_slice(&data);
+        wipe(&mut data);
+        buffer
+    }
+}
+
+impl Deref for SecretBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for SecretBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for SecretBuffer {
+    fn drop(&mut self) {
+        let whole = unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) };
+        wipe(whole);
+
+        if self.locked {
+            unlock(self.ptr.as_ptr(), self.layout.size());
+        }
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+// A 32-byte key kept in a `SecretBuffer`. It derefs to `[u8; 32]`, so it can
+// be passed wherever a key is expected.
+pub struct SecretKey(SecretBuffer);
+
+impl SecretKey {
+    // Takes the key out of `bytes`, wiping them.
+    pub fn new(bytes: &mut [u8; 32]) -> Self {
+        let key = SecretKey(SecretBuffer::from_slice(bytes));
+        wipe(bytes);
+        key
+    }
+}
+
+impl Deref for SecretKey {
+    type Target = [u8; 32];
+
+    fn deref(&self) -> &[u8; 32] {
+        (*self.0).try_into().expect("secret key is 32 bytes")
+    }
+}
+
+impl Clone for SecretKey {
+    fn clone(&self) -> Self {
+        SecretKey(SecretBuffer::from_slice(&self.0))
+    }
+}
+
+// Volatile writes, so the zeroing isn't optimised away as a dead store.
+pub fn wipe(data: &mut [u8]) {
+    for byte in data.iter_mut() {
+        unsafe { std::ptr::write_volatile(byte, 0) };
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    }
+}
+
+#[cfg(not(unix))]
+fn page_size() -> usize {
+    4096
+}
+
+#[cfg(unix)]
+fn lock(ptr: *mut u8, size: usize) -> Result<(), FerropassError> {
+    if unsafe { libc::mlock(ptr.cast(), size) } == 0 {
+        Ok(())
+    } else {
+        Err(FerropassError::Io(std::io::Error::last_os_error().to_string()))
+    }
+}
+
+#[cfg(unix)]
+fn unlock(ptr: *mut u8, size: usize) {
+    unsafe { libc::munlock(ptr.cast(), size) };
+}
+
+#[cfg(windows)]
+fn lock(ptr: *mut u8, size: usize) -> Result<(), FerropassError> {
+    if unsafe { windows_sys::Win32::System::Memory::VirtualLock(ptr.cast(), size) } != 0 {
+        Ok(())
+    } else {
+        Err(FerropassError::Io(std::io::Error::last_os_error().to_string()))
+    }
+}
+
+#[cfg(windows)]
+fn unlock(ptr: *mut u8, size: usize) {
+    unsafe { windows_sys::Win32::System::Memory::VirtualUnlock(ptr.cast(), size) };
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lock(_ptr: *mut u8, _size: usize) -> Result<(), FerropassError> {
+    Err(FerropassError::Io("not supported on this platform".to_string()))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn unlock(_ptr: *mut u8, _size: usize) {}
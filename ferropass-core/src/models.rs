@@ -0,0 +1,527 @@
+use crate::password::PasswordOptions;
+use crate::query::Query;
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use rand::Rng;
+use rand::rngs::OsRng;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Account {
+    id: String,                  // 32-bit hash represented as a string
+    username_or_email: String,   // Username or email for the account
+    description: Option<String>, // Optional description
+    #[serde(default)]
+    url: Option<String>,         // Optional address of the site
+    #[serde(default)]
+    group: Option<String>,       // Optional group, nested with "/" (e.g. "Work/Servers")
+    #[serde(default)]
+    tags: Vec<String>,           // Free-form labels, e.g. "shared" or "finance"
+    password: StoredPassword,    // Password for the account, sealed on its own
+    #[serde(default)]
+    notes: Option<String>,       // Optional free-form notes, shown only through the pager
+    #[serde(default)]
+    login_procedure: Vec<String>, // Ordered login steps, see login.rs for placeholders
+    #[serde(default)]
+    created_at: u64,             // Unix timestamp, 0 for entries predating timestamps
+    #[serde(default)]
+    updated_at: u64,             // Unix timestamp of the last edit
+    #[serde(default)]
+    password_updated_at: u64,    // Unix timestamp of the last password change
+    #[serde(default)]
+    policy: Option<Box<PasswordOptions>>, // What generated passwords for this account must look like
+    #[serde(default)]
+    autotype: Option<String>,    // Keystrokes for auto-type, see autotype.rs; None for the default
+    #[serde(default)]
+    totp: Option<Box<StoredPassword>>, // TOTP secret or otpauth:// URI, sealed like the password
+    #[serde(default)]
+    ssh_key: Option<Box<StoredPassword>>, // OpenSSH private key, sealed like the password, see ssh_agent.rs
+}
+
+impl Account {
+    pub fn new(username_or_email: String, description: Option<String>, password: StoredPassword) -> Self {
+        let id = generate_id();
+        let now = current_timestamp();
+        Account {
+            id,
+            username_or_email,
+            description,
+            url: None,
+            group: None,
+            tags: Vec::new(),
+            password,
+            notes: None,
+            login_procedure: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            password_updated_at: now,
+            policy: None,
+            autotype: None,
+            totp: None,
+            ssh_key: None,
+        }
+    }
+
+    // A copy under a new ID, for keeping two versions of an entry side by side.
+    pub fn duplicate(&self) -> Self {
+        Account { id: generate_id(), ..self.clone() }
+    }
+
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_username_or_email(&self) -> &str {
+        &self.username_or_email
+    }
+
+    pub fn get_description(&self) -> &Option<String> {
+        &self.description
+    }
+
+    pub fn get_url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    pub fn get_group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    pub fn get_tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn get_password(&self) -> &StoredPassword {
+        &self.password
+    }
+
+    pub fn get_notes(&self) -> &Option<String> {
+        &self.notes
+    }
+
+    pub fn get_login_procedure(&self) -> &[String] {
+        &self.login_procedure
+    }
+
+    pub fn get_created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    pub fn get_updated_at(&self) -> u64 {
+        self.updated_at
+    }
+
+    pub fn get_password_updated_at(&self) -> u64 {
+        self.password_updated_at
+    }
+
+    pub fn get_policy(&self) -> Option<&PasswordOptions> {
+        self.policy.as_deref()
+    }
+
+    pub fn get_autotype(&self) -> Option<&str> {
+        self.autotype.as_deref()
+    }
+
+    pub fn get_totp(&self) -> Option<&StoredPassword> {
+        self.totp.as_deref()
+    }
+
+    pub fn get_ssh_key(&self) -> Option<&StoredPassword> {
+        self.ssh_key.as_deref()
+    }
+
+    pub fn set_username_or_email(&mut self, username_or_email: String) {
+        self.username_or_email = username_or_email;
+        self.updated_at = current_timestamp();
+    }
+
+    pub fn set_description(&mut self, description: Option<String>) {
+        self.description = description;
+        self.updated_at = current_timestamp();
+    }
+
+    pub fn set_url(&mut self, url: Option<String>) {
+        self.url = url;
+        self.updated_at = current_timestamp();
+    }
+
+    pub fn set_group(&mut self, group: Option<String>) {
+        self.group = group;
+        self.updated_at = current_timestamp();
+    }
+
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = tags;
+        self.updated_at = current_timestamp();
+    }
+
+    pub fn set_notes(&mut self, notes: Option<String>) {
+        self.notes = notes;
+        self.updated_at = current_timestamp();
+    }
+
+    pub fn set_login_procedure(&mut self, login_procedure: Vec<String>) {
+        self.login_procedure = login_procedure;
+        self.updated_at = current_timestamp();
+    }
+
+    pub fn set_policy(&mut self, policy: Option<PasswordOptions>) {
+        self.policy = policy.map(Box::new);
+        self.updated_at = current_timestamp();
+    }
+
+    pub fn set_autotype(&mut self, autotype: Option<String>) {
+        self.autotype = autotype;
+        self.updated_at = current_timestamp();
+    }
+
+    pub fn set_totp(&mut self, totp: Option<StoredPassword>) {
+        self.totp = totp.map(Box::new);
+        self.updated_at = current_timestamp();
+    }
+
+    pub fn set_ssh_key(&mut self, ssh_key: Option<StoredPassword>) {
+        self.ssh_key = ssh_key.map(Box::new);
+        self.updated_at = current_timestamp();
+    }
+
+    pub fn set_password(&mut self, password: StoredPassword) {
+        self.password = password;
+        self.updated_at = current_timestamp();
+        self.password_updated_at = self.updated_at;
+    }
+
+    // Changes how the password is sealed without changing the password, so
+    // the timestamps stay as they are.
+    pub fn reseal_password(&mut self, password: StoredPassword) {
+        self.password = password;
+    }
+
+    pub fn reseal_totp(&mut self, totp: StoredPassword) {
+        self.totp = Some(Box::new(totp));
+    }
+
+    pub fn reseal_ssh_key(&mut self, ssh_key: StoredPassword) {
+        self.ssh_key = Some(Box::new(ssh_key));
+    }
+
+    // Takes one field from another version of the same entry when merging.
+    // The entry then counts as edited when the later of the two versions was,
+    // rather than now.
+    pub fn take_field(&mut self, other: &Account, field: Field) {
+        match field {
+            Field::Username => self.username_or_email = other.username_or_email.clone(),
+            Field::Description => self.description = other.description.clone(),
+            Field::Url => self.url = other.url.clone(),
+            Field::Group => self.group = other.group.clone(),
+            Field::Tags => self.tags = other.tags.clone(),
+            Field::Password => {
+                self.password = other.password.clone();
+                self.password_updated_at = other.password_updated_at;
+            },
+            Field::Notes => self.notes = other.notes.clone(),
+            Field::LoginProcedure => self.login_procedure = other.login_procedure.clone(),
+            Field::Policy => self.policy = other.policy.clone(),
+            Field::Autotype => self.autotype = other.autotype.clone(),
+            Field::Totp => self.totp = other.totp.clone(),
+            Field::SshKey => self.ssh_key = other.ssh_key.clone(),
+        }
+        self.updated_at = self.updated_at.max(other.updated_at);
+    }
+}
+
+// The parts of an account that merges compare and take from either version.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Field {
+    Username,
+    Description,
+    Url,
+    Group,
+    Tags,
+    Password,
+    Notes,
+    LoginProcedure,
+    Policy,
+    Autotype,
+    Totp,
+    SshKey,
+}
+
+impl Field {
+    pub const ALL: [Field; 12] = [
+        Field::Username,
+        Field::Description,
+        Field::Url,
+        Field::Group,
+        Field::Tags,
+        Field::Password,
+        Field::Notes,
+        Field::LoginProcedure,
+        Field::Policy,
+        Field::Autotype,
+        Field::Totp,
+        Field::SshKey,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Username => "Username/Email",
+            Field::Description => "Description",
+            Field::Url => "URL",
+            Field::Group => "Group",
+            Field::Tags => "Tags",
+            Field::Password => "Password",
+            Field::Notes => "Notes",
+            Field::LoginProcedure => "Login procedure",
+            Field::Policy => "Password policy",
+            Field::Autotype => "Auto-type",
+            Field::Totp => "TOTP",
+            Field::SshKey => "SSH key",
+        }
+    }
+}
+
+// An account's password as kept in the database: sealed under the vault's
+// entry key (see `EntryKey`), so it is only in plaintext while it is used.
+// Files from before per-entry encryption store it as a plain string, which is
+// sealed when the vault is unlocked.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum StoredPassword {
+    Sealed { sealed: String },
+    Plain(String),
+}
+
+// A single edit to a database, as recorded in the file's journal.
+#[derive(Serialize, Deserialize)]
+pub enum Change {
+    Upsert(Box<Account>),
+    Remove(String),
+    Settings(Settings),
+    Logged(LogRecord),
+}
+
+// A line of a vault's change log, such as how a merge conflict on an entry
+// was resolved.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct LogRecord {
+    at: u64,
+    entry_id: String,
+    text: String,
+}
+
+impl LogRecord {
+    pub fn get_at(&self) -> u64 {
+        self.at
+    }
+
+    pub fn get_entry_id(&self) -> &str {
+        &self.entry_id
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+}
+
+// Preferences stored inside the encrypted database, so each vault carries
+// its own.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Settings {
+    #[serde(default)]
+    auto_lock_minutes: Option<u64>,
+    // Never compact the journal, so every save stays on record.
+    #[serde(default)]
+    keep_history: bool,
+}
+
+impl Settings {
+    pub fn get_auto_lock_minutes(&self) -> Option<u64> {
+        self.auto_lock_minutes
+    }
+
+    pub fn set_auto_lock_minutes(&mut self, minutes: Option<u64>) {
+        self.auto_lock_minutes = minutes;
+    }
+
+    pub fn get_keep_history(&self) -> bool {
+        self.keep_history
+    }
+
+    pub fn set_keep_history(&mut self, keep_history: bool) {
+        self.keep_history = keep_history;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Database {
+    accounts: Vec<Account>,
+    #[serde(default)]
+    settings: Settings,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    change_log: Vec<LogRecord>,
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Database {
+            accounts: Vec::new(),
+            settings: Settings::default(),
+            change_log: Vec::new(),
+        }
+    }
+
+    pub fn get_settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn get_settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    pub fn get_change_log(&self) -> &[LogRecord] {
+        &self.change_log
+    }
+
+    pub fn log_change(&mut self, entry_id: &str, text: String) {
+        self.change_log.push(LogRecord { at: current_timestamp(), entry_id: entry_id.to_string(), text });
+    }
+
+    // Adds the records only `other` has, so merged copies keep both logs.
+    pub fn merge_change_log(&mut self, other: &Database) {
+        for record in &other.change_log {
+            if !self.change_log.contains(record) {
+                self.change_log.push(record.clone());
+            }
+        }
+        self.change_log.sort_by_key(|record| record.at);
+    }
+
+    pub fn add_account(&mut self, account: Account) {
+        self.accounts.push(account);
+    }
+
+    pub fn get_accounts(&self) -> &Vec<Account> {
+        &self.accounts
+    }
+
+    pub fn get_accounts_mut(&mut self) -> &mut Vec<Account> {
+        &mut self.accounts
+    }
+
+    pub fn get_account_by_id(&self, id: &str) -> Option<&Account> {
+        self.accounts.iter().find(|acc| acc.get_id() == id)
+    }
+
+    pub fn get_account_by_id_mut(&mut self, id: &str) -> Option<&mut Account> {
+        self.accounts.iter_mut().find(|acc| acc.get_id() == id)
+    }
+
+    pub fn query(&self) -> Query<'_> {
+        Query::new(self)
+    }
+
+    pub fn remove_account(&mut self, id: &str) -> bool {
+        let pos = self.accounts.iter().position(|acc| acc.get_id() == id);
+        if let Some(pos) = pos {
+            self.accounts.remove(pos);
+            true
+        } else {
+            false
+        }
+    }
+
+    // SHA-256 of the serialized contents, so two states can be told apart at
+    // a glance without showing any of them.
+    pub fn checksum(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+
+        format!("{:x}", Sha256::digest(json.as_bytes()))
+    }
+
+    pub fn diff(&self, newer: &Database) -> Vec<Change> {
+        let mut changes = Vec::new();
+
+        for account in &newer.accounts {
+            if self.get_account_by_id(account.get_id()) != Some(account) {
+                changes.push(Change::Upsert(Box::new(account.clone())));
+            }
+        }
+
+        for account in &self.accounts {
+            if newer.get_account_by_id(account.get_id()).is_none() {
+                changes.push(Change::Remove(account.get_id().to_string()));
+            }
+        }
+
+        if self.settings != newer.settings {
+            changes.push(Change::Settings(newer.settings.clone()));
+        }
+
+        for record in &newer.change_log {
+            if !self.change_log.contains(record) {
+                changes.push(Change::Logged(record.clone()));
+            }
+        }
+
+        changes
+    }
+
+    pub fn apply(&mut self, change: Change) {
+        match change {
+            Change::Upsert(account) => match self.get_account_by_id_mut(account.get_id()) {
+                Some(existing) => *existing = *account,
+                None => self.accounts.push(*account),
+            },
+            Change::Remove(id) => {
+                self.remove_account(&id);
+            },
+            Change::Settings(settings) => {
+                self.settings = settings;
+            },
+            Change::Logged(record) => {
+                self.change_log.push(record);
+            }
+        }
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Database::new()
+    }
+}
+
+pub fn current_timestamp() -> u64 {
+    Utc::now().timestamp().max(0) as u64
+}
+
+pub fn format_timestamp(timestamp: u64) -> String {
+    DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|dt| dt.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+// Splits comma-separated tags as typed or exported, dropping empty and
+// repeated ones.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+fn generate_id() -> String {
+    let timestamp = current_timestamp();
+    
+    let random_number: u32 = OsRng.gen_range(0..u32::MAX);
+    
+    let result = format!("{:x}", Sha256::digest(format!("{}{}", timestamp, random_number).as_bytes()));
+    
+    result[..8].to_string()
+}
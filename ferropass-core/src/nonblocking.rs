@@ -0,0 +1,112 @@
+use crate::encryption::{self, EntryKey, SessionKey};
+use crate::error::FerropassError;
+#[cfg(feature = "fs")]
+use crate::git;
+use crate::memlock;
+use crate::models::Database;
+use crate::storage::VaultStorage;
+
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+// Work handed to a thread of its own, finished when the thread is. Key
+// derivation and remote storage block for seconds at a time, which would
+// stall an async runtime's own threads. With the `tokio` feature, work
+// started inside a tokio runtime goes to its blocking thread pool instead;
+// elsewhere this works under any runtime.
+pub struct Task<T> {
+    inner: Inner<T>,
+}
+
+enum Inner<T> {
+    Thread(Arc<Mutex<Shared<T>>>),
+    #[cfg(feature = "tokio")]
+    Tokio(tokio::task::JoinHandle<Result<T, FerropassError>>),
+}
+
+struct Shared<T> {
+    result: Option<Result<T, FerropassError>>,
+    waker: Option<Waker>,
+}
+
+fn stopped() -> FerropassError {
+    FerropassError::Io("The background task stopped unexpectedly".to_string())
+}
+
+impl<T: Send + 'static> Task<T> {
+    pub fn spawn(work: impl FnOnce() -> Result<T, FerropassError> + Send + 'static) -> Self {
+        #[cfg(feature = "tokio")]
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            return Task { inner: Inner::Tokio(runtime.spawn_blocking(work)) };
+        }
+
+        let shared = Arc::new(Mutex::new(Shared { result: None, waker: None }));
+        let finished = Arc::clone(&shared);
+
+        thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(work)).unwrap_or_else(|_| Err(stopped()));
+            let mut shared = finished.lock().unwrap_or_else(PoisonError::into_inner);
+            shared.result = Some(result);
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        });
+
+        Task { inner: Inner::Thread(shared) }
+    }
+}
+
+impl<T> Future for Task<T> {
+    type Output = Result<T, FerropassError>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.get_mut().inner {
+            Inner::Thread(shared) => {
+                let mut shared = shared.lock().unwrap_or_else(PoisonError::into_inner);
+                match shared.result.take() {
+                    Some(result) => Poll::Ready(result),
+                    None => {
+                        shared.waker = Some(context.waker().clone());
+                        Poll::Pending
+                    },
+                }
+            },
+            #[cfg(feature = "tokio")]
+            Inner::Tokio(handle) => Pin::new(handle).poll(context).map(|result| result.unwrap_or_else(|_| Err(stopped()))),
+        }
+    }
+}
+
+// The passkey is wiped once the key has been derived from it.
+pub(crate) fn with_passkey<T>(mut passkey: String, work: impl FnOnce(&str) -> T) -> T {
+    let result = work(&passkey);
+    memlock::wipe(unsafe { passkey.as_bytes_mut() });
+    result
+}
+
+pub fn unlock_database(storage: Arc<dyn VaultStorage>, filepath: PathBuf, passkey: String) -> Task<(Database, SessionKey)> {
+    Task::spawn(move || with_passkey(passkey, |passkey| encryption::unlock_database(storage.as_ref(), &filepath, passkey)))
+}
+
+pub fn load_and_decrypt_database(storage: Arc<dyn VaultStorage>, filepath: PathBuf, passkey: String) -> Task<(Database, EntryKey)> {
+    Task::spawn(move || with_passkey(passkey, |passkey| encryption::load_and_decrypt_database(storage.as_ref(), &filepath, passkey)))
+}
+
+pub fn encrypt_and_save_database(storage: Arc<dyn VaultStorage>, database: Database, filepath: PathBuf, key: SessionKey) -> Task<()> {
+    Task::spawn(move || encryption::encrypt_and_save_database(storage.as_ref(), &database, &filepath, &key))
+}
+
+pub fn save_changes(storage: Arc<dyn VaultStorage>, previous: Database, database: Database, filepath: PathBuf, key: SessionKey) -> Task<()> {
+    Task::spawn(move || encryption::save_changes(storage.as_ref(), &previous, &database, &filepath, &key))
+}
+
+// `git::sync`, which fetches and pushes over the network.
+#[cfg(feature = "fs")]
+pub fn sync(filepath: PathBuf) -> Task<git::SyncReport> {
+    Task::spawn(move || git::sync(&filepath))
+}
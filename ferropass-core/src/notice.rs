@@ -0,0 +1,47 @@
+use std::fmt;
+use std::sync::{PoisonError, RwLock};
+
+// Something the user should hear about that isn't an error, such as a vault
+// opened from its offline copy or a wait after wrong passkeys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notice {
+    Warning(String),
+    Info(String),
+}
+
+impl fmt::Display for Notice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Notice::Warning(message) => write!(f, "Warning: {}", message),
+            Notice::Info(message) => f.write_str(message),
+        }
+    }
+}
+
+type Handler = Box<dyn Fn(&Notice) + Send + Sync>;
+
+static HANDLER: RwLock<Option<Handler>> = RwLock::new(None);
+
+// Notices go to `handler` from then on. Until a program sets one they are
+// dropped, so nothing is written on an embedder's terminal.
+pub fn set_handler(handler: impl Fn(&Notice) + Send + Sync + 'static) {
+    *HANDLER.write().unwrap_or_else(PoisonError::into_inner) = Some(Box::new(handler));
+}
+
+pub fn clear_handler() {
+    *HANDLER.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+pub fn send(notice: Notice) {
+    if let Some(handler) = HANDLER.read().unwrap_or_else(PoisonError::into_inner).as_ref() {
+        handler(&notice);
+    }
+}
+
+pub fn warn(message: impl Into<String>) {
+    send(Notice::Warning(message.into()));
+}
+
+pub fn info(message: impl Into<String>) {
+    send(Notice::Info(message.into()));
+}
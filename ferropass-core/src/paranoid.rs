@@ -0,0 +1,22 @@
+use crate::error::FerropassError;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static PARANOID: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    PARANOID.store(true, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    PARANOID.load(Ordering::SeqCst)
+}
+
+// Every integration that reaches outside the process (clipboard, network,
+// hooks, agent, auto-type) must pass through here before doing anything.
+pub fn ensure_allowed(integration: &str) -> Result<(), FerropassError> {
+    if is_enabled() {
+        return Err(FerropassError::Validation(format!("{} is disabled in paranoid mode", integration)));
+    }
+    Ok(())
+}
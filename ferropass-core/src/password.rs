@@ -0,0 +1,1169 @@
+use crate::config;
+use crate::entropy;
+use crate::error::FerropassError;
+
+use rand::{CryptoRng, Rng};
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+use std::fs;
+
+const SPECIAL_CHARS: &str = "!@#$%^&*()-_=+[]{}|;:,.<>?/";
+const LOWERCASE_CHARS: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE_CHARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const NUMBERS: &str = "0123456789";
+// Characters easily mistaken for one another when read aloud or off paper.
+const AMBIGUOUS_CHARS: &str = "0Oo1lI|'\"`";
+
+pub const DEFAULT_LENGTH: usize = 20;
+pub const MAX_LENGTH: usize = 1024;
+
+// Which characters a random password is drawn from, and how many of each
+// class it must contain at least. Lowercase letters are always included.
+// Accounts can keep one as their password policy, so it is stored in the
+// vault. Missing fields take their default, so presets in the config only
+// need to name what they change.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct PasswordOptions {
+    pub length: usize,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub avoid_ambiguous: bool,
+    // Characters a site refuses, left out of every class.
+    pub exclude: String,
+    pub min_lowercase: usize,
+    pub min_uppercase: usize,
+    pub min_digits: usize,
+    pub min_symbols: usize,
+    // Rules some sites add, which can't be met by construction; passwords
+    // breaking them are drawn again.
+    pub start_with_letter: bool,
+    pub max_repeat: Option<usize>,
+}
+
+impl Default for PasswordOptions {
+    fn default() -> Self {
+        PasswordOptions {
+            length: DEFAULT_LENGTH,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            avoid_ambiguous: false,
+            exclude: String::new(),
+            min_lowercase: 1,
+            min_uppercase: 1,
+            min_digits: 1,
+            min_symbols: 1,
+            start_with_letter: false,
+            max_repeat: None,
+        }
+    }
+}
+
+// One enabled character class: its name, its characters and how many of
+// them a password needs.
+struct CharClass {
+    name: &'static str,
+    chars: Vec<char>,
+    minimum: usize,
+}
+
+impl PasswordOptions {
+    // A class whose characters are all excluded is dropped.
+    fn classes(&self) -> Vec<CharClass> {
+        let mut classes = vec![("lowercase letters", LOWERCASE_CHARS, self.min_lowercase)];
+        if self.uppercase {
+            classes.push(("uppercase letters", UPPERCASE_CHARS, self.min_uppercase));
+        }
+        if self.digits {
+            classes.push(("digits", NUMBERS, self.min_digits));
+        }
+        if self.symbols {
+            classes.push(("symbols", SPECIAL_CHARS, self.min_symbols));
+        }
+        
+        classes.into_iter()
+            .map(|(name, chars, minimum)| CharClass {
+                name,
+                chars: chars.chars()
+                    .filter(|c| !self.avoid_ambiguous || !AMBIGUOUS_CHARS.contains(*c))
+                    .filter(|c| !self.exclude.contains(*c))
+                    .collect(),
+                minimum,
+            })
+            .filter(|class| !class.chars.is_empty())
+            .collect()
+    }
+    
+    pub fn check(&self) -> Result<(), FerropassError> {
+        let classes = self.classes();
+        if classes.is_empty() {
+            return Err(FerropassError::Validation("Every character is excluded, so there is nothing to generate from".to_string()));
+        }
+        
+        let required: usize = classes.iter().map(|class| class.minimum).sum();
+        if self.length < required.max(1) || self.length > MAX_LENGTH {
+            return Err(FerropassError::Validation(format!(
+                "Password length must be between {} and {} with the selected character sets and minimums", required.max(1), MAX_LENGTH
+            )));
+        }
+        
+        let all_chars: usize = classes.iter().map(|class| class.chars.len()).sum();
+        if self.start_with_letter && !classes.iter().any(|class| class.chars.iter().any(char::is_ascii_alphabetic)) {
+            return Err(FerropassError::Validation("The password must start with a letter, but every letter is excluded".to_string()));
+        }
+        match self.max_repeat {
+            Some(0) => return Err(FerropassError::Validation("At most 0 identical characters in a row leaves no password at all".to_string())),
+            Some(max) if all_chars == 1 && self.length > max => {
+                return Err(FerropassError::Validation(format!("Only one character is left, so it can't appear at most {} times in a row", max)));
+            },
+            _ => {},
+        }
+        
+        Ok(())
+    }
+    
+    // Whether a password drawn from the classes also follows the rules that
+    // aren't met by construction.
+    fn accepts(&self, password: &[char]) -> bool {
+        if self.start_with_letter && !password.first().is_some_and(char::is_ascii_alphabetic) {
+            return false;
+        }
+        if let Some(max) = self.max_repeat && password.chunk_by(|a, b| a == b).any(|run| run.len() > max) {
+            return false;
+        }
+        
+        true
+    }
+    
+    pub fn describe(&self) -> String {
+        let mut classes = vec!["lowercase"];
+        if self.uppercase {
+            classes.push("uppercase");
+        }
+        if self.digits {
+            classes.push("digits");
+        }
+        if self.symbols {
+            classes.push("symbols");
+        }
+        
+        let mut description = format!("{} characters, {}", self.length, classes.join(", "));
+        for class in self.classes() {
+            if class.minimum != 1 {
+                description.push_str(&format!(", at least {} {}", class.minimum, class.name));
+            }
+        }
+        if self.avoid_ambiguous {
+            description.push_str(", no ambiguous characters");
+        }
+        if !self.exclude.is_empty() {
+            description.push_str(&format!(", never {:?}", self.exclude));
+        }
+        if self.start_with_letter {
+            description.push_str(", starting with a letter");
+        }
+        if let Some(max) = self.max_repeat {
+            description.push_str(&format!(", no character more than {} times in a row", max));
+        }
+        description
+    }
+}
+
+// A generated password and how strong it is by construction: its entropy in
+// bits, and what it was drawn from.
+pub struct Generated {
+    pub password: String,
+    pub bits: f64,
+    pub space: String,
+}
+
+impl Generated {
+    pub fn describe(&self) -> String {
+        format!("{:.1} bits of entropy, {}", self.bits, self.space)
+    }
+}
+
+// Draws of a password breaking the rules before giving up on them.
+const MAX_ATTEMPTS: usize = 1000;
+
+// The minimum number of characters of each class is drawn from that class,
+// the rest from all of them, and the lot is shuffled. The entropy given counts
+// only those draws, leaving out what the shuffle adds, so it is a lower bound.
+// A password that breaks the other rules is drawn again, which takes the
+// share of passwords they reject off the entropy.
+pub fn generate_random_password(options: &PasswordOptions) -> Result<Generated, FerropassError> {
+    entropy::ensure()?;
+    generate_random_password_with(options, &mut OsRng)
+}
+
+pub fn generate_random_password_with<R: Rng + CryptoRng>(options: &PasswordOptions, rng: &mut R) -> Result<Generated, FerropassError> {
+    options.check()?;
+    
+    let classes = options.classes();
+    let all_chars: Vec<char> = classes.iter().flat_map(|class| class.chars.iter().copied()).collect();
+    
+    let required: usize = classes.iter().map(|class| class.minimum).sum();
+    let mut bits = classes.iter().map(|class| class.minimum as f64 * (class.chars.len() as f64).log2()).sum::<f64>()
+        + (options.length - required) as f64 * (all_chars.len() as f64).log2();
+    if options.start_with_letter {
+        let letters = all_chars.iter().filter(|c| c.is_ascii_alphabetic()).count();
+        bits -= (all_chars.len() as f64 / letters as f64).log2();
+    }
+    if let Some(max) = options.max_repeat {
+        bits += share_without_long_runs(all_chars.len(), options.length, max).log2();
+    }
+    
+    for _ in 0..MAX_ATTEMPTS {
+        let mut password_chars: Vec<char> = Vec::with_capacity(options.length);
+        
+        for class in &classes {
+            for _ in 0..class.minimum {
+                password_chars.push(*class.chars.choose(rng).unwrap());
+            }
+        }
+        
+        while password_chars.len() < options.length {
+            password_chars.push(*all_chars.choose(rng).unwrap());
+        }
+        
+        password_chars.shuffle(rng);
+        
+        if options.accepts(&password_chars) {
+            return Ok(Generated {
+                password: password_chars.into_iter().collect(),
+                bits,
+                space: format!("{} characters from a set of {}", options.length, all_chars.len()),
+            });
+        }
+    }
+    
+    Err(FerropassError::Validation(format!(
+        "No password following the policy ({}) came up in {} tries; relax its rules",
+        options.describe(), MAX_ATTEMPTS
+    )))
+}
+
+// The share of passwords of `length` characters drawn from a set of `size`
+// that repeat no character more than `max` times in a row, worked out by the
+// length of the run each one ends with.
+fn share_without_long_runs(size: usize, length: usize, max: usize) -> f64 {
+    let same = 1.0 / size as f64;
+    let mut runs = vec![0.0; max + 1];
+    runs[1] = 1.0;
+    
+    for _ in 1..length {
+        let total: f64 = runs.iter().sum();
+        let mut next = vec![0.0; max + 1];
+        next[1] = total * (1.0 - same);
+        for run in 2..=max {
+            next[run] = runs[run - 1] * same;
+        }
+        runs = next;
+    }
+    
+    runs.iter().sum()
+}
+
+pub fn is_password_valid(password: &str) -> bool {
+    if password.len() < 15 || common_password_reason(password).is_some() {
+        return false;
+    }
+    
+    let has_lowercase = password.chars().any(|c| LOWERCASE_CHARS.contains(c));
+    let has_uppercase = password.chars().any(|c| UPPERCASE_CHARS.contains(c));
+    let has_number = password.chars().any(|c| NUMBERS.contains(c));
+    let has_special = password.chars().any(|c| SPECIAL_CHARS.contains(c));
+    
+    has_lowercase && has_uppercase && has_number && has_special
+}
+
+// zxcvbn's top score: the password is estimated to need more than 10^10
+// guesses, which is what it takes to hold out against offline guessing.
+const STRONG_SCORE: u8 = 4;
+
+pub struct PasswordStrength {
+    pub score: u8,
+    pub bits: f64,
+    pub crack_time: String,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+impl PasswordStrength {
+    pub fn is_strong(&self) -> bool {
+        self.score >= STRONG_SCORE
+    }
+}
+
+// Estimates how hard a password is to guess from the patterns it is made of
+// (dictionary words, names, dates, keyboard runs, repeats, substitutions)
+// rather than from which kinds of characters it contains.
+// A password built on a common one scores at most 1, whatever zxcvbn makes
+// of the digits and symbols around it.
+pub fn rate_password(password: &str) -> PasswordStrength {
+    let entropy = zxcvbn::zxcvbn(password, &["ferropass"]);
+    let feedback = entropy.feedback();
+
+    let mut strength = PasswordStrength {
+        score: entropy.score().into(),
+        bits: entropy.guesses_log10() * std::f64::consts::LOG2_10,
+        crack_time: entropy.crack_times().offline_slow_hashing_1e4_per_second().to_string(),
+        warning: feedback.and_then(|feedback| feedback.warning()).map(|warning| warning.to_string()),
+        suggestions: feedback
+            .map(|feedback| feedback.suggestions().iter().map(|suggestion| suggestion.to_string()).collect())
+            .unwrap_or_default(),
+    };
+
+    if let Some(reason) = common_password_reason(password) {
+        strength.score = strength.score.min(1);
+        strength.warning = Some(format!("This password {}.", reason));
+    }
+    strength
+}
+
+const COMMON_PASSWORDS: &str = include_str!("wordlists/common.txt");
+// Rows of keys and runs of characters that guessers try in both directions.
+const RUNS: [&str; 6] = ["abcdefghijklmnopqrstuvwxyz", "01234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm", "1qaz2wsx3edc4rfv"];
+const MIN_RUN: usize = 4;
+
+// Letters commonly swapped for look-alike digits and symbols.
+fn undo_substitutions(text: &str, one: char) -> String {
+    text.chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' => one,
+            '3' => 'e',
+            '4' | '@' => 'a',
+            '5' | '$' => 's',
+            '7' => 't',
+            '!' => 'i',
+            c => c,
+        })
+        .collect()
+}
+
+// Why a password is among the first ones guessed, if it is: a common password
+// with digits and symbols around it and look-alike swaps ("Password123456!",
+// "P@ssw0rd1"), a common password repeated, a run of keys or characters, or
+// one character repeated.
+pub fn common_password_reason(password: &str) -> Option<String> {
+    let lower = password.to_lowercase();
+    
+    let mut chars = lower.chars();
+    if let Some(first) = chars.next() && chars.all(|c| c == first) {
+        return Some("is a single character repeated".to_string());
+    }
+    
+    if lower.chars().count() >= MIN_RUN {
+        let reversed: String = lower.chars().rev().collect();
+        if RUNS.iter().any(|run| run.contains(&lower) || run.contains(&reversed)) {
+            return Some("is a run of keys or characters".to_string());
+        }
+    }
+    
+    // Digits and symbols are stripped from the ends both before and after
+    // undoing swaps, for "Password1!" as well as "hell0".
+    let trimmed = lower.trim_matches(|c: char| !c.is_alphabetic());
+    for one in ['i', 'l'] {
+        let cores = [
+            undo_substitutions(trimmed, one),
+            undo_substitutions(&lower, one).trim_matches(|c: char| !c.is_alphabetic()).to_string(),
+        ];
+        
+        for core in cores {
+            let length = core.chars().count();
+        
+            for word in COMMON_PASSWORDS.lines() {
+                let repeats = length / word.chars().count().max(1);
+                if repeats > 0 && core == word.repeat(repeats) {
+                    return Some(format!("is built on \"{}\", one of the most common passwords", word));
+                }
+            }
+        }
+    }
+    
+    None
+}
+
+const EFF_LARGE_WORDLIST: &str = include_str!("wordlists/eff_large.txt");
+const GERMAN_WORDLIST: &str = include_str!("wordlists/de.txt");
+const FRENCH_WORDLIST: &str = include_str!("wordlists/fr.txt");
+const SPANISH_WORDLIST: &str = include_str!("wordlists/es.txt");
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [Language::English, Language::German, Language::French, Language::Spanish];
+    
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+            Language::French => "Français",
+            Language::Spanish => "Español",
+        }
+    }
+    
+    fn wordlist(&self) -> &'static str {
+        match self {
+            Language::English => EFF_LARGE_WORDLIST,
+            Language::German => GERMAN_WORDLIST,
+            Language::French => FRENCH_WORDLIST,
+            Language::Spanish => SPANISH_WORDLIST,
+        }
+    }
+    
+    pub fn words(&self) -> Vec<&'static str> {
+        self.wordlist()
+            .lines()
+            .filter_map(|line| line.split('\t').nth(1))
+            .collect()
+    }
+}
+
+// What a passphrase should reach with the default number of words. The EFF
+// list has 7776 words (five dice) and needs 6 of them, the others 1296 (four
+// dice) and need 8.
+const PASSPHRASE_TARGET_BITS: f64 = 77.0;
+// A list of your own must have at least as many words as a four-dice list.
+const MIN_CUSTOM_WORDS: usize = 1296;
+
+// The words a passphrase is drawn from: one of the embedded lists, or one of
+// your own from the wordlists directory.
+pub struct Wordlist {
+    name: String,
+    words: Vec<String>,
+}
+
+impl Wordlist {
+    pub fn builtin(language: Language) -> Self {
+        Wordlist {
+            name: language.name().to_string(),
+            words: language.words().into_iter().map(str::to_string).collect(),
+        }
+    }
+    
+    // Reads `<name>.txt` from the wordlists directory. It holds one word per
+    // line, optionally after its dice number as in the diceware lists; empty
+    // lines and lines starting with # are skipped, and repeated words count
+    // once.
+    pub fn load(name: &str) -> Result<Self, FerropassError> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(FerropassError::Validation(format!("Invalid wordlist name '{}'", name)));
+        }
+        let dir = config::current().wordlist_dir()
+            .ok_or_else(|| FerropassError::Validation("No wordlists directory; set wordlist_dir in the config or HOME".to_string()))?;
+        let path = dir.join(format!("{}.txt", name));
+        let content = fs::read_to_string(&path).map_err(|e| FerropassError::Io(format!("Failed to read wordlist {}: {}", path.display(), e)))?;
+        
+        let mut seen = HashSet::new();
+        let mut words = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            
+            let word = match line.split_whitespace().collect::<Vec<&str>>()[..] {
+                [word] => word,
+                [dice, word] if dice.chars().all(|c| c.is_ascii_digit()) => word,
+                _ => return Err(FerropassError::Format(format!("{} line {}: expected one word, optionally after its dice number", path.display(), number + 1))),
+            };
+            if seen.insert(word.to_lowercase()) {
+                words.push(word.to_string());
+            }
+        }
+        
+        if words.len() < MIN_CUSTOM_WORDS {
+            return Err(FerropassError::Validation(format!(
+                "Wordlist {} has {} different words, but at least {} are needed for {:.1} bits per word",
+                path.display(), words.len(), MIN_CUSTOM_WORDS, (MIN_CUSTOM_WORDS as f64).log2()
+            )));
+        }
+        
+        Ok(Wordlist { name: name.to_string(), words })
+    }
+    
+    // The names of the lists in the wordlists directory, whether or not they
+    // are valid.
+    pub fn custom_names() -> Vec<String> {
+        let Some(entries) = config::current().wordlist_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+            return Vec::new();
+        };
+        
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                match path.extension() {
+                    Some(extension) if extension == "txt" => Some(path.file_stem()?.to_string_lossy().to_string()),
+                    _ => None,
+                }
+            })
+            .collect();
+        names.sort();
+        names
+    }
+    
+    pub fn default_word_count(&self) -> usize {
+        (PASSPHRASE_TARGET_BITS / (self.words.len() as f64).log2()).ceil() as usize
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Separator {
+    Hyphen,
+    Space,
+    Dot,
+    Underscore,
+    Digit,
+}
+
+impl Separator {
+    pub const ALL: [Separator; 5] = [Separator::Hyphen, Separator::Space, Separator::Dot, Separator::Underscore, Separator::Digit];
+    
+    pub fn name(&self) -> &'static str {
+        match self {
+            Separator::Hyphen => "hyphen (word-word)",
+            Separator::Space => "space (word word)",
+            Separator::Dot => "dot (word.word)",
+            Separator::Underscore => "underscore (word_word)",
+            Separator::Digit => "random digit (word3word)",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Capitalization {
+    Lower,
+    Title,
+    Upper,
+    Mixed,
+}
+
+impl Capitalization {
+    pub const ALL: [Capitalization; 4] = [Capitalization::Lower, Capitalization::Title, Capitalization::Upper, Capitalization::Mixed];
+    
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capitalization::Lower => "lowercase (word)",
+            Capitalization::Title => "capitalized (Word)",
+            Capitalization::Upper => "uppercase (WORD)",
+            Capitalization::Mixed => "mixed, at random per word",
+        }
+    }
+}
+
+pub const MAX_WORDS: usize = 64;
+
+pub fn generate_passphrase(wordlist: &Wordlist, word_count: usize, separator: Separator, capitalization: Capitalization) -> Result<Generated, FerropassError> {
+    entropy::ensure()?;
+    generate_passphrase_with(wordlist, word_count, separator, capitalization, &mut OsRng)
+}
+
+pub fn generate_passphrase_with<R: Rng + CryptoRng>(
+    wordlist: &Wordlist,
+    word_count: usize,
+    separator: Separator,
+    capitalization: Capitalization,
+    rng: &mut R,
+) -> Result<Generated, FerropassError> {
+    if word_count == 0 || word_count > MAX_WORDS {
+        return Err(FerropassError::Validation(format!("A passphrase must have between 1 and {} words", MAX_WORDS)));
+    }
+    
+    let words = &wordlist.words;
+    
+    let mut passphrase = String::new();
+    
+    for i in 0..word_count {
+        if i > 0 {
+            match separator {
+                Separator::Hyphen => passphrase.push('-'),
+                Separator::Space => passphrase.push(' '),
+                Separator::Dot => passphrase.push('.'),
+                Separator::Underscore => passphrase.push('_'),
+                Separator::Digit => passphrase.push(NUMBERS.chars().nth(rng.gen_range(0..NUMBERS.len())).unwrap()),
+            }
+        }
+        
+        let word = words.choose(rng).unwrap();
+        let word = match capitalization {
+            Capitalization::Lower => word.to_string(),
+            Capitalization::Title => capitalize(word),
+            Capitalization::Upper => word.to_uppercase(),
+            Capitalization::Mixed if rng.gen_bool(0.5) => capitalize(word),
+            Capitalization::Mixed => word.to_string(),
+        };
+        passphrase.push_str(&word);
+    }
+    
+    // A random digit between words and random capitalization per word add
+    // their own choices to those of the words.
+    let mut bits = word_count as f64 * (words.len() as f64).log2();
+    if separator == Separator::Digit {
+        bits += (word_count - 1) as f64 * (NUMBERS.len() as f64).log2();
+    }
+    if capitalization == Capitalization::Mixed {
+        bits += word_count as f64;
+    }
+    
+    Ok(Generated {
+        password: passphrase,
+        bits,
+        space: format!("{} words from the {} list of {}", word_count, wordlist.name, words.len()),
+    })
+}
+
+pub const DEFAULT_MEMORABLE_WORDS: usize = 4;
+// What words are joined with in a memorable password.
+const MEMORABLE_SEPARATORS: &str = "0123456789!#$%&*+=?@";
+// Look-alike swaps, each made with a chance of one in three.
+const SUBSTITUTIONS: [(char, &str); 6] = [('a', "@4"), ('e', "3"), ('i', "1!"), ('o', "0"), ('s', "$5"), ('t', "7")];
+
+// Fewer, readable words than a passphrase, such as "Harbor7cl0ud&BONFIRE$5ketch":
+// each word lowercase, capitalized or uppercase, joined by a random digit or
+// symbol, and with some letters swapped for look-alikes. Guessers try the
+// swaps early, so they are left out of the entropy, which makes it a lower
+// bound.
+pub fn generate_memorable(word_count: usize) -> Result<Generated, FerropassError> {
+    entropy::ensure()?;
+    generate_memorable_with(word_count, &mut OsRng)
+}
+
+pub fn generate_memorable_with<R: Rng + CryptoRng>(word_count: usize, rng: &mut R) -> Result<Generated, FerropassError> {
+    if word_count == 0 || word_count > MAX_WORDS {
+        return Err(FerropassError::Validation(format!("A memorable password must have between 1 and {} words", MAX_WORDS)));
+    }
+    
+    let words = Language::English.words();
+    let separators: Vec<char> = MEMORABLE_SEPARATORS.chars().collect();
+    
+    let mut password = String::new();
+    
+    for i in 0..word_count {
+        if i > 0 {
+            password.push(*separators.choose(rng).unwrap());
+        }
+        
+        let word = words.choose(rng).unwrap();
+        let word = match rng.gen_range(0..3) {
+            0 => word.to_string(),
+            1 => capitalize(word),
+            _ => word.to_uppercase(),
+        };
+        
+        for c in word.chars() {
+            let swaps = SUBSTITUTIONS.iter()
+                .find(|(letter, _)| *letter == c.to_ascii_lowercase())
+                .map(|(_, swaps)| swaps.chars().collect::<Vec<char>>());
+            
+            match swaps {
+                Some(swaps) if rng.gen_ratio(1, 3) => password.push(*swaps.choose(rng).unwrap()),
+                _ => password.push(c),
+            }
+        }
+    }
+    
+    let bits = word_count as f64 * ((words.len() as f64).log2() + 3f64.log2())
+        + (word_count - 1) as f64 * (separators.len() as f64).log2();
+    
+    Ok(Generated {
+        password,
+        bits,
+        space: format!("{} words from a list of {} with swapped letters", word_count, words.len()),
+    })
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// Syllables are a consonant followed by a vowel; l, c, q, x and y are left
+// out so every syllable reads and sounds one way only.
+const SYLLABLE_CONSONANTS: &str = "bdfghjkmnprstvwz";
+const SYLLABLE_VOWELS: &str = "aeiou";
+const SYLLABLES_PER_GROUP: usize = 3;
+pub const DEFAULT_SYLLABLES: usize = 12;
+pub const MAX_SYLLABLES: usize = 128;
+
+// A password made of syllables, such as "kotabu-mesifa", grouped with hyphens
+// for easier memorizing. Every syllable is drawn independently, about 6.3
+// bits each.
+pub fn generate_pronounceable(syllables: usize) -> Result<Generated, FerropassError> {
+    entropy::ensure()?;
+    generate_pronounceable_with(syllables, &mut OsRng)
+}
+
+pub fn generate_pronounceable_with<R: Rng + CryptoRng>(syllables: usize, rng: &mut R) -> Result<Generated, FerropassError> {
+    if syllables == 0 || syllables > MAX_SYLLABLES {
+        return Err(FerropassError::Validation(format!("A pronounceable password must have between 1 and {} syllables", MAX_SYLLABLES)));
+    }
+    
+    let consonants: Vec<char> = SYLLABLE_CONSONANTS.chars().collect();
+    let vowels: Vec<char> = SYLLABLE_VOWELS.chars().collect();
+    
+    let mut password = String::new();
+    
+    for i in 0..syllables {
+        if i > 0 && i % SYLLABLES_PER_GROUP == 0 {
+            password.push('-');
+        }
+        password.push(*consonants.choose(rng).unwrap());
+        password.push(*vowels.choose(rng).unwrap());
+    }
+    
+    let space = consonants.len() * vowels.len();
+    Ok(Generated {
+        password,
+        bits: syllables as f64 * (space as f64).log2(),
+        space: format!("{} syllables from a set of {}", syllables, space),
+    })
+}
+
+pub const PATTERN_HELP: &str = "L letter, l lowercase, U uppercase, d digit, S symbol, a letter or digit, * any; \\ keeps the next character as it is";
+
+// The characters a placeholder in a pattern stands for.
+fn pattern_class(placeholder: char) -> Option<Vec<char>> {
+    let class = match placeholder {
+        'L' => format!("{}{}", LOWERCASE_CHARS, UPPERCASE_CHARS),
+        'l' => LOWERCASE_CHARS.to_string(),
+        'U' => UPPERCASE_CHARS.to_string(),
+        'd' => NUMBERS.to_string(),
+        'S' => SPECIAL_CHARS.to_string(),
+        'a' => format!("{}{}{}", LOWERCASE_CHARS, UPPERCASE_CHARS, NUMBERS),
+        '*' => format!("{}{}{}{}", LOWERCASE_CHARS, UPPERCASE_CHARS, NUMBERS, SPECIAL_CHARS),
+        _ => return None,
+    };
+    
+    Some(class.chars().collect())
+}
+
+// Fills each placeholder of a pattern such as "LLLLdddd-SS" with a random
+// character of its class and keeps everything else, for sites that insist on
+// a fixed format.
+pub fn generate_from_pattern(pattern: &str) -> Result<Generated, FerropassError> {
+    entropy::ensure()?;
+    generate_from_pattern_with(pattern, &mut OsRng)
+}
+
+pub fn generate_from_pattern_with<R: Rng + CryptoRng>(pattern: &str, rng: &mut R) -> Result<Generated, FerropassError> {
+    let mut password = String::with_capacity(pattern.len());
+    let mut class_sizes = Vec::new();
+    let mut chars = pattern.chars();
+    
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            password.push(chars.next().ok_or_else(|| FerropassError::Validation("The pattern ends with a lone \\".to_string()))?);
+            continue;
+        }
+        
+        match pattern_class(c) {
+            Some(class) => {
+                password.push(*class.choose(rng).unwrap());
+                class_sizes.push(class.len());
+            },
+            None => password.push(c),
+        }
+    }
+    
+    let (Some(smallest), Some(largest)) = (class_sizes.iter().min(), class_sizes.iter().max()) else {
+        return Err(FerropassError::Validation(format!("The pattern has no placeholders ({})", PATTERN_HELP)));
+    };
+    let sets = if smallest == largest {
+        format!("a set of {}", largest)
+    } else {
+        format!("sets of {} to {}", smallest, largest)
+    };
+    
+    Ok(Generated {
+        password,
+        bits: class_sizes.iter().map(|&size| (size as f64).log2()).sum(),
+        space: format!("{} random characters from {}", class_sizes.len(), sets),
+    })
+}
+
+pub const DEFAULT_PIN_LENGTH: usize = 6;
+const MIN_PIN_LENGTH: usize = 4;
+const MAX_PIN_LENGTH: usize = 64;
+// The ten repeated digits, ten ascending and ten descending runs (wrapping
+// from 9 to 0), and the 90 alternations of two different digits.
+const TRIVIAL_PINS: f64 = 120.0;
+
+// PINs people pick most often and therefore guess first: one digit repeated,
+// a run up or down, or two digits alternating.
+fn is_trivial_pin(digits: &[u8]) -> bool {
+    let steps: Vec<u8> = digits.windows(2).map(|pair| (10 + pair[1] - pair[0]) % 10).collect();
+    
+    let run = matches!(steps[0], 0 | 1 | 9) && steps.iter().all(|&step| step == steps[0]);
+    let alternating = digits.iter().enumerate().all(|(i, &digit)| digit == digits[i % 2]);
+    
+    run || alternating
+}
+
+// A numeric PIN for a card or phone, drawn again whenever it comes out trivial.
+pub fn generate_pin(length: usize) -> Result<Generated, FerropassError> {
+    entropy::ensure()?;
+    generate_pin_with(length, &mut OsRng)
+}
+
+pub fn generate_pin_with<R: Rng + CryptoRng>(length: usize, rng: &mut R) -> Result<Generated, FerropassError> {
+    if !(MIN_PIN_LENGTH..=MAX_PIN_LENGTH).contains(&length) {
+        return Err(FerropassError::Validation(format!("A PIN must have between {} and {} digits", MIN_PIN_LENGTH, MAX_PIN_LENGTH)));
+    }
+    
+    let digits = loop {
+        let digits: Vec<u8> = (0..length).map(|_| rng.gen_range(0..10)).collect();
+        if !is_trivial_pin(&digits) {
+            break digits;
+        }
+    };
+    
+    Ok(Generated {
+        password: digits.iter().map(|digit| char::from(b'0' + digit)).collect(),
+        bits: (10f64.powi(length as i32) - TRIVIAL_PINS).log2(),
+        space: format!("{} digits, leaving out {} trivial PINs", length, TRIVIAL_PINS),
+    })
+}
+
+// A named set of generator settings from the config, such as
+// "wifi-passphrase": {"kind": "passphrase", "words": 5, "separator": "space"}.
+// Settings left out take the same defaults as `gen`.
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Preset {
+    Random(PasswordOptions),
+    Passphrase {
+        #[serde(default)]
+        language: Option<Language>,
+        // A list of your own, used instead of the language's.
+        #[serde(default)]
+        wordlist: Option<String>,
+        #[serde(default)]
+        words: Option<usize>,
+        #[serde(default)]
+        separator: Option<Separator>,
+        #[serde(default)]
+        capitalization: Option<Capitalization>,
+    },
+    Memorable {
+        #[serde(default)]
+        words: Option<usize>,
+    },
+    Pronounceable {
+        #[serde(default)]
+        syllables: Option<usize>,
+    },
+    Pattern {
+        pattern: String,
+    },
+    Pin {
+        #[serde(default)]
+        length: Option<usize>,
+    },
+}
+
+impl Preset {
+    pub fn generate(&self) -> Result<Generated, FerropassError> {
+        match self {
+            Preset::Random(options) => generate_random_password(options),
+            Preset::Passphrase { language, wordlist, words, separator, capitalization } => {
+                let wordlist = match wordlist {
+                    Some(name) => Wordlist::load(name)?,
+                    None => Wordlist::builtin(language.unwrap_or(Language::English)),
+                };
+                generate_passphrase(
+                    &wordlist,
+                    words.unwrap_or(wordlist.default_word_count()),
+                    separator.unwrap_or(Separator::Hyphen),
+                    capitalization.unwrap_or(Capitalization::Lower),
+                )
+            },
+            Preset::Memorable { words } => generate_memorable(words.unwrap_or(DEFAULT_MEMORABLE_WORDS)),
+            Preset::Pronounceable { syllables } => generate_pronounceable(syllables.unwrap_or(DEFAULT_SYLLABLES)),
+            Preset::Pattern { pattern } => generate_from_pattern(pattern),
+            Preset::Pin { length } => generate_pin(length.unwrap_or(DEFAULT_PIN_LENGTH)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+
+    fn digits(pin: &str) -> Vec<u8> {
+        pin.bytes().map(|digit| digit - b'0').collect()
+    }
+
+    #[test]
+    fn trivial_pins_are_recognised() {
+        for pin in ["0000", "999999", "1234", "3456789", "8901", "9876", "2109", "1212", "0707", "909090"] {
+            assert!(is_trivial_pin(&digits(pin)), "{} should be trivial", pin);
+        }
+        for pin in ["1357", "2580", "1123", "0110", "9812", "4471"] {
+            assert!(!is_trivial_pin(&digits(pin)), "{} should not be trivial", pin);
+        }
+    }
+
+    #[test]
+    fn pins_are_digits_and_never_trivial() {
+        for _ in 0..500 {
+            let pin = generate_pin(MIN_PIN_LENGTH).unwrap();
+            assert_eq!(pin.password.len(), MIN_PIN_LENGTH);
+            assert!(pin.password.bytes().all(|c| c.is_ascii_digit()));
+            assert!(!is_trivial_pin(&digits(&pin.password)));
+        }
+
+        let pin = generate_pin(MIN_PIN_LENGTH).unwrap();
+        assert_eq!(pin.bits, (10_000.0 - TRIVIAL_PINS).log2());
+    }
+
+    #[test]
+    fn pin_length_is_bounded() {
+        assert!(matches!(generate_pin(MIN_PIN_LENGTH - 1), Err(FerropassError::Validation(_))));
+        assert!(matches!(generate_pin(MAX_PIN_LENGTH + 1), Err(FerropassError::Validation(_))));
+        assert!(matches!(generate_pin(0), Err(FerropassError::Validation(_))));
+        assert_eq!(generate_pin(MAX_PIN_LENGTH).unwrap().password.len(), MAX_PIN_LENGTH);
+    }
+
+    #[test]
+    fn patterns_fill_placeholders_and_keep_the_rest() {
+        let generated = generate_from_pattern("LLll-UUdd-SSa*\\d\\\\").unwrap();
+        let password: Vec<char> = generated.password.chars().collect();
+        assert_eq!(password.len(), 16);
+
+        assert!(password[..2].iter().all(char::is_ascii_alphabetic));
+        assert!(password[2..4].iter().all(char::is_ascii_lowercase));
+        assert_eq!(password[4], '-');
+        assert!(password[5..7].iter().all(char::is_ascii_uppercase));
+        assert!(password[7..9].iter().all(char::is_ascii_digit));
+        assert_eq!(password[9], '-');
+        assert!(password[10..12].iter().all(|&c| SPECIAL_CHARS.contains(c)));
+        assert!(password[12].is_ascii_alphanumeric());
+        assert_eq!(&password[14..], ['d', '\\']);
+
+        let sizes: [f64; 12] = [52.0, 52.0, 26.0, 26.0, 26.0, 26.0, 10.0, 10.0, 27.0, 27.0, 62.0, 89.0];
+        let expected: f64 = sizes.iter().map(|size| size.log2()).sum();
+        assert!((generated.bits - expected).abs() < 1e-9);
+        assert_eq!(generated.space, "12 random characters from sets of 10 to 89");
+    }
+
+    #[test]
+    fn patterns_without_placeholders_are_refused() {
+        assert!(matches!(generate_from_pattern("ddd\\"), Err(FerropassError::Validation(_))));
+        assert!(matches!(generate_from_pattern("\\d\\L-!"), Err(FerropassError::Validation(_))));
+        assert!(matches!(generate_from_pattern(""), Err(FerropassError::Validation(_))));
+        assert_eq!(generate_from_pattern("dddd").unwrap().space, "4 random characters from a set of 10");
+    }
+
+    #[test]
+    fn generated_passwords_follow_the_policy() {
+        let options = PasswordOptions {
+            length: 12,
+            min_digits: 4,
+            min_symbols: 2,
+            avoid_ambiguous: true,
+            exclude: "aeiou".to_string(),
+            start_with_letter: true,
+            max_repeat: Some(2),
+            ..PasswordOptions::default()
+        };
+
+        for _ in 0..200 {
+            let password: Vec<char> = generate_random_password(&options).unwrap().password.chars().collect();
+            assert_eq!(password.len(), 12);
+            assert!(password[0].is_ascii_alphabetic());
+            assert!(password.iter().filter(|c| c.is_ascii_lowercase()).count() >= 1);
+            assert!(password.iter().filter(|c| c.is_ascii_uppercase()).count() >= 1);
+            assert!(password.iter().filter(|c| c.is_ascii_digit()).count() >= 4);
+            assert!(password.iter().filter(|&&c| SPECIAL_CHARS.contains(c)).count() >= 2);
+            assert!(!password.iter().any(|&c| AMBIGUOUS_CHARS.contains(c) || "aeiou".contains(c)));
+            assert!(password.chunk_by(|a, b| a == b).all(|run| run.len() <= 2));
+        }
+    }
+
+    #[test]
+    fn policies_describe_what_they_change() {
+        assert_eq!(PasswordOptions::default().describe(), "20 characters, lowercase, uppercase, digits, symbols");
+
+        let options = PasswordOptions {
+            length: 12,
+            symbols: false,
+            min_digits: 4,
+            min_lowercase: 0,
+            avoid_ambiguous: true,
+            exclude: "aeiou".to_string(),
+            start_with_letter: true,
+            max_repeat: Some(2),
+            ..PasswordOptions::default()
+        };
+        assert_eq!(options.describe(), concat!(
+            "12 characters, lowercase, uppercase, digits, at least 0 lowercase letters, at least 4 digits, ",
+            "no ambiguous characters, never \"aeiou\", starting with a letter, no character more than 2 times in a row",
+        ));
+    }
+
+    #[test]
+    fn impossible_policies_are_refused() {
+        let letters_only = PasswordOptions { uppercase: false, digits: false, symbols: false, ..PasswordOptions::default() };
+        let cases = [
+            PasswordOptions { length: 3, ..PasswordOptions::default() },
+            PasswordOptions { length: MAX_LENGTH + 1, ..PasswordOptions::default() },
+            PasswordOptions { exclude: LOWERCASE_CHARS.to_string(), ..letters_only.clone() },
+            PasswordOptions { digits: true, exclude: LOWERCASE_CHARS.to_string(), start_with_letter: true, ..letters_only.clone() },
+            PasswordOptions { max_repeat: Some(0), ..PasswordOptions::default() },
+            PasswordOptions { length: 3, exclude: LOWERCASE_CHARS[1..].to_string(), max_repeat: Some(2), ..letters_only.clone() },
+        ];
+        for options in cases {
+            assert!(matches!(options.check(), Err(FerropassError::Validation(_))), "{} should be refused", options.describe());
+            assert!(generate_random_password(&options).is_err());
+        }
+
+        let single = PasswordOptions { length: 2, exclude: LOWERCASE_CHARS[1..].to_string(), max_repeat: Some(2), ..letters_only };
+        assert_eq!(generate_random_password(&single).unwrap().password, "aa");
+    }
+
+    #[test]
+    fn policies_that_never_come_up_give_up() {
+        let options = PasswordOptions {
+            length: 64,
+            uppercase: false,
+            digits: false,
+            symbols: false,
+            exclude: LOWERCASE_CHARS[2..].to_string(),
+            max_repeat: Some(1),
+            ..PasswordOptions::default()
+        };
+
+        assert!(options.check().is_ok());
+        match generate_random_password(&options) {
+            Err(FerropassError::Validation(message)) => assert!(message.contains("relax its rules")),
+            _ => panic!("a policy met by 2 of 2^64 passwords should give up"),
+        }
+    }
+
+    #[test]
+    fn common_passwords_are_recognised() {
+        let cases = [
+            ("Password123456!", "password"),
+            ("P@ssw0rd1", "password"),
+            ("hell0", "hello"),
+            ("dragondragon", "dragon"),
+            ("!!M0nk3y!!", "monkey"),
+        ];
+        for (password, word) in cases {
+            let reason = common_password_reason(password).unwrap();
+            assert!(reason.contains(&format!("\"{}\"", word)), "{}: {}", password, reason);
+            assert!(rate_password(password).score <= 1);
+        }
+
+        assert_eq!(common_password_reason("zzzzzzzz").unwrap(), "is a single character repeated");
+        for run in ["4321", "qwerty", "mnbvcxz", "ghijklmn"] {
+            assert_eq!(common_password_reason(run).unwrap(), "is a run of keys or characters");
+        }
+        assert_eq!(common_password_reason("tqX9#vLp2!mW"), None);
+    }
+
+    #[test]
+    fn valid_passwords_are_long_mixed_and_uncommon() {
+        assert!(is_password_valid("tqX9#vLp2!mWz7Rk"));
+        assert!(!is_password_valid("tqX9#vLp2!mW"));
+        assert!(!is_password_valid("tqx9#vlp2!mwz7rk"));
+        assert!(!is_password_valid("tqXa#vLpb!mWzcRk"));
+        assert!(!is_password_valid("P@ssw0rd123456789!"));
+    }
+
+    // Every generator run through one seeded source, so what each draws
+    // depends on what the ones before it drew.
+    fn generate_all(seed: u64) -> Vec<String> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let wordlist = Wordlist::builtin(Language::English);
+        let options = PasswordOptions { start_with_letter: true, max_repeat: Some(2), ..PasswordOptions::default() };
+
+        [
+            generate_random_password_with(&options, &mut rng),
+            generate_passphrase_with(&wordlist, 6, Separator::Digit, Capitalization::Mixed, &mut rng),
+            generate_memorable_with(DEFAULT_MEMORABLE_WORDS, &mut rng),
+            generate_pronounceable_with(DEFAULT_SYLLABLES, &mut rng),
+            generate_from_pattern_with("LLLLdddd-SS", &mut rng),
+            generate_pin_with(DEFAULT_PIN_LENGTH, &mut rng),
+        ]
+        .into_iter()
+        .map(|generated| generated.unwrap().password)
+        .collect()
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_passwords() {
+        for seed in 0..20 {
+            assert_eq!(generate_all(seed), generate_all(seed));
+        }
+
+        let runs: HashSet<Vec<String>> = (0..20).map(generate_all).collect();
+        assert_eq!(runs.len(), 20);
+        for generator in 0..6 {
+            let passwords: HashSet<String> = runs.iter().map(|run| run[generator].clone()).collect();
+            assert!(passwords.len() > 15, "generator {} repeats itself across seeds", generator);
+        }
+    }
+
+    #[test]
+    fn seeded_pins_spread_over_every_digit() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts = [0usize; 10];
+        for _ in 0..2500 {
+            for digit in digits(&generate_pin_with(MIN_PIN_LENGTH, &mut rng).unwrap().password) {
+                counts[digit as usize] += 1;
+            }
+        }
+
+        // 10,000 digits, 1,000 of each expected.
+        assert!(counts.iter().all(|&count| (900..1100).contains(&count)), "{:?}", counts);
+    }
+
+    #[test]
+    fn seeded_passwords_follow_their_options() {
+        let mut rng = StdRng::seed_from_u64(11);
+
+        let passphrase = generate_passphrase_with(&Wordlist::builtin(Language::English), 5, Separator::Dot, Capitalization::Upper, &mut rng).unwrap();
+        let words: Vec<&str> = passphrase.password.split('.').collect();
+        assert_eq!(words.len(), 5);
+        assert!(words.iter().all(|word| !word.is_empty() && *word == word.to_uppercase()));
+        assert_eq!(passphrase.bits, 5.0 * 7776f64.log2());
+
+        let pronounceable = generate_pronounceable_with(7, &mut rng).unwrap();
+        let groups: Vec<&str> = pronounceable.password.split('-').collect();
+        assert_eq!(groups.iter().map(|group| group.len()).collect::<Vec<_>>(), [6, 6, 2]);
+        for syllable in pronounceable.password.replace('-', "").as_bytes().chunks(2) {
+            assert!(SYLLABLE_CONSONANTS.contains(syllable[0] as char) && SYLLABLE_VOWELS.contains(syllable[1] as char));
+        }
+
+        let memorable = generate_memorable_with(3, &mut rng).unwrap();
+        assert!(memorable.password.chars().filter(|&c| MEMORABLE_SEPARATORS.contains(c)).count() >= 2);
+    }
+}
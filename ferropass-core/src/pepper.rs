@@ -0,0 +1,93 @@
+use crate::config;
+use crate::entropy;
+use crate::error::FerropassError;
+use crate::memlock::{self, SecretBuffer};
+
+use base64::{Engine as _, engine::general_purpose};
+use sha2::{Digest, Sha256};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PEPPER_LEN: usize = 32;
+
+// A machine-local secret mixed into key derivation, kept outside the vault
+// file so a copy of the vault alone isn't enough to start guessing the
+// passkey. Vault headers refer to it by a short ID. Like the keys it goes
+// into, it is kept in locked memory and wiped once dropped.
+pub struct Pepper {
+    bytes: SecretBuffer,
+}
+
+impl Pepper {
+    pub fn get_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn id(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"ferropass:pepper:");
+        hasher.update(&*self.bytes);
+
+        format!("{:X}", hasher.finalize())[..8].to_string()
+    }
+}
+
+pub fn path() -> Result<PathBuf, FerropassError> {
+    config::current().pepper_file()
+        .ok_or_else(|| FerropassError::Validation("No location for the pepper file; set pepper_file in the config or HOME".to_string()))
+}
+
+// Loads the pepper a vault was set up with, refusing a different one.
+pub fn load(id: &str) -> Result<Pepper, FerropassError> {
+    let path = path()?;
+    let pepper = read(&path).map_err(|e| e.context(format!("This vault needs pepper {} from {}", id, path.display())))?;
+
+    if pepper.id() != id {
+        return Err(FerropassError::Validation(format!(
+            "{} holds pepper {}, but this vault needs pepper {}", path.display(), pepper.id(), id
+        )));
+    }
+
+    Ok(pepper)
+}
+
+// Loads the machine's pepper, creating it first if there isn't one yet.
+// Also returns where it is kept and whether it was just created.
+pub fn load_or_create() -> Result<(Pepper, PathBuf, bool), FerropassError> {
+    let path = path()?;
+    if path.exists() {
+        let pepper = read(&path).map_err(|e| e.context(path.display()))?;
+        return Ok((pepper, path, false));
+    }
+
+    entropy::ensure()?;
+    let mut bytes = SecretBuffer::new(PEPPER_LEN);
+    OsRng.fill_bytes(&mut bytes);
+
+    let mut content = format!("{}\n", general_purpose::STANDARD.encode(&*bytes));
+    let written = config::write_private_file(&path, &content);
+    wipe_string(&mut content);
+    written?;
+
+    Ok((Pepper { bytes }, path, true))
+}
+
+fn read(path: &Path) -> Result<Pepper, FerropassError> {
+    let mut content = fs::read_to_string(path).map_err(|e| FerropassError::Io(e.to_string()))?;
+    let decoded = general_purpose::STANDARD.decode(content.trim());
+    wipe_string(&mut content);
+    let bytes = decoded.map(SecretBuffer::take)
+        .map_err(|_| FerropassError::Format("the file is not a ferropass pepper".to_string()))?;
+
+    if bytes.len() != PEPPER_LEN {
+        return Err(FerropassError::Format("the file is not a ferropass pepper".to_string()));
+    }
+
+    Ok(Pepper { bytes })
+}
+
+fn wipe_string(text: &mut str) {
+    memlock::wipe(unsafe { text.as_bytes_mut() });
+}
@@ -0,0 +1,151 @@
+use crate::models::{Account, Database};
+
+use std::cmp::{Ordering, Reverse};
+use std::vec;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SortBy {
+    // The description, or the username where there is none.
+    Title,
+    Username,
+    Url,
+    // Newest first.
+    Created,
+    Updated,
+}
+
+impl SortBy {
+    fn compare(self, a: &Account, b: &Account) -> Ordering {
+        match self {
+            SortBy::Title => title(a).cmp(&title(b)),
+            SortBy::Username => a.get_username_or_email().to_lowercase().cmp(&b.get_username_or_email().to_lowercase()),
+            SortBy::Url => a.get_url().unwrap_or("").to_lowercase().cmp(&b.get_url().unwrap_or("").to_lowercase()),
+            SortBy::Created => Reverse(a.get_created_at()).cmp(&Reverse(b.get_created_at())),
+            SortBy::Updated => Reverse(a.get_updated_at()).cmp(&Reverse(b.get_updated_at())),
+        }
+    }
+}
+
+fn title(account: &Account) -> String {
+    account.get_description().as_deref().unwrap_or(account.get_username_or_email()).to_lowercase()
+}
+
+// The accounts of a database that pass every filter given, from
+// `Database::query`. Repeating `id`, `tag` or `group` allows any of the
+// values; every other filter narrows the results further. Text is matched
+// ignoring case.
+pub struct Query<'a> {
+    database: &'a Database,
+    ids: Vec<String>,
+    tags: Vec<String>,
+    groups: Vec<String>,
+    names: Vec<String>,
+    urls: Vec<String>,
+    searches: Vec<String>,
+    sort: Option<SortBy>,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        Query {
+            database,
+            ids: Vec::new(),
+            tags: Vec::new(),
+            groups: Vec::new(),
+            names: Vec::new(),
+            urls: Vec::new(),
+            searches: Vec::new(),
+            sort: None,
+        }
+    }
+
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.ids.push(id.into());
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    // A group takes in the groups nested under it: "Work" also matches
+    // "Work/Servers".
+    pub fn group(mut self, group: impl Into<String>) -> Self {
+        self.groups.push(group.into());
+        self
+    }
+
+    // Text in the username or description, as `ferropass get` looks
+    // accounts up.
+    pub fn name_contains(mut self, text: impl Into<String>) -> Self {
+        self.names.push(text.into().to_lowercase());
+        self
+    }
+
+    pub fn url_contains(mut self, text: impl Into<String>) -> Self {
+        self.urls.push(text.into().to_lowercase());
+        self
+    }
+
+    // Text in the username, description, URL, group or tags.
+    pub fn search(mut self, text: impl Into<String>) -> Self {
+        self.searches.push(text.into().to_lowercase());
+        self
+    }
+
+    pub fn sorted_by(mut self, sort: SortBy) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    pub fn matches(&self, account: &Account) -> bool {
+        (self.ids.is_empty() || self.ids.iter().any(|id| id == account.get_id()))
+            && (self.tags.is_empty() || self.tags.iter()
+                .any(|tag| account.get_tags().iter().any(|own| own.eq_ignore_ascii_case(tag))))
+            && (self.groups.is_empty() || self.groups.iter()
+                .any(|group| account.get_group().is_some_and(|own| in_group(own, group))))
+            && self.names.iter().all(|text| {
+                account.get_username_or_email().to_lowercase().contains(text)
+                    || account.get_description().as_ref().is_some_and(|description| description.to_lowercase().contains(text))
+            })
+            && self.urls.iter().all(|text| account.get_url().is_some_and(|url| url.to_lowercase().contains(text)))
+            && self.searches.iter().all(|text| matches_search(account, text))
+    }
+
+    pub fn iter(&self) -> vec::IntoIter<&'a Account> {
+        let mut accounts: Vec<&'a Account> = self.database.get_accounts().iter()
+            .filter(|account| self.matches(account))
+            .collect();
+        if let Some(sort) = self.sort {
+            accounts.sort_by(|a, b| sort.compare(a, b));
+        }
+        accounts.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for Query<'a> {
+    type Item = &'a Account;
+    type IntoIter = vec::IntoIter<&'a Account>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+fn in_group(group: &str, wanted: &str) -> bool {
+    let (group, wanted) = (group.to_lowercase(), wanted.trim_end_matches('/').to_lowercase());
+    group == wanted || group.starts_with(&format!("{}/", wanted))
+}
+
+fn matches_search(account: &Account, text: &str) -> bool {
+    let fields = [
+        account.get_username_or_email(),
+        account.get_description().as_deref().unwrap_or(""),
+        account.get_url().unwrap_or(""),
+        account.get_group().unwrap_or(""),
+    ];
+    fields.into_iter()
+        .chain(account.get_tags().iter().map(String::as_str))
+        .any(|field| field.to_lowercase().contains(text))
+}
@@ -0,0 +1,334 @@
+use crate::config;
+use crate::error::FerropassError;
+use crate::paranoid;
+use crate::storage::{FileStorage, VaultLock, VaultStorage};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with("s3://"))
+}
+
+// A bucket on S3 or a compatible service such as MinIO or Backblaze B2, named
+// in the config under "s3_profiles" and used as `s3://<profile>/<key>`:
+// "backup": {"endpoint": "https://s3.us-west-002.backblazeb2.com",
+// "region": "us-west-002", "bucket": "my-vaults", "access_key_id": "...",
+// "secret_access_key_file": "~/.config/ferropass/b2-key"}. Without keys in
+// the profile, AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY are used.
+#[derive(Deserialize)]
+pub struct S3Profile {
+    endpoint: String,
+    #[serde(default)]
+    region: Option<String>,
+    bucket: String,
+    #[serde(default)]
+    access_key_id: Option<String>,
+    #[serde(default)]
+    secret_access_key_file: Option<PathBuf>,
+}
+
+// An object, or one version of it: `s3://<profile>/<key>?versionId=<id>`.
+struct Object {
+    profile: &'static S3Profile,
+    key: String,
+    version: Option<String>,
+}
+
+impl Object {
+    fn parse(path: &Path) -> Result<Self, FerropassError> {
+        let url = path.to_str().ok_or_else(|| FerropassError::Validation(format!("{} is not a valid S3 URL", path.display())))?;
+        let rest = url.strip_prefix("s3://").ok_or_else(|| FerropassError::Validation(format!("{} is not a valid S3 URL", url)))?;
+        let (profile, key) = rest.split_once('/').ok_or_else(|| FerropassError::Validation(format!("{} doesn't name an object, e.g. s3://<profile>/personal.fp", url)))?;
+        let (key, version) = match key.split_once("?versionId=") {
+            Some((key, version)) => (key, Some(version.to_string())),
+            None => (key, None),
+        };
+        if key.is_empty() {
+            return Err(FerropassError::Validation(format!("{} doesn't name an object, e.g. s3://<profile>/personal.fp", url)));
+        }
+
+        Ok(Object { profile: config::current().s3_profile(profile)?, key: key.to_string(), version })
+    }
+}
+
+pub struct S3Storage {
+    agent: ureq::Agent,
+    // What this session last read or wrote, since S3 can't append.
+    contents: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl S3Storage {
+    pub fn new() -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .user_agent(concat!("ferropass/", env!("CARGO_PKG_VERSION")))
+            .build();
+
+        S3Storage { agent, contents: Mutex::new(HashMap::new()) }
+    }
+
+    // Sends a request signed with AWS Signature Version 4, giving back the
+    // response for a success and the status, with the service's error code,
+    // for any other answer. Buckets are addressed by path, which every
+    // S3-compatible service accepts.
+    fn call(
+        &self,
+        method: &str,
+        profile: &S3Profile,
+        key: &str,
+        query: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<Result<ureq::Response, (u16, String)>, FerropassError> {
+        paranoid::ensure_allowed("Network access")?;
+
+        let (access_key_id, secret_access_key) = credentials(profile)?;
+        let endpoint = profile.endpoint.trim_end_matches('/');
+        let host = endpoint.split_once("://").map_or(endpoint, |(_, host)| host);
+        let region = profile.region.as_deref().unwrap_or("us-east-1");
+
+        let path = if key.is_empty() { format!("/{}", profile.bucket) } else { format!("/{}/{}", profile.bucket, key) };
+        let canonical_path = uri_encode(&path, false);
+        let mut query: Vec<(String, String)> = query.iter().map(|(name, value)| (uri_encode(name, true), uri_encode(value, true))).collect();
+        query.sort();
+        let canonical_query = query.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join("&");
+
+        let now = Utc::now();
+        let date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let day = now.format("%Y%m%d").to_string();
+        let payload_hash = sha256_hex(body);
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, canonical_path, canonical_query, host, payload_hash, date, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", day, region);
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", date, scope, sha256_hex(canonical_request.as_bytes()));
+
+        let mut signing_key = format!("AWS4{}", secret_access_key).into_bytes();
+        for part in [day.as_str(), region, "s3", "aws4_request"] {
+            signing_key = hmac_sha256(&signing_key, part.as_bytes());
+        }
+        let signature: String = hmac_sha256(&signing_key, string_to_sign.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect();
+
+        let mut url = format!("{}{}", endpoint, canonical_path);
+        if !canonical_query.is_empty() {
+            let sent: Vec<String> = query.iter().map(|(name, value)| if value.is_empty() { name.clone() } else { format!("{}={}", name, value) }).collect();
+            url = format!("{}?{}", url, sent.join("&"));
+        }
+        let request = self.agent.request(method, &url)
+            .set("x-amz-date", &date)
+            .set("x-amz-content-sha256", &payload_hash)
+            .set("Authorization", &format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                access_key_id, scope, signed_headers, signature
+            ));
+
+        let result = if method == "PUT" { request.send_bytes(body) } else { request.call() };
+        match result {
+            Ok(response) => Ok(Ok(response)),
+            Err(ureq::Error::Status(status, response)) => {
+                let body = response.into_string().unwrap_or_default();
+                Ok(Err((status, xml_values(&body, "Code").into_iter().next().unwrap_or_default())))
+            },
+            Err(ureq::Error::Transport(e)) => Err(FerropassError::Io(format!("can't reach {}: {}", host, e))),
+        }
+    }
+}
+
+impl Default for S3Storage {
+    fn default() -> Self {
+        S3Storage::new()
+    }
+}
+
+impl VaultStorage for S3Storage {
+    fn exists(&self, path: &Path) -> bool {
+        // Anything but "not found", such as refused keys, is for reading the
+        // object to report.
+        Object::parse(path).is_ok_and(|object| {
+            matches!(self.call("HEAD", object.profile, &object.key, &[], b""), Ok(Ok(_)) | Ok(Err((403, _))))
+        })
+    }
+
+    fn read_blob(&self, path: &Path) -> Result<Vec<u8>, FerropassError> {
+        let object = Object::parse(path)?;
+        let query: Vec<(&str, &str)> = object.version.as_deref().map(|version| ("versionId", version)).into_iter().collect();
+
+        let response = match self.call("GET", object.profile, &object.key, &query, b"") {
+            Ok(Ok(response)) => response,
+            Ok(Err((404, _))) => return Err(FerropassError::Io(format!("Error reading file: {} does not exist in the bucket", path.display()))),
+            Ok(Err(refusal)) => return Err(FerropassError::Io(format!("Error reading file: {}", refused(object.profile, refusal)))),
+            Err(e) => return Err(e.context("Error reading file")),
+        };
+
+        let mut contents = Vec::new();
+        response.into_reader().read_to_end(&mut contents)
+            .map_err(|e| FerropassError::Io(format!("Error reading file: {}", e)))?;
+        if object.version.is_none() {
+            self.contents.lock().map_err(|_| FerropassError::Io("S3 storage is unavailable".to_string()))?
+                .insert(path.display().to_string(), contents.clone());
+        }
+        Ok(contents)
+    }
+
+    // With versioning turned on for the bucket, each upload keeps the one it
+    // replaces as an earlier version.
+    fn write_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        let object = Object::parse(path)?;
+        if object.version.is_some() {
+            return Err(FerropassError::Io(format!("Error writing to file: {} is an earlier version, which can't be changed", path.display())));
+        }
+
+        match self.call("PUT", object.profile, &object.key, &[], contents) {
+            Ok(Ok(_)) => {},
+            Ok(Err(refusal)) => return Err(FerropassError::Io(format!("Error writing to file: {}", refused(object.profile, refusal)))),
+            Err(e) => return Err(e.context("Error writing to file")),
+        }
+        self.contents.lock().map_err(|_| FerropassError::Io("S3 storage is unavailable".to_string()))?
+            .insert(path.display().to_string(), contents.to_vec());
+        Ok(())
+    }
+
+    fn append_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        let mut combined = self.contents.lock().map_err(|_| FerropassError::Io("S3 storage is unavailable".to_string()))?
+            .get(&path.display().to_string())
+            .cloned()
+            .ok_or_else(|| FerropassError::Io(format!("Error writing to file: {} hasn't been read in this session", path.display())))?;
+
+        combined.extend_from_slice(contents);
+        self.write_blob(path, &combined)
+    }
+
+    // Every save is already kept as a version, so a snapshot is just the
+    // version there is now.
+    fn snapshot(&self, path: &Path) -> Result<PathBuf, FerropassError> {
+        let object = Object::parse(path)?;
+        let response = match self.call("HEAD", object.profile, &object.key, &[], b"") {
+            Ok(Ok(response)) => response,
+            Ok(Err(refusal)) => return Err(FerropassError::Io(format!("Failed to back up {}: {}", path.display(), refused(object.profile, refusal)))),
+            Err(e) => return Err(e.context(format!("Failed to back up {}", path.display()))),
+        };
+
+        match response.header("x-amz-version-id").filter(|version| *version != "null") {
+            Some(version) => Ok(PathBuf::from(format!("{}?versionId={}", path.display(), version))),
+            None => Err(FerropassError::Io(format!(
+                "Failed to back up {}: versioning isn't turned on for bucket {}",
+                path.display(), object.profile.bucket
+            ))),
+        }
+    }
+
+    // The object's earlier versions, leaving out the current one; deleted
+    // versions have no contents to go back to.
+    fn list_versions(&self, path: &Path) -> Result<Vec<(PathBuf, u64)>, FerropassError> {
+        let object = Object::parse(path)?;
+        let query = [("versions", ""), ("prefix", object.key.as_str())];
+        let response = match self.call("GET", object.profile, "", &query, b"") {
+            Ok(Ok(response)) => response,
+            Ok(Err(refusal)) => return Err(FerropassError::Io(format!("Failed to list versions of {}: {}", path.display(), refused(object.profile, refusal)))),
+            Err(e) => return Err(e.context(format!("Failed to list versions of {}", path.display()))),
+        };
+        let body = response.into_string().map_err(|e| FerropassError::Io(format!("Failed to list versions of {}: {}", path.display(), e)))?;
+
+        let mut versions = Vec::new();
+        for version in xml_values(&body, "Version") {
+            let value = |name: &str| xml_values(&version, name).into_iter().next().unwrap_or_default();
+            if value("Key") != object.key || value("IsLatest") == "true" {
+                continue;
+            }
+
+            let saved_at = DateTime::parse_from_rfc3339(&value("LastModified")).map_or(0, |saved_at| saved_at.timestamp().max(0) as u64);
+            versions.push((PathBuf::from(format!("{}?versionId={}", path.display(), value("VersionId"))), saved_at));
+        }
+        Ok(versions)
+    }
+
+    // S3 has no locks, so this only keeps out other sessions on this machine,
+    // through a lock file in the state directory.
+    fn lock(&self, path: &Path) -> Result<VaultLock, FerropassError> {
+        let dir = config::state_dir().ok_or_else(|| FerropassError::Io("No state directory to keep lock files in".to_string()))?.join("s3");
+        fs::create_dir_all(&dir).map_err(|e| FerropassError::Io(format!("Failed to create {}: {}", dir.display(), e)))?;
+
+        let name: String = path.display().to_string().chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+            .collect();
+        FileStorage.lock(&dir.join(name))
+            .map_err(|_| FerropassError::Io(format!("{} is open in another ferropass process; close it there first", path.display())))
+    }
+}
+
+fn credentials(profile: &S3Profile) -> Result<(String, String), FerropassError> {
+    let access_key_id = profile.access_key_id.clone()
+        .or_else(|| env::var("AWS_ACCESS_KEY_ID").ok())
+        .ok_or_else(|| FerropassError::Validation(format!("No access key for bucket {}; set access_key_id in its profile or AWS_ACCESS_KEY_ID", profile.bucket)))?;
+
+    let secret_access_key = match &profile.secret_access_key_file {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e| FerropassError::Io(format!("Failed to read {}: {}", path.display(), e)))?
+            .trim()
+            .to_string(),
+        None => env::var("AWS_SECRET_ACCESS_KEY")
+            .map_err(|_| FerropassError::Validation(format!("No secret key for bucket {}; set secret_access_key_file in its profile or AWS_SECRET_ACCESS_KEY", profile.bucket)))?,
+    };
+
+    Ok((access_key_id, secret_access_key))
+}
+
+fn refused(profile: &S3Profile, (status, code): (u16, String)) -> String {
+    match code.as_str() {
+        "" => format!("the service answered {}", status),
+        "AccessDenied" | "SignatureDoesNotMatch" | "InvalidAccessKeyId" => {
+            format!("access to bucket {} was refused ({}); check the profile's keys and region", profile.bucket, code)
+        },
+        _ => format!("the service answered {} ({})", status, code),
+    }
+}
+
+// Percent-encodes all but the characters S3 leaves alone; slashes separate
+// the key's parts in a path but are encoded in a query.
+fn uri_encode(text: &str, encode_slash: bool) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            b'/' if !encode_slash => "/".to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+// The text of every <name> element, which is as much XML as S3's answers need.
+fn xml_values(xml: &str, name: &str) -> Vec<String> {
+    let (open, close) = (format!("<{}>", name), format!("</{}>", name));
+    let mut values = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        let Some(end) = after.find(&close) else {
+            break;
+        };
+        values.push(after[..end].replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&amp;", "&"));
+        rest = &after[end + close.len()..];
+    }
+    values
+}
@@ -0,0 +1,291 @@
+use crate::backup;
+use crate::config;
+use crate::error::FerropassError;
+use crate::notice;
+use crate::paranoid;
+use crate::storage::{self, FileStorage, VaultLock, VaultStorage};
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+// Exit statuses of the scripts run on the server. ssh itself exits with 255
+// when it can't connect or log in.
+const MISSING: i32 = 3;
+const CHANGED: i32 = 4;
+const SSH_FAILED: i32 = 255;
+
+pub fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with("sftp://"))
+}
+
+// A file on a server reached over SSH, e.g. "sftp://me@nas.local/srv/vaults/personal.fp",
+// or "sftp://me@nas.local:2222/~/personal.fp" for one under the home directory.
+struct Location {
+    address: String,
+    destination: String,
+    port: Option<String>,
+    file: String,
+}
+
+impl Location {
+    fn parse(path: &Path) -> Result<Self, FerropassError> {
+        let url = path.to_str().ok_or_else(|| FerropassError::Validation(format!("{} is not a valid URL", path.display())))?;
+        let rest = url.strip_prefix("sftp://").ok_or_else(|| FerropassError::Validation(format!("{} is not an sftp:// URL", url)))?;
+        let (authority, file) = rest.split_once('/').unwrap_or((rest, ""));
+        let (login, host) = authority.rsplit_once('@').map_or((None, authority), |(login, host)| (Some(login), host));
+        let (host, port) = match host.rsplit_once(':') {
+            Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => (host, Some(port.to_string())),
+            _ => (host, None),
+        };
+
+        if host.is_empty() || file.is_empty() || file.ends_with('/') {
+            return Err(FerropassError::Validation(format!("{} doesn't name a file on a server", path.display())));
+        }
+        Ok(Location {
+            address: url.to_string(),
+            destination: login.map_or_else(|| host.to_string(), |login| format!("{}@{}", login, host)),
+            port,
+            // Remote commands start in the home directory.
+            file: file.strip_prefix("~/").map_or_else(|| format!("/{}", file), str::to_string),
+        })
+    }
+}
+
+// The `cksum` of a file as this session last read or wrote it. Writes only
+// go through while the server's copy still has it, so a save never replaces
+// changes made elsewhere since the vault was opened.
+struct Known {
+    checksum: Option<String>,
+    contents: Vec<u8>,
+}
+
+pub struct SftpStorage {
+    known: Mutex<HashMap<String, Known>>,
+    offline: Mutex<HashSet<String>>,
+}
+
+impl SftpStorage {
+    pub fn new() -> Self {
+        SftpStorage { known: Mutex::new(HashMap::new()), offline: Mutex::new(HashSet::new()) }
+    }
+
+    // Runs `script` with sh on the server, with `input` on its stdin. ssh
+    // asks for passwords and host key confirmations on the terminal itself;
+    // one connection is shared by all the commands of a session, so that only
+    // happens once.
+    fn run(&self, location: &Location, script: &str, input: &[u8]) -> Result<(i32, Vec<u8>), FerropassError> {
+        paranoid::ensure_allowed("Network access")?;
+
+        let ssh = config::current().ssh_command();
+        let mut words = ssh.split_whitespace();
+        let mut command = Command::new(words.next().unwrap_or("ssh"));
+        command.args(words);
+        if let Some(dir) = config::state_dir().map(|dir| dir.join("ssh")) && fs::create_dir_all(&dir).is_ok() {
+            command.arg("-o").arg("ControlMaster=auto")
+                .arg("-o").arg(format!("ControlPath={}", dir.join("%C").display()))
+                .arg("-o").arg("ControlPersist=60");
+        }
+        if let Some(port) = &location.port {
+            command.arg("-p").arg(port);
+        }
+        command.arg("--").arg(&location.destination).arg(format!("sh -c {}", quote(script)));
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| FerropassError::Io(format!("can't run {}: {}", ssh, e)))?;
+        // A script that refuses the change exits without reading its input,
+        // so a failed write only matters if it claims to have succeeded.
+        let written = child.stdin.take().map_or(Ok(()), |mut stdin| stdin.write_all(input));
+        let output = child.wait_with_output().map_err(|e| FerropassError::Io(format!("the connection to {} failed: {}", location.destination, e)))?;
+
+        let status = output.status.code().unwrap_or(SSH_FAILED);
+        if status == SSH_FAILED {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("ssh failed");
+            return Err(FerropassError::Io(format!("can't reach {}: {}", location.destination, reason.trim())));
+        }
+        if status != 0 && status != MISSING && status != CHANGED {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(FerropassError::Io(format!("{} on {}", stderr.trim(), location.destination)));
+        }
+        if let Err(e) = written && status == 0 {
+            return Err(FerropassError::Io(format!("the connection to {} failed: {}", location.destination, e)));
+        }
+        Ok((status, output.stdout))
+    }
+
+    fn known_checksum(&self, location: &Location) -> Result<Option<Option<String>>, FerropassError> {
+        Ok(self.known.lock().map_err(|_| FerropassError::Io("SFTP storage is unavailable".to_string()))?
+            .get(&location.address)
+            .map(|known| known.checksum.clone()))
+    }
+
+    fn remember(&self, location: &Location, checksum: Option<String>, contents: Vec<u8>) -> Result<(), FerropassError> {
+        if let Ok(path) = cache_path(location) {
+            let _ = FileStorage.write_blob(&path, &contents);
+        }
+
+        self.known.lock().map_err(|_| FerropassError::Io("SFTP storage is unavailable".to_string()))?
+            .insert(location.address.clone(), Known { checksum, contents });
+        self.offline.lock().map_err(|_| FerropassError::Io("SFTP storage is unavailable".to_string()))?
+            .remove(&location.address);
+        Ok(())
+    }
+
+    // Without the server, the copy cached when the file was last opened or
+    // saved is read instead, so the vault can still be looked at.
+    fn read_cached(&self, location: &Location, error: FerropassError) -> Result<Vec<u8>, FerropassError> {
+        let path = cache_path(location)?;
+        let contents = fs::read(&path).map_err(|_| FerropassError::Io(format!("Error reading file: {}", error)))?;
+
+        let first_time = self.offline.lock().map_err(|_| FerropassError::Io("SFTP storage is unavailable".to_string()))?
+            .insert(location.address.clone());
+        if first_time {
+            notice::warn(format!(
+                "{}; using the copy of {} cached when it was last opened. Changes can't be saved until the server is back.",
+                error, location.address
+            ));
+        }
+        Ok(contents)
+    }
+
+    // Checks the file is still as last seen, or absent if it wasn't seen,
+    // then replaces it with `contents` or appends them, and prints its new
+    // checksum. A replacement is written next to the vault and renamed over
+    // it, like a local save.
+    fn change(&self, path: &Path, contents: &[u8], append: bool) -> Result<(), FerropassError> {
+        let location = Location::parse(path)?;
+        let update = if append {
+            "cat >> \"$f\" || exit 1;"
+        } else {
+            "t=\"$f.tmp$$\"; cat > \"$t\" && mv -f -- \"$t\" \"$f\" || { rm -f -- \"$t\"; exit 1; };"
+        };
+        let guard = match self.known_checksum(&location)? {
+            Some(Some(checksum)) => format!("[ \"$(cksum < \"$f\" 2>/dev/null)\" = {} ] || exit {};", quote(&checksum), CHANGED),
+            Some(None) => String::new(),
+            None => format!("[ ! -e \"$f\" ] || exit {};", CHANGED),
+        };
+        let script = format!(
+            "f={}; {} {} cksum < \"$f\"",
+            quote(&location.file), guard, update
+        );
+
+        let (status, output) = self.run(&location, &script, contents).map_err(|e| e.context("Error writing to file"))?;
+        if status == CHANGED {
+            return Err(changed_on_server(path));
+        }
+        let checksum = String::from_utf8_lossy(&output).trim().to_string();
+
+        let mut known_contents = self.known.lock().map_err(|_| FerropassError::Io("SFTP storage is unavailable".to_string()))?
+            .remove(&location.address)
+            .map(|known| known.contents)
+            .unwrap_or_default();
+        if append {
+            known_contents.extend_from_slice(contents);
+        } else {
+            known_contents = contents.to_vec();
+        }
+        self.remember(&location, (!checksum.is_empty()).then_some(checksum), known_contents)
+    }
+}
+
+impl Default for SftpStorage {
+    fn default() -> Self {
+        SftpStorage::new()
+    }
+}
+
+impl VaultStorage for SftpStorage {
+    fn exists(&self, path: &Path) -> bool {
+        let Ok(location) = Location::parse(path) else {
+            return false;
+        };
+        if self.known.lock().is_ok_and(|known| known.contains_key(&location.address)) {
+            return true;
+        }
+
+        match self.run(&location, &format!("[ -e {} ] || exit {}", quote(&location.file), MISSING), &[]) {
+            Ok((status, _)) => status != MISSING,
+            Err(_) => cache_path(&location).is_ok_and(|path| path.exists()),
+        }
+    }
+
+    // The checksum comes first, on a line of its own, then the file.
+    fn read_blob(&self, path: &Path) -> Result<Vec<u8>, FerropassError> {
+        let location = Location::parse(path)?;
+        let script = format!("f={}; [ -e \"$f\" ] || exit {}; cksum < \"$f\" && cat -- \"$f\"", quote(&location.file), MISSING);
+
+        let output = match self.run(&location, &script, &[]) {
+            Ok((MISSING, _)) => return Err(FerropassError::Io(format!("Error reading file: {} does not exist on the server", path.display()))),
+            Ok((_, output)) => output,
+            Err(FerropassError::Io(e)) if e.starts_with("can't reach") => return self.read_cached(&location, FerropassError::Io(e)),
+            Err(e) => return Err(e.context("Error reading file")),
+        };
+        let split = output.iter().position(|byte| *byte == b'\n')
+            .ok_or_else(|| FerropassError::Io(format!("Error reading file: {} gave an unexpected answer", location.destination)))?;
+        let checksum = String::from_utf8_lossy(&output[..split]).trim().to_string();
+        let contents = output[split + 1..].to_vec();
+
+        if self.known_checksum(&location)?.is_some_and(|known| known.as_ref() != Some(&checksum)) {
+            return Err(changed_on_server(path));
+        }
+        self.remember(&location, Some(checksum), contents.clone())?;
+        Ok(contents)
+    }
+
+    fn write_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        self.change(path, contents, false)
+    }
+
+    fn append_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        self.change(path, contents, true)
+    }
+
+    // Backups of a remote vault are kept on this machine, next to its cached
+    // copy, which always matches what was last read from or written to the
+    // server.
+    fn snapshot(&self, path: &Path) -> Result<PathBuf, FerropassError> {
+        backup::snapshot(&cache_path(&Location::parse(path)?)?)
+    }
+
+    fn list_versions(&self, path: &Path) -> Result<Vec<(PathBuf, u64)>, FerropassError> {
+        Ok(backup::list(&cache_path(&Location::parse(path)?)?))
+    }
+
+    // Opening a vault starts from whatever the server has now, so versions
+    // seen earlier, of it and its signature, are forgotten. Other processes
+    // on this machine are kept out through the cached copy's lock file;
+    // elsewhere, checksums catch them.
+    fn lock(&self, path: &Path) -> Result<VaultLock, FerropassError> {
+        let location = Location::parse(path)?;
+        self.known.lock().map_err(|_| FerropassError::Io("SFTP storage is unavailable".to_string()))?
+            .retain(|address, _| address.strip_prefix(&location.address).is_none_or(|rest| !rest.is_empty() && !rest.starts_with('.')));
+
+        let cache = cache_path(&location)?;
+        if let Some(dir) = cache.parent() {
+            fs::create_dir_all(dir).map_err(|e| FerropassError::Io(format!("Failed to create {}: {}", dir.display(), e)))?;
+        }
+        FileStorage.lock(&cache).map_err(|_| FerropassError::Io(format!("{} is open in another ferropass process; close it there first", path.display())))
+    }
+}
+
+fn cache_path(location: &Location) -> Result<PathBuf, FerropassError> {
+    let file_name = location.file.rsplit('/').next().unwrap_or_default();
+    storage::cache_path("sftp", &location.address, file_name)
+}
+
+// Single-quoted for sh, which takes everything inside literally.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn changed_on_server(path: &Path) -> FerropassError {
+    FerropassError::Io(format!("{} was changed on the server since it was opened here; reopen it to see those changes, then make yours again", path.display()))
+}
@@ -0,0 +1,191 @@
+use crate::entropy;
+use crate::error::FerropassError;
+
+use sha2::{Digest, Sha256};
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+const SHARE_PREFIX: &str = "FPS";
+
+// One point on the sharing polynomials: the same x for every byte of the
+// secret, one y per byte.
+pub struct Share {
+    threshold: u8,
+    index: u8,
+    data: Vec<u8>,
+}
+
+impl Share {
+    pub fn get_threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    pub fn get_index(&self) -> u8 {
+        self.index
+    }
+
+    // `FPS-<threshold>-<index>-` followed by the data in groups of four hex
+    // digits and a short checksum group that catches transcription mistakes.
+    pub fn encode(&self) -> String {
+        let mut groups: Vec<String> = self.data.chunks(2).map(hex).collect();
+        groups.push(hex(&self.checksum()));
+
+        format!("{}-{}-{}-{}", SHARE_PREFIX, self.threshold, self.index, groups.join("-"))
+    }
+
+    pub fn parse(text: &str) -> Result<Share, FerropassError> {
+        let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase();
+        let mut parts = compact.split('-');
+
+        if parts.next() != Some(SHARE_PREFIX) {
+            return Err(FerropassError::Validation(format!("A share starts with {}-", SHARE_PREFIX)));
+        }
+
+        // `split` never needs fewer than 2 shares, and a share claiming fewer
+        // would rebuild a secret from itself alone.
+        let threshold = parts.next().and_then(|part| part.parse::<u8>().ok()).filter(|threshold| *threshold >= 2)
+            .ok_or_else(|| FerropassError::Validation("The share's threshold is missing or invalid".to_string()))?;
+        let index = parts.next().and_then(|part| part.parse::<u8>().ok()).filter(|index| *index > 0)
+            .ok_or_else(|| FerropassError::Validation("The share's number is missing or invalid".to_string()))?;
+
+        let digits: String = parts.collect();
+        if digits.len() < 4 || !digits.len().is_multiple_of(2) {
+            return Err(FerropassError::Validation("The share is incomplete".to_string()));
+        }
+        let bytes = unhex(&digits).ok_or_else(|| FerropassError::Validation("The share contains characters other than hex digits".to_string()))?;
+        let (data, checksum) = bytes.split_at(bytes.len() - 2);
+
+        let share = Share { threshold, index, data: data.to_vec() };
+        if share.checksum() != checksum {
+            return Err(FerropassError::Validation("The share's checksum doesn't match; check it for typos".to_string()));
+        }
+
+        Ok(share)
+    }
+
+    fn checksum(&self) -> [u8; 2] {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("ferropass:share:{}:{}:", self.threshold, self.index).as_bytes());
+        hasher.update(&self.data);
+
+        let digest = hasher.finalize();
+        [digest[0], digest[1]]
+    }
+}
+
+// Splits `secret` so that any `threshold` of the `count` shares rebuild it
+// and fewer reveal nothing about it.
+pub fn split(secret: &[u8], threshold: u8, count: u8) -> Result<Vec<Share>, FerropassError> {
+    if threshold < 2 {
+        return Err(FerropassError::Validation("At least 2 shares must be required to rebuild the key".to_string()));
+    }
+    if count < threshold {
+        return Err(FerropassError::Validation(format!("Can't require {} shares when only {} are made", threshold, count)));
+    }
+    entropy::ensure()?;
+
+    let mut shares: Vec<Share> = (1..=count)
+        .map(|index| Share { threshold, index, data: Vec::with_capacity(secret.len()) })
+        .collect();
+
+    let mut coefficients = vec![0u8; threshold as usize];
+    for &byte in secret {
+        coefficients[0] = byte;
+        OsRng.fill_bytes(&mut coefficients[1..]);
+
+        for share in &mut shares {
+            share.data.push(evaluate(&coefficients, share.index));
+        }
+    }
+
+    coefficients.fill(0);
+    Ok(shares)
+}
+
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>, FerropassError> {
+    let first = shares.first().ok_or_else(|| FerropassError::Validation("No shares were given".to_string()))?;
+
+    if shares.len() < first.threshold as usize {
+        return Err(FerropassError::Validation(format!("{} shares are needed, only {} were given", first.threshold, shares.len())));
+    }
+    for (position, share) in shares.iter().enumerate() {
+        if share.threshold != first.threshold || share.data.len() != first.data.len() {
+            return Err(FerropassError::Validation(format!("Share {} is from a different set", share.index)));
+        }
+        if shares[..position].iter().any(|earlier| earlier.index == share.index) {
+            return Err(FerropassError::Validation(format!("Share {} was given more than once", share.index)));
+        }
+    }
+
+    let shares = &shares[..first.threshold as usize];
+    let secret = (0..first.data.len())
+        .map(|byte| {
+            shares.iter().fold(0, |sum, share| {
+                sum ^ mul(share.data[byte], lagrange_at_zero(shares, share.index))
+            })
+        })
+        .collect();
+
+    Ok(secret)
+}
+
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients.iter().rev().fold(0, |result, &coefficient| mul(result, x) ^ coefficient)
+}
+
+fn lagrange_at_zero(shares: &[Share], index: u8) -> u8 {
+    shares.iter()
+        .filter(|other| other.index != index)
+        .fold(1, |product, other| mul(product, mul(other.index, inverse(other.index ^ index))))
+}
+
+// Multiplication in GF(2^8) with the AES polynomial.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+
+    product
+}
+
+// a^254 is the multiplicative inverse of a in GF(2^8).
+fn inverse(a: u8) -> u8 {
+    let mut result = 1;
+    let mut base = a;
+    let mut exponent = 254u8;
+
+    while exponent != 0 {
+        if exponent & 1 != 0 {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+        exponent >>= 1;
+    }
+
+    result
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+fn unhex(digits: &str) -> Option<Vec<u8>> {
+    if !digits.is_ascii() {
+        return None;
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|start| u8::from_str_radix(&digits[start..start + 2], 16).ok())
+        .collect()
+}
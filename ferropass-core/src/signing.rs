@@ -0,0 +1,150 @@
+use crate::config;
+use crate::entropy;
+use crate::error::FerropassError;
+use crate::storage::VaultStorage;
+
+use base64::{Engine as _, engine::general_purpose};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// A detached signature kept next to the vault as `<file>.sig`. It covers
+// the whole file, journal included, and is renewed after every save.
+#[derive(Serialize, Deserialize)]
+struct SignatureFile {
+    key: String,
+    signature: String,
+}
+
+pub fn signature_path(filepath: &Path) -> PathBuf {
+    let file_name = filepath.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    filepath.with_file_name(format!("{}.sig", file_name))
+}
+
+pub fn key_id(key: &VerifyingKey) -> String {
+    key.as_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// The start of a key ID, for messages.
+pub fn short_id(public_key: &str) -> String {
+    public_key.chars().take(16).collect::<String>().to_uppercase()
+}
+
+// Checks the file's contents against its signature and the public key named
+// in its header.
+pub fn verify(storage: &dyn VaultStorage, filepath: &Path, public_key: &str, contents: &[u8]) -> Result<(), FerropassError> {
+    let mismatch = || FerropassError::Crypto(format!(
+        "{}'s signature doesn't match its contents: it was changed outside ferropass, replaced, or a save was interrupted. \
+         Check it with `ferropass verify`, then run `ferropass sign` on it to sign it again",
+        filepath.display()
+    ));
+
+    let key_bytes: [u8; 32] = unhex(public_key)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| FerropassError::Format("Header names an invalid signing key".to_string()))?;
+    let verifying_key = VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|_| FerropassError::Format("Header names an invalid signing key".to_string()))?;
+
+    let sig_path = signature_path(filepath);
+    if !storage.exists(&sig_path) {
+        return Err(FerropassError::Crypto(format!("{} is signed, but its signature file {} is missing", filepath.display(), sig_path.display())));
+    }
+    let signature_file: SignatureFile = serde_json::from_slice(&storage.read_blob(&sig_path)?)
+        .map_err(|e| FerropassError::Format(format!("Error parsing {}: {}", sig_path.display(), e)))?;
+
+    if signature_file.key != public_key {
+        return Err(mismatch());
+    }
+    let signature = general_purpose::STANDARD.decode(&signature_file.signature)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .ok_or_else(|| FerropassError::Format(format!("{} doesn't hold a valid signature", sig_path.display())))?;
+
+    verifying_key.verify(&message(contents), &signature).map_err(|_| mismatch())
+}
+
+pub fn sign(storage: &dyn VaultStorage, filepath: &Path, signing_key: &SigningKey) -> Result<(), FerropassError> {
+    let contents = storage.read_blob(filepath)?;
+    let signature_file = SignatureFile {
+        key: key_id(&signing_key.verifying_key()),
+        signature: general_purpose::STANDARD.encode(signing_key.sign(&message(&contents)).to_bytes()),
+    };
+
+    let mut json = serde_json::to_string(&signature_file)
+        .map_err(|e| FerropassError::Format(format!("Error serializing signature: {}", e)))?;
+    json.push('\n');
+
+    storage.write_blob(&signature_path(filepath), json.as_bytes())
+}
+
+pub fn path() -> Result<PathBuf, FerropassError> {
+    config::current().signing_key_file()
+        .ok_or_else(|| FerropassError::Validation("No location for the signing key; set signing_key_file in the config or HOME".to_string()))
+}
+
+// Loads the local key a vault is signed with, refusing a different one.
+pub fn load(public_key: &str) -> Result<SigningKey, FerropassError> {
+    let path = path()?;
+    let signing_key = read(&path).map_err(|e| e.context(format!(
+        "This vault is signed with key {}, which must be at {} to save it", short_id(public_key), path.display()
+    )))?;
+
+    if key_id(&signing_key.verifying_key()) != public_key {
+        return Err(FerropassError::Crypto(format!(
+            "{} holds signing key {}, but this vault is signed with key {}",
+            path.display(), short_id(&key_id(&signing_key.verifying_key())), short_id(public_key)
+        )));
+    }
+
+    Ok(signing_key)
+}
+
+// Loads the machine's signing key, creating it first if there isn't one yet.
+// Also returns where it is kept and whether it was just created.
+pub fn load_or_create() -> Result<(SigningKey, PathBuf, bool), FerropassError> {
+    let path = path()?;
+    if path.exists() {
+        let signing_key = read(&path).map_err(|e| e.context(path.display()))?;
+        return Ok((signing_key, path, false));
+    }
+
+    entropy::ensure()?;
+    let mut seed = [0u8; 32];
+    OsRng.fill_bytes(&mut seed);
+    let signing_key = SigningKey::from_bytes(&seed);
+    seed.fill(0);
+
+    config::write_private_file(&path, format!("{}\n", general_purpose::STANDARD.encode(signing_key.to_bytes())))?;
+
+    Ok((signing_key, path, true))
+}
+
+fn read(path: &Path) -> Result<SigningKey, FerropassError> {
+    let content = fs::read_to_string(path).map_err(|e| FerropassError::Io(e.to_string()))?;
+    let seed: [u8; 32] = general_purpose::STANDARD.decode(content.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| FerropassError::Format("the file is not a ferropass signing key".to_string()))?;
+
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+fn message(contents: &[u8]) -> Vec<u8> {
+    let mut message = b"ferropass:signature:".to_vec();
+    message.extend_from_slice(contents);
+    message
+}
+
+fn unhex(digits: &str) -> Option<Vec<u8>> {
+    if !digits.is_ascii() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|start| u8::from_str_radix(&digits[start..start + 2], 16).ok())
+        .collect()
+}
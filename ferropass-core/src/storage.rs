@@ -0,0 +1,402 @@
+#[cfg(feature = "fs")]
+use crate::backup;
+#[cfg(feature = "remote")]
+use crate::config;
+use crate::error::FerropassError;
+#[cfg(feature = "fs")]
+use crate::git;
+use crate::models::{Change, Database};
+#[cfg(feature = "fs")]
+use crate::notice;
+#[cfg(feature = "remote")]
+use crate::s3::{self, S3Storage};
+#[cfg(feature = "remote")]
+use crate::sftp::{self, SftpStorage};
+#[cfg(feature = "remote")]
+use crate::webdav::{self, WebDavStorage};
+
+use chrono::Local;
+#[cfg(feature = "remote")]
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::fs::{self, OpenOptions, TryLockError};
+#[cfg(feature = "fs")]
+use std::io::{self, Read, Write};
+#[cfg(all(feature = "fs", unix))]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// What a save wrote, for storage that keeps a record of saves, such as git
+// commits. It counts entries without naming them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Save {
+    // The whole database, holding this many entries.
+    Full { entries: usize },
+    // Changes appended to the journal.
+    Changes { added: usize, changed: usize, removed: usize, settings: bool },
+    // How the vault is unlocked changed; the entries didn't.
+    Rekeyed,
+}
+
+impl Save {
+    // `changes`, appended to a vault that held `previous`.
+    pub fn of_changes(previous: &Database, changes: &[Change]) -> Self {
+        let (mut added, mut changed, mut removed, mut settings) = (0, 0, 0, false);
+        for change in changes {
+            match change {
+                Change::Upsert(account) if previous.get_account_by_id(account.get_id()).is_some() => changed += 1,
+                Change::Upsert(_) => added += 1,
+                Change::Remove(_) => removed += 1,
+                Change::Settings(_) => settings = true,
+                Change::Logged(_) => {},
+            }
+        }
+        Save::Changes { added, changed, removed, settings }
+    }
+}
+
+// Where vault files live. Blobs are addressed by path; versions are earlier
+// copies kept by `snapshot`, listed with the time they were taken (seconds
+// since the Unix epoch).
+pub trait VaultStorage: Send + Sync {
+    fn exists(&self, path: &Path) -> bool;
+
+    fn read_blob(&self, path: &Path) -> Result<Vec<u8>, FerropassError>;
+
+    // Replaces the blob as a whole; readers see either the old or the new one.
+    fn write_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError>;
+
+    fn append_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError>;
+
+    fn snapshot(&self, path: &Path) -> Result<PathBuf, FerropassError>;
+
+    fn list_versions(&self, path: &Path) -> Result<Vec<(PathBuf, u64)>, FerropassError>;
+
+    // Keeps other ferropass processes from writing the vault until the
+    // returned lock is dropped.
+    fn lock(&self, path: &Path) -> Result<VaultLock, FerropassError>;
+
+    fn read_text(&self, path: &Path) -> Result<String, FerropassError> {
+        String::from_utf8(self.read_blob(path)?)
+            .map_err(|_| FerropassError::Io("Error reading file: contents are not valid UTF-8".to_string()))
+    }
+
+    // False for storage that is gone once the process exits.
+    fn is_persistent(&self) -> bool {
+        true
+    }
+
+    // Called after each save, once the vault is signed.
+    fn saved(&self, _path: &Path, _save: Save) {}
+
+    fn latest_version(&self, path: &Path) -> Option<(PathBuf, u64)> {
+        self.list_versions(path).ok()?
+            .into_iter()
+            .max_by_key(|(_, saved_at)| *saved_at)
+    }
+}
+
+// Held for as long as a vault is open for writing.
+pub struct VaultLock {
+    _file: Option<File>,
+}
+
+#[cfg(feature = "fs")]
+pub struct FileStorage;
+
+#[cfg(feature = "fs")]
+impl VaultStorage for FileStorage {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn read_blob(&self, path: &Path) -> Result<Vec<u8>, FerropassError> {
+        fs::read(path).map_err(|e| FerropassError::Io(format!("Error reading file: {}", e)))
+    }
+
+    fn write_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        write_atomically(path, contents).map_err(|e| FerropassError::Io(format!("Error writing to file: {}", e)))
+    }
+
+    fn append_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        append_durably(path, contents).map_err(|e| FerropassError::Io(format!("Error writing to file: {}", e)))
+    }
+
+    fn snapshot(&self, path: &Path) -> Result<PathBuf, FerropassError> {
+        backup::snapshot(path)
+    }
+
+    fn list_versions(&self, path: &Path) -> Result<Vec<(PathBuf, u64)>, FerropassError> {
+        Ok(backup::list(path))
+    }
+
+    // Commits the vault when it is in a git work tree, if autocommit is on.
+    fn saved(&self, path: &Path, save: Save) {
+        git::commit_saved(path, save);
+    }
+
+    // The lock is taken on `<file>.lock` next to the vault rather than on the
+    // vault itself, since saves replace the vault file by renaming over it.
+    // The lock file is left in place; it holds the PID of the last holder.
+    fn lock(&self, path: &Path) -> Result<VaultLock, FerropassError> {
+        let file_name = path.file_name()
+            .ok_or_else(|| FerropassError::Validation("Database path has no file name".to_string()))?
+            .to_string_lossy();
+        let lock_path = path.with_file_name(format!("{}.lock", file_name));
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+            .map_err(|e| FerropassError::Io(format!("Failed to open lock file {}: {}", lock_path.display(), e)))?;
+
+        match file.try_lock() {
+            Ok(()) => {
+                let _ = file.set_len(0);
+                let _ = write!(file, "{}", std::process::id());
+                Ok(VaultLock { _file: Some(file) })
+            },
+            Err(TryLockError::WouldBlock) => {
+                let mut holder = String::new();
+                let _ = file.read_to_string(&mut holder);
+                let holder = match holder.trim() {
+                    "" => String::new(),
+                    pid => format!(" (PID {})", pid),
+                };
+                Err(FerropassError::Io(format!("{} is open in another ferropass process{}; close it there first", path.display(), holder)))
+            },
+            Err(TryLockError::Error(e)) => {
+                notice::warn(format!("{} can't be locked ({}); make sure no other ferropass process writes to it", path.display(), e));
+                Ok(VaultLock { _file: None })
+            }
+        }
+    }
+}
+
+// What the interactive session opens vaults through: http(s) URLs are files
+// on a WebDAV server, s3:// URLs objects in a bucket, anything else a path on
+// this machine.
+#[cfg(feature = "remote")]
+pub struct RoutedStorage {
+    webdav: WebDavStorage,
+    s3: S3Storage,
+    sftp: SftpStorage,
+}
+
+#[cfg(feature = "remote")]
+impl RoutedStorage {
+    pub fn new() -> Self {
+        RoutedStorage { webdav: WebDavStorage::new(), s3: S3Storage::new(), sftp: SftpStorage::new() }
+    }
+
+    // See `WebDavStorage::with_password_prompt`.
+    pub fn with_password_prompt(mut self, prompt: impl Fn(&str, &str) -> Result<String, FerropassError> + Send + Sync + 'static) -> Self {
+        self.webdav = self.webdav.with_password_prompt(prompt);
+        self
+    }
+
+    fn route(&self, path: &Path) -> &dyn VaultStorage {
+        if webdav::is_url(path) {
+            &self.webdav
+        } else if s3::is_url(path) {
+            &self.s3
+        } else if sftp::is_url(path) {
+            &self.sftp
+        } else {
+            &FileStorage
+        }
+    }
+}
+
+#[cfg(feature = "remote")]
+impl Default for RoutedStorage {
+    fn default() -> Self {
+        RoutedStorage::new()
+    }
+}
+
+#[cfg(feature = "remote")]
+impl VaultStorage for RoutedStorage {
+    fn exists(&self, path: &Path) -> bool {
+        self.route(path).exists(path)
+    }
+
+    fn read_blob(&self, path: &Path) -> Result<Vec<u8>, FerropassError> {
+        self.route(path).read_blob(path)
+    }
+
+    fn write_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        self.route(path).write_blob(path, contents)
+    }
+
+    fn append_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        self.route(path).append_blob(path, contents)
+    }
+
+    fn snapshot(&self, path: &Path) -> Result<PathBuf, FerropassError> {
+        self.route(path).snapshot(path)
+    }
+
+    fn list_versions(&self, path: &Path) -> Result<Vec<(PathBuf, u64)>, FerropassError> {
+        self.route(path).list_versions(path)
+    }
+
+    fn lock(&self, path: &Path) -> Result<VaultLock, FerropassError> {
+        self.route(path).lock(path)
+    }
+
+    fn saved(&self, path: &Path, save: Save) {
+        self.route(path).saved(path, save)
+    }
+}
+
+// Where the last copy of a remote vault is kept, under `kind` in the state
+// directory, named after the file and a hash of its address.
+#[cfg(feature = "remote")]
+pub fn cache_path(kind: &str, address: &str, file_name: &str) -> Result<PathBuf, FerropassError> {
+    let dir = config::state_dir().ok_or_else(|| FerropassError::Io("No state directory to cache remote vaults in".to_string()))?;
+
+    let digest = format!("{:x}", Sha256::digest(address.as_bytes()));
+
+    Ok(dir.join(kind).join(format!("{}-{}", &digest[..16], file_name)))
+}
+
+#[cfg(feature = "fs")]
+fn append_durably(filepath: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().append(true).open(filepath)?;
+    file.write_all(contents)?;
+    file.sync_data()
+}
+
+// Writes to a temporary file in the same directory and renames it over the
+// target, so a crash mid-write leaves either the old or the new vault intact.
+#[cfg(feature = "fs")]
+fn write_atomically(filepath: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = match filepath.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = filepath.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "database path has no file name"))?;
+    let temp_path = dir.join(format!(".{}.tmp{}", file_name.to_string_lossy(), std::process::id()));
+
+    // Whatever is at the temporary path is left over from a failed save, or
+    // planted there; it is never written through.
+    match fs::remove_file(&temp_path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {},
+    }
+    write_and_rename(&temp_path, filepath, contents)?;
+
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+fn write_and_rename(temp_path: &Path, filepath: &Path, contents: &[u8]) -> io::Result<()> {
+    // Fails rather than opening a file someone else created at the same path
+    // meanwhile.
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(temp_path)?;
+
+    // A new vault is only readable by its owner; an existing one keeps its
+    // permissions.
+    let result = fs::metadata(filepath).map_or(Ok(()), |metadata| file.set_permissions(metadata.permissions()))
+        .and_then(|()| file.write_all(contents))
+        .and_then(|()| file.sync_all());
+    drop(file);
+
+    let result = result.and_then(|()| fs::rename(temp_path, filepath));
+    if result.is_err() {
+        let _ = fs::remove_file(temp_path);
+    }
+    result
+}
+
+// Keeps every blob in memory and forgets it when dropped, for `--ephemeral`
+// sessions and for exercising code without touching the disk.
+#[derive(Default)]
+pub struct MemoryStorage {
+    blobs: Mutex<HashMap<PathBuf, Vec<u8>>>,
+    // Snapshots are stored as blobs of their own, like backup files on disk;
+    // this records which ones belong to which vault.
+    versions: Mutex<HashMap<PathBuf, Vec<(PathBuf, u64)>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage::default()
+    }
+}
+
+impl VaultStorage for MemoryStorage {
+    fn is_persistent(&self) -> bool {
+        false
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.blobs.lock().is_ok_and(|blobs| blobs.contains_key(path))
+    }
+
+    fn read_blob(&self, path: &Path) -> Result<Vec<u8>, FerropassError> {
+        let blobs = self.blobs.lock().map_err(|_| FerropassError::Io("In-memory storage is unavailable".to_string()))?;
+
+        blobs.get(path)
+            .cloned()
+            .ok_or_else(|| FerropassError::Io(format!("Error reading file: {} does not exist in this session", path.display())))
+    }
+
+    fn write_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        let mut blobs = self.blobs.lock().map_err(|_| FerropassError::Io("In-memory storage is unavailable".to_string()))?;
+        blobs.insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+
+    fn append_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        let mut blobs = self.blobs.lock().map_err(|_| FerropassError::Io("In-memory storage is unavailable".to_string()))?;
+
+        let blob = blobs.get_mut(path)
+            .ok_or_else(|| FerropassError::Io(format!("Error writing to file: {} does not exist in this session", path.display())))?;
+        blob.extend_from_slice(contents);
+        Ok(())
+    }
+
+    fn snapshot(&self, path: &Path) -> Result<PathBuf, FerropassError> {
+        let contents = self.read_blob(path)?;
+        let mut versions = self.versions.lock().map_err(|_| FerropassError::Io("In-memory storage is unavailable".to_string()))?;
+        let versions = versions.entry(path.to_path_buf()).or_default();
+
+        let now = Local::now();
+        let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let name = path.with_file_name(format!(
+            "{}.{}-{}.bak", file_name, now.format("%Y%m%d-%H%M%S"), versions.len() + 1
+        ));
+
+        self.write_blob(&name, &contents)?;
+        versions.push((name.clone(), now.timestamp().max(0) as u64));
+        Ok(name)
+    }
+
+    // Only this process can see the blobs, so there is nobody to lock out.
+    fn lock(&self, _path: &Path) -> Result<VaultLock, FerropassError> {
+        Ok(VaultLock { _file: None })
+    }
+
+    fn list_versions(&self, path: &Path) -> Result<Vec<(PathBuf, u64)>, FerropassError> {
+        let versions = self.versions.lock().map_err(|_| FerropassError::Io("In-memory storage is unavailable".to_string()))?;
+
+        Ok(versions.get(path)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
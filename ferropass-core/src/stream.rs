@@ -0,0 +1,161 @@
+use crate::algorithms::AeadCipher;
+use crate::error::FerropassError;
+use crate::memlock::SecretBuffer;
+
+use std::io::{self, Read, Write};
+
+// Plaintext is encrypted in chunks of this size, each with its own tag, so
+// saving or opening a vault never holds all of its plaintext at once.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+// Chunk `index` is encrypted under the base nonce with the index XORed into
+// its last eight bytes, and its associated data names the index and whether
+// it is the last chunk, so chunks can't be reordered, and the data can't be
+// cut short at a chunk boundary. Every chunk but the last holds exactly
+// `chunk_size` bytes of plaintext; the last holds fewer, possibly none.
+pub fn chunk_nonce(nonce: &[u8], index: u64) -> Vec<u8> {
+    let mut chunk_nonce = nonce.to_vec();
+    let start = chunk_nonce.len().saturating_sub(8);
+
+    for (byte, counter) in chunk_nonce[start..].iter_mut().zip(index.to_be_bytes()) {
+        *byte ^= counter;
+    }
+
+    chunk_nonce
+}
+
+fn chunk_associated_data(aad: &[u8], index: u64, last: bool) -> Vec<u8> {
+    let mut chunk_aad = aad.to_vec();
+    chunk_aad.extend_from_slice(format!(":chunk:{}{}", index, if last { ":last" } else { "" }).as_bytes());
+    chunk_aad
+}
+
+// Encrypts what is written to it chunk by chunk into `output`. `finish` must
+// be called to write the last chunk.
+pub struct ChunkWriter<'a, W: Write> {
+    cipher: &'a dyn AeadCipher,
+    key: &'a [u8; 32],
+    nonce: &'a [u8],
+    aad: &'a [u8],
+    chunk_size: usize,
+    buffer: SecretBuffer,
+    filled: usize,
+    index: u64,
+    output: W,
+}
+
+impl<'a, W: Write> ChunkWriter<'a, W> {
+    pub fn new(cipher: &'a dyn AeadCipher, key: &'a [u8; 32], nonce: &'a [u8], aad: &'a [u8], chunk_size: usize, output: W) -> Self {
+        ChunkWriter { cipher, key, nonce, aad, chunk_size, buffer: SecretBuffer::new(chunk_size), filled: 0, index: 0, output }
+    }
+
+    // Encrypts the first `len` bytes of the buffer as the next chunk.
+    fn write_chunk(&mut self, len: usize, last: bool) -> io::Result<()> {
+        let ciphertext = self.cipher.encrypt(
+            self.key,
+            &chunk_nonce(self.nonce, self.index),
+            &self.buffer[..len],
+            &chunk_associated_data(self.aad, self.index, last)
+        ).map_err(io::Error::other)?;
+
+        self.index += 1;
+        self.filled = 0;
+        self.output.write_all(&ciphertext)
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.filled == self.chunk_size {
+            self.write_chunk(self.chunk_size, false)?;
+            self.write_chunk(0, true)?;
+        } else {
+            self.write_chunk(self.filled, true)?;
+        }
+
+        Ok(self.output)
+    }
+}
+
+impl<W: Write> Write for ChunkWriter<'_, W> {
+    // A full chunk is only written once more data follows it, since the last
+    // chunk must be shorter than the others.
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let mut rest = data;
+
+        while !rest.is_empty() {
+            if self.filled == self.chunk_size {
+                self.write_chunk(self.chunk_size, false)?;
+            }
+
+            let len = rest.len().min(self.chunk_size - self.filled);
+            self.buffer[self.filled..self.filled + len].copy_from_slice(&rest[..len]);
+            self.filled += len;
+            rest = &rest[len..];
+        }
+
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Decrypts and authenticates chunks from `input` as they are read.
+pub struct ChunkReader<'a, R: Read> {
+    cipher: &'a dyn AeadCipher,
+    key: &'a [u8; 32],
+    nonce: &'a [u8],
+    aad: &'a [u8],
+    chunk_size: usize,
+    input: R,
+    plaintext: SecretBuffer,
+    position: usize,
+    index: u64,
+    done: bool,
+}
+
+impl<'a, R: Read> ChunkReader<'a, R> {
+    pub fn new(cipher: &'a dyn AeadCipher, key: &'a [u8; 32], nonce: &'a [u8], aad: &'a [u8], chunk_size: usize, input: R) -> Self {
+        ChunkReader { cipher, key, nonce, aad, chunk_size, input, plaintext: SecretBuffer::new(0), position: 0, index: 0, done: false }
+    }
+
+    fn read_chunk(&mut self) -> io::Result<()> {
+        let full_len = self.chunk_size + self.cipher.tag_len();
+        let mut ciphertext = Vec::with_capacity(full_len);
+        (&mut self.input).take(full_len as u64).read_to_end(&mut ciphertext)?;
+
+        if ciphertext.len() < self.cipher.tag_len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, FerropassError::Format("Encrypted data is cut off".to_string())));
+        }
+
+        let last = ciphertext.len() < full_len;
+        self.plaintext = self.cipher.decrypt(
+            self.key,
+            &chunk_nonce(self.nonce, self.index),
+            &ciphertext,
+            &chunk_associated_data(self.aad, self.index, last)
+        ).map(SecretBuffer::take)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, FerropassError::InvalidPasskey))?;
+
+        self.position = 0;
+        self.index += 1;
+        self.done = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ChunkReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.read_chunk()?;
+        }
+
+        let len = buf.len().min(self.plaintext.len() - self.position);
+        buf[..len].copy_from_slice(&self.plaintext[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
@@ -0,0 +1,92 @@
+use crate::config;
+use crate::error::FerropassError;
+use crate::models::{current_timestamp, format_timestamp};
+use crate::notice;
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+// Wrong passkeys allowed in a row before attempts start being delayed.
+const FREE_ATTEMPTS: u32 = 3;
+const MAX_DELAY_SECS: u64 = 300;
+const STATE_FILE: &str = "unlock-failures.json";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+struct Failures {
+    count: u32,
+    last: u64,
+}
+
+impl Failures {
+    // Doubles with each failure past the free ones, up to five minutes.
+    fn delay(&self) -> u64 {
+        match self.count.checked_sub(FREE_ATTEMPTS) {
+            Some(extra) => 2u64.saturating_pow(extra + 1).min(MAX_DELAY_SECS),
+            None => 0,
+        }
+    }
+}
+
+// Runs one passkey attempt on a vault, first waiting out the delay earned
+// by earlier wrong passkeys. Consecutive failures are counted per file in
+// the local state directory and cleared by the next success. This only slows
+// down guessing through ferropass itself; it is no defence against a copy of
+// the file. Attempts are refused while the count can't be read or a wrong
+// passkey can't be counted, so damaging the state doesn't lift the delay.
+pub fn guard<T>(filepath: &Path, attempt: impl FnOnce() -> Result<T, FerropassError>) -> Result<T, FerropassError> {
+    wait(filepath)?;
+    let result = attempt();
+    record(filepath, &result)?;
+    result
+}
+
+pub fn wait(filepath: &Path) -> Result<(), FerropassError> {
+    let state: HashMap<String, Failures> = config::load_state(STATE_FILE)?;
+    let Some(failures) = state.get(&config::vault_id(filepath)).copied() else {
+        return Ok(());
+    };
+
+    let remaining = (failures.last + failures.delay()).saturating_sub(current_timestamp());
+    if remaining > 0 {
+        notice::info(format!(
+            "{} wrong passkey(s) in a row for {}, waiting {} second(s) before trying again...",
+            failures.count, filepath.display(), remaining
+        ));
+        thread::sleep(Duration::from_secs(remaining));
+    }
+    Ok(())
+}
+
+// Only a wrong passkey counts as a failure; other errors leave the count as
+// it is. Failing to clear the count after the right passkey is only warned
+// about.
+pub fn record<T>(filepath: &Path, result: &Result<T, FerropassError>) -> Result<(), FerropassError> {
+    let vault = config::vault_id(filepath);
+    let updated = config::update_state(STATE_FILE, |state: &mut HashMap<String, Failures>| match result {
+        Ok(_) => state.remove(&vault),
+        Err(FerropassError::InvalidPasskey) => {
+            let failures = state.entry(vault).or_insert(Failures { count: 0, last: 0 });
+            failures.count += 1;
+            failures.last = current_timestamp();
+            Some(*failures)
+        },
+        Err(_) => None,
+    });
+
+    match (result, updated) {
+        (Ok(_), Err(e)) => notice::warn(e.to_string()),
+        (Err(_), Err(e)) => return Err(e),
+        (Ok(_), Ok(Some(failures))) => notice::warn(format!(
+            "{} wrong passkey(s) were tried on {} since it was last opened, the last at {}",
+            failures.count, filepath.display(), format_timestamp(failures.last)
+        )),
+        (Err(_), Ok(Some(failures))) if failures.delay() > 0 => {
+            notice::info(format!("The next attempt on {} waits {} second(s).", filepath.display(), failures.delay()));
+        },
+        _ => {},
+    }
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use crate::error::FerropassError;
+use crate::memlock::{self, SecretBuffer};
+
+use hmac::digest::core_api::BlockSizeUser;
+use hmac::digest::Digest;
+use hmac::{Mac, SimpleHmac};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+#[derive(Clone, Copy)]
+enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+// A time-based one-time password generator (RFC 6238), as set up by a site's
+// QR code. Defaults are those of nearly every site: SHA-1, 6 digits, a new
+// code every 30 seconds. The secret is kept in locked memory and wiped when
+// the generator is dropped.
+pub struct Totp {
+    secret: SecretBuffer,
+    algorithm: Algorithm,
+    digits: u32,
+    period: u64,
+}
+
+impl Totp {
+    // Takes the base32 secret a site shows next to its QR code, or the
+    // otpauth:// URI the QR code holds.
+    pub fn parse(text: &str) -> Result<Totp, FerropassError> {
+        let text = text.trim();
+        let Some(uri) = text.strip_prefix("otpauth://") else {
+            return Ok(Totp { secret: decode_base32(text)?, algorithm: Algorithm::Sha1, digits: 6, period: 30 });
+        };
+
+        let Some(rest) = uri.strip_prefix("totp/") else {
+            return Err(FerropassError::Format("Only time-based (otpauth://totp/) one-time passwords are supported".to_string()));
+        };
+        let query = rest.split_once('?').map_or("", |(_, query)| query);
+
+        let mut secret = None;
+        let (mut algorithm, mut digits, mut period) = (Algorithm::Sha1, 6, 30);
+        for (name, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            match name {
+                "secret" => secret = Some(decode_base32(value)?),
+                "algorithm" => algorithm = match value.to_uppercase().as_str() {
+                    "SHA1" => Algorithm::Sha1,
+                    "SHA256" => Algorithm::Sha256,
+                    "SHA512" => Algorithm::Sha512,
+                    _ => return Err(FerropassError::Format(format!("Unsupported TOTP algorithm '{}'", value))),
+                },
+                "digits" => digits = value.parse().ok()
+                    .filter(|digits| (6..=10).contains(digits))
+                    .ok_or_else(|| FerropassError::Format(format!("Invalid number of TOTP digits '{}'", value)))?,
+                "period" => period = value.parse().ok()
+                    .filter(|period| *period > 0)
+                    .ok_or_else(|| FerropassError::Format(format!("Invalid TOTP period '{}'", value)))?,
+                _ => {},
+            }
+        }
+
+        let Some(secret) = secret else {
+            return Err(FerropassError::Format("The otpauth:// URI has no secret".to_string()));
+        };
+        Ok(Totp { secret, algorithm, digits, period })
+    }
+
+    pub fn code_at(&self, timestamp: u64) -> String {
+        let counter = (timestamp / self.period).to_be_bytes();
+        let hash = match self.algorithm {
+            Algorithm::Sha1 => hmac::<Sha1>(&self.secret, &counter),
+            Algorithm::Sha256 => hmac::<Sha256>(&self.secret, &counter),
+            Algorithm::Sha512 => hmac::<Sha512>(&self.secret, &counter),
+        };
+
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let truncated = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+
+        format!("{:0width$}", u64::from(truncated) % 10u64.pow(self.digits), width = self.digits as usize)
+    }
+
+    // Seconds until the code shown at `timestamp` is replaced.
+    pub fn remaining(&self, timestamp: u64) -> u64 {
+        self.period - timestamp % self.period
+    }
+}
+
+fn hmac<D: Digest + BlockSizeUser>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = SimpleHmac::<D>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+// RFC 4648 base32, ignoring case, spaces, dashes and padding, since sites
+// show secrets in groups like "JBSW Y3DP EHPK 3PXP". The bytes are sized up
+// front so they're never reallocated and left behind unwiped.
+fn decode_base32(text: &str) -> Result<SecretBuffer, FerropassError> {
+    let mut bytes = Vec::with_capacity(text.len() * 5 / 8 + 1);
+    let mut buffer = 0u64;
+    let mut bits = 0;
+
+    for c in text.chars().filter(|c| !matches!(c, ' ' | '-' | '=')) {
+        let value = match c.to_ascii_uppercase() {
+            letter @ 'A'..='Z' => letter as u64 - 'A' as u64,
+            digit @ '2'..='7' => digit as u64 - '2' as u64 + 26,
+            _ => {
+                memlock::wipe(&mut bytes);
+                return Err(FerropassError::Format(format!("'{}' can't appear in a base32 TOTP secret", c)));
+            },
+        };
+
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    if bytes.is_empty() {
+        return Err(FerropassError::Format("The TOTP secret is empty".to_string()));
+    }
+    Ok(SecretBuffer::take(bytes))
+}
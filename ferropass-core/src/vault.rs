@@ -0,0 +1,198 @@
+use crate::encryption::{encrypt_and_save_database, rewrap_and_save, save_changes, unlock_database, EntryKey, SessionKey};
+use crate::error::FerropassError;
+use crate::events::{Event, Hooks};
+use crate::models::{Account, Database};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::nonblocking::{self, Task};
+use crate::storage::{MemoryStorage, VaultLock, VaultStorage};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Where a vault kept only in memory is in its storage.
+const IN_MEMORY_PATH: &str = "memory.fp";
+
+// A vault opened for reading and writing: where it is kept, its database and
+// the key it is saved under, held locked until it is dropped. Its hooks hear
+// of it being unlocked and saved, and of each entry a save adds, edits or
+// deletes.
+pub struct Vault {
+    storage: Arc<dyn VaultStorage>,
+    path: PathBuf,
+    database: Database,
+    key: SessionKey,
+    // The database as it was last read or written, so a save only appends
+    // what changed since.
+    saved: Database,
+    hooks: Hooks,
+    _lock: VaultLock,
+}
+
+impl Vault {
+    // Makes a new, empty vault at `path`, refusing to replace one already
+    // there.
+    pub fn create(storage: Arc<dyn VaultStorage>, path: impl AsRef<Path>, passkey: &str) -> Result<Self, FerropassError> {
+        Vault::create_with_hooks(storage, path, passkey, Hooks::new())
+    }
+
+    pub fn create_with_hooks(storage: Arc<dyn VaultStorage>, path: impl AsRef<Path>, passkey: &str, hooks: Hooks) -> Result<Self, FerropassError> {
+        let path = path.as_ref().to_path_buf();
+        // Checked under the lock, so two creating the same vault can't both
+        // find it missing.
+        let lock = storage.lock(&path)?;
+        if storage.exists(&path) {
+            return Err(FerropassError::Validation(format!("There is already a vault at {}", path.display())));
+        }
+        let key = SessionKey::generate(passkey)?;
+        let database = Database::new();
+        encrypt_and_save_database(storage.as_ref(), &database, &path, &key)?;
+        hooks.emit(&Event::Saved { path: path.clone() });
+
+        Ok(Vault { storage, path, saved: database.clone(), database, key, hooks, _lock: lock })
+    }
+
+    pub fn open(storage: Arc<dyn VaultStorage>, path: impl AsRef<Path>, passkey: &str) -> Result<Self, FerropassError> {
+        Vault::open_with_hooks(storage, path, passkey, Hooks::new())
+    }
+
+    pub fn open_with_hooks(storage: Arc<dyn VaultStorage>, path: impl AsRef<Path>, passkey: &str, hooks: Hooks) -> Result<Self, FerropassError> {
+        let path = path.as_ref().to_path_buf();
+        let lock = storage.lock(&path)?;
+        let (database, key) = unlock_database(storage.as_ref(), &path, passkey)?;
+        hooks.emit(&Event::Unlocked { path: path.clone() });
+
+        Ok(Vault { storage, path, saved: database.clone(), database, key, hooks, _lock: lock })
+    }
+
+    // A new vault kept only in memory and gone once it is dropped, for tests
+    // and for programs that keep the file somewhere of their own, which
+    // `to_bytes` hands them.
+    pub fn in_memory(passkey: &str) -> Result<Self, FerropassError> {
+        Vault::create(Arc::new(MemoryStorage::new()), IN_MEMORY_PATH, passkey)
+    }
+
+    // Opens a vault file read into memory, kept there like `in_memory`'s.
+    // Signed vaults can't be opened this way, since their signature is a
+    // file of its own.
+    pub fn from_bytes(contents: &[u8], passkey: &str) -> Result<Self, FerropassError> {
+        let storage = MemoryStorage::new();
+        storage.write_blob(Path::new(IN_MEMORY_PATH), contents)?;
+        Vault::open(Arc::new(storage), IN_MEMORY_PATH, passkey)
+    }
+
+    // The vault file as last saved.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, FerropassError> {
+        self.storage.read_blob(&self.path)
+    }
+
+    // Writes what changed since the vault was opened or last saved.
+    pub fn save(&mut self) -> Result<(), FerropassError> {
+        save_changes(self.storage.as_ref(), &self.saved, &self.database, &self.path, &self.key)?;
+        self.hooks.emit_saved(&self.path, &self.saved, &self.database);
+        self.saved = self.database.clone();
+        Ok(())
+    }
+
+    // `create`, `open` and `save` on a thread of their own, for async
+    // callers.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn create_async(storage: Arc<dyn VaultStorage>, path: PathBuf, passkey: String) -> Task<Self> {
+        Task::spawn(move || nonblocking::with_passkey(passkey, |passkey| Vault::create(storage, path, passkey)))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_async(storage: Arc<dyn VaultStorage>, path: PathBuf, passkey: String) -> Task<Self> {
+        Task::spawn(move || nonblocking::with_passkey(passkey, |passkey| Vault::open(storage, path, passkey)))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn save_async(&mut self) -> Result<(), FerropassError> {
+        let database = self.database.clone();
+        nonblocking::save_changes(Arc::clone(&self.storage), self.saved.clone(), database.clone(), self.path.clone(), self.key.clone()).await?;
+        self.hooks.emit_saved(&self.path, &self.saved, &database);
+        self.saved = database;
+        Ok(())
+    }
+
+    // Wraps the data key under `passkey` and saves the vault with it; the
+    // entries themselves aren't re-encrypted.
+    pub fn change_passkey(&mut self, passkey: &str) -> Result<(), FerropassError> {
+        let key = self.key.rekey(passkey)?;
+        rewrap_and_save(self.storage.as_ref(), &self.database, &self.path, &key)?;
+        self.hooks.emit_saved(&self.path, &self.saved, &self.database);
+        self.key = key;
+        self.saved = self.database.clone();
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[Account] {
+        self.database.get_accounts()
+    }
+
+    pub fn find(&self, term: &str) -> Result<&Account, FerropassError> {
+        find(&self.database, term, &self.path)
+    }
+
+    // Adds an entry with `password` sealed under the vault's entry key. It
+    // is written on the next save.
+    pub fn add_entry(&mut self, username_or_email: &str, description: Option<&str>, password: &str) -> Result<&Account, FerropassError> {
+        let password = self.entry_key().seal(password)?;
+        self.database.add_account(Account::new(username_or_email.to_string(), description.map(str::to_string), password));
+        Ok(self.database.get_accounts().last().expect("the entry was just added"))
+    }
+
+    // The password of `account`, opened with the vault's entry key.
+    pub fn password(&self, account: &Account) -> Result<String, FerropassError> {
+        self.entry_key().open(account.get_password())
+    }
+
+    pub fn entry_key(&self) -> EntryKey {
+        self.key.entry_key()
+    }
+
+    pub fn fingerprint(&self) -> String {
+        self.key.fingerprint()
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_database(&self) -> &Database {
+        &self.database
+    }
+
+    pub fn get_database_mut(&mut self) -> &mut Database {
+        &mut self.database
+    }
+
+    pub fn get_key(&self) -> &SessionKey {
+        &self.key
+    }
+
+    pub fn get_hooks_mut(&mut self) -> &mut Hooks {
+        &mut self.hooks
+    }
+}
+
+// Takes an account ID, or a piece of a username or description that only one
+// account has.
+pub fn find<'a>(database: &'a Database, term: &str, filepath: &Path) -> Result<&'a Account, FerropassError> {
+    if let Some(account) = database.get_account_by_id(term) {
+        return Ok(account);
+    }
+
+    let matches: Vec<&Account> = database.query().name_contains(term).into_iter().collect();
+
+    match matches.as_slice() {
+        [account] => Ok(account),
+        [] => Err(FerropassError::Validation(format!("No account in {} has that ID, or matches it by username or description", filepath.display()))),
+        _ => Err(FerropassError::Validation(format!(
+            "{} accounts match, use an ID: {}",
+            matches.len(),
+            matches.iter()
+                .map(|account| format!("{} ({})", account.get_id(), account.get_username_or_email()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
@@ -0,0 +1,353 @@
+use crate::backup;
+use crate::error::FerropassError;
+use crate::notice;
+use crate::paranoid;
+use crate::storage::{self, FileStorage, VaultLock, VaultStorage};
+
+use base64::{Engine as _, engine::general_purpose};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with("https://") || path.starts_with("http://"))
+}
+
+// A file on a WebDAV server, such as Nextcloud or ownCloud, addressed by its
+// URL with any login taken out of it.
+struct Location {
+    address: String,
+    host: String,
+    user: Option<String>,
+    password: Option<String>,
+}
+
+impl Location {
+    // The login can be given in the URL, e.g.
+    // "https://me@cloud.example.com/remote.php/dav/files/me/personal.fp".
+    fn parse(path: &Path) -> Result<Self, FerropassError> {
+        let url = path.to_str().ok_or_else(|| FerropassError::Validation(format!("{} is not a valid URL", path.display())))?;
+        let (scheme, rest) = url.split_once("://").ok_or_else(|| FerropassError::Validation(format!("{} is not a valid URL", url)))?;
+        let (authority, file) = rest.split_once('/').unwrap_or((rest, ""));
+        let (userinfo, host) = authority.rsplit_once('@').map_or((None, authority), |(userinfo, host)| (Some(userinfo), host));
+        let (user, password) = match userinfo.map(|userinfo| userinfo.split_once(':').unwrap_or((userinfo, ""))) {
+            Some((user, password)) => (Some(percent_decode(user)), (!password.is_empty()).then(|| percent_decode(password))),
+            None => (None, None),
+        };
+
+        if host.is_empty() || file.is_empty() || file.ends_with('/') {
+            return Err(FerropassError::Validation(format!("{} doesn't name a file on a server", path.display())));
+        }
+        Ok(Location { address: format!("{}://{}/{}", scheme, host, file), host: host.to_string(), user, password })
+    }
+}
+
+// The version of a file this session last read or wrote. Writes only go
+// through if the server still has that version, by its ETag, so a save never
+// replaces changes made elsewhere since the vault was opened.
+struct Known {
+    etag: Option<String>,
+    contents: Vec<u8>,
+}
+
+pub struct WebDavStorage {
+    agent: ureq::Agent,
+    known: Mutex<HashMap<String, Known>>,
+    // Passwords typed this session, by user and host.
+    passwords: Mutex<HashMap<String, String>>,
+    prompt: Option<PasswordPrompt>,
+    offline: Mutex<HashSet<String>>,
+}
+
+type PasswordPrompt = Box<dyn Fn(&str, &str) -> Result<String, FerropassError> + Send + Sync>;
+
+impl WebDavStorage {
+    pub fn new() -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .user_agent(concat!("ferropass/", env!("CARGO_PKG_VERSION")))
+            .build();
+
+        WebDavStorage {
+            agent,
+            known: Mutex::new(HashMap::new()),
+            passwords: Mutex::new(HashMap::new()),
+            prompt: None,
+            offline: Mutex::new(HashSet::new()),
+        }
+    }
+
+    // `prompt` is asked for the password of a user on a host, given in that
+    // order, the first time the server needs it. Without one, a URL with a
+    // username must also carry the password.
+    pub fn with_password_prompt(mut self, prompt: impl Fn(&str, &str) -> Result<String, FerropassError> + Send + Sync + 'static) -> Self {
+        self.prompt = Some(Box::new(prompt));
+        self
+    }
+
+    // Sends a request, giving back the response for a success and the status
+    // for any other answer; failing to reach the server at all is the error.
+    fn call(&self, method: &str, location: &Location, headers: &[(&str, &str)], body: Option<&[u8]>) -> Result<Result<ureq::Response, u16>, FerropassError> {
+        paranoid::ensure_allowed("Network access")?;
+
+        let mut request = self.agent.request(method, &location.address);
+        if let Some(authorization) = self.authorization(location)? {
+            request = request.set("Authorization", &authorization);
+        }
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+
+        let result = match body {
+            Some(body) => request.send_bytes(body),
+            None => request.call(),
+        };
+        match result {
+            Ok(response) => Ok(Ok(response)),
+            Err(ureq::Error::Status(401, _)) => {
+                // A mistyped password is forgotten, so the next attempt asks again.
+                if let Some(user) = &location.user {
+                    self.passwords.lock().map_err(|_| FerropassError::Io("WebDAV storage is unavailable".to_string()))?
+                        .remove(&format!("{}@{}", user, location.host));
+                }
+                Ok(Err(401))
+            },
+            Err(ureq::Error::Status(status, _)) => Ok(Err(status)),
+            Err(ureq::Error::Transport(e)) => Err(FerropassError::Io(format!("can't reach {}: {}", location.host, e))),
+        }
+    }
+
+    // Basic authentication, asking for the password the first time a server
+    // is used in a session. Nextcloud and ownCloud accept app passwords here.
+    fn authorization(&self, location: &Location) -> Result<Option<String>, FerropassError> {
+        let Some(user) = &location.user else {
+            return Ok(None);
+        };
+
+        let password = match &location.password {
+            Some(password) => password.clone(),
+            None => {
+                let mut passwords = self.passwords.lock().map_err(|_| FerropassError::Io("WebDAV storage is unavailable".to_string()))?;
+                let key = format!("{}@{}", user, location.host);
+                match passwords.get(&key) {
+                    Some(password) => password.clone(),
+                    None => {
+                        let prompt = self.prompt.as_ref().ok_or_else(|| {
+                            FerropassError::Validation(format!("{} needs a password for {}; put it in the URL", location.host, user))
+                        })?;
+                        let password = prompt(user, &location.host)?;
+                        passwords.insert(key, password.clone());
+                        password
+                    },
+                }
+            },
+        };
+
+        Ok(Some(format!("Basic {}", general_purpose::STANDARD.encode(format!("{}:{}", user, password)))))
+    }
+
+    fn etag_of(&self, location: &Location) -> Option<String> {
+        match self.call("HEAD", location, &[], None) {
+            Ok(Ok(response)) => response.header("ETag").map(str::to_string),
+            _ => None,
+        }
+    }
+
+    fn remember(&self, location: &Location, etag: Option<String>, contents: &[u8]) -> Result<(), FerropassError> {
+        self.known.lock().map_err(|_| FerropassError::Io("WebDAV storage is unavailable".to_string()))?
+            .insert(location.address.clone(), Known { etag, contents: contents.to_vec() });
+        self.offline.lock().map_err(|_| FerropassError::Io("WebDAV storage is unavailable".to_string()))?
+            .remove(&location.address);
+
+        // The cache is only a convenience, so failing to update it is not an
+        // error; the server's copy is what counts.
+        if let Ok(path) = cache_path(location) {
+            let _ = FileStorage.write_blob(&path, contents);
+        }
+        Ok(())
+    }
+
+    // Without the server, the copy cached when the file was last opened or
+    // saved is read instead, so the vault can still be looked at.
+    fn read_cached(&self, location: &Location, error: FerropassError) -> Result<Vec<u8>, FerropassError> {
+        let path = cache_path(location)?;
+        let contents = fs::read(&path).map_err(|_| FerropassError::Io(format!("Error reading file: {}", error)))?;
+
+        let first_time = self.offline.lock().map_err(|_| FerropassError::Io("WebDAV storage is unavailable".to_string()))?
+            .insert(location.address.clone());
+        if first_time {
+            notice::warn(format!(
+                "{}; using the copy of {} cached when it was last opened. Changes can't be saved until the server is back.",
+                error, location.address
+            ));
+        }
+        Ok(contents)
+    }
+}
+
+impl Default for WebDavStorage {
+    fn default() -> Self {
+        WebDavStorage::new()
+    }
+}
+
+impl VaultStorage for WebDavStorage {
+    fn exists(&self, path: &Path) -> bool {
+        let Ok(location) = Location::parse(path) else {
+            return false;
+        };
+        if self.known.lock().is_ok_and(|known| known.contains_key(&location.address)) {
+            return true;
+        }
+
+        match self.call("HEAD", &location, &[], None) {
+            Ok(Ok(_)) => true,
+            // Anything else, such as a login being needed, is for reading
+            // the file to report.
+            Ok(Err(status)) => status != 404,
+            Err(_) => cache_path(&location).is_ok_and(|path| path.exists()),
+        }
+    }
+
+    fn read_blob(&self, path: &Path) -> Result<Vec<u8>, FerropassError> {
+        let location = Location::parse(path)?;
+        let known_etag = self.known.lock().map_err(|_| FerropassError::Io("WebDAV storage is unavailable".to_string()))?
+            .get(&location.address)
+            .map(|known| known.etag.clone());
+
+        let headers: Vec<(&str, &str)> = match &known_etag {
+            Some(Some(etag)) => vec![("If-None-Match", etag.as_str())],
+            _ => Vec::new(),
+        };
+        let response = match self.call("GET", &location, &headers, None) {
+            // Not an error to ureq, but there is no body: the copy already
+            // held is still the server's.
+            Ok(Ok(response)) if response.status() == 304 => {
+                let known = self.known.lock().map_err(|_| FerropassError::Io("WebDAV storage is unavailable".to_string()))?;
+                return Ok(known.get(&location.address).map(|known| known.contents.clone()).unwrap_or_default());
+            },
+            Ok(Ok(response)) => response,
+            Ok(Err(404)) => return Err(FerropassError::Io(format!("Error reading file: {} does not exist on the server", path.display()))),
+            Ok(Err(status)) => return Err(FerropassError::Io(format!("Error reading file: {}", refused(&location, status)))),
+            Err(e) => return self.read_cached(&location, e),
+        };
+
+        let etag = response.header("ETag").map(str::to_string);
+        let mut contents = Vec::new();
+        response.into_reader().read_to_end(&mut contents)
+            .map_err(|e| FerropassError::Io(format!("Error reading file: {}", e)))?;
+
+        if known_etag.is_some_and(|known_etag| known_etag != etag) {
+            return Err(changed_on_server(path));
+        }
+        self.remember(&location, etag, &contents)?;
+        Ok(contents)
+    }
+
+    // A new file is only created if nobody else created it first, and an
+    // existing one only replaced if it is still the version last seen.
+    fn write_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        let location = Location::parse(path)?;
+        let known_etag = self.known.lock().map_err(|_| FerropassError::Io("WebDAV storage is unavailable".to_string()))?
+            .get(&location.address)
+            .map(|known| known.etag.clone());
+
+        let precondition = match &known_etag {
+            Some(Some(etag)) => Some(("If-Match", etag.as_str())),
+            // The server doesn't give ETags, so changes can't be detected.
+            Some(None) => None,
+            None => Some(("If-None-Match", "*")),
+        };
+        let response = match self.call("PUT", &location, precondition.as_slice(), Some(contents)) {
+            Ok(Ok(response)) => response,
+            Ok(Err(412)) => return Err(changed_on_server(path)),
+            Ok(Err(status)) => return Err(FerropassError::Io(format!("Error writing to file: {}", refused(&location, status)))),
+            Err(e) => return Err(e.context("Error writing to file")),
+        };
+
+        let etag = response.header("ETag").map(str::to_string).or_else(|| self.etag_of(&location));
+        self.remember(&location, etag, contents)
+    }
+
+    // WebDAV can't append, so the whole file is sent again.
+    fn append_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        let location = Location::parse(path)?;
+        let mut combined = self.known.lock().map_err(|_| FerropassError::Io("WebDAV storage is unavailable".to_string()))?
+            .get(&location.address)
+            .map(|known| known.contents.clone())
+            .ok_or_else(|| FerropassError::Io(format!("Error writing to file: {} hasn't been read in this session", path.display())))?;
+
+        combined.extend_from_slice(contents);
+        self.write_blob(path, &combined)
+    }
+
+    // Backups of a remote vault are kept on this machine, next to its cached
+    // copy, which always matches what was last read from or written to the
+    // server.
+    fn snapshot(&self, path: &Path) -> Result<PathBuf, FerropassError> {
+        backup::snapshot(&cache_path(&Location::parse(path)?)?)
+    }
+
+    fn list_versions(&self, path: &Path) -> Result<Vec<(PathBuf, u64)>, FerropassError> {
+        Ok(backup::list(&cache_path(&Location::parse(path)?)?))
+    }
+
+    // Opening a vault starts from whatever the server has now, so versions
+    // seen earlier, of it and its signature, are forgotten. Other processes on this machine are kept
+    // out through the cached copy's lock file; elsewhere, ETags catch them.
+    fn lock(&self, path: &Path) -> Result<VaultLock, FerropassError> {
+        let location = Location::parse(path)?;
+        self.known.lock().map_err(|_| FerropassError::Io("WebDAV storage is unavailable".to_string()))?
+            .retain(|address, _| address.strip_prefix(&location.address).is_none_or(|rest| !rest.is_empty() && !rest.starts_with('.')));
+
+        let cache = cache_path(&location)?;
+        if let Some(dir) = cache.parent() {
+            fs::create_dir_all(dir).map_err(|e| FerropassError::Io(format!("Failed to create {}: {}", dir.display(), e)))?;
+        }
+        FileStorage.lock(&cache).map_err(|_| FerropassError::Io(format!("{} is open in another ferropass process; close it there first", path.display())))
+    }
+}
+
+fn cache_path(location: &Location) -> Result<PathBuf, FerropassError> {
+    let file_name = location.address.rsplit('/').next().unwrap_or_default();
+    storage::cache_path("webdav", &location.address, &percent_decode(file_name))
+}
+
+fn refused(location: &Location, status: u16) -> String {
+    match (status, &location.user) {
+        (401, Some(user)) => format!("{} didn't accept the password for {}", location.host, user),
+        (401, None) => format!("{} needs a login; put your username in the URL, e.g. https://me@{}/...", location.host, location.host),
+        _ => format!("{} answered {}", location.host, status),
+    }
+}
+
+fn changed_on_server(path: &Path) -> FerropassError {
+    FerropassError::Io(format!("{} was changed on the server since it was opened here; reopen it to see those changes, then make yours again", path.display()))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            },
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
@@ -0,0 +1,114 @@
+use ferropass_core::encryption::{encrypt_and_save_database, load_and_decrypt_database, rewrap_and_save, save_changes, unlock_database, SessionKey};
+use ferropass_core::events::{self, Event, Hooks};
+use ferropass_core::models::Database;
+use ferropass_core::storage::MemoryStorage;
+use ferropass_core::vault::Vault;
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Once};
+
+const PASSKEY: &str = "correct horse battery staple";
+
+static HEARD: Mutex<Vec<Event>> = Mutex::new(Vec::new());
+
+// What the process-wide listeners heard about `path`. Tests run side by side,
+// so each keeps to a path of its own.
+fn heard(path: &Path) -> Vec<Event> {
+    static LISTEN: Once = Once::new();
+    LISTEN.call_once(|| events::listen(|event| HEARD.lock().unwrap().push(event.clone())));
+
+    let mut heard = HEARD.lock().unwrap();
+    let (mine, others) = heard.drain(..).partition(|event| event.get_path() == path);
+    *heard = others;
+    mine
+}
+
+fn path(name: &str) -> PathBuf {
+    let path = PathBuf::from(format!("{}.fp", name));
+    heard(&path);
+    path
+}
+
+fn id_of(vault: &Vault, username: &str) -> String {
+    vault.find(username).unwrap().get_id().to_string()
+}
+
+#[test]
+fn shared_unlock_functions_are_heard() {
+    let storage = MemoryStorage::new();
+    let path = path("unlock");
+    encrypt_and_save_database(&storage, &Database::new(), &path, &SessionKey::generate(PASSKEY).unwrap()).unwrap();
+    assert_eq!(heard(&path), [Event::Saved { path: path.clone() }]);
+
+    unlock_database(&storage, &path, PASSKEY).unwrap();
+    load_and_decrypt_database(&storage, &path, PASSKEY).unwrap();
+    assert_eq!(heard(&path), [Event::Unlocked { path: path.clone() }, Event::Unlocked { path: path.clone() }]);
+
+    assert!(unlock_database(&storage, &path, "wrong horse battery staple").is_err());
+    assert!(heard(&path).is_empty());
+}
+
+#[test]
+fn shared_saves_report_the_entries_they_change() {
+    let storage = Arc::new(MemoryStorage::new());
+    let path = path("saves");
+
+    let mut vault = Vault::create(storage.clone(), &path, PASSKEY).unwrap();
+    vault.add_entry("alice", None, "s3cret-Passw0rd").unwrap();
+    vault.add_entry("bob", None, "hunter2hunter2").unwrap();
+    vault.save().unwrap();
+    let (alice, bob) = (id_of(&vault, "alice"), id_of(&vault, "bob"));
+    drop(vault);
+    heard(&path);
+
+    // Saved the way the interactive session does, without a `Vault`.
+    let (previous, key) = unlock_database(storage.as_ref(), &path, PASSKEY).unwrap();
+    let mut database = previous.clone();
+    database.get_account_by_id_mut(&alice).unwrap().set_description(Some("Mail".to_string()));
+    database.remove_account(&bob);
+    save_changes(storage.as_ref(), &previous, &database, &path, &key).unwrap();
+    assert_eq!(heard(&path), [
+        Event::Unlocked { path: path.clone() },
+        Event::EntryEdited { path: path.clone(), id: alice.clone() },
+        Event::EntryDeleted { path: path.clone(), id: bob.clone() },
+        Event::Saved { path: path.clone() },
+    ]);
+
+    // A full save works out what changed from the file it replaces.
+    let mut compacted = database.clone();
+    compacted.get_account_by_id_mut(&alice).unwrap().set_description(None);
+    encrypt_and_save_database(storage.as_ref(), &compacted, &path, &key).unwrap();
+    assert_eq!(heard(&path), [Event::EntryEdited { path: path.clone(), id: alice }, Event::Saved { path: path.clone() }]);
+
+    rewrap_and_save(storage.as_ref(), &compacted, &path, &key.rekey("another horse battery staple").unwrap()).unwrap();
+    assert_eq!(heard(&path), [Event::Saved { path: path.clone() }]);
+}
+
+#[test]
+fn vault_hooks_and_process_listeners_each_hear_once() {
+    let path = path("vault");
+    let own = Arc::new(Mutex::new(Vec::new()));
+    let mut hooks = Hooks::new();
+    let sink = own.clone();
+    hooks.add(move |event| sink.lock().unwrap().push(event.clone()));
+
+    let mut vault = Vault::create_with_hooks(Arc::new(MemoryStorage::new()), &path, PASSKEY, hooks).unwrap();
+    vault.add_entry("carol", None, "another-password").unwrap();
+    vault.save().unwrap();
+
+    let expected = [
+        Event::Saved { path: path.clone() },
+        Event::EntryAdded { path: path.clone(), id: id_of(&vault, "carol") },
+        Event::Saved { path: path.clone() },
+    ];
+    assert_eq!(*own.lock().unwrap(), expected);
+    assert_eq!(heard(&path), expected);
+}
+
+#[test]
+fn events_describe_themselves() {
+    let path = PathBuf::from("/vaults/personal.fp");
+    assert_eq!(Event::Unlocked { path: path.clone() }.to_string(), "Unlocked /vaults/personal.fp");
+    assert_eq!(Event::EntryAdded { path: path.clone(), id: "a1b2".to_string() }.to_string(), "Added entry a1b2 to /vaults/personal.fp");
+    assert_eq!(Event::EntryDeleted { path, id: "a1b2".to_string() }.to_string(), "Deleted entry a1b2 from /vaults/personal.fp");
+}
@@ -0,0 +1,128 @@
+// Journal lengths are only kept for vaults in local files.
+#![cfg(feature = "fs")]
+
+use ferropass_core::encryption;
+use ferropass_core::storage::FileStorage;
+use ferropass_core::vault::Vault;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Once, PoisonError, RwLock};
+
+const PASSKEY: &str = "correct horse battery staple";
+
+// Held for writing by the test that damages the state file, so that no other
+// test reads it meanwhile.
+static STATE: RwLock<()> = RwLock::new(());
+
+// Each test gets a directory of its own; the config and state directories
+// are shared by the whole test binary and set before any test reads them.
+fn dir(name: &str) -> PathBuf {
+    static SETUP: Once = Once::new();
+    let root = std::env::temp_dir().join(format!("ferropass-journal-{}", std::process::id()));
+    SETUP.call_once(|| {
+        let _ = fs::remove_dir_all(&root);
+        unsafe {
+            std::env::set_var("XDG_CONFIG_HOME", root.join("config"));
+            std::env::set_var("XDG_STATE_HOME", root.join("state"));
+        }
+    });
+
+    let dir = root.join(name);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn state_file() -> PathBuf {
+    PathBuf::from(std::env::var_os("XDG_STATE_HOME").unwrap()).join("ferropass").join("journals.json")
+}
+
+fn open(path: &Path) -> Result<Vault, ferropass_core::error::FerropassError> {
+    Vault::open(Arc::new(FileStorage), path, PASSKEY)
+}
+
+// A vault whose journal holds a record per entry, closed again.
+fn vault_with_journal(path: &Path, names: &[&str]) {
+    let mut vault = Vault::create(Arc::new(FileStorage), path, PASSKEY).unwrap();
+    vault.get_database_mut().get_settings_mut().set_keep_history(true);
+    vault.save().unwrap();
+    for name in names {
+        vault.add_entry(name, None, "journal-password").unwrap();
+        vault.save().unwrap();
+    }
+}
+
+fn drop_last_record(path: &Path) {
+    let text = fs::read_to_string(path).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    let kept: String = lines[..lines.len() - 1].iter().map(|line| format!("{}\n", line)).collect();
+    fs::write(path, kept).unwrap();
+}
+
+#[test]
+fn journal_cut_short_after_it_was_seen_is_refused() {
+    let _state = STATE.read().unwrap_or_else(PoisonError::into_inner);
+    let path = dir("cut").join("vault.fp");
+    vault_with_journal(&path, &["alice", "bob"]);
+
+    drop_last_record(&path);
+    let error = open(&path).err().expect("a shortened journal opened");
+    assert!(error.to_string().contains("saves may have been removed"), "{}", error);
+}
+
+#[test]
+fn forgotten_journal_opens_shorter() {
+    let _state = STATE.read().unwrap_or_else(PoisonError::into_inner);
+    let path = dir("forget").join("vault.fp");
+    vault_with_journal(&path, &["carol", "dave"]);
+
+    drop_last_record(&path);
+    encryption::forget_journal(&path).unwrap();
+    let vault = open(&path).unwrap();
+    assert_eq!(vault.entries().len(), 1);
+}
+
+#[test]
+fn journal_grown_elsewhere_opens() {
+    let _state = STATE.read().unwrap_or_else(PoisonError::into_inner);
+    let path = dir("grown").join("vault.fp");
+    vault_with_journal(&path, &["erin"]);
+    let copy = dir("grown").join("copy.fp");
+    fs::copy(&path, &copy).unwrap();
+
+    // The vault gains a record this machine only sees once it opens it.
+    let mut elsewhere = open(&copy).unwrap();
+    elsewhere.add_entry("frank", None, "journal-password").unwrap();
+    elsewhere.save().unwrap();
+    drop(elsewhere);
+    fs::rename(&copy, &path).unwrap();
+
+    assert_eq!(open(&path).unwrap().entries().len(), 2);
+}
+
+#[test]
+fn damaged_state_is_refused_rather_than_reset() {
+    let _state = STATE.write().unwrap_or_else(PoisonError::into_inner);
+    let path = dir("damaged").join("vault.fp");
+    vault_with_journal(&path, &["grace"]);
+
+    let state = state_file();
+    let saved = fs::read(&state).unwrap();
+    fs::write(&state, b"{ not json").unwrap();
+    let error = open(&path).err().expect("opened with damaged journal state");
+    fs::write(&state, saved).unwrap();
+    assert!(error.to_string().contains("is damaged"), "{}", error);
+}
+
+#[cfg(unix)]
+#[test]
+fn state_is_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let _state = STATE.read().unwrap_or_else(PoisonError::into_inner);
+    let path = dir("private").join("vault.fp");
+    vault_with_journal(&path, &["heidi"]);
+
+    let mode = fs::metadata(state_file()).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
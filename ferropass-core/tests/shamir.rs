@@ -0,0 +1,64 @@
+use ferropass_core::shamir::{self, Share};
+
+const SECRET: &[u8] = b"a 32-byte recovery key goes here";
+
+#[test]
+fn any_threshold_of_the_shares_rebuild_the_secret() {
+    let shares = shamir::split(SECRET, 3, 5).unwrap();
+    let encoded: Vec<String> = shares.iter().map(Share::encode).collect();
+
+    for picked in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+        let shares: Vec<Share> = picked.iter().map(|&index| Share::parse(&encoded[index]).unwrap()).collect();
+        assert_eq!(shamir::combine(&shares).unwrap(), SECRET);
+    }
+}
+
+#[test]
+fn fewer_than_the_threshold_are_refused() {
+    let shares = shamir::split(SECRET, 3, 5).unwrap();
+
+    assert!(shamir::combine(&shares[..2]).is_err());
+    assert!(shamir::combine(&[]).is_err());
+}
+
+#[test]
+fn shares_parse_with_spaces_and_lowercase() {
+    let share = &shamir::split(SECRET, 2, 3).unwrap()[2];
+    let typed = share.encode().to_lowercase().replace('-', " - ");
+
+    let parsed = Share::parse(&typed).unwrap();
+    assert_eq!((parsed.get_threshold(), parsed.get_index()), (2, 3));
+    assert_eq!(parsed.encode(), share.encode());
+}
+
+#[test]
+fn mistyped_share_is_caught_by_its_checksum() {
+    let encoded = shamir::split(SECRET, 2, 3).unwrap()[0].encode();
+    let position = encoded.len() - 6;
+    let wrong = if &encoded[position..position + 1] == "0" { "1" } else { "0" };
+    let typo = format!("{}{}{}", &encoded[..position], wrong, &encoded[position + 1..]);
+
+    assert!(Share::parse(&typo).is_err());
+}
+
+#[test]
+fn thresholds_below_two_are_refused() {
+    assert!(shamir::split(SECRET, 1, 3).is_err());
+    assert!(shamir::split(SECRET, 4, 3).is_err());
+
+    let encoded = shamir::split(SECRET, 2, 3).unwrap()[0].encode();
+    for threshold in ["0", "1"] {
+        assert!(Share::parse(&encoded.replacen("FPS-2-", &format!("FPS-{}-", threshold), 1)).is_err());
+    }
+}
+
+#[test]
+fn shares_from_different_sets_are_refused() {
+    let first = shamir::split(SECRET, 2, 3).unwrap();
+    let second = shamir::split(SECRET, 3, 3).unwrap();
+    let mixed: Vec<Share> = [&first[0], &second[1], &second[2]].iter()
+        .map(|share| Share::parse(&share.encode()).unwrap())
+        .collect();
+
+    assert!(shamir::combine(&mixed).is_err());
+}
@@ -0,0 +1,48 @@
+// Saves go through a temporary file next to the vault, which only exists for
+// local files.
+#![cfg(all(feature = "fs", unix))]
+
+use ferropass_core::storage::{FileStorage, VaultStorage};
+
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ferropass-storage-{}", std::process::id())).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn mode(path: &Path) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+#[test]
+fn planted_temporary_file_is_not_written_through() {
+    let dir = dir("planted");
+    let path = dir.join("vault.fp");
+    let target = dir.join("elsewhere");
+    fs::write(&target, b"untouched").unwrap();
+    symlink(&target, dir.join(format!(".vault.fp.tmp{}", std::process::id()))).unwrap();
+
+    FileStorage.write_blob(&path, b"saved").unwrap();
+    assert_eq!(fs::read(&target).unwrap(), b"untouched");
+    assert_eq!(fs::read(&path).unwrap(), b"saved");
+    assert!(!fs::symlink_metadata(&path).unwrap().file_type().is_symlink());
+}
+
+#[test]
+fn new_files_are_private_and_existing_ones_keep_their_permissions() {
+    let dir = dir("permissions");
+    let path = dir.join("vault.fp");
+
+    FileStorage.write_blob(&path, b"first").unwrap();
+    assert_eq!(mode(&path), 0o600);
+
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+    FileStorage.write_blob(&path, b"second").unwrap();
+    assert_eq!(mode(&path), 0o640);
+    assert_eq!(fs::read(&path).unwrap(), b"second");
+}
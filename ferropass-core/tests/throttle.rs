@@ -0,0 +1,119 @@
+// Wrong passkeys are only counted for vaults in local files.
+#![cfg(feature = "fs")]
+
+use ferropass_core::error::FerropassError;
+use ferropass_core::storage::FileStorage;
+use ferropass_core::throttle;
+use ferropass_core::vault::Vault;
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Once, PoisonError, RwLock};
+use std::thread;
+
+const PASSKEY: &str = "correct horse battery staple";
+
+// Held for writing by the test that damages the state file, so that no other
+// test reads it meanwhile.
+static STATE: RwLock<()> = RwLock::new(());
+
+// Each test gets a vault of its own; the config and state directories are
+// shared by the whole test binary and set before any test reads them.
+fn vault(name: &str) -> PathBuf {
+    static SETUP: Once = Once::new();
+    let root = std::env::temp_dir().join(format!("ferropass-throttle-{}", std::process::id()));
+    SETUP.call_once(|| {
+        let _ = fs::remove_dir_all(&root);
+        unsafe {
+            std::env::set_var("XDG_CONFIG_HOME", root.join("config"));
+            std::env::set_var("XDG_STATE_HOME", root.join("state"));
+        }
+    });
+
+    let dir = root.join(name);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("vault.fp");
+    Vault::create(Arc::new(FileStorage), &path, PASSKEY).unwrap();
+    path
+}
+
+fn state_file() -> PathBuf {
+    PathBuf::from(std::env::var_os("XDG_STATE_HOME").unwrap()).join("ferropass").join("unlock-failures.json")
+}
+
+// The wrong passkeys counted for `path`, as written to the state file.
+fn failures(path: &Path) -> u64 {
+    let Ok(content) = fs::read_to_string(state_file()) else {
+        return 0;
+    };
+    let state: Value = serde_json::from_str(&content).unwrap();
+    let vault = fs::canonicalize(path).unwrap().display().to_string();
+    state[vault.as_str()]["count"].as_u64().unwrap_or(0)
+}
+
+fn open(path: &Path, passkey: &str) -> Result<Vault, FerropassError> {
+    Vault::open(Arc::new(FileStorage), path, passkey)
+}
+
+#[test]
+fn wrong_passkeys_are_counted_until_the_right_one() {
+    let _state = STATE.read().unwrap_or_else(PoisonError::into_inner);
+    let path = vault("counted");
+
+    for _ in 0..2 {
+        assert_eq!(open(&path, "wrong horse battery staple").err(), Some(FerropassError::InvalidPasskey));
+    }
+    assert_eq!(failures(&path), 2);
+
+    open(&path, PASSKEY).unwrap();
+    assert_eq!(failures(&path), 0);
+}
+
+#[test]
+fn failures_from_many_processes_at_once_all_count() {
+    let _state = STATE.read().unwrap_or_else(PoisonError::into_inner);
+    let path = vault("concurrent");
+
+    let attempts: Vec<_> = (0..8)
+        .map(|_| {
+            let path = path.clone();
+            thread::spawn(move || throttle::record::<()>(&path, &Err(FerropassError::InvalidPasskey)).unwrap())
+        })
+        .collect();
+    for attempt in attempts {
+        attempt.join().unwrap();
+    }
+
+    assert_eq!(failures(&path), 8);
+}
+
+#[test]
+fn damaged_state_refuses_attempts_rather_than_resetting() {
+    let _state = STATE.write().unwrap_or_else(PoisonError::into_inner);
+    let path = vault("damaged");
+    throttle::record::<()>(&path, &Err(FerropassError::InvalidPasskey)).unwrap();
+
+    let state = state_file();
+    let saved = fs::read(&state).unwrap();
+    fs::write(&state, b"{ not json").unwrap();
+    let opened = open(&path, PASSKEY);
+    fs::write(&state, saved).unwrap();
+
+    let error = opened.err().expect("opened with damaged throttle state");
+    assert!(error.to_string().contains("is damaged"), "{}", error);
+    assert_eq!(failures(&path), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn state_is_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let _state = STATE.read().unwrap_or_else(PoisonError::into_inner);
+    let path = vault("private");
+    throttle::record::<()>(&path, &Err(FerropassError::InvalidPasskey)).unwrap();
+
+    let mode = fs::metadata(state_file()).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
@@ -0,0 +1,94 @@
+use ferropass_core::totp::Totp;
+
+const SHA1_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+const SHA256_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+const SHA512_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA";
+
+// RFC 6238, appendix B.
+const TIMES: [u64; 6] = [59, 1111111109, 1111111111, 1234567890, 2000000000, 20000000000];
+const SHA1_CODES: [&str; 6] = ["94287082", "07081804", "14050471", "89005924", "69279037", "65353130"];
+const SHA256_CODES: [&str; 6] = ["46119246", "68084774", "67062674", "91819424", "90698825", "77737706"];
+const SHA512_CODES: [&str; 6] = ["90693936", "25091201", "99943326", "93441116", "38618901", "47863826"];
+
+fn uri(query: &str) -> String {
+    format!("otpauth://totp/Example:alice@example.com?{}&issuer=Example", query)
+}
+
+fn assert_codes(totp: &Totp, codes: [&str; 6]) {
+    for (time, code) in TIMES.iter().zip(codes) {
+        assert_eq!(totp.code_at(*time), code, "at {}", time);
+    }
+}
+
+#[test]
+fn codes_match_the_rfc_test_vectors() {
+    assert_codes(&Totp::parse(&uri(&format!("secret={}&digits=8", SHA1_SECRET))).unwrap(), SHA1_CODES);
+    assert_codes(&Totp::parse(&uri(&format!("secret={}&algorithm=SHA256&digits=8", SHA256_SECRET))).unwrap(), SHA256_CODES);
+    assert_codes(&Totp::parse(&uri(&format!("secret={}&algorithm=sha512&digits=8", SHA512_SECRET))).unwrap(), SHA512_CODES);
+}
+
+#[test]
+fn bare_secret_gives_six_digits_every_thirty_seconds() {
+    let totp = Totp::parse(SHA1_SECRET).unwrap();
+
+    assert_eq!(totp.code_at(59), "287082");
+    assert_eq!(totp.code_at(1111111109), "081804");
+    assert_eq!(totp.remaining(59), 1);
+    assert_eq!(totp.remaining(60), 30);
+}
+
+#[test]
+fn secrets_are_read_as_sites_show_them() {
+    let grouped = "gezd gnbv-gy3t qojq GEZD GNBV GY3T QOJQ====";
+
+    assert_eq!(Totp::parse(grouped).unwrap().code_at(59), "287082");
+    assert_eq!(Totp::parse(&uri(&format!("secret={}&digits=8", grouped.replace(' ', "")))).unwrap().code_at(59), "94287082");
+}
+
+#[test]
+fn invalid_secrets_are_refused() {
+    assert!(Totp::parse("").is_err());
+    assert!(Totp::parse("====").is_err());
+    assert!(Totp::parse("GEZDGNBV1").is_err());
+    assert!(Totp::parse("GEZDGNBV!").is_err());
+}
+
+#[test]
+fn period_changes_when_codes_are_replaced() {
+    let totp = Totp::parse(&uri(&format!("secret={}&digits=8&period=60", SHA1_SECRET))).unwrap();
+
+    assert_eq!(totp.code_at(119), Totp::parse(&uri(&format!("secret={}&digits=8", SHA1_SECRET))).unwrap().code_at(59));
+    assert_eq!(totp.remaining(59), 1);
+    assert_eq!(totp.remaining(60), 60);
+}
+
+#[test]
+fn digits_and_period_out_of_bounds_are_refused() {
+    for query in ["digits=5", "digits=11", "digits=0", "digits=-6", "digits=eight", "period=0", "period=-30", "period=soon"] {
+        assert!(Totp::parse(&uri(&format!("secret={}&{}", SHA1_SECRET, query))).is_err(), "{}", query);
+    }
+
+    for digits in 6..=10 {
+        let code = Totp::parse(&uri(&format!("secret={}&digits={}", SHA1_SECRET, digits))).unwrap().code_at(59);
+        assert_eq!(code.len(), digits);
+    }
+}
+
+#[test]
+fn unknown_algorithm_is_refused() {
+    for algorithm in ["MD5", "SHA384", ""] {
+        assert!(Totp::parse(&uri(&format!("secret={}&algorithm={}", SHA1_SECRET, algorithm))).is_err(), "{}", algorithm);
+    }
+}
+
+#[test]
+fn uri_without_a_secret_is_refused() {
+    assert!(Totp::parse(&uri("digits=8")).is_err());
+    assert!(Totp::parse("otpauth://totp/Example:alice@example.com").is_err());
+    assert!(Totp::parse(&uri("secret=")).is_err());
+}
+
+#[test]
+fn counter_based_passwords_are_refused() {
+    assert!(Totp::parse(&format!("otpauth://hotp/Example?secret={}&counter=0", SHA1_SECRET)).is_err());
+}
@@ -0,0 +1,214 @@
+use ferropass_core::error::FerropassError;
+use ferropass_core::storage::{MemoryStorage, VaultLock, VaultStorage};
+use ferropass_core::vault::Vault;
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const PASSKEY: &str = "correct horse battery staple";
+
+fn vault_with_history() -> Vault {
+    let mut vault = Vault::in_memory(PASSKEY).unwrap();
+    vault.get_database_mut().get_settings_mut().set_keep_history(true);
+    vault.save().unwrap();
+    vault
+}
+
+// Base snapshot first, then one journal record per line.
+fn lines(bytes: &[u8]) -> Vec<String> {
+    String::from_utf8(bytes.to_vec()).unwrap().lines().map(str::to_string).collect()
+}
+
+fn join(lines: &[String]) -> Vec<u8> {
+    lines.iter().map(|line| format!("{}\n", line)).collect::<String>().into_bytes()
+}
+
+#[test]
+fn saved_entries_reopen_from_bytes() {
+    let mut vault = Vault::in_memory(PASSKEY).unwrap();
+    vault.add_entry("alice@example.com", Some("Mail"), "s3cret-Passw0rd").unwrap();
+    vault.save().unwrap();
+
+    let reopened = Vault::from_bytes(&vault.to_bytes().unwrap(), PASSKEY).unwrap();
+    let account = reopened.find("alice").unwrap();
+    assert_eq!(account.get_description().as_deref(), Some("Mail"));
+    assert_eq!(reopened.password(account).unwrap(), "s3cret-Passw0rd");
+    assert_eq!(reopened.fingerprint(), vault.fingerprint());
+}
+
+#[test]
+fn edits_survive_a_save_and_reopen() {
+    let mut vault = Vault::in_memory(PASSKEY).unwrap();
+    let id = vault.add_entry("bob", None, "hunter2hunter2").unwrap().get_id().to_string();
+    vault.save().unwrap();
+
+    let mut reopened = Vault::from_bytes(&vault.to_bytes().unwrap(), PASSKEY).unwrap();
+    reopened.get_database_mut().get_account_by_id_mut(&id).unwrap().set_description(Some("Forum".to_string()));
+    reopened.add_entry("carol", None, "another-password").unwrap();
+    reopened.save().unwrap();
+
+    let reopened = Vault::from_bytes(&reopened.to_bytes().unwrap(), PASSKEY).unwrap();
+    assert_eq!(reopened.entries().len(), 2);
+    assert_eq!(reopened.find(&id).unwrap().get_description().as_deref(), Some("Forum"));
+    assert!(reopened.find("carol").is_ok());
+}
+
+#[test]
+fn unsaved_edits_are_not_written() {
+    let mut vault = Vault::in_memory(PASSKEY).unwrap();
+    vault.add_entry("dave", None, "not-saved-yet").unwrap();
+
+    let reopened = Vault::from_bytes(&vault.to_bytes().unwrap(), PASSKEY).unwrap();
+    assert!(reopened.entries().is_empty());
+}
+
+#[test]
+fn wrong_passkey_is_refused() {
+    let vault = Vault::in_memory(PASSKEY).unwrap();
+
+    let opened = Vault::from_bytes(&vault.to_bytes().unwrap(), "wrong horse battery staple");
+    assert_eq!(opened.err(), Some(FerropassError::InvalidPasskey));
+}
+
+#[test]
+fn changed_passkey_replaces_the_old_one() {
+    let mut vault = Vault::in_memory(PASSKEY).unwrap();
+    vault.add_entry("erin", None, "kept-across-rekey").unwrap();
+    vault.save().unwrap();
+    vault.change_passkey("a new and different passkey").unwrap();
+
+    let bytes = vault.to_bytes().unwrap();
+    assert!(Vault::from_bytes(&bytes, PASSKEY).is_err());
+    let reopened = Vault::from_bytes(&bytes, "a new and different passkey").unwrap();
+    assert_eq!(reopened.password(reopened.find("erin").unwrap()).unwrap(), "kept-across-rekey");
+}
+
+#[test]
+fn journal_records_replay_in_order() {
+    let mut vault = vault_with_history();
+    for name in ["frank", "grace", "heidi"] {
+        vault.add_entry(name, None, "journal-password").unwrap();
+        vault.save().unwrap();
+    }
+
+    let bytes = vault.to_bytes().unwrap();
+    assert!(lines(&bytes).len() > 3);
+    let reopened = Vault::from_bytes(&bytes, PASSKEY).unwrap();
+    let names: Vec<&str> = reopened.entries().iter().map(|account| account.get_username_or_email()).collect();
+    assert_eq!(names, ["frank", "grace", "heidi"]);
+}
+
+#[test]
+fn tampered_journal_record_is_refused() {
+    let mut vault = vault_with_history();
+    vault.add_entry("ivan", None, "journal-password").unwrap();
+    vault.save().unwrap();
+
+    let mut lines = lines(&vault.to_bytes().unwrap());
+    let last = lines.last_mut().unwrap();
+    let mut record: serde_json::Value = serde_json::from_str(last).unwrap();
+    let data = record["data"].as_str().unwrap();
+    let flipped = if data.starts_with('A') { "B" } else { "A" };
+    record["data"] = format!("{}{}", flipped, &data[1..]).into();
+    *last = record.to_string();
+
+    assert!(Vault::from_bytes(&join(&lines), PASSKEY).is_err());
+}
+
+#[test]
+fn journal_missing_a_middle_record_is_refused() {
+    let mut vault = vault_with_history();
+    for name in ["judy", "mallory", "niaj"] {
+        vault.add_entry(name, None, "journal-password").unwrap();
+        vault.save().unwrap();
+    }
+
+    // Drop the record that added mallory and renumber the rest to close the
+    // gap.
+    let mut lines = lines(&vault.to_bytes().unwrap());
+    let removed = lines.len() - 2;
+    lines.remove(removed);
+    for (seq, line) in lines.iter_mut().skip(1).enumerate() {
+        let mut record: serde_json::Value = serde_json::from_str(line).unwrap();
+        record["seq"] = seq.into();
+        *line = record.to_string();
+    }
+
+    assert!(Vault::from_bytes(&join(&lines), PASSKEY).is_err());
+}
+
+#[test]
+fn records_from_another_copy_are_refused() {
+    let mut vault = vault_with_history();
+    vault.add_entry("olivia", None, "journal-password").unwrap();
+    vault.save().unwrap();
+    let bytes = vault.to_bytes().unwrap();
+
+    // Two copies of the same file, each saved on its own.
+    let mut ours = Vault::from_bytes(&bytes, PASSKEY).unwrap();
+    ours.add_entry("peggy", None, "journal-password").unwrap();
+    ours.save().unwrap();
+    let mut theirs = Vault::from_bytes(&bytes, PASSKEY).unwrap();
+    for name in ["rupert", "sybil"] {
+        theirs.add_entry(name, None, "journal-password").unwrap();
+        theirs.save().unwrap();
+    }
+
+    let mut lines = lines(&ours.to_bytes().unwrap());
+    lines.push(self::lines(&theirs.to_bytes().unwrap()).pop().unwrap());
+    assert!(Vault::from_bytes(&join(&lines), PASSKEY).is_err());
+}
+
+// Memory storage that notes when it is asked whether a vault exists and when
+// it is locked.
+#[derive(Default)]
+struct Recording {
+    inner: MemoryStorage,
+    calls: Mutex<Vec<&'static str>>,
+}
+
+impl VaultStorage for Recording {
+    fn exists(&self, path: &Path) -> bool {
+        self.calls.lock().unwrap().push("exists");
+        self.inner.exists(path)
+    }
+
+    fn read_blob(&self, path: &Path) -> Result<Vec<u8>, FerropassError> {
+        self.inner.read_blob(path)
+    }
+
+    fn write_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        self.inner.write_blob(path, contents)
+    }
+
+    fn append_blob(&self, path: &Path, contents: &[u8]) -> Result<(), FerropassError> {
+        self.inner.append_blob(path, contents)
+    }
+
+    fn snapshot(&self, path: &Path) -> Result<PathBuf, FerropassError> {
+        self.inner.snapshot(path)
+    }
+
+    fn list_versions(&self, path: &Path) -> Result<Vec<(PathBuf, u64)>, FerropassError> {
+        self.inner.list_versions(path)
+    }
+
+    fn lock(&self, path: &Path) -> Result<VaultLock, FerropassError> {
+        self.calls.lock().unwrap().push("lock");
+        self.inner.lock(path)
+    }
+}
+
+#[test]
+fn create_checks_for_a_vault_under_the_lock() {
+    let storage = Arc::new(Recording::default());
+
+    let vault = Vault::create(storage.clone(), "vault.fp", PASSKEY).unwrap();
+    assert_eq!(storage.calls.lock().unwrap()[..2], ["lock", "exists"]);
+    drop(vault);
+
+    storage.calls.lock().unwrap().clear();
+    let created = Vault::create(storage.clone(), "vault.fp", PASSKEY);
+    assert!(matches!(created.err(), Some(FerropassError::Validation(message)) if message.contains("already a vault")));
+    assert_eq!(*storage.calls.lock().unwrap(), ["lock", "exists"]);
+}
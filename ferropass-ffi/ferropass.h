@@ -0,0 +1,79 @@
+/* C interface to the FerroPass vault format. Link against libferropass_ffi,
+ * built with `cargo build -p ferropass-ffi --release`.
+ *
+ * Functions returning int give FERROPASS_OK on success or one of the other
+ * FERROPASS_* codes on failure, after which ferropass_last_error describes
+ * it. Strings passed in are nul-terminated UTF-8 and are only borrowed for
+ * the call; the library keeps no copy of passkeys and passwords. Strings
+ * handed out belong to the caller, who frees each with
+ * ferropass_string_free, which wipes it first. */
+
+#ifndef FERROPASS_H
+#define FERROPASS_H
+
+#include <stddef.h>
+
+#define FERROPASS_OK 0
+/* A file or server couldn't be read or written, or the library failed
+ * unexpectedly. */
+#define FERROPASS_IO 1
+/* Encryption or key derivation failed. */
+#define FERROPASS_CRYPTO 2
+/* The passkey is wrong, or the vault was tampered with. */
+#define FERROPASS_INVALID_PASSKEY 3
+/* The vault isn't in the format it should be. */
+#define FERROPASS_FORMAT 4
+/* An argument is null or not UTF-8, or names something that doesn't
+ * exist. */
+#define FERROPASS_VALIDATION 5
+
+#define FERROPASS_NOTICE_WARNING 0
+#define FERROPASS_NOTICE_INFO 1
+
+/* Receives a notice of kind FERROPASS_NOTICE_*, with the context it was set
+ * with. message is owned by the library and only valid during the call. */
+typedef void (*FerropassNoticeCallback)(int kind, const char *message, void *context);
+
+/* An open vault, held locked until it is freed. */
+typedef struct FerropassVault FerropassVault;
+
+/* Opens the vault at path, a file or a WebDAV, S3 or SFTP URL, and stores it
+ * in *out, which the caller frees with ferropass_vault_free. *out is left
+ * alone on failure. */
+int ferropass_vault_open(const char *path, const char *passkey, FerropassVault **out);
+/* Makes a new, empty vault at path, failing if one is already there, and
+ * stores it in *out as ferropass_vault_open does. */
+int ferropass_vault_create(const char *path, const char *passkey, FerropassVault **out);
+/* Closes the vault, dropping any changes that weren't saved. vault may be
+ * null, and must not be used again. */
+void ferropass_vault_free(FerropassVault *vault);
+
+/* The number of entries in the vault, or 0 if it is null. */
+size_t ferropass_vault_len(const FerropassVault *vault);
+/* Finds the entry with the ID term, or the one entry whose username or
+ * description contains it, and stores new copies of its username and
+ * password in *username and *password. Either may be null if it isn't
+ * wanted. The caller owns the strings and frees each with
+ * ferropass_string_free. On failure neither is written. */
+int ferropass_vault_get(const FerropassVault *vault, const char *term, char **username, char **password);
+/* Adds an entry, written on the next ferropass_vault_save. description may
+ * be null. */
+int ferropass_vault_add(FerropassVault *vault, const char *username, const char *description, const char *password);
+/* Writes the changes made since the vault was opened or last saved. */
+int ferropass_vault_save(FerropassVault *vault);
+
+/* Passes warnings and other messages for the user to callback from then on,
+ * or drops them again if it is null. They are dropped until this is called.
+ * callback may be called from any thread, and context must stay valid until
+ * the callback is replaced. */
+void ferropass_set_notice_callback(FerropassNoticeCallback callback, void *context);
+
+/* A description of the last failure on this thread, or null. The string is
+ * owned by the library: don't free it. It stays valid until the next call on
+ * this thread that fails. */
+const char *ferropass_last_error(void);
+/* Wipes and frees a string handed out by ferropass_vault_get. string may be
+ * null, and must not be used again. */
+void ferropass_string_free(char *string);
+
+#endif
@@ -0,0 +1,304 @@
+//! A C interface to `ferropass-core`, so programs in other languages open and
+//! save the same vault files as the command line. `ferropass.h` declares it.
+//!
+//! Functions that can fail return one of the `FERROPASS_*` codes, `0` on
+//! success; `ferropass_last_error` then describes the failure. Strings handed
+//! out are freed with `ferropass_string_free`, which wipes them first, and
+//! the library keeps no copy of the passkeys and passwords handed in.
+
+use ferropass_core::error::FerropassError;
+use ferropass_core::memlock;
+use ferropass_core::notice::{self, Notice};
+use ferropass_core::storage::RoutedStorage;
+use ferropass_core::vault::Vault;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Arc;
+
+pub const FERROPASS_OK: c_int = 0;
+pub const FERROPASS_IO: c_int = 1;
+pub const FERROPASS_CRYPTO: c_int = 2;
+pub const FERROPASS_INVALID_PASSKEY: c_int = 3;
+pub const FERROPASS_FORMAT: c_int = 4;
+pub const FERROPASS_VALIDATION: c_int = 5;
+
+pub const FERROPASS_NOTICE_WARNING: c_int = 0;
+pub const FERROPASS_NOTICE_INFO: c_int = 1;
+
+/// Receives a notice of kind `FERROPASS_NOTICE_*`, with the `context` it was
+/// set with. `message` is only valid during the call.
+pub type FerropassNoticeCallback = extern "C" fn(kind: c_int, message: *const c_char, context: *mut c_void);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// An open vault, held locked until it is freed.
+pub struct FerropassVault(Vault);
+
+fn fail(error: FerropassError) -> c_int {
+    let code = match error {
+        FerropassError::Io(_) => FERROPASS_IO,
+        FerropassError::Crypto(_) => FERROPASS_CRYPTO,
+        FerropassError::InvalidPasskey => FERROPASS_INVALID_PASSKEY,
+        FerropassError::Format(_) => FERROPASS_FORMAT,
+        FerropassError::Validation(_) => FERROPASS_VALIDATION,
+    };
+    let message = CString::new(error.to_string().replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    code
+}
+
+fn status(result: Result<(), FerropassError>) -> c_int {
+    match result {
+        Ok(()) => FERROPASS_OK,
+        Err(error) => fail(error),
+    }
+}
+
+// Every entry point runs its body through here: a panic must not unwind
+// into the caller's frames, so it fails like any other error, or gives
+// `otherwise` where there is no error code to return.
+fn guarded<T>(otherwise: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|_| {
+        fail(FerropassError::Io("ferropass stopped unexpectedly".to_string()));
+        otherwise
+    })
+}
+
+fn checked(body: impl FnOnce() -> Result<(), FerropassError>) -> c_int {
+    guarded(FERROPASS_IO, || status(body()))
+}
+
+// Borrows a caller's string without copying it.
+unsafe fn text<'a>(pointer: *const c_char, name: &str) -> Result<&'a str, FerropassError> {
+    if pointer.is_null() {
+        return Err(FerropassError::Validation(format!("{} is null", name)));
+    }
+    unsafe { CStr::from_ptr(pointer) }.to_str()
+        .map_err(|_| FerropassError::Validation(format!("{} is not valid UTF-8", name)))
+}
+
+// Moves `value` into a C string, wiping it. The buffer is sized for the
+// terminating nul up front, so it is never reallocated and left behind
+// unwiped.
+fn hand_out(mut value: String) -> Result<*mut c_char, FerropassError> {
+    let mut bytes = Vec::with_capacity(value.len() + 1);
+    bytes.extend_from_slice(value.as_bytes());
+    memlock::wipe(unsafe { value.as_bytes_mut() });
+    match CString::new(bytes) {
+        Ok(string) => Ok(string.into_raw()),
+        Err(e) => {
+            let mut bytes = e.into_vec();
+            memlock::wipe(&mut bytes);
+            Err(FerropassError::Format("The value contains a nul character".to_string()))
+        },
+    }
+}
+
+unsafe fn opened(path: *const c_char, passkey: *const c_char, out: *mut *mut FerropassVault, open: fn(&str, &str) -> Result<Vault, FerropassError>) -> c_int {
+    checked(|| {
+        if out.is_null() {
+            return Err(FerropassError::Validation("out is null".to_string()));
+        }
+        let vault = open(unsafe { text(path, "path") }?, unsafe { text(passkey, "passkey") }?)?;
+        unsafe { *out = Box::into_raw(Box::new(FerropassVault(vault))) };
+        Ok(())
+    })
+}
+
+/// Opens the vault at `path`, a file or a WebDAV, S3 or SFTP URL, and stores
+/// it in `*out`.
+///
+/// # Safety
+///
+/// `path` and `passkey` must be nul-terminated strings and `out` must point
+/// to writable memory for one pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ferropass_vault_open(path: *const c_char, passkey: *const c_char, out: *mut *mut FerropassVault) -> c_int {
+    unsafe { opened(path, passkey, out, |path, passkey| Vault::open(Arc::new(RoutedStorage::new()), path, passkey)) }
+}
+
+/// Makes a new, empty vault at `path` and stores it in `*out`.
+///
+/// # Safety
+///
+/// As for `ferropass_vault_open`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ferropass_vault_create(path: *const c_char, passkey: *const c_char, out: *mut *mut FerropassVault) -> c_int {
+    unsafe { opened(path, passkey, out, |path, passkey| Vault::create(Arc::new(RoutedStorage::new()), path, passkey)) }
+}
+
+/// Closes the vault, dropping any changes that weren't saved.
+///
+/// # Safety
+///
+/// `vault` must be null or come from `ferropass_vault_open` or
+/// `ferropass_vault_create`, and not be used again.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ferropass_vault_free(vault: *mut FerropassVault) {
+    guarded((), || {
+        if !vault.is_null() {
+            drop(unsafe { Box::from_raw(vault) });
+        }
+    })
+}
+
+/// The number of entries in the vault.
+///
+/// # Safety
+///
+/// `vault` must be an open vault.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ferropass_vault_len(vault: *const FerropassVault) -> usize {
+    guarded(0, || unsafe { vault.as_ref() }.map_or(0, |vault| vault.0.entries().len()))
+}
+
+/// Finds the entry with the ID `term`, or the one entry whose username or
+/// description contains it, and stores its username and password in
+/// `*username` and `*password`. Either may be null if it isn't wanted. On
+/// failure neither is written.
+///
+/// # Safety
+///
+/// `vault` must be an open vault, `term` a nul-terminated string, and
+/// `username` and `password` null or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ferropass_vault_get(vault: *const FerropassVault, term: *const c_char, username: *mut *mut c_char, password: *mut *mut c_char) -> c_int {
+    checked(|| {
+        let vault = &unsafe { vault.as_ref() }.ok_or_else(|| FerropassError::Validation("vault is null".to_string()))?.0;
+        let account = vault.find(unsafe { text(term, "term") }?)?;
+
+        let found_username = if username.is_null() { None } else { Some(hand_out(account.get_username_or_email().to_string())?) };
+        let found_password = if password.is_null() { Ok(None) } else { vault.password(account).and_then(hand_out).map(Some) };
+        let found_password = match found_password {
+            Ok(found) => found,
+            Err(error) => {
+                if let Some(found) = found_username {
+                    unsafe { ferropass_string_free(found) };
+                }
+                return Err(error);
+            },
+        };
+
+        if let Some(found) = found_username {
+            unsafe { *username = found };
+        }
+        if let Some(found) = found_password {
+            unsafe { *password = found };
+        }
+        Ok(())
+    })
+}
+
+/// Adds an entry, written on the next `ferropass_vault_save`. `description`
+/// may be null.
+///
+/// # Safety
+///
+/// `vault` must be an open vault, and `username` and `password`
+/// nul-terminated strings, as is `description` unless it is null.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ferropass_vault_add(vault: *mut FerropassVault, username: *const c_char, description: *const c_char, password: *const c_char) -> c_int {
+    checked(|| {
+        let vault = &mut unsafe { vault.as_mut() }.ok_or_else(|| FerropassError::Validation("vault is null".to_string()))?.0;
+        let description = if description.is_null() { None } else { Some(unsafe { text(description, "description") }?) };
+        vault.add_entry(unsafe { text(username, "username") }?, description, unsafe { text(password, "password") }?)?;
+        Ok(())
+    })
+}
+
+/// Writes the changes made since the vault was opened or last saved.
+///
+/// # Safety
+///
+/// `vault` must be an open vault.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ferropass_vault_save(vault: *mut FerropassVault) -> c_int {
+    checked(|| match unsafe { vault.as_mut() } {
+        Some(vault) => vault.0.save(),
+        None => Err(FerropassError::Validation("vault is null".to_string())),
+    })
+}
+
+/// Passes warnings and other messages for the user, such as a vault opened
+/// from its offline copy, to `callback` from then on, or drops them again if
+/// it is null. They are dropped until this is called.
+///
+/// # Safety
+///
+/// `callback` may be called from any thread, and `context` must stay valid
+/// for it until the callback is replaced.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ferropass_set_notice_callback(callback: Option<FerropassNoticeCallback>, context: *mut c_void) {
+    guarded((), || {
+        let Some(callback) = callback else {
+            notice::clear_handler();
+            return;
+        };
+        let context = context as usize;
+        notice::set_handler(move |notice| {
+            let (kind, message) = match notice {
+                Notice::Warning(message) => (FERROPASS_NOTICE_WARNING, message),
+                Notice::Info(message) => (FERROPASS_NOTICE_INFO, message),
+            };
+            let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+            callback(kind, message.as_ptr(), context as *mut c_void);
+        });
+    })
+}
+
+/// A description of the last failure on this thread, or null. It stays valid
+/// until the next call that fails.
+#[unsafe(no_mangle)]
+pub extern "C" fn ferropass_last_error() -> *const c_char {
+    guarded(ptr::null(), || LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr())))
+}
+
+/// Wipes and frees a string from `ferropass_vault_get`.
+///
+/// # Safety
+///
+/// `string` must be null or come from this library, and not be used again.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ferropass_string_free(string: *mut c_char) {
+    guarded((), || {
+        if !string.is_null() {
+            let mut bytes = unsafe { CString::from_raw(string) }.into_bytes_with_nul();
+            memlock::wipe(&mut bytes);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(ferropass_last_error()) }.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn panics_fail_with_an_error_code() {
+        assert_eq!(checked(|| panic!("unexpected")), FERROPASS_IO);
+        assert_eq!(last_error(), "ferropass stopped unexpectedly");
+    }
+
+    #[test]
+    fn panics_without_an_error_code_give_the_fallback() {
+        assert_eq!(guarded(7usize, || panic!("unexpected")), 7);
+        assert!(guarded(ptr::null::<c_char>(), || panic!("unexpected")).is_null());
+        assert_eq!(last_error(), "ferropass stopped unexpectedly");
+    }
+
+    #[test]
+    fn errors_map_to_their_codes() {
+        assert_eq!(checked(|| Err(FerropassError::Crypto("bad key".to_string()))), FERROPASS_CRYPTO);
+        assert_eq!(last_error(), "bad key");
+        assert_eq!(checked(|| Err(FerropassError::Format("bad\0file".to_string()))), FERROPASS_FORMAT);
+        assert_eq!(last_error(), "badfile");
+        assert_eq!(checked(|| Ok(())), FERROPASS_OK);
+    }
+}
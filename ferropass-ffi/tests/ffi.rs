@@ -0,0 +1,178 @@
+use ferropass_ffi::*;
+
+use std::ffi::{c_char, CStr, CString};
+use std::fs;
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::Once;
+
+const PASSKEY: &str = "correct horse battery staple";
+
+// Each test gets a directory of its own; the config and state directories,
+// where wrong passkeys are counted, are shared by the whole test binary.
+fn dir(name: &str) -> PathBuf {
+    static SETUP: Once = Once::new();
+    let root = std::env::temp_dir().join(format!("ferropass-ffi-{}", std::process::id()));
+    SETUP.call_once(|| {
+        let _ = fs::remove_dir_all(&root);
+        unsafe {
+            std::env::set_var("XDG_CONFIG_HOME", root.join("config"));
+            std::env::set_var("XDG_STATE_HOME", root.join("state"));
+        }
+    });
+
+    let dir = root.join(name);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn c(text: &str) -> CString {
+    CString::new(text).unwrap()
+}
+
+fn last_error() -> String {
+    let message = ferropass_last_error();
+    assert!(!message.is_null());
+    unsafe { CStr::from_ptr(message) }.to_str().unwrap().to_string()
+}
+
+// Takes back a string handed out by the library.
+fn take(string: *mut c_char) -> String {
+    assert!(!string.is_null());
+    let text = unsafe { CStr::from_ptr(string) }.to_str().unwrap().to_string();
+    unsafe { ferropass_string_free(string) };
+    text
+}
+
+// A saved vault with one entry, closed again.
+fn vault(name: &str) -> CString {
+    let path = c(dir(name).join("vault.fp").to_str().unwrap());
+    let mut vault = ptr::null_mut();
+    unsafe {
+        assert_eq!(ferropass_vault_create(path.as_ptr(), c(PASSKEY).as_ptr(), &mut vault), FERROPASS_OK);
+        assert_eq!(ferropass_vault_add(vault, c("alice@example.com").as_ptr(), c("Mail").as_ptr(), c("s3cret-Passw0rd").as_ptr()), FERROPASS_OK);
+        assert_eq!(ferropass_vault_save(vault), FERROPASS_OK);
+        ferropass_vault_free(vault);
+    }
+    path
+}
+
+#[test]
+fn created_entries_reopen_and_are_handed_back() {
+    let path = vault("round-trip");
+
+    let mut vault = ptr::null_mut();
+    unsafe {
+        assert_eq!(ferropass_vault_open(path.as_ptr(), c(PASSKEY).as_ptr(), &mut vault), FERROPASS_OK);
+        assert_eq!(ferropass_vault_len(vault), 1);
+
+        let (mut username, mut password) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(ferropass_vault_get(vault, c("alice").as_ptr(), &mut username, &mut password), FERROPASS_OK);
+        assert_eq!(take(username), "alice@example.com");
+        assert_eq!(take(password), "s3cret-Passw0rd");
+
+        let mut password = ptr::null_mut();
+        assert_eq!(ferropass_vault_get(vault, c("Mail").as_ptr(), ptr::null_mut(), &mut password), FERROPASS_OK);
+        assert_eq!(take(password), "s3cret-Passw0rd");
+
+        ferropass_vault_free(vault);
+    }
+}
+
+#[test]
+fn unknown_entry_leaves_the_outputs_alone() {
+    let path = vault("unknown");
+
+    let mut vault = ptr::null_mut();
+    unsafe {
+        assert_eq!(ferropass_vault_open(path.as_ptr(), c(PASSKEY).as_ptr(), &mut vault), FERROPASS_OK);
+
+        let (mut username, mut password) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(ferropass_vault_get(vault, c("bob").as_ptr(), &mut username, &mut password), FERROPASS_VALIDATION);
+        assert!(username.is_null() && password.is_null());
+        assert!(!last_error().is_empty());
+
+        ferropass_vault_free(vault);
+    }
+}
+
+#[test]
+fn wrong_passkey_is_refused() {
+    let path = vault("wrong-passkey");
+
+    let mut vault = ptr::null_mut();
+    unsafe {
+        assert_eq!(ferropass_vault_open(path.as_ptr(), c("wrong horse battery staple").as_ptr(), &mut vault), FERROPASS_INVALID_PASSKEY);
+    }
+    assert!(vault.is_null());
+    assert!(!last_error().is_empty());
+}
+
+#[test]
+fn existing_vault_is_not_created_over() {
+    let path = vault("existing");
+
+    let mut vault = ptr::null_mut();
+    unsafe {
+        assert_eq!(ferropass_vault_create(path.as_ptr(), c(PASSKEY).as_ptr(), &mut vault), FERROPASS_VALIDATION);
+    }
+    assert!(vault.is_null());
+}
+
+#[test]
+fn null_pointers_are_refused() {
+    let path = vault("null");
+    let passkey = c(PASSKEY);
+
+    let mut vault = ptr::null_mut();
+    unsafe {
+        assert_eq!(ferropass_vault_open(ptr::null(), passkey.as_ptr(), &mut vault), FERROPASS_VALIDATION);
+        assert_eq!(last_error(), "path is null");
+        assert_eq!(ferropass_vault_open(path.as_ptr(), ptr::null(), &mut vault), FERROPASS_VALIDATION);
+        assert_eq!(last_error(), "passkey is null");
+        assert_eq!(ferropass_vault_open(path.as_ptr(), passkey.as_ptr(), ptr::null_mut()), FERROPASS_VALIDATION);
+        assert_eq!(last_error(), "out is null");
+        assert!(vault.is_null());
+
+        let mut password = ptr::null_mut();
+        assert_eq!(ferropass_vault_len(ptr::null()), 0);
+        assert_eq!(ferropass_vault_get(ptr::null(), c("alice").as_ptr(), ptr::null_mut(), &mut password), FERROPASS_VALIDATION);
+        assert_eq!(ferropass_vault_add(ptr::null_mut(), c("bob").as_ptr(), ptr::null(), c("hunter2").as_ptr()), FERROPASS_VALIDATION);
+        assert_eq!(ferropass_vault_save(ptr::null_mut()), FERROPASS_VALIDATION);
+        assert!(password.is_null());
+
+        assert_eq!(ferropass_vault_open(path.as_ptr(), passkey.as_ptr(), &mut vault), FERROPASS_OK);
+        assert_eq!(ferropass_vault_get(vault, ptr::null(), ptr::null_mut(), &mut password), FERROPASS_VALIDATION);
+        assert_eq!(last_error(), "term is null");
+        assert_eq!(ferropass_vault_add(vault, ptr::null(), ptr::null(), c("hunter2").as_ptr()), FERROPASS_VALIDATION);
+        assert_eq!(ferropass_vault_add(vault, c("bob").as_ptr(), ptr::null(), ptr::null()), FERROPASS_VALIDATION);
+        assert_eq!(ferropass_vault_len(vault), 1);
+
+        ferropass_vault_free(vault);
+        ferropass_vault_free(ptr::null_mut());
+        ferropass_string_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn invalid_utf8_is_refused() {
+    let path = vault("utf8");
+    let invalid = CString::new(vec![0x66, 0x6f, 0xff, 0x6f]).unwrap();
+
+    let mut vault = ptr::null_mut();
+    unsafe {
+        assert_eq!(ferropass_vault_open(path.as_ptr(), invalid.as_ptr(), &mut vault), FERROPASS_VALIDATION);
+        assert_eq!(last_error(), "passkey is not valid UTF-8");
+        assert!(vault.is_null());
+
+        assert_eq!(ferropass_vault_open(path.as_ptr(), c(PASSKEY).as_ptr(), &mut vault), FERROPASS_OK);
+        let mut password = ptr::null_mut();
+        assert_eq!(ferropass_vault_get(vault, invalid.as_ptr(), ptr::null_mut(), &mut password), FERROPASS_VALIDATION);
+        assert_eq!(last_error(), "term is not valid UTF-8");
+        assert_eq!(ferropass_vault_add(vault, c("bob").as_ptr(), invalid.as_ptr(), c("hunter2").as_ptr()), FERROPASS_VALIDATION);
+        assert_eq!(last_error(), "description is not valid UTF-8");
+        assert!(password.is_null());
+
+        ferropass_vault_free(vault);
+    }
+}
@@ -0,0 +1,572 @@
+use crate::cli::Cli;
+use crate::rpc;
+use crate::ssh_agent::{self, SshKey};
+use crate::summary;
+
+use ferropass_core::config;
+use ferropass_core::encryption::{EncryptedData, EntryKey};
+use ferropass_core::entropy;
+use ferropass_core::error::FerropassError;
+use ferropass_core::events::{self, Event};
+use ferropass_core::memlock::SecretKey;
+use ferropass_core::models::{current_timestamp, format_timestamp, Database};
+use ferropass_core::paranoid;
+use ferropass_core::storage::FileStorage;
+use ferropass_core::throttle;
+use ferropass_core::vault::find;
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Serialize, Deserialize};
+use subtle::ConstantTimeEq;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+// A connection left idle this long is closed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+// Each request is a line of JSON, answered the same way. Lines with a
+// "jsonrpc" member are for tools instead, see rpc.rs.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "kebab-case")]
+enum Request {
+    Unlock { file: PathBuf, passkey: String },
+    Get { file: PathBuf, account: String },
+    Summary { file: PathBuf, since: Duration, max_age: Duration },
+    Lock { file: Option<PathBuf> },
+    Status,
+    Stop,
+}
+
+// Requests in the native protocol carry the secret the agent wrote next to
+// its socket when it started, which only ferropass itself reads. Other tools
+// go through JSON-RPC, where a token limits what they may do.
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    secret: String,
+    #[serde(flatten)]
+    request: Request,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "kebab-case")]
+enum Response {
+    Password { username: String, password: String },
+    Summary { text: String },
+    Locked,
+    Done { count: usize },
+    Status { vaults: Vec<PathBuf> },
+    Error { error: FerropassError },
+}
+
+// The agent keeps each vault's data key, never its passkey or decrypted
+// contents: every request reads the file again and opens it with the key,
+// which skips key derivation, so answers are quick and always current.
+// SSH keys are the exception: ssh asks for them on every connection, so each
+// vault's are kept parsed, and read again only once its file changes.
+pub struct Unlocked {
+    key: SecretKey,
+    last_used: Instant,
+    ssh_keys: Vec<SshKey>,
+    read_at: Option<SystemTime>,
+}
+
+impl Unlocked {
+    fn new(filepath: &Path, key: SecretKey) -> Self {
+        let mut unlocked = Unlocked { key, last_used: Instant::now(), ssh_keys: Vec::new(), read_at: None };
+        unlocked.refresh_ssh_keys(filepath);
+        unlocked
+    }
+
+    // Keys that don't open are left out.
+    fn refresh_ssh_keys(&mut self, filepath: &Path) {
+        let modified = fs::metadata(filepath).and_then(|metadata| metadata.modified()).ok();
+        if modified.is_some() && modified == self.read_at {
+            return;
+        }
+        self.read_at = modified;
+
+        let Ok((database, entry_key)) = open(filepath, &self.key) else {
+            self.ssh_keys.clear();
+            return;
+        };
+        self.ssh_keys = database.get_accounts().iter()
+            .filter_map(|account| {
+                let text = entry_key.open(account.get_ssh_key()?).ok()?;
+                SshKey::parse(text, || Err(FerropassError::Io("The SSH key is protected with a passphrase".to_string()))).ok()
+            })
+            .collect();
+    }
+}
+
+pub type UnlockedVaults = HashMap<PathBuf, Unlocked>;
+type Vaults = Arc<Mutex<UnlockedVaults>>;
+
+// Next to the other per-login sockets when there is a runtime directory.
+fn socket_path() -> Result<PathBuf, FerropassError> {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir).join("ferropass").join("agent.sock")),
+        _ => config::state_dir()
+            .map(|dir| dir.join("agent.sock"))
+            .ok_or_else(|| FerropassError::Io("Cannot determine where the agent's socket goes: HOME is not set".to_string())),
+    }
+}
+
+fn secret_path(path: &Path) -> PathBuf {
+    path.with_file_name("agent.secret")
+}
+
+// Where the agent speaks the ssh-agent protocol, for SSH_AUTH_SOCK.
+fn ssh_socket_path(path: &Path) -> PathBuf {
+    path.with_file_name("ssh-agent.sock")
+}
+
+// Starts the agent in the background, or runs it here with `foreground`.
+// A vault locks again once it has gone `lock_after` without use.
+pub fn run(lock_after: Duration, foreground: bool) -> Result<(), FerropassError> {
+    paranoid::ensure_allowed("The agent")?;
+    let path = socket_path()?;
+    if request(Request::Status).is_ok_and(|response| response.is_some()) {
+        return Err(FerropassError::Io(format!("An agent is already running on {}", path.display())));
+    }
+
+    if foreground {
+        // A log of the vaults unlocked in the agent.
+        events::listen(|event| println!("{} {}", format_timestamp(current_timestamp()), event));
+        return serve(&path, lock_after);
+    }
+
+    let executable = env::current_exe().map_err(|e| FerropassError::Io(format!("Cannot find the ferropass executable: {}", e)))?;
+    let mut command = Command::new(executable);
+    command.args(["agent", "--foreground", "--lock-after", &format!("{}s", lock_after.as_secs())])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // Out of the terminal's process group, so Ctrl-C there doesn't stop it.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let child = command.spawn().map_err(|e| FerropassError::Io(format!("Failed to start the agent: {}", e)))?;
+
+    for _ in 0..50 {
+        thread::sleep(Duration::from_millis(100));
+        if request(Request::Status).is_ok_and(|response| response.is_some()) {
+            eprintln!("Agent started (pid {}), listening on {}", child.id(), path.display());
+            // On stdout alone, so `eval "$(ferropass agent)"` sets it.
+            println!("SSH_AUTH_SOCK={}; export SSH_AUTH_SOCK;", ssh_socket_path(&path).display());
+            return Ok(());
+        }
+    }
+    Err(FerropassError::Io("The agent didn't start; run `ferropass agent --foreground` to see why".to_string()))
+}
+
+pub fn status() -> Result<(), FerropassError> {
+    match request(Request::Status)? {
+        Some(Response::Status { vaults }) => {
+            if vaults.is_empty() {
+                println!("The agent is running with no vault unlocked.");
+            } else {
+                println!("The agent is running with {} vault(s) unlocked:", vaults.len());
+                for vault in vaults {
+                    println!("  {}", vault.display());
+                }
+            }
+            println!("SSH keys are served on {}", ssh_socket_path(&socket_path()?).display());
+        },
+        Some(response) => return Err(unexpected(response)),
+        None => println!("No agent is running."),
+    }
+    Ok(())
+}
+
+pub fn stop() -> Result<(), FerropassError> {
+    match request(Request::Stop)? {
+        Some(Response::Done { .. }) => println!("Agent stopped."),
+        Some(response) => return Err(unexpected(response)),
+        None => println!("No agent is running."),
+    }
+    Ok(())
+}
+
+pub fn unlock_vault(filepath: &Path, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    paranoid::ensure_allowed("The agent")?;
+    let file = canonical(filepath)?;
+    if request(Request::Status)?.is_none() {
+        return Err(FerropassError::Io("No agent is running; start one with `ferropass agent`".to_string()));
+    }
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    match request(Request::Unlock { file, passkey })? {
+        Some(Response::Done { .. }) => println!("Unlocked {} in the agent.", filepath.display()),
+        Some(response) => return Err(unexpected(response)),
+        None => return Err(FerropassError::Io("The agent stopped".to_string())),
+    }
+    Ok(())
+}
+
+// Makes the agent forget the key of one vault, or of all of them.
+pub fn lock(filepath: Option<&Path>) -> Result<(), FerropassError> {
+    let file = filepath.map(canonical).transpose()?;
+    match request(Request::Lock { file })? {
+        Some(Response::Done { count }) => println!("Locked {} vault(s) in the agent.", count),
+        Some(response) => return Err(unexpected(response)),
+        None => println!("No agent is running."),
+    }
+    Ok(())
+}
+
+// An account's username and password through the agent, unlocking the vault
+// in it first if needed. Without a running agent there is nothing, and the
+// caller opens the vault itself.
+pub fn get(filepath: &Path, account: &str, passkey: impl FnOnce() -> Result<String, FerropassError>) -> Result<Option<(String, String)>, FerropassError> {
+    let get = |file| Request::Get { file, account: account.to_string() };
+    match ask(filepath, get, passkey)? {
+        Some(Response::Password { username, password }) => Ok(Some((username, password))),
+        Some(response) => Err(unexpected(response)),
+        None => Ok(None),
+    }
+}
+
+// The vault's summary, built by the agent the same way, so that cron needs
+// no passkey while the vault stays unlocked in it.
+pub fn summary(filepath: &Path, since: Duration, max_age: Duration, passkey: impl FnOnce() -> Result<String, FerropassError>) -> Result<Option<String>, FerropassError> {
+    match ask(filepath, |file| Request::Summary { file, since, max_age }, passkey)? {
+        Some(Response::Summary { text }) => Ok(Some(text)),
+        Some(response) => Err(unexpected(response)),
+        None => Ok(None),
+    }
+}
+
+// Sends a request about one vault, unlocking it with `passkey` and asking
+// again if the agent has it locked. The agent isn't used in paranoid mode.
+fn ask(filepath: &Path, make: impl Fn(PathBuf) -> Request, passkey: impl FnOnce() -> Result<String, FerropassError>) -> Result<Option<Response>, FerropassError> {
+    if paranoid::is_enabled() {
+        return Ok(None);
+    }
+    let file = canonical(filepath)?;
+
+    match request(make(file.clone()))? {
+        Some(Response::Locked) => {
+            match request(Request::Unlock { file: file.clone(), passkey: passkey()? })? {
+                Some(Response::Done { .. }) => request(make(file)),
+                response => Ok(response),
+            }
+        },
+        response => Ok(response),
+    }
+}
+
+fn canonical(filepath: &Path) -> Result<PathBuf, FerropassError> {
+    fs::canonicalize(filepath).map_err(|e| FerropassError::Io(format!("Error reading file: {}: {}", filepath.display(), e)))
+}
+
+fn unexpected(response: Response) -> FerropassError {
+    match response {
+        Response::Error { error } => error,
+        Response::Locked => FerropassError::Validation("The vault is locked in the agent".to_string()),
+        _ => FerropassError::Io("The agent gave an unexpected answer".to_string()),
+    }
+}
+
+// Sends one request to the agent. Nothing listening on the socket means no
+// agent is running.
+#[cfg(unix)]
+fn request(request: Request) -> Result<Option<Response>, FerropassError> {
+    let path = socket_path()?;
+    let Ok(mut stream) = UnixStream::connect(&path) else {
+        return Ok(None);
+    };
+    stream.set_read_timeout(Some(Duration::from_secs(60))).map_err(|e| FerropassError::Io(format!("Agent connection failed: {}", e)))?;
+    let secret = fs::read_to_string(secret_path(&path))
+        .map_err(|e| FerropassError::Io(format!("Cannot read the agent's secret: {}", e)))?;
+
+    let mut line = serde_json::to_string(&Envelope { secret, request }).map_err(|e| FerropassError::Io(format!("Error serializing request: {}", e)))?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).map_err(|e| FerropassError::Io(format!("Agent connection failed: {}", e)))?;
+
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer).map_err(|e| FerropassError::Io(format!("Agent connection failed: {}", e)))?;
+    serde_json::from_str(&answer).map(Some).map_err(|_| FerropassError::Io("The agent gave an unexpected answer".to_string()))
+}
+
+#[cfg(not(unix))]
+fn request(_request: Request) -> Result<Option<Response>, FerropassError> {
+    Ok(None)
+}
+
+// Only this user can reach the socket: it is created in a directory no one
+// else can enter, and is itself private.
+#[cfg(unix)]
+fn serve(path: &Path, lock_after: Duration) -> Result<(), FerropassError> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    if let Some(dir) = path.parent() {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)
+            .map_err(|e| FerropassError::Io(format!("Failed to create {}: {}", dir.display(), e)))?;
+    }
+    let listen = |path: &Path| {
+        // What is left of an agent that didn't exit cleanly.
+        let _ = fs::remove_file(path);
+        let listener = UnixListener::bind(path).map_err(|e| FerropassError::Io(format!("Failed to listen on {}: {}", path.display(), e)))?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+            .map_err(|e| FerropassError::Io(format!("Failed to restrict {}: {}", path.display(), e)))?;
+        Ok::<_, FerropassError>(listener)
+    };
+    let secret = new_secret()?;
+    let secret_file = secret_path(path);
+    let _ = fs::remove_file(&secret_file);
+    config::write_private_file(&secret_file, &secret)?;
+    let secret = Arc::new(secret);
+
+    let listener = listen(path)?;
+    let ssh_path = ssh_socket_path(path);
+    let ssh_listener = listen(&ssh_path)?;
+    println!("Agent listening on {}", path.display());
+    println!("SSH_AUTH_SOCK={}; export SSH_AUTH_SOCK;", ssh_path.display());
+
+    let vaults: Vaults = Arc::default();
+    // ssh keeps its connection open for as long as it runs, so each one gets
+    // a thread of its own.
+    let ssh_vaults = vaults.clone();
+    thread::spawn(move || {
+        for stream in ssh_listener.incoming().flatten() {
+            let vaults = ssh_vaults.clone();
+            thread::spawn(move || serve_ssh(stream, &vaults));
+        }
+    });
+    if !lock_after.is_zero() {
+        let vaults = vaults.clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(5));
+            if let Ok(mut vaults) = vaults.lock() {
+                vaults.retain(|_, unlocked| unlocked.last_used.elapsed() < lock_after);
+            }
+        });
+    }
+
+    // Tools may keep their connection open too.
+    for stream in listener.incoming().flatten() {
+        let (vaults, secret) = (vaults.clone(), secret.clone());
+        let (path, ssh_path, secret_file) = (path.to_path_buf(), ssh_path.clone(), secret_file.clone());
+        thread::spawn(move || match handle(stream, &vaults, &secret) {
+            Ok(true) => {
+                let _ = fs::remove_file(&path);
+                let _ = fs::remove_file(&ssh_path);
+                let _ = fs::remove_file(&secret_file);
+                std::process::exit(0);
+            },
+            Ok(false) => {},
+            Err(e) => eprintln!("{}", e),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve(_path: &Path, _lock_after: Duration) -> Result<(), FerropassError> {
+    Err(FerropassError::Io("The agent needs Unix domain sockets, which this platform doesn't have".to_string()))
+}
+
+// Answers the requests on one connection until it closes, telling whether
+// the agent was asked to stop.
+#[cfg(unix)]
+fn handle(stream: UnixStream, vaults: &Vaults, secret: &str) -> Result<bool, FerropassError> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT)).map_err(|e| FerropassError::Io(format!("Client connection failed: {}", e)))?;
+    stream.set_write_timeout(Some(IDLE_TIMEOUT)).map_err(|e| FerropassError::Io(format!("Client connection failed: {}", e)))?;
+
+    for line in BufReader::new(&stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) else {
+            write_line(&stream, &rpc::parse_error())?;
+            continue;
+        };
+
+        if message.get("jsonrpc").is_some() {
+            let Ok(mut vaults) = vaults.lock() else {
+                return Err(FerropassError::Io("The agent is unavailable".to_string()));
+            };
+            if let Some(response) = rpc::answer(&message, &mut vaults) {
+                write_line(&stream, &response)?;
+            }
+            continue;
+        }
+
+        if !message.get("secret").and_then(serde_json::Value::as_str).is_some_and(|given| same_secret(given, secret)) {
+            let error = FerropassError::Validation("Only ferropass itself can use this protocol; tools use JSON-RPC with a token".to_string());
+            write_line(&stream, &Response::Error { error })?;
+            continue;
+        }
+
+        let request = serde_json::from_value::<Envelope>(message).map(|envelope| envelope.request);
+        let stop = matches!(request, Ok(Request::Stop));
+        let response = match request {
+            Ok(request) => answer(request, vaults),
+            Err(e) => Response::Error { error: FerropassError::Format(format!("Malformed request: {}", e)) },
+        };
+        write_line(&stream, &response)?;
+        if stop {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(unix)]
+fn new_secret() -> Result<String, FerropassError> {
+    entropy::ensure()?;
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// Takes as long whatever the secrets have in common.
+fn same_secret(given: &str, secret: &str) -> bool {
+    given.as_bytes().ct_eq(secret.as_bytes()).into()
+}
+
+#[cfg(unix)]
+fn write_line(mut stream: &UnixStream, message: &impl Serialize) -> Result<(), FerropassError> {
+    let mut line = serde_json::to_string(message).map_err(|e| FerropassError::Io(format!("Error serializing response: {}", e)))?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).map_err(|e| FerropassError::Io(format!("Client connection failed: {}", e)))
+}
+
+// ssh-agent messages are length-prefixed, with as many on one connection as
+// the client likes. A vault counts as used when one of its keys signs.
+#[cfg(unix)]
+fn serve_ssh(mut stream: UnixStream, vaults: &Vaults) {
+    loop {
+        let Some(message) = ssh_agent::read_message(&mut stream) else {
+            return;
+        };
+
+        let Ok(mut vaults) = vaults.lock() else {
+            return;
+        };
+        for (path, unlocked) in vaults.iter_mut() {
+            unlocked.refresh_ssh_keys(path);
+        }
+        let (paths, keys): (Vec<&PathBuf>, Vec<&SshKey>) = vaults.iter()
+            .flat_map(|(path, unlocked)| unlocked.ssh_keys.iter().map(move |key| (path, key)))
+            .unzip();
+        let (response, signed) = ssh_agent::answer(&message, &keys);
+        if let Some(path) = signed.map(|index| paths[index].clone()) && let Some(unlocked) = vaults.get_mut(&path) {
+            unlocked.last_used = Instant::now();
+        }
+        drop(vaults);
+
+        if stream.write_all(&ssh_agent::frame(response)).is_err() {
+            return;
+        }
+    }
+}
+
+fn answer(request: Request, vaults: &Vaults) -> Response {
+    // Deriving the key takes a second or more, so it happens before the
+    // vaults are locked, leaving other clients be.
+    let unlocked = match &request {
+        Request::Unlock { file, passkey } => Some(unlock(file, passkey).map(|key| Unlocked::new(file, key))),
+        _ => None,
+    };
+
+    let Ok(mut vaults) = vaults.lock() else {
+        return Response::Error { error: FerropassError::Io("The agent is unavailable".to_string()) };
+    };
+
+    match request {
+        Request::Unlock { file, .. } => match unlocked.expect("the key was derived above") {
+            Ok(unlocked) => {
+                vaults.insert(file, unlocked);
+                Response::Done { count: 1 }
+            },
+            Err(error) => Response::Error { error },
+        },
+        Request::Get { file, account } => {
+            let found = use_vault(&mut vaults, &file).and_then(|opened| opened.map(|(database, entry_key)| {
+                let account = find(&database, &account, &file)?;
+                Ok((account.get_username_or_email().to_string(), entry_key.open(account.get_password())?))
+            }).transpose());
+            match found {
+                Ok(Some((username, password))) => Response::Password { username, password },
+                Ok(None) => Response::Locked,
+                Err(error) => Response::Error { error },
+            }
+        },
+        Request::Summary { file, since, max_age } => {
+            let built = use_vault(&mut vaults, &file).and_then(|opened| opened.map(|(database, entry_key)| {
+                summary::digest(&file, &database, &entry_key, since, max_age)
+            }).transpose());
+            match built {
+                Ok(Some(text)) => Response::Summary { text },
+                Ok(None) => Response::Locked,
+                Err(error) => Response::Error { error },
+            }
+        },
+        Request::Lock { file: Some(file) } => Response::Done { count: usize::from(vaults.remove(&file).is_some()) },
+        Request::Lock { file: None } => {
+            let count = vaults.len();
+            vaults.clear();
+            Response::Done { count }
+        },
+        Request::Status => Response::Status { vaults: vaults.keys().cloned().collect() },
+        Request::Stop => {
+            let count = vaults.len();
+            vaults.clear();
+            Response::Done { count }
+        },
+    }
+}
+
+// Opens a vault unlocked in the agent, counting it as used. Nothing means it
+// is locked, which includes having been re-encrypted under another data key
+// since it was unlocked.
+pub fn use_vault(vaults: &mut UnlockedVaults, file: &Path) -> Result<Option<(Database, EntryKey)>, FerropassError> {
+    let Some(unlocked) = vaults.get_mut(file) else {
+        return Ok(None);
+    };
+    match open(file, &unlocked.key) {
+        Ok(opened) => {
+            unlocked.last_used = Instant::now();
+            Ok(Some(opened))
+        },
+        Err(FerropassError::InvalidPasskey) => {
+            vaults.remove(file);
+            Ok(None)
+        },
+        Err(e) => Err(e),
+    }
+}
+
+fn unlock(filepath: &Path, passkey: &str) -> Result<SecretKey, FerropassError> {
+    let encrypted_data = EncryptedData::read_signed(&FileStorage, filepath)?;
+    let key = throttle::guard(filepath, || {
+        let key = encrypted_data.unlock_key(passkey)?;
+        // Files from before the data key have nothing the passkey unwraps,
+        // so only decrypting them proves it right.
+        encrypted_data.open(&key)?;
+        Ok(key)
+    })?;
+    events::emit(&Event::Unlocked { path: filepath.to_path_buf() });
+    Ok(key)
+}
+
+fn open(filepath: &Path, key: &[u8; 32]) -> Result<(Database, EntryKey), FerropassError> {
+    let encrypted_data = EncryptedData::read_signed(&FileStorage, filepath)?;
+    let mut database = encrypted_data.open(key)?;
+    let entry_key = encrypted_data.entry_key(key);
+    entry_key.seal_plaintext(&mut database)?;
+    Ok((database, entry_key))
+}
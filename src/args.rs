@@ -0,0 +1,453 @@
+use crate::import::OnConflict;
+use crate::rpc::Method;
+
+use ferropass_core::password::{Capitalization, Language, Separator, DEFAULT_LENGTH, DEFAULT_PIN_LENGTH, DEFAULT_SYLLABLES};
+
+use clap::{ArgGroup, Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "ferropass", version, about = "A secure, command-line password manager")]
+pub struct Args {
+    /// Refuse clipboard, network, hooks, agent and auto-type regardless of config
+    #[arg(long, global = true)]
+    pub paranoid: bool,
+    /// Keep every database in memory for this interactive session only; nothing is written to disk
+    #[arg(long)]
+    pub ephemeral: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print a digest of recent vault activity, suitable for running from cron
+    Summary {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// How far back to look, e.g. 12h, 7d, 2w
+        #[arg(long, default_value = "7d", value_parser = parse_duration)]
+        since: Duration,
+        /// Maximum password age before it is considered expired
+        #[arg(long, default_value = "365d", value_parser = parse_duration)]
+        max_age: Duration,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+        /// Mail the digest to this address instead of printing it
+        #[arg(long)]
+        mail: Option<String>,
+        /// Command used to deliver mail; receives the message on stdin
+        #[arg(long, default_value = "sendmail -t")]
+        sendmail: String,
+    },
+    /// List the edits saved to a vault's journal, with when each was made and what it changed
+    History {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Show a database file's format version, key derivation parameters and vault ID, without the passkey
+    Info {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+    },
+    /// Check a database file's structure and authenticate it without opening a session
+    Verify {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Salvage what can be read from a damaged database file and report which parts are broken
+    Recover {
+        /// Path to the damaged database file (.fp)
+        file: PathBuf,
+        /// Write the recovered accounts to this new database file
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Import the entries of another database file into this one
+    Import {
+        /// Path to the database file (.fp) to import into
+        #[arg(required = true)]
+        file: Option<PathBuf>,
+        /// File to import from: a database file (.fp), a LastPass or 1Password export, a browser's saved-passwords CSV, or a pass (password-store) directory
+        #[arg(required = true)]
+        source: Option<PathBuf>,
+        /// Format of the file to import from, one of those --list-formats shows [default: detected from its extension and header]
+        #[arg(long)]
+        format: Option<String>,
+        /// List the formats that can be imported, and exit
+        #[arg(long, exclusive = true)]
+        list_formats: bool,
+        /// Remove every existing entry and keep only the imported ones
+        #[arg(long, conflicts_with = "overwrite")]
+        replace: bool,
+        /// Let imported entries replace existing entries with the same ID
+        #[arg(long)]
+        overwrite: bool,
+        /// What to do with imported entries that match an existing one's description, username and URL [default: ask]
+        #[arg(long, value_enum)]
+        on_conflict: Option<OnConflict>,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Add an entry someone shared with you as an encrypted bundle
+    ImportEntry {
+        /// Path to the database file (.fp) to add the entry to
+        file: PathBuf,
+        /// File holding the bundle [default: paste it on standard input]
+        bundle: Option<PathBuf>,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Show entries as QR codes to scan into a vault on another machine, with no network in between
+    QrExport {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// ID of an entry to include; repeat for more [default: every entry]
+        #[arg(long = "entry")]
+        entries: Vec<String>,
+        /// Draw the codes for a terminal with a light background
+        #[arg(long)]
+        light_background: bool,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Add the entries from `ferropass qr-export` to a vault, with their codes scanned or pasted one per line
+    QrImport {
+        /// Path to the database file (.fp) to add the entries to
+        file: PathBuf,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Merge another copy of a vault into this one, asking which version to keep where they differ
+    Merge {
+        /// Path to the database file (.fp) to merge into
+        file: PathBuf,
+        /// Database file (.fp) to merge from; it is left unchanged
+        other: PathBuf,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Sync a vault kept in a git repository: commit, pull, merge both copies where they differ, and push
+    Sync {
+        /// Path to the database file (.fp), inside a git work tree whose branch has an upstream
+        file: PathBuf,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Send a vault to another machine on the local network, protected by a one-time code
+    Send {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// Listen on this TCP port instead of any free one
+        #[arg(long, default_value_t = 0)]
+        port: u16,
+    },
+    /// Receive a vault from `ferropass send` on another machine
+    Receive {
+        /// The code shown by `ferropass send`, such as 17-gravity-uncle; asked for if left out
+        code: Option<String>,
+        /// Connect to the sender at this address and port instead of looking for it
+        #[arg(long)]
+        from: Option<String>,
+        /// Save the vault here instead of under its own name in the current directory
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Export all or some entries to a new database file protected by a separate, one-off password
+    Export {
+        /// Path to the database file (.fp) to export from
+        #[arg(required = true)]
+        file: Option<PathBuf>,
+        /// Database file (.fp) to create, or the plain-text file with --plaintext
+        #[arg(required = true)]
+        output: Option<PathBuf>,
+        /// Only export the account with this ID; repeat to select several
+        #[arg(long = "id")]
+        ids: Vec<String>,
+        /// Only export accounts with this tag; repeat to allow several
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Only export accounts in this group or the groups nested under it; repeat to allow several
+        #[arg(long = "group")]
+        groups: Vec<String>,
+        /// Only export accounts whose username, description, URL, group or tags contain this text
+        #[arg(long)]
+        search: Option<String>,
+        /// Write an UNENCRYPTED file in this format instead, such as csv or json, e.g. to move to another password manager
+        #[arg(long)]
+        plaintext: Option<String>,
+        /// List the plain-text formats that can be exported, and exit
+        #[arg(long, exclusive = true)]
+        list_formats: bool,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Print a recovery kit for a vault: fingerprint, KDF parameters, storage locations and space for the passkey
+    RecoveryKit {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// Write the kit to this file instead of printing it; a name ending in .pdf writes a printable PDF
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Run built-in known-answer tests and report whether this build and platform can be trusted
+    SelfTest,
+    /// Require this machine's pepper file, as well as the passkey, to open a vault
+    Pepper {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// Stop requiring the pepper
+        #[arg(long)]
+        remove: bool,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Split a recovery key into shares, any `threshold` of which can set a new passkey
+    RecoveryShares {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// Number of shares needed to reset the passkey
+        #[arg(long)]
+        threshold: u8,
+        /// Number of shares to make
+        #[arg(long)]
+        shares: u8,
+        /// Write each share to its own file in this directory instead of printing them
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Set a new passkey for a vault using its recovery shares
+    ResetPasskey {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+    },
+    /// Change a vault's passkey
+    ChangePasskey {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// Read the current passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Sign a vault with this machine's Ed25519 key, or sign it again after checking it with the passkey
+    Sign {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// Stop signing the vault
+        #[arg(long)]
+        remove: bool,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// List the backups of a vault, or earlier versions of one in a bucket, and put one back
+    RestoreBackup {
+        /// Path or URL of the database file (.fp)
+        file: PathBuf,
+        /// Only list the backups
+        #[arg(long, conflicts_with = "backup")]
+        list: bool,
+        /// Restore this backup, by its number in the list, instead of asking
+        #[arg(long)]
+        backup: Option<usize>,
+    },
+    /// Report weak, reused and old passwords and accounts without a second factor, with how to fix each
+    Audit {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// Also look each password up in Have I Been Pwned; only the first 5 characters of its SHA-1 hash leave the machine
+        #[arg(long)]
+        hibp: bool,
+        /// Passwords unchanged for longer than this are reported as old
+        #[arg(long, default_value = "365d", value_parser = parse_duration)]
+        max_age: Duration,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Copy one account's password to the clipboard, or print it for another program, without starting a session
+    Get {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// The account's ID, or part of its username or description that no other account shares
+        account: String,
+        /// Print only the password on stdout, for piping into another program
+        #[arg(long)]
+        stdout: bool,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Keep vaults unlocked in a background process, so `get` answers without deriving the key each time
+    #[command(group(ArgGroup::new("control").multiple(false)))]
+    Agent {
+        /// Run in this terminal instead of in the background
+        #[arg(long, group = "control")]
+        foreground: bool,
+        /// Lock a vault again after it goes unused this long, e.g. 30m or 8h; 0 never locks it
+        #[arg(long, default_value = "15m", value_parser = parse_duration, conflicts_with_all = ["status", "stop"])]
+        lock_after: Duration,
+        /// Show whether the agent is running and which vaults it has unlocked
+        #[arg(long, group = "control")]
+        status: bool,
+        /// Stop the agent, locking every vault in it
+        #[arg(long, group = "control")]
+        stop: bool,
+    },
+    /// Unlock a vault in the agent, so its SSH keys are served and `get` doesn't ask for the passkey
+    Unlock {
+        /// Path to the database file (.fp)
+        file: PathBuf,
+        /// Read the passkey from this file instead of prompting
+        #[arg(long)]
+        passkey_file: Option<PathBuf>,
+    },
+    /// Create a token that lets a tool use the agent's JSON-RPC interface, or list them without a name
+    Token {
+        /// What the token is for, e.g. "i3blocks"
+        name: Option<String>,
+        /// Only let the token reach this vault; repeat for more [default: every vault]
+        #[arg(long = "vault", requires = "name", conflicts_with = "revoke")]
+        vaults: Vec<PathBuf>,
+        /// Only let the token call these methods [default: all of them]
+        #[arg(long, value_enum, value_delimiter = ',', requires = "name", conflicts_with = "revoke")]
+        allow: Vec<Method>,
+        /// Revoke the token with this name
+        #[arg(long, requires = "name")]
+        revoke: bool,
+    },
+    /// Lock a vault in the agent, or every vault without a file
+    Lock {
+        /// Path to the database file (.fp)
+        file: Option<PathBuf>,
+    },
+    /// Generate a random password, passphrase or PIN and print or copy it, without opening a vault
+    #[command(name = "gen", group(ArgGroup::new("mode").multiple(false)), group(ArgGroup::new("words_mode").multiple(false)))]
+    Gen {
+        /// Copy the password to the clipboard instead of printing it
+        #[arg(long)]
+        copy: bool,
+        /// Number of characters
+        #[arg(long, default_value_t = DEFAULT_LENGTH, conflicts_with = "mode")]
+        length: usize,
+        /// Leave out uppercase letters
+        #[arg(long, conflicts_with = "mode")]
+        no_uppercase: bool,
+        /// Leave out digits
+        #[arg(long, conflicts_with = "mode")]
+        no_digits: bool,
+        /// Leave out symbols
+        #[arg(long, conflicts_with = "mode")]
+        no_symbols: bool,
+        /// Leave out characters that are easy to confuse, such as 0/O, 1/l/I and quotes
+        #[arg(long, conflicts_with = "mode")]
+        no_ambiguous: bool,
+        /// Characters to leave out, such as ones a site refuses, e.g. --exclude '&% '
+        #[arg(long, default_value = "", conflicts_with = "mode")]
+        exclude: String,
+        /// Minimum number of lowercase letters
+        #[arg(long, default_value_t = 1, conflicts_with = "mode")]
+        min_lowercase: usize,
+        /// Minimum number of uppercase letters
+        #[arg(long, default_value_t = 1, conflicts_with = "mode")]
+        min_uppercase: usize,
+        /// Minimum number of digits
+        #[arg(long, default_value_t = 1, conflicts_with = "mode")]
+        min_digits: usize,
+        /// Minimum number of symbols
+        #[arg(long, default_value_t = 1, conflicts_with = "mode")]
+        min_symbols: usize,
+        /// Make the first character a letter
+        #[arg(long, conflicts_with = "mode")]
+        start_with_letter: bool,
+        /// Allow no character more than this many times in a row
+        #[arg(long, conflicts_with = "mode")]
+        max_repeat: Option<usize>,
+        /// Generate a diceware passphrase instead of a random password
+        #[arg(long, group = "mode", group = "words_mode")]
+        passphrase: bool,
+        /// Generate a memorable password: a few words with random case, look-alike swaps and digits or symbols between them
+        #[arg(long, group = "mode", group = "words_mode")]
+        memorable: bool,
+        /// Generate a pronounceable password made of syllables, for passwords that must be memorized
+        #[arg(long, group = "mode")]
+        pronounceable: bool,
+        /// Number of syllables in the pronounceable password
+        #[arg(long, default_value_t = DEFAULT_SYLLABLES, requires = "pronounceable")]
+        syllables: usize,
+        /// Fill in a pattern such as LLLLdddd-SS: L letter, l lowercase, U uppercase, d digit, S symbol, a letter or digit, * any; \ keeps the next character
+        #[arg(long, group = "mode")]
+        pattern: Option<String>,
+        /// Generate with a preset from the config, such as "wifi-passphrase"
+        #[arg(long, group = "mode")]
+        preset: Option<String>,
+        /// Generate a numeric PIN, never a trivial one such as 0000, 1234 or 1212
+        #[arg(long, group = "mode")]
+        pin: bool,
+        /// Number of digits in the PIN
+        #[arg(long, default_value_t = DEFAULT_PIN_LENGTH, requires = "pin")]
+        pin_length: usize,
+        /// Number of words in the passphrase or memorable password [default: 6 in English, 8 in the other languages, 4 for memorable]
+        #[arg(long, requires = "words_mode")]
+        words: Option<usize>,
+        /// Wordlist to draw the passphrase from
+        #[arg(long, value_enum, default_value = "english", requires = "passphrase")]
+        language: Language,
+        /// Draw the passphrase from a wordlist of your own, <NAME>.txt in the wordlists directory
+        #[arg(long, requires = "passphrase", conflicts_with = "language")]
+        wordlist: Option<String>,
+        /// What goes between the words of the passphrase
+        #[arg(long, value_enum, default_value = "hyphen", requires = "passphrase")]
+        separator: Separator,
+        /// How the words of the passphrase are capitalized
+        #[arg(long, value_enum, default_value = "lower", requires = "passphrase")]
+        capitalization: Capitalization,
+    },
+}
+
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+
+    let amount: u64 = amount.parse()
+        .map_err(|_| format!("Invalid duration '{}', expected e.g. 30s, 15m, 12h, 7d or 2w", input))?;
+
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("Unknown duration unit '{}', expected s, m, h, d or w", unit)),
+    };
+
+    amount.checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Duration '{}' is too long", input))
+}
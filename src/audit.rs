@@ -0,0 +1,186 @@
+use crate::cli::Cli;
+use crate::hibp;
+use crate::login;
+use crate::pager;
+
+use ferropass_core::encryption::load_and_decrypt_database;
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::{current_timestamp, Account};
+use ferropass_core::password::rate_password;
+use ferropass_core::storage::FileStorage;
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::path::Path;
+use std::time::Duration;
+
+// What the audit needs from each password, worked out one password at a time
+// so they are never all open together.
+struct PasswordCheck {
+    score: u8,
+    strong: bool,
+    warning: Option<String>,
+    hash: String,
+    breaches: Option<u64>,
+}
+
+pub fn run(filepath: &Path, hibp: bool, max_age: Duration, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    let mut checker = if hibp { Some(hibp::Checker::new()?) } else { None };
+
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    let (database, entry_key) = load_and_decrypt_database(&FileStorage, filepath, &passkey)?;
+
+    if checker.is_some() {
+        eprintln!(
+            "Checking {} password(s) against Have I Been Pwned; only the first 5 characters of each SHA-1 hash are sent.",
+            database.get_accounts().len()
+        );
+    }
+
+    let mut checks = HashMap::new();
+    for account in database.get_accounts() {
+        let password = entry_key.open(account.get_password())
+            .map_err(|e| e.context(format!("Account {}", account.get_id())))?;
+        let strength = rate_password(&password);
+
+        checks.insert(account.get_id().to_string(), PasswordCheck {
+            score: strength.score,
+            strong: strength.is_strong(),
+            warning: if strength.is_strong() { None } else { strength.warning },
+            hash: format!("{:x}", Sha256::digest(password.as_bytes())),
+            breaches: checker.as_mut().map(|checker| checker.breach_count(&password)).transpose()?,
+        });
+    }
+
+    let accounts: Vec<&Account> = database.get_accounts().iter().collect();
+    let report = build_report(filepath, &accounts, &checks, max_age)
+        .map_err(|e| FerropassError::Io(format!("Error building audit report: {}", e)))?;
+    pager::page("Audit", &report)?;
+
+    let breached = checks.values().filter(|check| check.breaches.unwrap_or(0) > 0).count();
+    if breached > 0 {
+        return Err(FerropassError::Validation(format!("{} compromised password(s) found", breached)));
+    }
+    Ok(())
+}
+
+fn build_report(
+    filepath: &Path,
+    accounts: &[&Account],
+    checks: &HashMap<String, PasswordCheck>,
+    max_age: Duration,
+) -> Result<String, fmt::Error> {
+    let now = current_timestamp();
+    let mut out = String::new();
+    writeln!(out, "=== FerroPass audit of {} ===", filepath.display())?;
+    writeln!(out, "Accounts: {}", accounts.len())?;
+    writeln!(out)?;
+
+    let mut findings = 0;
+
+    if checks.values().any(|check| check.breaches.is_some()) {
+        let breached: Vec<&Account> = accounts.iter()
+            .filter(|acc| checks[acc.get_id()].breaches.unwrap_or(0) > 0)
+            .copied()
+            .collect();
+        findings += breached.len();
+
+        writeln!(out, "Compromised passwords ({}):", breached.len())?;
+        for account in &breached {
+            write_account_line(&mut out, account, &format!("seen {} time(s) in data breaches", checks[account.get_id()].breaches.unwrap_or(0)))?;
+        }
+        if !breached.is_empty() {
+            writeln!(out, "  Fix: change these first. Attackers try breached passwords on every site, however strong they look.")?;
+        }
+        writeln!(out)?;
+    }
+
+    let weak: Vec<&Account> = accounts.iter()
+        .filter(|acc| !checks[acc.get_id()].strong)
+        .copied()
+        .collect();
+    findings += weak.len();
+
+    writeln!(out, "Weak passwords ({}):", weak.len())?;
+    for account in &weak {
+        let check = &checks[account.get_id()];
+        let detail = match &check.warning {
+            Some(warning) => format!("strength {}/4, {}", check.score, warning),
+            None => format!("strength {}/4", check.score),
+        };
+        write_account_line(&mut out, account, &detail)?;
+    }
+    if !weak.is_empty() {
+        writeln!(out, "  Fix: generate a new password from the account menu, a random one or a passphrase.")?;
+    }
+    writeln!(out)?;
+
+    let mut groups: HashMap<&str, Vec<&Account>> = HashMap::new();
+    for account in accounts {
+        groups.entry(checks[account.get_id()].hash.as_str()).or_default().push(account);
+    }
+    let mut reused: Vec<Vec<&Account>> = groups.into_values().filter(|group| group.len() > 1).collect();
+    reused.sort_by_key(|group| group[0].get_id().to_string());
+    findings += reused.iter().map(Vec::len).sum::<usize>();
+
+    writeln!(out, "Reused passwords ({} group(s)):", reused.len())?;
+    for (i, group) in reused.iter().enumerate() {
+        for account in group {
+            write_account_line(&mut out, account, &format!("shared password #{}", i + 1))?;
+        }
+    }
+    if !reused.is_empty() {
+        writeln!(out, "  Fix: give each site its own password, so a breach of one doesn't open the others.")?;
+    }
+    writeln!(out)?;
+
+    let old: Vec<&Account> = accounts.iter()
+        .filter(|acc| acc.get_password_updated_at() != 0)
+        .filter(|acc| acc.get_password_updated_at() + max_age.as_secs() < now)
+        .copied()
+        .collect();
+    findings += old.len();
+
+    writeln!(out, "Old passwords, unchanged for more than {} days ({}):", max_age.as_secs() / 86400, old.len())?;
+    for account in &old {
+        let days = (now - account.get_password_updated_at()) / 86400;
+        write_account_line(&mut out, account, &format!("last changed {} days ago", days))?;
+    }
+    if !old.is_empty() {
+        writeln!(out, "  Fix: change them, starting with email, banking and anything that can reset other accounts.")?;
+    }
+    writeln!(out)?;
+
+    let no_second_factor: Vec<&Account> = accounts.iter()
+        .filter(|acc| acc.get_totp().is_none() && !login::has_second_factor(acc.get_login_procedure()))
+        .copied()
+        .collect();
+    findings += no_second_factor.len();
+
+    writeln!(out, "No second factor recorded ({}):", no_second_factor.len())?;
+    for account in &no_second_factor {
+        write_account_line(&mut out, account, "no TOTP secret, and no login step asks for a code or key")?;
+    }
+    if !no_second_factor.is_empty() {
+        writeln!(out, "  Fix: turn on two-factor authentication at the site where it is offered, then store its TOTP")?;
+        writeln!(out, "  secret with the account or add it to the login procedure, e.g. \"Enter the code from the app\".")?;
+    }
+    writeln!(out)?;
+
+    writeln!(out, "{} finding(s).", findings)?;
+    Ok(out)
+}
+
+fn write_account_line(out: &mut String, account: &Account, detail: &str) -> fmt::Result {
+    let desc = account.get_description()
+        .as_ref()
+        .map_or("", |s| s.as_str());
+
+    writeln!(out, "  {:<10} {:<30} {:<20} {}",
+        account.get_id(),
+        account.get_username_or_email(),
+        desc,
+        detail
+    )
+}
@@ -0,0 +1,167 @@
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::Account;
+use ferropass_core::paranoid;
+
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+// What is typed for accounts without a sequence of their own. A sequence is
+// free text with placeholders:
+//   {username}    the account's username or email
+//   {password}    the account's password
+//   {tab}         the Tab key
+//   {enter}       the Enter key
+//   {delay:500}   a pause of that many milliseconds, e.g. for a page to load
+pub const DEFAULT_SEQUENCE: &str = "{username}{tab}{password}{enter}";
+
+pub enum Step {
+    Text(String),
+    Username,
+    Password,
+    Key(Key),
+    Delay(Duration),
+}
+
+#[derive(Clone, Copy)]
+pub enum Key {
+    Tab,
+    Enter,
+}
+
+// Unlike login steps, an unknown placeholder is refused rather than typed as
+// written, since a typo would otherwise end up in a login form.
+pub fn parse(sequence: &str) -> Result<Vec<Step>, FerropassError> {
+    let mut steps = Vec::new();
+    let mut rest = sequence;
+
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            return Err(FerropassError::Format(format!("Unclosed placeholder in '{}'", &rest[start..])));
+        };
+        let placeholder = &rest[start + 1..start + len];
+
+        if start > 0 {
+            steps.push(Step::Text(rest[..start].to_string()));
+        }
+        steps.push(match placeholder {
+            "username" => Step::Username,
+            "password" => Step::Password,
+            "tab" => Step::Key(Key::Tab),
+            "enter" => Step::Key(Key::Enter),
+            _ => match placeholder.strip_prefix("delay:").map(str::parse) {
+                Some(Ok(millis)) => Step::Delay(Duration::from_millis(millis)),
+                _ => return Err(FerropassError::Format(format!("Unknown auto-type placeholder {{{}}}", placeholder))),
+            },
+        });
+        rest = &rest[start + len + 1..];
+    }
+
+    if !rest.is_empty() {
+        steps.push(Step::Text(rest.to_string()));
+    }
+    Ok(steps)
+}
+
+// The tool keystrokes are sent through. Text goes to it on stdin, so the
+// password never shows up in a process list.
+#[derive(Clone, Copy)]
+enum Typist {
+    Xdotool,
+    Wtype,
+    Osascript,
+}
+
+impl Typist {
+    fn detect() -> Result<Typist, FerropassError> {
+        if cfg!(target_os = "macos") {
+            Ok(Typist::Osascript)
+        } else if env::var_os("WAYLAND_DISPLAY").is_some() {
+            Ok(Typist::Wtype)
+        } else if env::var_os("DISPLAY").is_some() {
+            Ok(Typist::Xdotool)
+        } else {
+            Err(FerropassError::Io("Auto-type needs a desktop session: xdotool on X11, wtype on Wayland or macOS".to_string()))
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Typist::Xdotool => "xdotool",
+            Typist::Wtype => "wtype",
+            Typist::Osascript => "osascript",
+        }
+    }
+
+    fn type_text(self, text: &str) -> Result<(), FerropassError> {
+        match self {
+            Typist::Xdotool => self.run(&["type", "--clearmodifiers", "--file", "-"], text),
+            Typist::Wtype => self.run(&["-"], text),
+            Typist::Osascript => self.run(&[], &format!(
+                "tell application \"System Events\" to keystroke \"{}\"",
+                text.replace('\\', "\\\\").replace('"', "\\\"")
+            )),
+        }
+    }
+
+    fn press(self, key: Key) -> Result<(), FerropassError> {
+        match (self, key) {
+            (Typist::Xdotool, Key::Tab) => self.run(&["key", "--clearmodifiers", "Tab"], ""),
+            (Typist::Xdotool, Key::Enter) => self.run(&["key", "--clearmodifiers", "Return"], ""),
+            (Typist::Wtype, Key::Tab) => self.run(&["-k", "Tab"], ""),
+            (Typist::Wtype, Key::Enter) => self.run(&["-k", "Return"], ""),
+            (Typist::Osascript, Key::Tab) => self.run(&[], "tell application \"System Events\" to key code 48"),
+            (Typist::Osascript, Key::Enter) => self.run(&[], "tell application \"System Events\" to key code 36"),
+        }
+    }
+
+    fn run(self, args: &[&str], input: &str) -> Result<(), FerropassError> {
+        let mut child = Command::new(self.program())
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| FerropassError::Io(format!("Failed to run {} for auto-type: {}", self.program(), e)))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input.as_bytes())
+                .map_err(|e| FerropassError::Io(format!("Failed to send keystrokes to {}: {}", self.program(), e)))?;
+        }
+
+        let status = child.wait().map_err(|e| FerropassError::Io(format!("Failed to run {}: {}", self.program(), e)))?;
+        if !status.success() {
+            return Err(FerropassError::Io(format!("{} failed to type ({})", self.program(), status)));
+        }
+        Ok(())
+    }
+}
+
+// Types the account's sequence into whichever window has the focus once
+// `delay` has passed, leaving time to switch back to the login form. The
+// password is only opened when the sequence reaches it.
+pub fn run(
+    account: &Account,
+    open_password: &dyn Fn() -> Result<String, FerropassError>,
+    delay: Duration,
+) -> Result<(), FerropassError> {
+    paranoid::ensure_allowed("Auto-type")?;
+
+    let steps = parse(account.get_autotype().unwrap_or(DEFAULT_SEQUENCE))?;
+    let typist = Typist::detect()?;
+
+    thread::sleep(delay);
+
+    for step in steps {
+        match step {
+            Step::Text(text) => typist.type_text(&text)?,
+            Step::Username => typist.type_text(account.get_username_or_email())?,
+            Step::Password => typist.type_text(&open_password()?)?,
+            Step::Key(key) => typist.press(key)?,
+            Step::Delay(pause) => thread::sleep(pause),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use crate::cli::Cli;
+
+use ferropass_core::error::FerropassError;
+use ferropass_core::storage::{FileStorage, VaultStorage};
+use ferropass_core::vault::Vault;
+
+use std::path::Path;
+use std::sync::Arc;
+
+// Wraps the vault's data key under a new passkey. The encrypted accounts
+// are left as they are.
+pub fn run(filepath: &Path, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    let passkey = Cli::read_passkey(passkey_file, "Enter current passkey: ")?;
+    let mut vault = Vault::open(Arc::new(FileStorage), filepath, &passkey)?;
+
+    let new_passkey = Cli::prompt_for_valid_passkey("Enter a new passkey")?;
+    if new_passkey == passkey {
+        return Err(FerropassError::Validation("The new passkey is the same as the current one".to_string()));
+    }
+
+    let backup = FileStorage.snapshot(filepath)?;
+    vault.change_passkey(&new_passkey)?;
+
+    println!("The passkey for {} was changed.", filepath.display());
+    println!("The previous file, kept at {}, still opens with the old passkey; delete it if that passkey may be known to others.", backup.display());
+    Ok(())
+}
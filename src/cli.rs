@@ -1,671 +1,1744 @@
-use crate::clipboard::copy_to_clipboard;
-use crate::encryption::{encrypt_and_save_database, load_and_decrypt_database};
-use crate::models::{Account, Database};
-use crate::password::{generate_random_password, is_password_valid};
-
-use std::io::{self, Write};
-use std::path::PathBuf;
-use rpassword::read_password;
-use crossterm::{
-    execute,
-    terminal::{Clear, ClearType},
-};
-
-pub struct CLI {
-    current_database_path: Option<PathBuf>,
-    current_database: Option<Database>,
-}
-
-impl CLI {
-    pub fn new() -> Self {
-        CLI {
-            current_database_path: None,
-            current_database: None,
-        }
-    }
-    
-    pub fn clear_screen() -> Result<(), String> {
-        if let Err(e) = execute!(io::stdout(), Clear(ClearType::All)) {
-            return Err(format!("Failed to clear screen: {}", e));
-        }
-        Ok(())
-    }
-    
-    pub fn prompt_input(prompt: &str) -> Result<String, String> {
-        print!("{}", prompt);
-        io::stdout().flush().map_err(|e| format!("Failed to flush stdout: {}", e))?;
-        
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).map_err(|e| format!("Failed to read input: {}", e))?;
-        
-        Ok(input.trim().to_string())
-    }
-    
-    pub fn prompt_password(prompt: &str) -> Result<String, String> {
-        print!("{}", prompt);
-        io::stdout().flush().map_err(|e| format!("Failed to flush stdout: {}", e))?;
-        
-        read_password().map_err(|e| format!("Failed to read password: {}", e))
-    }
-    
-    pub fn run(&mut self) -> Result<(), String> {
-        Self::clear_screen()?;
-        
-        loop {
-            println!("=== FP Password Manager ===");
-            println!("1. Create a new password database");
-            println!("2. Open an existing password database");
-            println!("3. Exit");
-            
-            let choice = Self::prompt_input("Enter your choice (1-3): ")?;
-            
-            match choice.as_str() {
-                "1" => self.create_new_database()?,
-                "2" => self.open_existing_database()?,
-                "3" => break,
-                _ => {
-                    println!("Invalid choice, please try again.");
-                    continue;
-                }
-            }
-            
-            if self.current_database.is_some() {
-                self.database_menu()?;
-            }
-        }
-        
-        Ok(())
-    }
-    
-    fn create_new_database(&mut self) -> Result<(), String> {
-        Self::clear_screen()?;
-        println!("=== Create New Database ===");
-        
-        let db_name = Self::prompt_input("Enter database name (without extension): ")?;
-        let mut filepath = PathBuf::from(&db_name);
-        filepath.set_extension("fp");
-        
-        if filepath.exists() {
-            println!("A database with this name already exists. Please choose a different name.");
-            return Ok(());
-        }
-        
-        let passkey = self.prompt_for_valid_passkey()?;
-        
-        let database = Database::new();
-        
-        encrypt_and_save_database(&database, &filepath, &passkey)?;
-        
-        println!("Database created successfully!");
-        
-        self.current_database_path = Some(filepath);
-        self.current_database = Some(database);
-        
-        Ok(())
-    }
-    
-    fn open_existing_database(&mut self) -> Result<(), String> {
-        Self::clear_screen()?;
-        println!("=== Open Existing Database ===");
-        
-        let filepath_str = Self::prompt_input("Enter absolute path to database file (.fp): ")?;
-        let filepath = PathBuf::from(filepath_str);
-        
-        if !filepath.exists() {
-            println!("File not found. Please check the path and try again.");
-            Self::prompt_input("Press Enter to continue...")?;
-            return Ok(());
-        }
-        
-        let passkey = Self::prompt_password("Enter database passkey: ")?;
-        
-        if passkey.is_empty() {
-            println!("Passkey cannot be empty.");
-            Self::prompt_input("Press Enter to continue...")?;
-            return Ok(());
-        }
-        
-        match load_and_decrypt_database(&filepath, &passkey) {
-            Ok(database) => {
-                println!("Database loaded successfully!");
-                self.current_database_path = Some(filepath);
-                self.current_database = Some(database);
-            },
-            Err(e) => {
-                println!("Failed to open database: {}", e);
-                Self::prompt_input("Press Enter to continue...")?;
-            }
-        }
-        
-        Ok(())
-    }
-    
-    fn database_menu(&mut self) -> Result<(), String> {
-        loop {
-            Self::clear_screen()?;
-            
-            println!("=== Database Menu ===");
-            println!("Database: {:?}", self.current_database_path.as_ref().unwrap());
-            println!("1. List accounts");
-            println!("2. View/Edit account");
-            println!("3. Add new account");
-            println!("4. Delete account");
-            println!("5. Return to main menu");
-            
-            let choice = Self::prompt_input("Enter your choice (1-5): ")?;
-            
-            match choice.as_str() {
-                "1" => self.list_accounts()?,
-                "2" => self.view_edit_account()?,
-                "3" => self.add_account()?,
-                "4" => self.delete_account()?,
-                "5" => break,
-                _ => {
-                    println!("Invalid choice, please try again.");
-                    continue;
-                }
-            }
-            
-        }
-        
-        Ok(())
-    }
-    
-    fn list_accounts(&self) -> Result<(), String> {
-        Self::clear_screen()?;
-        println!("=== Account List ===");
-        
-        if let Some(db) = &self.current_database {
-            let accounts = db.get_accounts();
-            
-            if accounts.is_empty() {
-                println!("No accounts found in the database.");
-            } else {
-                println!("{:<10} {:<30} {:<20}", "ID", "Username/Email", "Description");
-                println!("{:-<60}", "");
-                
-                for account in accounts {
-                    let desc = account.get_description()
-                        .as_ref()
-                        .map_or("", |s| s.as_str());
-                    
-                    println!("{:<10} {:<30} {:<20}", 
-                        account.get_id(),
-                        account.get_username_or_email(),
-                        desc
-                    );
-                }
-            }
-        } else {
-            println!("No database loaded.");
-        }
-        
-        Self::prompt_input("Press Enter to continue...")?;
-        Ok(())
-    }
-    
-    fn view_edit_account(&mut self) -> Result<(), String> {
-        Self::clear_screen()?;
-        println!("=== View/Edit Account ===");
-        
-        if let Some(db) = &self.current_database {
-            let accounts = db.get_accounts();
-            
-            if accounts.is_empty() {
-                println!("No accounts found in the database.");
-                Self::prompt_input("Press Enter to continue...")?;
-                return Ok(());
-            } else {
-                println!("{:<10} {:<30} {:<20}", "ID", "Username/Email", "Description");
-                println!("{:-<60}", "");
-                
-                for account in accounts {
-                    let desc = account.get_description()
-                        .as_ref()
-                        .map_or("", |s| s.as_str());
-                    
-                    println!("{:<10} {:<30} {:<20}", 
-                        account.get_id(),
-                        account.get_username_or_email(),
-                        desc
-                    );
-                }
-                println!();
-            }
-        } else {
-            println!("No database loaded.");
-            Self::prompt_input("Press Enter to continue...")?;
-            return Ok(());
-        }
-        
-        let account_id = Self::prompt_input("Enter account ID: ")?;
-        
-        if let Some(db) = &self.current_database {
-            if let Some(_account) = db.get_account_by_id(&account_id) {
-                self.account_menu(&account_id)?;
-            } else {
-                println!("Account not found.");
-                Self::prompt_input("Press Enter to continue...")?;
-            }
-        } else {
-            println!("No database loaded.");
-            Self::prompt_input("Press Enter to continue...")?;
-        }
-        
-        Ok(())
-    }
-    
-    fn account_menu(&mut self, account_id: &str) -> Result<(), String> {
-        loop {
-            Self::clear_screen()?;
-            
-            let account = if let Some(db) = &self.current_database {
-                if let Some(acc) = db.get_account_by_id(account_id) {
-                    acc.clone()
-                } else {
-                    println!("Account not found.");
-                    return Ok(());
-                }
-            } else {
-                println!("No database loaded.");
-                return Ok(());
-            };
-            
-            println!("=== Account Details ===");
-            println!("ID: {}", account.get_id());
-            println!("Username/Email: {}", account.get_username_or_email());
-            println!("Description: {}", account.get_description().as_ref().map_or("", |s| s.as_str()));
-            println!("Password: [HIDDEN]");
-            println!();
-            println!("1. Edit account information");
-            println!("2. Copy password to clipboard");
-            println!("3. Generate new password");
-            println!("4. Return to database menu");
-            
-            let choice = Self::prompt_input("Enter your choice (1-4): ")?;
-            
-            match choice.as_str() {
-                "1" => self.edit_account(account_id)?,
-                "2" => self.copy_password(account_id)?,
-                "3" => self.generate_new_password(account_id)?,
-                "4" => break,
-                _ => {
-                    println!("Invalid choice, please try again.");
-                    continue;
-                }
-            }
-        }
-        
-        Ok(())
-    }
-    
-    fn edit_account(&mut self, account_id: &str) -> Result<(), String> {
-        Self::clear_screen()?;
-        println!("=== Edit Account ===");
-        
-        let passkey = Self::prompt_password("Enter database passkey: ")?;
-        
-        if passkey.is_empty() {
-            println!("Passkey cannot be empty.");
-            Self::prompt_input("Press Enter to continue...")?;
-            return Ok(());
-        }
-        
-        if let Some(path) = &self.current_database_path {
-            if load_and_decrypt_database(path, &passkey).is_err() {
-                println!("Invalid passkey. Changes not made.");
-                Self::prompt_input("Press Enter to continue...")?;
-                return Ok(());
-            }
-            
-            if let Some(db) = &mut self.current_database {
-                if let Some(account) = db.get_account_by_id_mut(account_id) {
-                    println!("Current Username/Email: {}", account.get_username_or_email());
-                    let new_username = Self::prompt_input("Enter new Username/Email (leave empty to keep current): ")?;
-                    
-                    if !new_username.is_empty() {
-                        account.set_username_or_email(new_username);
-                    }
-                    
-                    let current_desc = account.get_description().as_ref().map_or("", |s| s.as_str());
-                    println!("Current Description: {}", current_desc);
-                    let new_desc = Self::prompt_input("Enter new Description (leave empty to keep current): ")?;
-                    
-                    if !new_desc.is_empty() {
-                        account.set_description(Some(new_desc));
-                    } else if new_desc.is_empty() && !current_desc.is_empty() {
-                        let keep_desc = Self::prompt_input("Do you want to keep the current description? (y/n): ")?;
-                        if keep_desc.to_lowercase() == "n" {
-                            account.set_description(None);
-                        }
-                    }
-                    
-                    println!("Edit password? (y/n): ");
-                    let edit_password = Self::prompt_input("")?;
-                    
-                    if edit_password.to_lowercase() == "y" {
-                        let password_action = Self::prompt_input("Do you want to (1) enter a new password or (2) generate a random one? (1/2): ")?;
-                        
-                        if password_action == "1" {
-                            let mut valid_password = false;
-                            let mut new_password = String::new();
-                            
-                            while !valid_password {
-                                new_password = Self::prompt_password("Enter new password: ")?;
-                                
-                                if is_password_valid(&new_password) {
-                                    valid_password = true;
-                                } else {
-                                    println!("Password must be at least 15 characters, contain at least one uppercase letter, one lowercase letter, one number, and one special character.");
-                                }
-                            }
-                            
-                            account.set_password(new_password);
-                            println!("Password updated successfully!");
-                        } else if password_action == "2" {
-                            let new_password = generate_random_password();
-                            account.set_password(new_password.clone());
-                            println!("Generated password: {}", new_password);
-                            println!("Password updated successfully!");
-                        } else {
-                            println!("Invalid choice, password not updated.");
-                        }
-                    }
-                    
-                    println!("Account updated successfully!");
-                    
-                    encrypt_and_save_database(db, path, &passkey)?;
-                    println!("Changes saved successfully!");
-                } else {
-                    println!("Account not found.");
-                }
-            } else {
-                println!("No database loaded.");
-            }
-        } else {
-            println!("No database loaded.");
-        }
-        
-        Self::prompt_input("Press Enter to continue...")?;
-        Ok(())
-    }
-    
-    fn copy_password(&self, account_id: &str) -> Result<(), String> {
-        Self::clear_screen()?;
-        println!("=== Copy Password ===");
-        
-        let passkey = Self::prompt_password("Enter database passkey: ")?;
-        
-        if passkey.is_empty() {
-            println!("Passkey cannot be empty.");
-            Self::prompt_input("Press Enter to continue...")?;
-            return Ok(());
-        }
-        
-        if let Some(path) = &self.current_database_path {
-            if load_and_decrypt_database(path, &passkey).is_err() {
-                println!("Invalid passkey. Password not copied.");
-                Self::prompt_input("Press Enter to continue...")?;
-                return Ok(());
-            }
-            
-            if let Some(db) = &self.current_database {
-                if let Some(account) = db.get_account_by_id(account_id) {
-                    copy_to_clipboard(account.get_password())?;
-                    println!("Password copied to clipboard!");
-                } else {
-                    println!("Account not found.");
-                }
-            } else {
-                println!("No database loaded.");
-            }
-        } else {
-            println!("No database loaded.");
-        }
-        
-        Self::prompt_input("Press Enter to continue...")?;
-        Ok(())
-    }
-    
-    fn generate_new_password(&mut self, account_id: &str) -> Result<(), String> {
-        Self::clear_screen()?;
-        println!("=== Generate New Password ===");
-        
-        let passkey = Self::prompt_password("Enter database passkey: ")?;
-        
-        if passkey.is_empty() {
-            println!("Passkey cannot be empty.");
-            Self::prompt_input("Press Enter to continue...")?;
-            return Ok(());
-        }
-        
-        if let Some(path) = &self.current_database_path {
-            if load_and_decrypt_database(path, &passkey).is_err() {
-                println!("Invalid passkey. Password not generated.");
-                Self::prompt_input("Press Enter to continue...")?;
-                return Ok(());
-            }
-            
-            if let Some(db) = &mut self.current_database {
-                if let Some(account) = db.get_account_by_id_mut(account_id) {
-                    let new_password = generate_random_password();
-                    
-                    println!("Generated password: {}", new_password);
-                    let confirm = Self::prompt_input("Do you want to set this as the new password? (y/n): ")?;
-                    
-                    if confirm.to_lowercase() == "y" {
-                        account.set_password(new_password);
-                        println!("Password updated successfully!");
-                        
-                        encrypt_and_save_database(db, path, &passkey)?;
-                        println!("Changes saved successfully!");
-                    } else {
-                        println!("Password not updated.");
-                    }
-                } else {
-                    println!("Account not found.");
-                }
-            } else {
-                println!("No database loaded.");
-            }
-        } else {
-            println!("No database loaded.");
-        }
-        
-        Self::prompt_input("Press Enter to continue...")?;
-        Ok(())
-    }
-    
-    fn add_account(&mut self) -> Result<(), String> {
-        Self::clear_screen()?;
-        println!("=== Add New Account ===");
-        
-        let username = Self::prompt_input("Enter Username/Email: ")?;
-        
-        if username.is_empty() {
-            println!("Username/Email cannot be empty.");
-            Self::prompt_input("Press Enter to continue...")?;
-            return Ok(());
-        }
-        
-        let description = Self::prompt_input("Enter Description (optional): ")?;
-        let description = if description.is_empty() { None } else { Some(description) };
-        
-        let password_choice = Self::prompt_input("Do you want to (1) enter your own password or (2) generate a random one? (1/2): ")?;
-        
-        let password = if password_choice == "1" {
-            let mut valid_password = false;
-            let mut pwd = String::new();
-            
-            while !valid_password {
-                pwd = Self::prompt_password("Enter password: ")?;
-                
-                if is_password_valid(&pwd) {
-                    valid_password = true;
-                } else {
-                    println!("Password must be at least 15 characters, contain at least one uppercase letter, one lowercase letter, one number, and one special character.");
-                }
-            }
-            
-            pwd
-        } else if password_choice == "2" {
-            let pwd = generate_random_password();
-            println!("Generated password: {}", pwd);
-            pwd
-        } else {
-            println!("Invalid choice. Using a generated password.");
-            let pwd = generate_random_password();
-            println!("Generated password: {}", pwd);
-            pwd
-        };
-        
-        if let Some(db) = &mut self.current_database {
-            if let Some(path) = &self.current_database_path {
-                let passkey = Self::prompt_password("Enter database passkey to save changes: ")?;
-                
-                if passkey.is_empty() {
-                    println!("Passkey cannot be empty.");
-                    Self::prompt_input("Press Enter to continue...")?;
-                    return Ok(());
-                }
-                
-                if load_and_decrypt_database(path, &passkey).is_err() {
-                    println!("Invalid passkey. Account not created.");
-                    Self::prompt_input("Press Enter to continue...")?;
-                    return Ok(());
-                }
-                
-                let account = Account::new(username, description, password);
-                db.add_account(account);
-                
-                encrypt_and_save_database(db, path, &passkey)?;
-                println!("Account added successfully!");
-                println!("Changes saved successfully!");
-            } else {
-                println!("No database path found.");
-            }
-        } else {
-            println!("No database loaded.");
-        }
-        
-        Self::prompt_input("Press Enter to continue...")?;
-        Ok(())
-    }
-    
-    fn delete_account(&mut self) -> Result<(), String> {
-        Self::clear_screen()?;
-        println!("=== Delete Account ===");
-        
-        if let Some(db) = &self.current_database {
-            let accounts = db.get_accounts();
-            
-            if accounts.is_empty() {
-                println!("No accounts found in the database.");
-                Self::prompt_input("Press Enter to continue...")?;
-                return Ok(());
-            } else {
-                println!("{:<10} {:<30} {:<20}", "ID", "Username/Email", "Description");
-                println!("{:-<60}", "");
-                
-                for account in accounts {
-                    let desc = account.get_description()
-                        .as_ref()
-                        .map_or("", |s| s.as_str());
-                    
-                    println!("{:<10} {:<30} {:<20}", 
-                        account.get_id(),
-                        account.get_username_or_email(),
-                        desc
-                    );
-                }
-                println!();
-            }
-        } else {
-            println!("No database loaded.");
-            Self::prompt_input("Press Enter to continue...")?;
-            return Ok(());
-        }
-        
-        let account_id = Self::prompt_input("Enter account ID to delete: ")?;
-        
-        if let Some(db) = &self.current_database {
-            if db.get_account_by_id(&account_id).is_none() {
-                println!("Account not found.");
-                Self::prompt_input("Press Enter to continue...")?;
-                return Ok(());
-            }
-        } else {
-            println!("No database loaded.");
-            Self::prompt_input("Press Enter to continue...")?;
-            return Ok(());
-        }
-        
-        let passkey = Self::prompt_password("Enter database passkey: ")?;
-        
-        if passkey.is_empty() {
-            println!("Passkey cannot be empty.");
-            Self::prompt_input("Press Enter to continue...")?;
-            return Ok(());
-        }
-        
-        if let Some(path) = &self.current_database_path {
-            if load_and_decrypt_database(path, &passkey).is_err() {
-                println!("Invalid passkey. Deletion cancelled.");
-                Self::prompt_input("Press Enter to continue...")?;
-                return Ok(());
-            }
-            
-            let confirm = Self::prompt_input("Are you sure you want to delete this account? (y/n): ")?;
-            
-            if confirm.to_lowercase() == "y" {
-                if let Some(db) = &mut self.current_database {
-                    if db.remove_account(&account_id) {
-                        println!("Account deleted successfully!");
-                        
-                        encrypt_and_save_database(db, path, &passkey)?;
-                        println!("Changes saved successfully!");
-                    } else {
-                        println!("Account not found.");
-                    }
-                } else {
-                    println!("No database loaded.");
-                }
-            } else {
-                println!("Deletion cancelled.");
-            }
-        } else {
-            println!("No database loaded.");
-        }
-        
-        Self::prompt_input("Press Enter to continue...")?;
-        Ok(())
-    }
-    
-    fn prompt_for_valid_passkey(&self) -> Result<String, String> {
-        loop {
-            let passkey = Self::prompt_password("Enter database passkey (min. 15 chars, must include uppercase, lowercase, number, and special character): ")?;
-            
-            if passkey.is_empty() {
-                println!("Passkey cannot be empty.");
-                continue;
-            }
-            
-            if is_password_valid(&passkey) {
-                let confirm_passkey = Self::prompt_password("Confirm passkey: ")?;
-                
-                if confirm_passkey.is_empty() {
-                    println!("Confirmation passkey cannot be empty.");
-                    continue;
-                }
-                
-                if passkey == confirm_passkey {
-                    return Ok(passkey);
-                } else {
-                    println!("Passkeys do not match. Please try again.");
-                }
-            } else {
-                println!("Invalid passkey. It must be at least 15 characters, and contain at least one uppercase letter, one lowercase letter, one number, and one special character.");
-            }
-        }
-    }
-}
\ No newline at end of file
+use crate::autotype;
+use crate::clipboard::{self, copy_to_clipboard};
+use crate::fingerprint;
+use crate::login;
+use crate::pager;
+use crate::phonetic;
+use crate::save_queue::SaveQueue;
+use crate::share;
+use crate::ssh_agent::SshKey;
+use crate::terminal;
+use crate::username;
+
+use ferropass_core::config;
+use ferropass_core::encryption::{encrypt_and_save_database, rewrap_and_save, unlock_database, upgrade_reason, EncryptedData, SessionKey, CURRENT_FORMAT_VERSION};
+use ferropass_core::error::FerropassError;
+use ferropass_core::memlock;
+use ferropass_core::models::{current_timestamp, parse_tags, Account, Database};
+use ferropass_core::query::SortBy;
+use ferropass_core::storage::{RoutedStorage, VaultLock, VaultStorage};
+use ferropass_core::totp::Totp;
+use ferropass_core::paranoid;
+use ferropass_core::password::{
+    generate_from_pattern, generate_memorable, generate_passphrase, generate_pin, generate_pronounceable, generate_random_password, rate_password,
+    Capitalization, Generated, Language, PasswordOptions, PasswordStrength, Separator, Wordlist,
+    DEFAULT_LENGTH, DEFAULT_MEMORABLE_WORDS, DEFAULT_PIN_LENGTH, DEFAULT_SYLLABLES, MAX_SYLLABLES, MAX_WORDS, PATTERN_HELP,
+};
+
+use std::io::{self, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use rpassword::read_password;
+
+pub struct Cli {
+    current_database_path: Option<PathBuf>,
+    current_database: Option<Database>,
+    session_key: Option<SessionKey>,
+    vault_lock: Option<VaultLock>,
+    storage: Arc<dyn VaultStorage>,
+    save_queue: SaveQueue,
+}
+
+impl Cli {
+    pub fn new(storage: Arc<dyn VaultStorage>) -> Self {
+        Cli {
+            current_database_path: None,
+            current_database: None,
+            session_key: None,
+            vault_lock: None,
+            save_queue: SaveQueue::new(Arc::clone(&storage)),
+            storage,
+        }
+    }
+    
+    pub fn clear_screen() {
+        terminal::clear_screen();
+    }
+    
+    pub fn prompt_input(prompt: &str) -> Result<String, FerropassError> {
+        print!("{}", prompt);
+        io::stdout().flush().map_err(|e| FerropassError::Io(format!("Failed to flush stdout: {}", e)))?;
+        
+        let input = terminal::read_line().map_err(|e| FerropassError::Io(format!("Failed to read input: {}", e)))?;
+        
+        Ok(input.trim().to_string())
+    }
+    
+    pub fn prompt_password(prompt: &str) -> Result<String, FerropassError> {
+        print!("{}", prompt);
+        io::stdout().flush().map_err(|e| FerropassError::Io(format!("Failed to flush stdout: {}", e)))?;
+        
+        terminal::wait_for_line()
+            .and_then(|()| read_password())
+            .map_err(|e| FerropassError::Io(format!("Failed to read password: {}", e)))
+    }
+    
+    // The storage vaults are opened through, asking for WebDAV passwords on
+    // the terminal.
+    pub fn routed_storage() -> RoutedStorage {
+        RoutedStorage::new().with_password_prompt(|user, host| {
+            rpassword::prompt_password(format!("Password for {} on {}: ", user, host))
+                .map_err(|e| FerropassError::Io(format!("Failed to read password: {}", e)))
+        })
+    }
+    
+    pub fn prompt_multiline(prompt: &str) -> Result<String, FerropassError> {
+        println!("{}", prompt);
+        println!("(Finish with a line containing only '.')");
+        
+        let mut lines = Vec::new();
+        loop {
+            let read = terminal::read_line().map_err(|e| FerropassError::Io(format!("Failed to read input: {}", e)))?;
+            let line = read.trim_end_matches(['\r', '\n']);
+            
+            if read.is_empty() || line == "." {
+                break;
+            }
+            lines.push(line.to_string());
+        }
+        
+        Ok(lines.join("\n"))
+    }
+    
+    fn prompt_choice<T: Copy>(prompt: &str, options: &[T], name: fn(&T) -> &'static str) -> Result<T, FerropassError> {
+        println!("{}", prompt);
+        for (i, option) in options.iter().enumerate() {
+            println!("{}. {}", i + 1, name(option));
+        }
+        
+        let choice = Self::prompt_input("Enter your choice (default 1): ")?;
+        match choice.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= options.len() => Ok(options[n - 1]),
+            _ => Ok(options[0]),
+        }
+    }
+    
+    // Lists of your own come after the embedded ones.
+    fn prompt_wordlist() -> Result<Wordlist, FerropassError> {
+        let custom = Wordlist::custom_names();
+        println!("Wordlist:");
+        for (i, language) in Language::ALL.iter().enumerate() {
+            println!("{}. {}", i + 1, language.name());
+        }
+        for (i, name) in custom.iter().enumerate() {
+            println!("{}. {} (your own)", Language::ALL.len() + i + 1, name);
+        }
+        
+        loop {
+            let choice = Self::prompt_input("Enter your choice (default 1): ")?;
+            let index = if choice.is_empty() { Some(0) } else { choice.parse::<usize>().ok().and_then(|n| n.checked_sub(1)) };
+            
+            match index {
+                Some(i) if i < Language::ALL.len() => return Ok(Wordlist::builtin(Language::ALL[i])),
+                Some(i) if i < Language::ALL.len() + custom.len() => match Wordlist::load(&custom[i - Language::ALL.len()]) {
+                    Ok(wordlist) => return Ok(wordlist),
+                    Err(e) => println!("{}.", e),
+                },
+                _ => println!("Invalid choice, please try again."),
+            }
+        }
+    }
+    
+    fn prompt_passphrase() -> Result<Generated, FerropassError> {
+        let wordlist = Self::prompt_wordlist()?;
+        let word_count = loop {
+            let count = Self::prompt_input(&format!("Number of words (default {}): ", wordlist.default_word_count()))?;
+            if count.is_empty() {
+                break wordlist.default_word_count();
+            }
+            match count.parse::<usize>() {
+                Ok(count) if (1..=MAX_WORDS).contains(&count) => break count,
+                _ => println!("Please enter a number of words between 1 and {}.", MAX_WORDS),
+            }
+        };
+        let separator = Self::prompt_choice("Separator between words:", &Separator::ALL, Separator::name)?;
+        let capitalization = Self::prompt_choice("Capitalization:", &Capitalization::ALL, Capitalization::name)?;
+        
+        generate_passphrase(&wordlist, word_count, separator, capitalization)
+    }
+    
+    fn prompt_memorable() -> Result<Generated, FerropassError> {
+        let word_count = loop {
+            let count = Self::prompt_input(&format!("Number of words (default {}): ", DEFAULT_MEMORABLE_WORDS))?;
+            if count.is_empty() {
+                break DEFAULT_MEMORABLE_WORDS;
+            }
+            match count.parse::<usize>() {
+                Ok(count) if (1..=MAX_WORDS).contains(&count) => break count,
+                _ => println!("Please enter a number of words between 1 and {}.", MAX_WORDS),
+            }
+        };
+        
+        generate_memorable(word_count)
+    }
+    
+    fn prompt_pronounceable() -> Result<Generated, FerropassError> {
+        let syllables = loop {
+            let count = Self::prompt_input(&format!("Number of syllables (default {}): ", DEFAULT_SYLLABLES))?;
+            if count.is_empty() {
+                break DEFAULT_SYLLABLES;
+            }
+            match count.parse::<usize>() {
+                Ok(count) if (1..=MAX_SYLLABLES).contains(&count) => break count,
+                _ => println!("Please enter a number of syllables between 1 and {}.", MAX_SYLLABLES),
+            }
+        };
+        
+        generate_pronounceable(syllables)
+    }
+    
+    fn prompt_pattern() -> Result<Generated, FerropassError> {
+        println!("Pattern: {}.", PATTERN_HELP);
+        loop {
+            let pattern = Self::prompt_input("Enter a pattern, e.g. LLLLdddd-SS: ")?;
+            match generate_from_pattern(&pattern) {
+                Ok(password) => return Ok(password),
+                Err(e) => println!("{}.", e),
+            }
+        }
+    }
+    
+    fn prompt_pin() -> Result<Generated, FerropassError> {
+        loop {
+            let length = Self::prompt_input(&format!("Number of digits (default {}): ", DEFAULT_PIN_LENGTH))?;
+            let length = if length.is_empty() {
+                DEFAULT_PIN_LENGTH
+            } else {
+                match length.parse() {
+                    Ok(length) => length,
+                    Err(_) => {
+                        println!("Invalid length, please enter a number.");
+                        continue;
+                    }
+                }
+            };
+            
+            match generate_pin(length) {
+                Ok(generated) => return Ok(generated),
+                Err(e) => println!("{}.", e),
+            }
+        }
+    }
+    
+    // With an account's policy, a random password follows it without asking.
+    fn prompt_generated_password(policy: Option<&PasswordOptions>) -> Result<String, FerropassError> {
+        println!("Generate:");
+        match policy {
+            Some(policy) => println!("1. A random password following the account's policy ({})", policy.describe()),
+            None => println!("1. A random password"),
+        }
+        println!("2. A passphrase");
+        println!("3. A pronounceable password");
+        println!("4. A password following a pattern");
+        println!("5. A numeric PIN");
+        println!("6. A memorable password of a few words with swapped letters");
+        let has_presets = !config::current().presets().is_empty();
+        if has_presets {
+            println!("7. A saved preset");
+        }
+        
+        let generated = match Self::prompt_input("Enter your choice (default 1): ")?.as_str() {
+            "2" => Self::prompt_passphrase()?,
+            "3" => Self::prompt_pronounceable()?,
+            "4" => Self::prompt_pattern()?,
+            "5" => Self::prompt_pin()?,
+            "6" => Self::prompt_memorable()?,
+            "7" if has_presets => Self::prompt_preset()?,
+            _ => match policy {
+                Some(policy) => generate_random_password(policy)?,
+                None => generate_random_password(&Self::prompt_password_options()?)?,
+            },
+        };
+        
+        Ok(Self::show_generated(generated))
+    }
+    
+    fn prompt_preset() -> Result<Generated, FerropassError> {
+        let presets = config::current().presets();
+        println!("Presets:");
+        for (i, name) in presets.keys().enumerate() {
+            println!("{}. {}", i + 1, name);
+        }
+        
+        loop {
+            let choice = Self::prompt_input("Enter a preset's number or name (default 1): ")?;
+            let preset = match choice.parse::<usize>() {
+                _ if choice.is_empty() => presets.values().next(),
+                Ok(n) if n >= 1 => presets.values().nth(n - 1),
+                _ => presets.get(&choice),
+            };
+            
+            match preset.map(|preset| preset.generate()) {
+                Some(Ok(generated)) => return Ok(generated),
+                Some(Err(e)) => println!("{}.", e),
+                None => println!("Invalid choice, please try again."),
+            }
+        }
+    }
+    
+    fn show_generated(generated: Generated) -> String {
+        println!("Generated password: {}", generated.password);
+        println!("Strength: {}.", generated.describe());
+        generated.password
+    }
+    
+    fn prompt_password_options() -> Result<PasswordOptions, FerropassError> {
+        loop {
+            let length = Self::prompt_input(&format!("Length (default {}): ", DEFAULT_LENGTH))?;
+            let length = if length.is_empty() {
+                DEFAULT_LENGTH
+            } else {
+                match length.parse() {
+                    Ok(length) => length,
+                    Err(_) => {
+                        println!("Invalid length, please enter a number.");
+                        continue;
+                    }
+                }
+            };
+            
+            let mut options = PasswordOptions {
+                length,
+                uppercase: Self::prompt_input("Include uppercase letters? (Y/n): ")?.to_lowercase() != "n",
+                digits: Self::prompt_input("Include digits? (Y/n): ")?.to_lowercase() != "n",
+                symbols: Self::prompt_input("Include symbols? (Y/n): ")?.to_lowercase() != "n",
+                avoid_ambiguous: Self::prompt_input("Avoid characters that are easy to confuse, like 0/O and 1/l/I? (y/N): ")?.to_lowercase() == "y",
+                exclude: Self::prompt_input("Characters the site doesn't allow, e.g. &% (optional): ")?,
+                ..PasswordOptions::default()
+            };
+            
+            if Self::prompt_input("Require more than one of some kind of character? (y/N): ")?.to_lowercase() == "y" {
+                options.min_lowercase = Self::prompt_minimum("lowercase letters")?;
+                if options.uppercase {
+                    options.min_uppercase = Self::prompt_minimum("uppercase letters")?;
+                }
+                if options.digits {
+                    options.min_digits = Self::prompt_minimum("digits")?;
+                }
+                if options.symbols {
+                    options.min_symbols = Self::prompt_minimum("symbols")?;
+                }
+            }
+            
+            if Self::prompt_input("Does the site have other rules, like starting with a letter? (y/N): ")?.to_lowercase() == "y" {
+                options.start_with_letter = Self::prompt_input("Must start with a letter? (y/N): ")?.to_lowercase() == "y";
+                let max_repeat = Self::prompt_input("Most identical characters allowed in a row (leave empty for no limit): ")?;
+                options.max_repeat = match max_repeat.parse() {
+                    Ok(max) => Some(max),
+                    Err(_) if max_repeat.is_empty() => None,
+                    Err(_) => {
+                        println!("Invalid number, please enter a number.");
+                        continue;
+                    }
+                };
+            }
+            
+            match options.check() {
+                Ok(()) => return Ok(options),
+                Err(e) => println!("{}.", e),
+            }
+        }
+    }
+    
+    fn prompt_minimum(class: &str) -> Result<usize, FerropassError> {
+        loop {
+            let minimum = Self::prompt_input(&format!("At least how many {}? (default 1): ", class))?;
+            if minimum.is_empty() {
+                return Ok(1);
+            }
+            match minimum.parse() {
+                Ok(minimum) => return Ok(minimum),
+                Err(_) => println!("Invalid number, please enter a number."),
+            }
+        }
+    }
+    
+    pub fn read_passkey(passkey_file: Option<&Path>, prompt: &str) -> Result<String, FerropassError> {
+        let passkey = match passkey_file {
+            Some(path) => fs::read_to_string(path)
+                .map_err(|e| FerropassError::Io(format!("Failed to read passkey file: {}", e)))?
+                .trim_end_matches(['\r', '\n'])
+                .to_string(),
+            None => Self::prompt_password(prompt)?,
+        };
+        
+        if passkey.is_empty() {
+            return Err(FerropassError::Validation("Passkey cannot be empty".to_string()));
+        }
+        
+        Ok(passkey)
+    }
+    
+    // Like `read_passkey`, but the prompt goes to the terminal instead of
+    // stdout, for commands whose output is piped into another program.
+    pub fn read_passkey_on_terminal(passkey_file: Option<&Path>, prompt: &str) -> Result<String, FerropassError> {
+        if passkey_file.is_some() {
+            return Self::read_passkey(passkey_file, prompt);
+        }
+        
+        let passkey = rpassword::prompt_password(prompt).map_err(|e| FerropassError::Io(format!("Failed to read password: {}", e)))?;
+        if passkey.is_empty() {
+            return Err(FerropassError::Validation("Passkey cannot be empty".to_string()));
+        }
+        
+        Ok(passkey)
+    }
+    
+    pub fn run(&mut self) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        
+        loop {
+            println!("=== FP Password Manager ===");
+            if paranoid::is_enabled() {
+                println!("[Paranoid mode: clipboard, network, hooks, agent and auto-type are disabled]");
+            }
+            if !self.storage.is_persistent() {
+                println!("[Ephemeral mode: databases are kept in memory and discarded on exit]");
+            }
+            println!("1. Create a new password database");
+            println!("2. Open an existing password database");
+            println!("3. Exit");
+            
+            let choice = Self::prompt_input("Enter your choice (1-3): ")?;
+            
+            match choice.as_str() {
+                "1" => self.create_new_database()?,
+                "2" => self.open_existing_database()?,
+                "3" => break,
+                _ => {
+                    println!("Invalid choice, please try again.");
+                    continue;
+                }
+            }
+            
+            // Input that doesn't come before the auto-lock time ends whatever
+            // was going on; the vault locks and asks for its passkey again.
+            if self.current_database.is_some() {
+                while let Err(e) = self.database_menu() {
+                    if !terminal::timed_out() {
+                        return Err(e);
+                    }
+                    self.lock();
+                }
+                self.close_database();
+            }
+        }
+        
+        self.save_queue.flush()
+    }
+    
+    fn create_new_database(&mut self) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Create New Database ===");
+        
+        let db_name = Self::prompt_input("Enter database name (without extension): ")?;
+        let mut filepath = PathBuf::from(&db_name);
+        filepath.set_extension("fp");
+        
+        // Checked under the lock, so another process can't create the vault
+        // in between and have it overwritten with an empty one.
+        let vault_lock = match self.storage.lock(&filepath) {
+            Ok(vault_lock) => vault_lock,
+            Err(e) => {
+                println!("{}", e);
+                Self::prompt_input("Press Enter to continue...")?;
+                return Ok(());
+            }
+        };
+        
+        if self.storage.exists(&filepath) {
+            println!("A database with this name already exists. Please choose a different name.");
+            return Ok(());
+        }
+        
+        let passkey = Self::prompt_for_valid_passkey("Enter database passkey")?;
+        let key = SessionKey::generate(&passkey)?;
+        
+        let database = Database::new();
+        
+        encrypt_and_save_database(self.storage.as_ref(), &database, &filepath, &key)?;
+        
+        println!("Database created successfully!");
+        println!("Vault fingerprint: {}", key.fingerprint());
+        fingerprint::remember(&filepath, &key.fingerprint())?;
+        
+        self.save_queue.track(&filepath, &database);
+        
+        self.current_database_path = Some(filepath);
+        self.current_database = Some(database);
+        self.session_key = Some(key);
+        self.vault_lock = Some(vault_lock);
+        
+        Ok(())
+    }
+    
+    fn open_existing_database(&mut self) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Open Existing Database ===");
+        
+        let filepath_str = Self::prompt_input("Enter absolute path or WebDAV, S3 or SFTP URL of database file (.fp): ")?;
+        let filepath = PathBuf::from(filepath_str);
+        
+        if !self.storage.exists(&filepath) {
+            println!("File not found. Please check the path and try again.");
+            Self::prompt_input("Press Enter to continue...")?;
+            return Ok(());
+        }
+        
+        let vault_lock = match self.storage.lock(&filepath) {
+            Ok(vault_lock) => vault_lock,
+            Err(e) => {
+                println!("{}", e);
+                Self::prompt_input("Press Enter to continue...")?;
+                return Ok(());
+            }
+        };
+        
+        let passkey = Self::prompt_password("Enter database passkey: ")?;
+        
+        if passkey.is_empty() {
+            println!("Passkey cannot be empty.");
+            Self::prompt_input("Press Enter to continue...")?;
+            return Ok(());
+        }
+        
+        match unlock_database(self.storage.as_ref(), &filepath, &passkey) {
+            Ok((database, key)) => {
+                println!("Database loaded successfully!");
+                if !self.confirm_fingerprint(&filepath, &key)? {
+                    return Ok(());
+                }
+                // A failed upgrade leaves the file as it was, still usable
+                // in its old format.
+                let key = match self.offer_upgrade(&filepath, &database, &key, &passkey) {
+                    Ok(upgraded) => upgraded.unwrap_or(key),
+                    Err(e) => {
+                        println!("The database could not be upgraded and stays in its current format: {}", e);
+                        Self::prompt_input("Press Enter to continue...")?;
+                        key
+                    },
+                };
+                self.save_queue.track(&filepath, &database);
+                self.current_database_path = Some(filepath);
+                self.current_database = Some(database);
+                self.session_key = Some(key);
+                self.vault_lock = Some(vault_lock);
+            },
+            Err(e) => {
+                println!("Failed to open database: {}", e);
+                Self::prompt_input("Press Enter to continue...")?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    // Compares the vault's fingerprint with the one this machine last saw at
+    // the same path, so a file swapped for another vault is noticed. Returns
+    // false if the user doesn't want to use it.
+    fn confirm_fingerprint(&self, filepath: &Path, key: &SessionKey) -> Result<bool, FerropassError> {
+        // A file in an older format has no data key of its own yet, so it
+        // gets its fingerprint when it is first saved in the current one.
+        let has_data_key = EncryptedData::read(self.storage.as_ref(), filepath)
+            .is_ok_and(|encrypted_data| encrypted_data.version() == CURRENT_FORMAT_VERSION);
+        if !has_data_key {
+            return Ok(true);
+        }
+        
+        let Some(previous) = fingerprint::check(filepath, &key.fingerprint())? else {
+            return Ok(true);
+        };
+        
+        println!("WARNING: {} now holds a different vault.", filepath.display());
+        println!("  Fingerprint when last opened here: {}", previous);
+        println!("  Fingerprint now:                   {}", key.fingerprint());
+        println!("The file may have been replaced. Only carry on if you replaced it yourself.");
+        
+        let confirm = Self::prompt_input("Use this vault and trust its fingerprint from now on? (y/n): ")?;
+        if confirm.to_lowercase() != "y" {
+            println!("Database not opened.");
+            Self::prompt_input("Press Enter to continue...")?;
+            return Ok(false);
+        }
+        
+        fingerprint::remember(filepath, &key.fingerprint())?;
+        Ok(true)
+    }
+    
+    // Offers to re-encrypt a file whose header is outdated. Returns the new
+    // session key if it was upgraded.
+    fn offer_upgrade(&self, filepath: &Path, database: &Database, key: &SessionKey, passkey: &str) -> Result<Option<SessionKey>, FerropassError> {
+        let Some(reason) = upgrade_reason(self.storage.as_ref(), filepath) else {
+            return Ok(None);
+        };
+        
+        println!("This database can be upgraded: {}.", reason);
+        let confirm = Self::prompt_input("Re-encrypt it with the current format and parameters? (y/n): ")?;
+        if confirm.to_lowercase() != "y" {
+            return Ok(None);
+        }
+        
+        let backup = self.storage.snapshot(filepath)?;
+        let key = key.rekey(passkey)?;
+        rewrap_and_save(self.storage.as_ref(), database, filepath, &key)?;
+        
+        println!("Database upgraded. The previous version was kept at {}.", backup.display());
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(Some(key))
+    }
+    
+    // Clears the decrypted database and the session key but keeps the path,
+    // so unlocking only needs the passkey again.
+    fn lock(&mut self) {
+        if let Err(e) = self.save_queue.flush() {
+            println!("{}", e);
+        }
+        if let Some(path) = &self.current_database_path {
+            self.save_queue.forget(path);
+        }
+        
+        self.current_database = None;
+        self.session_key = None;
+        terminal::set_idle_timeout(None);
+        let _ = clipboard::clear_copied();
+    }
+    
+    // Locks the database and lets go of it entirely, so another process can
+    // open it.
+    fn close_database(&mut self) {
+        self.lock();
+        self.current_database_path = None;
+        self.vault_lock = None;
+    }
+    
+    fn unlock(&mut self) -> Result<bool, FerropassError> {
+        Self::clear_screen();
+        println!("=== Database Locked ===");
+        
+        let Some(path) = self.current_database_path.clone() else {
+            return Ok(false);
+        };
+        println!("Database: {:?}", path);
+        
+        loop {
+            let passkey = Self::prompt_password("Enter database passkey (leave empty to return to main menu): ")?;
+            
+            if passkey.is_empty() {
+                return Ok(false);
+            }
+            
+            match unlock_database(self.storage.as_ref(), &path, &passkey) {
+                Ok((database, key)) => {
+                    if !self.confirm_fingerprint(&path, &key)? {
+                        self.close_database();
+                        return Ok(false);
+                    }
+                    self.save_queue.track(&path, &database);
+                    self.current_database = Some(database);
+                    self.session_key = Some(key);
+                    return Ok(true);
+                },
+                Err(e) => println!("Failed to unlock database: {}", e),
+            }
+        }
+    }
+    
+    fn database_menu(&mut self) -> Result<(), FerropassError> {
+        loop {
+            if self.session_key.is_none() && !self.unlock()? {
+                break;
+            }
+            
+            Self::clear_screen();
+            
+            for error in self.save_queue.take_errors() {
+                println!("Warning: a background save failed: {}", error);
+            }
+            
+            let auto_lock = self.current_database.as_ref().and_then(|db| db.get_settings().get_auto_lock_minutes());
+            terminal::set_idle_timeout(auto_lock.map(|minutes| Duration::from_secs(minutes.saturating_mul(60))));
+            
+            println!("=== Database Menu ===");
+            println!("Database: {:?}", self.current_database_path.as_ref().unwrap());
+            println!("Fingerprint: {}", self.session_key.as_ref().unwrap().fingerprint());
+            if auto_lock.is_some() && !terminal::IDLE_TIMEOUT_SUPPORTED {
+                println!("[Auto-lock is set but isn't supported on this platform yet]");
+            }
+            println!("1. List accounts");
+            println!("2. View/Edit account");
+            println!("3. Add new account");
+            println!("4. Delete account");
+            println!("5. Settings");
+            println!("6. Lock database");
+            println!("7. Clear clipboard");
+            println!("8. Return to main menu");
+            
+            let choice = Self::prompt_input("Enter your choice (1-8): ")?;
+            
+            match choice.as_str() {
+                "1" => self.list_accounts()?,
+                "2" => self.view_edit_account()?,
+                "3" => self.add_account()?,
+                "4" => self.delete_account()?,
+                "5" => self.settings_menu()?,
+                "6" => self.lock(),
+                "7" => {
+                    match clipboard::clear() {
+                        Ok(()) => println!("Clipboard cleared."),
+                        Err(e) => println!("Clipboard not cleared: {}", e),
+                    }
+                    Self::prompt_input("Press Enter to continue...")?;
+                },
+                "8" => {
+                    if let Err(e) = self.save_queue.flush() {
+                        println!("{}", e);
+                        Self::prompt_input("Press Enter to continue...")?;
+                    }
+                    break;
+                },
+                _ => {
+                    println!("Invalid choice, please try again.");
+                    continue;
+                }
+            }
+            
+        }
+        
+        Ok(())
+    }
+    
+    fn list_accounts(&self) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Account List ===");
+        
+        if let Some(db) = &self.current_database {
+            if db.get_accounts().is_empty() {
+                println!("No accounts found in the database.");
+            } else {
+                let search = Self::prompt_input("Search (press Enter to list all): ")?;
+                let mut query = db.query().sorted_by(SortBy::Title);
+                if !search.is_empty() {
+                    query = query.search(search);
+                }
+                let accounts: Vec<&Account> = query.into_iter().collect();
+                
+                if accounts.is_empty() {
+                    println!("No accounts match.");
+                } else {
+                    println!("{:<10} {:<30} {:<20}", "ID", "Username/Email", "Description");
+                    println!("{:-<60}", "");
+                }
+                
+                for account in accounts {
+                    let desc = account.get_description()
+                        .as_ref()
+                        .map_or("", |s| s.as_str());
+                    
+                    println!("{:<10} {:<30} {:<20}", 
+                        account.get_id(),
+                        account.get_username_or_email(),
+                        desc
+                    );
+                }
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn view_edit_account(&mut self) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== View/Edit Account ===");
+        
+        if let Some(db) = &self.current_database {
+            let accounts = db.get_accounts();
+            
+            if accounts.is_empty() {
+                println!("No accounts found in the database.");
+                Self::prompt_input("Press Enter to continue...")?;
+                return Ok(());
+            } else {
+                println!("{:<10} {:<30} {:<20}", "ID", "Username/Email", "Description");
+                println!("{:-<60}", "");
+                
+                for account in accounts {
+                    let desc = account.get_description()
+                        .as_ref()
+                        .map_or("", |s| s.as_str());
+                    
+                    println!("{:<10} {:<30} {:<20}", 
+                        account.get_id(),
+                        account.get_username_or_email(),
+                        desc
+                    );
+                }
+                println!();
+            }
+        } else {
+            println!("No database loaded.");
+            Self::prompt_input("Press Enter to continue...")?;
+            return Ok(());
+        }
+        
+        let account_id = Self::prompt_input("Enter account ID: ")?;
+        
+        if let Some(db) = &self.current_database {
+            if let Some(_account) = db.get_account_by_id(&account_id) {
+                self.account_menu(&account_id)?;
+            } else {
+                println!("Account not found.");
+                Self::prompt_input("Press Enter to continue...")?;
+            }
+        } else {
+            println!("No database loaded.");
+            Self::prompt_input("Press Enter to continue...")?;
+        }
+        
+        Ok(())
+    }
+    
+    fn account_menu(&mut self, account_id: &str) -> Result<(), FerropassError> {
+        loop {
+            Self::clear_screen();
+            
+            let account = if let Some(db) = &self.current_database {
+                if let Some(acc) = db.get_account_by_id(account_id) {
+                    acc.clone()
+                } else {
+                    println!("Account not found.");
+                    return Ok(());
+                }
+            } else {
+                println!("No database loaded.");
+                return Ok(());
+            };
+            
+            println!("=== Account Details ===");
+            println!("ID: {}", account.get_id());
+            println!("Username/Email: {}", account.get_username_or_email());
+            println!("Description: {}", account.get_description().as_ref().map_or("", |s| s.as_str()));
+            println!("URL: {}", account.get_url().unwrap_or(""));
+            println!("Group: {}", account.get_group().unwrap_or(""));
+            println!("Tags: {}", account.get_tags().join(", "));
+            println!("Password: [HIDDEN]");
+            println!("Notes: {}", account.get_notes().as_ref().map_or("(none)", |_| "[HIDDEN]"));
+            match account.get_login_procedure().len() {
+                0 => println!("Login procedure: (none)"),
+                steps => println!("Login procedure: {} step(s)", steps),
+            }
+            println!("Password policy: {}", account.get_policy().map_or("(none)".to_string(), PasswordOptions::describe));
+            println!("Auto-type: {}", account.get_autotype().unwrap_or(autotype::DEFAULT_SEQUENCE));
+            println!("TOTP: {}", if account.get_totp().is_some() { "[HIDDEN]" } else { "(none)" });
+            println!("SSH key: {}", if account.get_ssh_key().is_some() { "[HIDDEN]" } else { "(none)" });
+            println!();
+            println!("1. Edit account information");
+            println!("2. Copy password to clipboard");
+            println!("3. Generate new password");
+            println!("4. View notes");
+            println!("5. Walk through login procedure");
+            println!("6. Reveal password characters at positions...");
+            println!("7. Set password policy");
+            println!("8. Auto-type into another window");
+            println!("9. Copy current TOTP code");
+            println!("10. Spell out password for reading aloud");
+            println!("11. Share as an encrypted bundle");
+            println!("12. SSH key");
+            println!("13. Return to database menu");
+            
+            let choice = Self::prompt_input("Enter your choice (1-13): ")?;
+            
+            match choice.as_str() {
+                "1" => self.edit_account(account_id)?,
+                "2" => self.copy_password(account_id)?,
+                "3" => self.generate_new_password(account_id)?,
+                "4" => self.view_notes(account_id)?,
+                "5" => self.walk_login_procedure(account_id)?,
+                "6" => self.reveal_characters(account_id)?,
+                "7" => self.set_password_policy(account_id)?,
+                "8" => self.auto_type(account_id)?,
+                "9" => self.copy_totp_code(account_id)?,
+                "10" => self.spell_password(account_id)?,
+                "11" => self.share_entry(account_id)?,
+                "12" => self.ssh_key(account_id)?,
+                "13" => break,
+                _ => {
+                    println!("Invalid choice, please try again.");
+                    continue;
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    fn edit_account(&mut self, account_id: &str) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Edit Account ===");
+        
+        if let Some(path) = &self.current_database_path && let Some(key) = &self.session_key {
+            if let Some(db) = &mut self.current_database {
+                if let Some(account) = db.get_account_by_id_mut(account_id) {
+                    println!("Current Username/Email: {}", account.get_username_or_email());
+                    let new_username = Self::prompt_input("Enter new Username/Email (leave empty to keep current): ")?;
+                    
+                    if !new_username.is_empty() {
+                        account.set_username_or_email(new_username);
+                    }
+                    
+                    let current_desc = account.get_description().as_ref().map_or("", |s| s.as_str());
+                    println!("Current Description: {}", current_desc);
+                    let new_desc = Self::prompt_input("Enter new Description (leave empty to keep current): ")?;
+                    
+                    if !new_desc.is_empty() {
+                        account.set_description(Some(new_desc));
+                    } else if new_desc.is_empty() && !current_desc.is_empty() {
+                        let keep_desc = Self::prompt_input("Do you want to keep the current description? (y/n): ")?;
+                        if keep_desc.to_lowercase() == "n" {
+                            account.set_description(None);
+                        }
+                    }
+                    
+                    let current_url = account.get_url().unwrap_or("").to_string();
+                    println!("Current URL: {}", current_url);
+                    let new_url = Self::prompt_input("Enter new URL (leave empty to keep current): ")?;
+                    
+                    if !new_url.is_empty() {
+                        account.set_url(Some(new_url));
+                    } else if !current_url.is_empty() {
+                        let keep_url = Self::prompt_input("Do you want to keep the current URL? (y/n): ")?;
+                        if keep_url.to_lowercase() == "n" {
+                            account.set_url(None);
+                        }
+                    }
+                    
+                    let current_group = account.get_group().unwrap_or("").to_string();
+                    println!("Current Group: {}", current_group);
+                    let new_group = Self::prompt_input("Enter new Group, nested with / (leave empty to keep current): ")?;
+                    
+                    if !new_group.is_empty() {
+                        account.set_group(Some(new_group));
+                    } else if !current_group.is_empty() {
+                        let keep_group = Self::prompt_input("Do you want to keep the current group? (y/n): ")?;
+                        if keep_group.to_lowercase() == "n" {
+                            account.set_group(None);
+                        }
+                    }
+                    
+                    println!("Current Tags: {}", account.get_tags().join(", "));
+                    let new_tags = Self::prompt_input("Enter new Tags, separated by commas (leave empty to keep current): ")?;
+                    
+                    if !new_tags.is_empty() {
+                        account.set_tags(parse_tags(&new_tags));
+                    } else if !account.get_tags().is_empty() {
+                        let keep_tags = Self::prompt_input("Do you want to keep the current tags? (y/n): ")?;
+                        if keep_tags.to_lowercase() == "n" {
+                            account.set_tags(Vec::new());
+                        }
+                    }
+                    
+                    let has_notes = account.get_notes().is_some();
+                    let edit_notes = Self::prompt_input(if has_notes {
+                        "Edit notes? (y/n): "
+                    } else {
+                        "Add notes? (y/n): "
+                    })?;
+                    
+                    if edit_notes.to_lowercase() == "y" {
+                        let notes = Self::prompt_multiline("Enter notes (leave empty to remove them):")?;
+                        account.set_notes(if notes.trim().is_empty() { None } else { Some(notes) });
+                    }
+                    
+                    if !account.get_login_procedure().is_empty() {
+                        println!("Current login procedure:");
+                        for (i, step) in account.get_login_procedure().iter().enumerate() {
+                            println!("  {}. {}", i + 1, step);
+                        }
+                    }
+                    let edit_procedure = Self::prompt_input("Edit login procedure? (y/n): ")?;
+                    
+                    if edit_procedure.to_lowercase() == "y" {
+                        println!("One step per line. {{username}} is replaced by the username, {{chars:2,5,7}} reveals only those");
+                        println!("characters of the password and {{chars}} asks which characters the site wants each time.");
+                        let steps = Self::prompt_multiline("Enter login steps (leave empty to remove the procedure):")?;
+                        account.set_login_procedure(steps.lines()
+                            .map(|step| step.trim().to_string())
+                            .filter(|step| !step.is_empty())
+                            .collect());
+                    }
+                    
+                    println!("Current auto-type sequence: {}", account.get_autotype().unwrap_or(autotype::DEFAULT_SEQUENCE));
+                    let edit_autotype = Self::prompt_input("Edit auto-type sequence? (y/n): ")?;
+                    
+                    if edit_autotype.to_lowercase() == "y" {
+                        println!("{{username}}, {{password}}, {{tab}} and {{enter}} type what they say, {{delay:500}} waits that many");
+                        println!("milliseconds, and anything else is typed as written.");
+                        let sequence = Self::prompt_input("Enter auto-type sequence (leave empty for the default): ")?;
+                        
+                        if sequence.is_empty() {
+                            account.set_autotype(None);
+                        } else {
+                            match autotype::parse(&sequence) {
+                                Ok(_) => account.set_autotype(Some(sequence)),
+                                Err(e) => println!("Auto-type sequence not changed: {}", e),
+                            }
+                        }
+                    }
+                    
+                    let edit_totp = Self::prompt_input(if account.get_totp().is_some() {
+                        "Edit TOTP secret? (y/n): "
+                    } else {
+                        "Add a TOTP secret for two-factor codes? (y/n): "
+                    })?;
+                    
+                    if edit_totp.to_lowercase() == "y" {
+                        let secret = Self::prompt_password("Enter the secret shown with the site's QR code, or its otpauth:// URI (leave empty to remove it): ")?;
+                        
+                        if secret.trim().is_empty() {
+                            account.set_totp(None);
+                        } else {
+                            match Totp::parse(&secret) {
+                                Ok(_) => account.set_totp(Some(key.entry_key().seal(secret.trim())?)),
+                                Err(e) => println!("TOTP secret not changed: {}", e),
+                            }
+                        }
+                    }
+                    
+                    println!("Edit password? (y/n): ");
+                    let edit_password = Self::prompt_input("")?;
+                    
+                    if edit_password.to_lowercase() == "y" {
+                        let password_action = Self::prompt_input("Do you want to (1) enter a new password or (2) generate one? (1/2): ")?;
+                        
+                        if password_action == "1" {
+                            let new_password = Self::prompt_own_password("Enter new password: ")?;
+                            account.set_password(key.entry_key().seal(&new_password)?);
+                            println!("Password updated successfully!");
+                        } else if password_action == "2" {
+                            let new_password = Self::prompt_generated_password(account.get_policy())?;
+                            account.set_password(key.entry_key().seal(&new_password)?);
+                            println!("Password updated successfully!");
+                        } else {
+                            println!("Invalid choice, password not updated.");
+                        }
+                    }
+                    
+                    println!("Account updated successfully!");
+                    
+                    self.save_queue.enqueue(db, path, key);
+                    println!("Saving changes in the background.");
+                } else {
+                    println!("Account not found.");
+                }
+            } else {
+                println!("No database loaded.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn copy_password(&self, account_id: &str) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Copy Password ===");
+        
+        if let Some(db) = &self.current_database {
+            if let Some(account) = db.get_account_by_id(account_id) {
+                let copied = self.open_password(account)
+                    .and_then(|password| copy_to_clipboard(&password).map(|()| password));
+                match copied {
+                    Ok(password) => {
+                        println!("Password copied to clipboard!");
+                        if let Some(after) = config::current().clipboard_clear_after() {
+                            clipboard::clear_later(&password, after);
+                            return Self::show_clear_countdown(after);
+                        }
+                    },
+                    Err(e) => println!("Password not copied: {}", e),
+                }
+            } else {
+                println!("Account not found.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    // Counts down to the clipboard being cleared until Enter is pressed; the
+    // clipboard is cleared on time either way. With linear output it says
+    // when once instead of updating the line every second.
+    fn show_clear_countdown(after: Duration) -> Result<(), FerropassError> {
+        if config::current().linear_output() || !cfg!(unix) {
+            println!("The clipboard will be cleared in {} seconds.", after.as_secs());
+            Self::prompt_input("Press Enter to continue...")?;
+            return Ok(());
+        }
+        
+        for remaining in (1..=after.as_secs()).rev() {
+            print!("\rClipboard is cleared in {:>3} s. Press Enter to continue... ", remaining);
+            io::stdout().flush().map_err(|e| FerropassError::Io(format!("Failed to flush stdout: {}", e)))?;
+            
+            if terminal::wait_for_input(Duration::from_secs(1)) {
+                terminal::read_line().map_err(|e| FerropassError::Io(format!("Failed to read input: {}", e)))?;
+                return Ok(());
+            }
+        }
+        
+        println!("\rClipboard cleared.{:<50}", "");
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn copy_totp_code(&self, account_id: &str) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Copy TOTP Code ===");
+        
+        if let Some(db) = &self.current_database && let Some(key) = &self.session_key {
+            if let Some(account) = db.get_account_by_id(account_id) {
+                let Some(secret) = account.get_totp() else {
+                    println!("This account has no TOTP secret; add one by editing the account.");
+                    Self::prompt_input("Press Enter to continue...")?;
+                    return Ok(());
+                };
+                
+                let now = current_timestamp();
+                let copied = key.entry_key().open(secret)
+                    .and_then(|secret| Totp::parse(&secret))
+                    .and_then(|totp| {
+                        let code = totp.code_at(now);
+                        copy_to_clipboard(&code)?;
+                        Ok((code, totp.remaining(now)))
+                    });
+                
+                match copied {
+                    Ok((code, remaining)) => {
+                        println!("TOTP code copied to clipboard! It is valid for {} more seconds, and cleared when it expires.", remaining);
+                        clipboard::clear_later(&code, Duration::from_secs(remaining));
+                        return Self::show_clear_countdown(Duration::from_secs(remaining));
+                    },
+                    Err(e) => println!("TOTP code not copied: {}", e),
+                }
+            } else {
+                println!("Account not found.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn auto_type(&self, account_id: &str) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Auto-type ===");
+        
+        if let Some(db) = &self.current_database {
+            if let Some(account) = db.get_account_by_id(account_id) {
+                let delay = config::current().autotype_delay();
+                println!("Switch to the window to type into; typing starts in {} seconds.", delay.as_secs());
+                
+                match autotype::run(account, &|| self.open_password(account), delay) {
+                    Ok(()) => println!("Typed the auto-type sequence."),
+                    Err(e) => println!("Auto-type failed: {}", e),
+                }
+            } else {
+                println!("Account not found.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    // Passwords stay sealed in the open database; this opens one for as long
+    // as the caller holds on to it.
+    fn open_password(&self, account: &Account) -> Result<String, FerropassError> {
+        let key = self.session_key.as_ref().ok_or_else(|| FerropassError::Validation("No database loaded.".to_string()))?;
+        key.entry_key().open(account.get_password())
+    }
+    
+    fn view_notes(&self, account_id: &str) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== View Notes ===");
+        
+        if let Some(db) = &self.current_database {
+            if let Some(account) = db.get_account_by_id(account_id) {
+                match account.get_notes() {
+                    Some(notes) => {
+                        let title = format!("Notes for {}", account.get_username_or_email());
+                        pager::page(&title, notes)?;
+                        return Ok(());
+                    },
+                    None => println!("This account has no notes."),
+                }
+            } else {
+                println!("Account not found.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn walk_login_procedure(&self, account_id: &str) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Login Procedure ===");
+        
+        if let Some(db) = &self.current_database {
+            if let Some(account) = db.get_account_by_id(account_id) {
+                let steps = account.get_login_procedure();
+                
+                if steps.is_empty() {
+                    println!("This account has no login procedure. Add one with Edit account information.");
+                }
+                
+                for (i, step) in steps.iter().enumerate() {
+                    let open_password = || self.open_password(account);
+                    let mut ask_positions = || Self::prompt_input("  Which character positions are asked for? (e.g. 2,5,7): ");
+                    
+                    match login::render_step(step, account, &open_password, &mut ask_positions) {
+                        Ok(rendered) => println!("{}. {}", i + 1, rendered),
+                        Err(e) => println!("{}. {} ({})", i + 1, step, e),
+                    }
+                    
+                    if i + 1 < steps.len() {
+                        Self::prompt_input("   Press Enter for the next step...")?;
+                    }
+                }
+            } else {
+                println!("Account not found.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn reveal_characters(&self, account_id: &str) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Reveal Password Characters ===");
+        
+        if let Some(db) = &self.current_database {
+            if let Some(account) = db.get_account_by_id(account_id) {
+                let positions = Self::prompt_input("Enter the character positions to reveal (e.g. 2,5,9): ")?;
+                
+                match self.open_password(account).and_then(|password| login::reveal_positions(&password, &positions)) {
+                    Ok(revealed) => println!("{}", revealed),
+                    Err(e) => println!("{}", e),
+                }
+            } else {
+                println!("Account not found.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    // Opens in the pager, so the spelled-out password is wiped from the
+    // scrollback once it has been read out.
+    fn spell_password(&self, account_id: &str) -> Result<(), FerropassError> {
+        if let Some(db) = &self.current_database {
+            if let Some(account) = db.get_account_by_id(account_id) {
+                match self.open_password(account) {
+                    Ok(password) => {
+                        let title = format!("Password for {}", account.get_username_or_email());
+                        return pager::page(&title, &phonetic::spell(&password));
+                    },
+                    Err(e) => println!("{}", e),
+                }
+            } else {
+                println!("Account not found.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn share_entry(&self, account_id: &str) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Share Entry ===");
+        
+        if let Some(db) = &self.current_database && let Some(key) = &self.session_key {
+            if let Some(account) = db.get_account_by_id(account_id) {
+                println!("The entry is sealed under a password of its own. Send the bundle and the password to the");
+                println!("recipient by different routes; they add it to their vault with `ferropass import-entry`.");
+                let password = Self::prompt_for_valid_passkey("Enter a password for the bundle")?;
+                let bundle = share::create(account, &key.entry_key(), &password)?;
+                
+                let output = Self::prompt_input("Save the bundle to a file (leave empty to show it here): ")?;
+                if output.is_empty() {
+                    println!();
+                    println!("{}", bundle);
+                    println!();
+                } else {
+                    match config::write_private_file(Path::new(&output), format!("{}\n", bundle)) {
+                        Ok(()) => println!("Bundle saved to {}", output),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+            } else {
+                println!("Account not found.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn generate_new_password(&mut self, account_id: &str) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Generate New Password ===");
+        
+        if let Some(path) = &self.current_database_path && let Some(key) = &self.session_key {
+            if let Some(db) = &mut self.current_database {
+                if let Some(account) = db.get_account_by_id_mut(account_id) {
+                    let new_password = Self::prompt_generated_password(account.get_policy())?;
+                    
+                    let confirm = Self::prompt_input("Do you want to set this as the new password? (y/n): ")?;
+                    
+                    if confirm.to_lowercase() == "y" {
+                        account.set_password(key.entry_key().seal(&new_password)?);
+                        println!("Password updated successfully!");
+                        
+                        self.save_queue.enqueue(db, path, key);
+                        println!("Saving changes in the background.");
+                    } else {
+                        println!("Password not updated.");
+                    }
+                } else {
+                    println!("Account not found.");
+                }
+            } else {
+                println!("No database loaded.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn set_password_policy(&mut self, account_id: &str) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Password Policy ===");
+        println!("Passwords generated for this account follow its policy, so they fit what the site accepts.");
+        
+        if let Some(path) = &self.current_database_path && let Some(key) = &self.session_key {
+            if let Some(db) = &mut self.current_database {
+                if let Some(account) = db.get_account_by_id_mut(account_id) {
+                    let remove = match account.get_policy() {
+                        Some(policy) => {
+                            println!("Current policy: {}", policy.describe());
+                            Self::prompt_input("Do you want to (1) replace it or (2) remove it? (1/2): ")? == "2"
+                        },
+                        None => false,
+                    };
+                    
+                    if remove {
+                        account.set_policy(None);
+                        println!("Password policy removed.");
+                    } else {
+                        let policy = Self::prompt_password_options()?;
+                        println!("New policy: {}", policy.describe());
+                        account.set_policy(Some(policy));
+                        println!("Password policy saved.");
+                    }
+                    
+                    self.save_queue.enqueue(db, path, key);
+                    println!("Saving changes in the background.");
+                } else {
+                    println!("Account not found.");
+                }
+            } else {
+                println!("No database loaded.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    // The key is served by `ferropass agent` once the vault is unlocked there,
+    // so it never has to be on disk unencrypted.
+    fn ssh_key(&mut self, account_id: &str) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== SSH Key ===");
+        
+        if let Some(path) = &self.current_database_path && let Some(key) = &self.session_key {
+            if let Some(db) = &mut self.current_database {
+                if let Some(account) = db.get_account_by_id_mut(account_id) {
+                    let entry_key = key.entry_key();
+                    let current = account.get_ssh_key()
+                        .map(|ssh_key| entry_key.open(ssh_key).and_then(|text| SshKey::parse(text, || Err(FerropassError::Validation("The stored key has a passphrase".to_string())))))
+                        .transpose();
+                    
+                    let choice = match &current {
+                        Ok(Some(ssh_key)) => {
+                            println!("Public key: {}", ssh_key.public_line());
+                            println!("Fingerprint: {}", ssh_key.fingerprint());
+                            Self::prompt_input("Do you want to (1) generate a new key, (2) read one from a file or (3) remove it? Leave empty to keep it: ")?
+                        },
+                        Ok(None) => Self::prompt_input("Do you want to (1) generate a key or (2) read one from a file? Leave empty to cancel: ")?,
+                        Err(e) => {
+                            println!("The stored key can't be read: {}", e);
+                            Self::prompt_input("Do you want to (1) generate a new key, (2) read one from a file or (3) remove it? Leave empty to keep it: ")?
+                        },
+                    };
+                    
+                    let new_key = match choice.as_str() {
+                        "1" => Some(SshKey::generate(account.get_username_or_email())),
+                        "2" => {
+                            let file = PathBuf::from(Self::prompt_input("Enter the absolute path to the private key file: ")?);
+                            Some(fs::read_to_string(&file)
+                                .map_err(|e| FerropassError::Io(format!("Error reading file: {}: {}", file.display(), e)))
+                                .and_then(|text| SshKey::parse(text, || Self::prompt_password("Passphrase of the key: "))))
+                        },
+                        "3" if account.get_ssh_key().is_some() => {
+                            account.set_ssh_key(None);
+                            println!("SSH key removed.");
+                            None
+                        },
+                        _ => {
+                            println!("SSH key not changed.");
+                            Self::prompt_input("Press Enter to continue...")?;
+                            return Ok(());
+                        },
+                    };
+                    
+                    match new_key.transpose() {
+                        Ok(Some(ssh_key)) => {
+                            let mut text = ssh_key.to_openssh();
+                            let sealed = entry_key.seal(&text);
+                            memlock::wipe(unsafe { text.as_bytes_mut() });
+                            account.set_ssh_key(Some(sealed?));
+                            println!("SSH key saved. Add the public key to the servers' authorized_keys:");
+                            println!("{}", ssh_key.public_line());
+                            if choice == "2" {
+                                println!("The vault has its own copy now, so the key file can be deleted.");
+                            }
+                        },
+                        Ok(None) => {},
+                        Err(e) => {
+                            println!("SSH key not changed: {}", e);
+                            Self::prompt_input("Press Enter to continue...")?;
+                            return Ok(());
+                        },
+                    }
+                    
+                    self.save_queue.enqueue(db, path, key);
+                    println!("Saving changes in the background.");
+                } else {
+                    println!("Account not found.");
+                }
+            } else {
+                println!("No database loaded.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn add_account(&mut self) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Add New Account ===");
+        
+        let username = match Self::prompt_input("Enter Username/Email (leave empty to generate one): ")? {
+            username if username.is_empty() => Self::prompt_generated_username()?,
+            username => username,
+        };
+        
+        let description = Self::prompt_input("Enter Description (optional): ")?;
+        let description = if description.is_empty() { None } else { Some(description) };
+        
+        let url = Self::prompt_input("Enter URL (optional): ")?;
+        let url = if url.is_empty() { None } else { Some(url) };
+        
+        let password_choice = Self::prompt_input("Do you want to (1) enter your own password or (2) generate one? (1/2): ")?;
+        
+        let password = if password_choice == "1" {
+            Self::prompt_own_password("Enter password: ")?
+        } else if password_choice == "2" {
+            Self::prompt_generated_password(None)?
+        } else {
+            println!("Invalid choice. Using a generated password.");
+            Self::show_generated(generate_random_password(&PasswordOptions::default())?)
+        };
+        
+        if let Some(db) = &mut self.current_database {
+            if let Some(path) = &self.current_database_path && let Some(key) = &self.session_key {
+                let mut account = Account::new(username, description, key.entry_key().seal(&password)?);
+                account.set_url(url);
+                db.add_account(account);
+                
+                self.save_queue.enqueue(db, path, key);
+                println!("Account added successfully!");
+                println!("Saving changes in the background.");
+            } else {
+                println!("No database path found.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn prompt_generated_username() -> Result<String, FerropassError> {
+        let alias_base = config::current().alias_base();
+        
+        let username = match alias_base {
+            Some(base) => {
+                println!("Generate:");
+                println!("1. A random handle");
+                println!("2. A unique alias of {}", base);
+                match Self::prompt_input("Enter your choice (default 1): ")?.as_str() {
+                    "2" => username::generate_alias(base)?,
+                    _ => username::generate_handle()?,
+                }
+            },
+            None => {
+                println!("Generating a random handle. Set alias_base in the config to generate email aliases too.");
+                username::generate_handle()?
+            },
+        };
+        
+        println!("Generated username: {}", username);
+        Ok(username)
+    }
+    
+    fn delete_account(&mut self) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Delete Account ===");
+        
+        if let Some(db) = &self.current_database {
+            let accounts = db.get_accounts();
+            
+            if accounts.is_empty() {
+                println!("No accounts found in the database.");
+                Self::prompt_input("Press Enter to continue...")?;
+                return Ok(());
+            } else {
+                println!("{:<10} {:<30} {:<20}", "ID", "Username/Email", "Description");
+                println!("{:-<60}", "");
+                
+                for account in accounts {
+                    let desc = account.get_description()
+                        .as_ref()
+                        .map_or("", |s| s.as_str());
+                    
+                    println!("{:<10} {:<30} {:<20}", 
+                        account.get_id(),
+                        account.get_username_or_email(),
+                        desc
+                    );
+                }
+                println!();
+            }
+        } else {
+            println!("No database loaded.");
+            Self::prompt_input("Press Enter to continue...")?;
+            return Ok(());
+        }
+        
+        let account_id = Self::prompt_input("Enter account ID to delete: ")?;
+        
+        if let Some(db) = &self.current_database {
+            if db.get_account_by_id(&account_id).is_none() {
+                println!("Account not found.");
+                Self::prompt_input("Press Enter to continue...")?;
+                return Ok(());
+            }
+        } else {
+            println!("No database loaded.");
+            Self::prompt_input("Press Enter to continue...")?;
+            return Ok(());
+        }
+        
+        if let Some(path) = &self.current_database_path && let Some(key) = &self.session_key {
+            let confirm = Self::prompt_input("Are you sure you want to delete this account? (y/n): ")?;
+            
+            if confirm.to_lowercase() == "y" {
+                if let Some(db) = &mut self.current_database {
+                    if db.remove_account(&account_id) {
+                        println!("Account deleted successfully!");
+                        
+                        self.save_queue.enqueue(db, path, key);
+                        println!("Saving changes in the background.");
+                    } else {
+                        println!("Account not found.");
+                    }
+                } else {
+                    println!("No database loaded.");
+                }
+            } else {
+                println!("Deletion cancelled.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn settings_menu(&mut self) -> Result<(), FerropassError> {
+        Self::clear_screen();
+        println!("=== Database Settings ===");
+        
+        if let Some(path) = &self.current_database_path && let Some(key) = &self.session_key {
+            if let Some(db) = &mut self.current_database {
+                let mut settings = db.get_settings().clone();
+                match settings.get_auto_lock_minutes() {
+                    Some(minutes) => println!("Auto-lock: after {} minute(s) of inactivity", minutes),
+                    None => println!("Auto-lock: off"),
+                }
+                println!("Edit history: {}", if settings.get_keep_history() { "kept in full" } else { "compacted now and then" });
+                
+                let input = Self::prompt_input("Minutes of inactivity before locking (0 to turn off, leave empty to keep current): ")?;
+                match input.parse::<u64>() {
+                    _ if input.is_empty() => {},
+                    Ok(minutes) => settings.set_auto_lock_minutes(if minutes == 0 { None } else { Some(minutes) }),
+                    Err(_) => println!("Invalid number, auto-lock not changed."),
+                }
+                
+                let input = Self::prompt_input("Keep every edit in the journal instead of compacting it? (y/n, leave empty to keep current): ")?;
+                match input.to_lowercase().as_str() {
+                    "" => {},
+                    "y" => settings.set_keep_history(true),
+                    "n" => settings.set_keep_history(false),
+                    _ => println!("Invalid answer, edit history not changed."),
+                }
+                
+                if settings == *db.get_settings() {
+                    println!("Settings not changed.");
+                } else {
+                    *db.get_settings_mut() = settings;
+                    println!("Settings updated successfully!");
+                    
+                    self.save_queue.enqueue(db, path, key);
+                    println!("Saving changes in the background.");
+                }
+            } else {
+                println!("No database loaded.");
+            }
+        } else {
+            println!("No database loaded.");
+        }
+        
+        Self::prompt_input("Press Enter to continue...")?;
+        Ok(())
+    }
+    
+    fn print_strength(strength: &PasswordStrength) {
+        println!("Strength: {}/4, about {:.0} bits; estimated time to guess offline: {}.", strength.score, strength.bits, strength.crack_time);
+    }
+    
+    fn print_strength_feedback(strength: &PasswordStrength) {
+        if let Some(warning) = &strength.warning {
+            println!("  {}", warning);
+        }
+        for suggestion in &strength.suggestions {
+            println!("  {}", suggestion);
+        }
+    }
+    
+    // Reads a password the user chose themselves. A weak one is only
+    // warned about, since it may be all a site allows.
+    fn prompt_own_password(prompt: &str) -> Result<String, FerropassError> {
+        loop {
+            let password = Self::prompt_password(prompt)?;
+            
+            if password.is_empty() {
+                println!("Password cannot be empty.");
+                continue;
+            }
+            
+            let strength = rate_password(&password);
+            Self::print_strength(&strength);
+            
+            if strength.is_strong() {
+                return Ok(password);
+            }
+            
+            println!("This password is easy to guess.");
+            Self::print_strength_feedback(&strength);
+            
+            let confirm = Self::prompt_input("Use it anyway? (y/n): ")?;
+            if confirm.to_lowercase() == "y" {
+                return Ok(password);
+            }
+        }
+    }
+    
+    pub fn prompt_for_valid_passkey(prompt: &str) -> Result<String, FerropassError> {
+        loop {
+            let passkey = Self::prompt_password(&format!("{} (a long passphrase of unrelated words works well): ", prompt))?;
+            
+            if passkey.is_empty() {
+                println!("Passkey cannot be empty.");
+                continue;
+            }
+            
+            let strength = rate_password(&passkey);
+            Self::print_strength(&strength);
+            
+            if !strength.is_strong() {
+                println!("This passkey is too easy to guess.");
+                Self::print_strength_feedback(&strength);
+                continue;
+            }
+            
+            let confirm_passkey = Self::prompt_password("Confirm passkey: ")?;
+            
+            if confirm_passkey.is_empty() {
+                println!("Confirmation passkey cannot be empty.");
+                continue;
+            }
+            
+            if passkey == confirm_passkey {
+                return Ok(passkey);
+            } else {
+                println!("Passkeys do not match. Please try again.");
+            }
+        }
+    }
+}
+// Leaving the session, whether by Exit or an error, takes a copied secret out
+// of the clipboard along with it.
+impl Drop for Cli {
+    fn drop(&mut self) {
+        let _ = clipboard::clear_copied();
+    }
+}
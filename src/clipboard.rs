@@ -1,9 +1,379 @@
-use clipboard::{ClipboardContext, ClipboardProvider};
-
-pub fn copy_to_clipboard(text: &str) -> Result<(), String> {
-    let mut ctx: ClipboardContext = ClipboardProvider::new()
-        .map_err(|e| format!("Failed to initialize clipboard: {}", e))?;
-    
-    ctx.set_contents(text.to_string())
-        .map_err(|e| format!("Failed to copy to clipboard: {}", e))
-}
\ No newline at end of file
+use ferropass_core::config::{self, ClipboardBackend, Selection};
+use ferropass_core::error::FerropassError;
+use ferropass_core::paranoid;
+
+use base64::{Engine as _, engine::general_purpose};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::io::{self, Write};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+pub trait Clipboard: Send + Sync {
+    fn set_contents(&self, text: &str) -> Result<(), FerropassError>;
+    // None when the backend has no way to read the clipboard back.
+    fn get_contents(&self) -> Option<String>;
+    fn describe(&self) -> &'static str;
+
+    fn clear(&self) -> Result<(), FerropassError> {
+        self.set_contents("")
+    }
+}
+
+fn selection() -> Selection {
+    config::current().clipboard_selection()
+}
+
+// The desktop's clipboard through arboard. It is kept open for the whole
+// process, since on X11 the copied text is only there while its owner is.
+// Copies are marked as secrets in the platform's way, so clipboard history
+// managers that follow it leave them out: `x-kde-passwordManagerHint` on
+// X11, exclusion from history, cloud sync and monitoring on Windows, and
+// `org.nspasteboard.ConcealedType` on macOS.
+pub struct SystemClipboard {
+    clipboard: Mutex<arboard::Clipboard>,
+}
+
+impl SystemClipboard {
+    fn connect() -> Result<SystemClipboard, FerropassError> {
+        let clipboard = arboard::Clipboard::new().map_err(|e| FerropassError::Io(format!("Failed to initialize clipboard: {}", e)))?;
+        Ok(SystemClipboard { clipboard: Mutex::new(clipboard) })
+    }
+
+    fn clipboard(&self) -> std::sync::MutexGuard<'_, arboard::Clipboard> {
+        self.clipboard.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+impl Clipboard for SystemClipboard {
+    fn set_contents(&self, text: &str) -> Result<(), FerropassError> {
+        use arboard::{LinuxClipboardKind, SetExtLinux};
+
+        let mut clipboard = self.clipboard();
+        if selection().clipboard() {
+            clipboard.set().clipboard(LinuxClipboardKind::Clipboard).exclude_from_history().text(text)
+                .map_err(|e| FerropassError::Io(format!("Failed to copy to clipboard: {}", e)))?;
+        }
+        if selection().primary() {
+            clipboard.set().clipboard(LinuxClipboardKind::Primary).exclude_from_history().text(text)
+                .map_err(|e| FerropassError::Io(format!("Failed to copy to primary selection: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    fn get_contents(&self) -> Option<String> {
+        use arboard::{GetExtLinux, LinuxClipboardKind};
+
+        let kind = if selection().clipboard() { LinuxClipboardKind::Clipboard } else { LinuxClipboardKind::Primary };
+        self.clipboard().get().clipboard(kind).text().ok()
+    }
+
+    fn describe(&self) -> &'static str {
+        "system clipboard"
+    }
+
+    fn clear(&self) -> Result<(), FerropassError> {
+        use arboard::{ClearExtLinux, LinuxClipboardKind};
+
+        let mut clipboard = self.clipboard();
+        if selection().clipboard() {
+            clipboard.clear_with().clipboard(LinuxClipboardKind::Clipboard)
+                .map_err(|e| FerropassError::Io(format!("Failed to clear clipboard: {}", e)))?;
+        }
+        if selection().primary() {
+            clipboard.clear_with().clipboard(LinuxClipboardKind::Primary)
+                .map_err(|e| FerropassError::Io(format!("Failed to clear primary selection: {}", e)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten")))))]
+impl Clipboard for SystemClipboard {
+    fn set_contents(&self, text: &str) -> Result<(), FerropassError> {
+        if selection().primary() {
+            return Err(FerropassError::Io("There is no primary selection on this platform".to_string()));
+        }
+
+        let mut clipboard = self.clipboard();
+        let set = clipboard.set();
+        #[cfg(windows)]
+        let set = arboard::SetExtWindows::exclude_from_monitoring(set);
+        #[cfg(target_os = "macos")]
+        let set = arboard::SetExtApple::exclude_from_history(set);
+
+        set.text(text).map_err(|e| FerropassError::Io(format!("Failed to copy to clipboard: {}", e)))
+    }
+
+    fn get_contents(&self) -> Option<String> {
+        self.clipboard().get_text().ok()
+    }
+
+    fn describe(&self) -> &'static str {
+        "system clipboard"
+    }
+
+    fn clear(&self) -> Result<(), FerropassError> {
+        self.clipboard().clear().map_err(|e| FerropassError::Io(format!("Failed to clear clipboard: {}", e)))
+    }
+}
+
+// Wayland's clipboard, through the data-control protocol that wlroots
+// compositors and KDE support. The system clipboard is X11's, which on
+// Wayland works for XWayland programs at best. Copies are marked as
+// sensitive, so clipboard managers that honour it leave them out of their
+// history.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub struct WaylandClipboard;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl WaylandClipboard {
+    fn connect() -> Result<(), FerropassError> {
+        use wl_clipboard_rs::paste::{self, ClipboardType, Error, Seat};
+
+        match paste::get_mime_types(ClipboardType::Regular, Seat::Unspecified) {
+            Ok(_) | Err(Error::NoSeats | Error::ClipboardEmpty | Error::NoMimeType) => Ok(()),
+            Err(e) => Err(FerropassError::Io(format!("Failed to initialize Wayland clipboard: {}", e))),
+        }
+    }
+
+    fn copy_type() -> wl_clipboard_rs::copy::ClipboardType {
+        use wl_clipboard_rs::copy::ClipboardType;
+
+        match selection() {
+            Selection::Clipboard => ClipboardType::Regular,
+            Selection::Primary => ClipboardType::Primary,
+            Selection::Both => ClipboardType::Both,
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Clipboard for WaylandClipboard {
+    fn set_contents(&self, text: &str) -> Result<(), FerropassError> {
+        use wl_clipboard_rs::copy::{MimeType, Options, Source};
+
+        let mut options = Options::new();
+        options.clipboard(Self::copy_type()).sensitive(true);
+        options.copy(Source::Bytes(text.as_bytes().into()), MimeType::Text)
+            .map_err(|e| FerropassError::Io(format!("Failed to copy to clipboard: {}", e)))
+    }
+
+    fn get_contents(&self) -> Option<String> {
+        use std::io::Read;
+        use wl_clipboard_rs::paste::{self, ClipboardType, MimeType, Seat};
+
+        let clipboard = if selection().clipboard() { ClipboardType::Regular } else { ClipboardType::Primary };
+        let (mut pipe, _) = paste::get_contents(clipboard, Seat::Unspecified, MimeType::Text).ok()?;
+        let mut contents = String::new();
+        pipe.read_to_string(&mut contents).ok()?;
+        Some(contents)
+    }
+
+    fn describe(&self) -> &'static str {
+        "Wayland clipboard"
+    }
+
+    fn clear(&self) -> Result<(), FerropassError> {
+        use wl_clipboard_rs::copy::{self, Seat};
+
+        copy::clear(Self::copy_type(), Seat::All)
+            .map_err(|e| FerropassError::Io(format!("Failed to clear clipboard: {}", e)))
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub struct WaylandClipboard;
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+impl WaylandClipboard {
+    fn connect() -> Result<(), FerropassError> {
+        Err(FerropassError::Io("Wayland is not supported on this platform".to_string()))
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+impl Clipboard for WaylandClipboard {
+    fn set_contents(&self, _text: &str) -> Result<(), FerropassError> {
+        Self::connect()
+    }
+
+    fn get_contents(&self) -> Option<String> {
+        None
+    }
+
+    fn describe(&self) -> &'static str {
+        "Wayland clipboard"
+    }
+}
+
+// The OSC 52 escape sequence, which asks the terminal to set the clipboard
+// on the machine it runs on. This works over SSH with no clipboard on the
+// remote side, if the terminal allows it; many only do once it is enabled in
+// their settings. Inside tmux the sequence is passed through to the outer
+// terminal, which needs `allow-passthrough` on.
+pub struct Osc52Clipboard;
+
+impl Osc52Clipboard {
+    fn sequence(text: &str) -> String {
+        let target = match selection() {
+            Selection::Clipboard => "c",
+            Selection::Primary => "p",
+            Selection::Both => "pc",
+        };
+        let osc = format!("\x1b]52;{};{}\x07", target, general_purpose::STANDARD.encode(text));
+        if env::var_os("TMUX").is_some() {
+            format!("\x1bPtmux;{}\x1b\\", osc.replace('\x1b', "\x1b\x1b"))
+        } else {
+            osc
+        }
+    }
+}
+
+impl Clipboard for Osc52Clipboard {
+    fn set_contents(&self, text: &str) -> Result<(), FerropassError> {
+        let mut tty = open_tty().map_err(|e| FerropassError::Io(format!("Failed to reach the terminal: {}", e)))?;
+        tty.write_all(Self::sequence(text).as_bytes())
+            .and_then(|()| tty.flush())
+            .map_err(|e| FerropassError::Io(format!("Failed to copy to clipboard: {}", e)))
+    }
+
+    fn get_contents(&self) -> Option<String> {
+        None
+    }
+
+    fn describe(&self) -> &'static str {
+        "OSC 52 through the terminal"
+    }
+}
+
+static BACKEND: OnceLock<Result<Box<dyn Clipboard>, FerropassError>> = OnceLock::new();
+
+// A hash of the last secret copied, so it can be cleared on exit or lock if
+// it is still in the clipboard.
+static COPIED: Mutex<Option<String>> = Mutex::new(None);
+
+fn current() -> Result<&'static dyn Clipboard, FerropassError> {
+    paranoid::ensure_allowed("Clipboard access")?;
+
+    BACKEND.get_or_init(detect).as_deref().map_err(Clone::clone)
+}
+
+fn detect() -> Result<Box<dyn Clipboard>, FerropassError> {
+    match config::current().clipboard_backend() {
+        ClipboardBackend::System => SystemClipboard::connect().map(|clipboard| Box::new(clipboard) as Box<dyn Clipboard>),
+        ClipboardBackend::Wayland => WaylandClipboard::connect().map(|()| Box::new(WaylandClipboard) as Box<dyn Clipboard>),
+        ClipboardBackend::Osc52 => Ok(Box::new(Osc52Clipboard)),
+        ClipboardBackend::Auto => detect_auto(),
+    }
+}
+
+fn detect_auto() -> Result<Box<dyn Clipboard>, FerropassError> {
+    let mut errors = Vec::new();
+
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        match WaylandClipboard::connect() {
+            Ok(()) => return Ok(Box::new(WaylandClipboard)),
+            Err(e) => errors.push(e),
+        }
+    }
+    match SystemClipboard::connect() {
+        Ok(clipboard) => return Ok(Box::new(clipboard)),
+        Err(e) => errors.push(e),
+    }
+    if open_tty().is_ok() {
+        return Ok(Box::new(Osc52Clipboard));
+    }
+
+    Err(FerropassError::Io(format!("No clipboard could be reached in {}: {}", display_server(), errors.iter().map(FerropassError::to_string).collect::<Vec<_>>().join("; "))))
+}
+
+// What the environment says it is running under, for error messages.
+fn display_server() -> &'static str {
+    match env::var("XDG_SESSION_TYPE").unwrap_or_default().as_str() {
+        "wayland" => "this Wayland session",
+        "x11" => "this X11 session",
+        "tty" => "this text console session",
+        _ if env::var_os("WAYLAND_DISPLAY").is_some() => "this Wayland session",
+        _ if env::var_os("DISPLAY").is_some() => "this X11 session",
+        _ if cfg!(any(windows, target_os = "macos")) => "this desktop session",
+        _ => "a session with no display server (neither WAYLAND_DISPLAY nor DISPLAY is set)",
+    }
+}
+
+#[cfg(unix)]
+fn open_tty() -> io::Result<Box<dyn Write>> {
+    Ok(Box::new(std::fs::OpenOptions::new().write(true).open("/dev/tty")?))
+}
+
+#[cfg(not(unix))]
+fn open_tty() -> io::Result<Box<dyn Write>> {
+    use std::io::IsTerminal;
+
+    if io::stdout().is_terminal() {
+        Ok(Box::new(io::stdout()))
+    } else {
+        Err(io::Error::other("output is not a terminal"))
+    }
+}
+
+pub fn copy_to_clipboard(text: &str) -> Result<(), FerropassError> {
+    current()?.set_contents(text)?;
+    *COPIED.lock().unwrap_or_else(|e| e.into_inner()) = Some(digest(text));
+    Ok(())
+}
+
+// Clears the clipboard whatever it holds.
+pub fn clear() -> Result<(), FerropassError> {
+    current()?.clear()?;
+    COPIED.lock().unwrap_or_else(|e| e.into_inner()).take();
+    Ok(())
+}
+
+// Clears the last secret copied, if nothing else was copied over it since.
+pub fn clear_copied() -> Result<(), FerropassError> {
+    match COPIED.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        Some(copied) => clear_if_unchanged(&copied),
+        None => Ok(()),
+    }
+}
+
+// Picks the clipboard without touching its contents, and says which it is.
+pub fn check_backend() -> Result<&'static str, FerropassError> {
+    Ok(current()?.describe())
+}
+
+// Clears the clipboard in the background once `after` has passed, unless
+// something else was copied in the meantime. Only a hash of the text is kept
+// to tell. If ferropass exits first, an X11 clipboard is emptied anyway,
+// since the selection belongs to the process.
+pub fn clear_later(text: &str, after: Duration) -> thread::JoinHandle<()> {
+    let copied = digest(text);
+    thread::spawn(move || {
+        thread::sleep(after);
+
+        let mut last = COPIED.lock().unwrap_or_else(|e| e.into_inner());
+        if last.as_ref() == Some(&copied) {
+            last.take();
+        }
+        drop(last);
+        let _ = clear_if_unchanged(&copied);
+    })
+}
+
+// A backend that can't read the clipboard back is cleared regardless; a
+// password left behind is worse than losing something copied since.
+fn clear_if_unchanged(copied: &str) -> Result<(), FerropassError> {
+    let clipboard = current()?;
+
+    match clipboard.get_contents() {
+        Some(contents) if digest(&contents) != copied => Ok(()),
+        _ => clipboard.clear(),
+    }
+}
+
+fn digest(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
@@ -0,0 +1,74 @@
+use crate::cli::Cli;
+
+use ferropass_core::encryption::{encrypt_and_save_database, load_and_decrypt_database, EntryKey, SessionKey};
+use ferropass_core::error::FerropassError;
+use ferropass_core::export::{select, write_plaintext, Exporter, Selection};
+use ferropass_core::formats::Registry;
+use ferropass_core::models::{Account, Database};
+use ferropass_core::storage::FileStorage;
+
+use std::path::Path;
+
+// Writes the chosen entries to a new database file under a one-off password,
+// so they can be handed to someone without sharing the master passkey.
+pub fn run(
+    filepath: &Path,
+    output: &Path,
+    selection: &Selection,
+    plaintext: Option<String>,
+    passkey_file: Option<&Path>,
+) -> Result<(), FerropassError> {
+    if output.exists() {
+        return Err(FerropassError::Validation(format!("{} already exists", output.display())));
+    }
+
+    let registry = Registry::new();
+    let exporter = plaintext.map(|name| registry.exporter(&name)).transpose()?;
+
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    let (database, entry_key) = load_and_decrypt_database(&FileStorage, filepath, &passkey)?;
+
+    if let Some(exporter) = exporter {
+        let accounts = select(&database, selection, filepath)?;
+        return export_plaintext(&accounts, output, exporter, &entry_key);
+    }
+
+    let mut exported = Database::new();
+    for account in select(&database, selection, filepath)? {
+        exported.add_account(account.clone());
+    }
+
+    let export_password = Cli::prompt_for_valid_passkey("Enter a password for the export")?;
+    let key = SessionKey::generate(&export_password)?;
+    key.entry_key().reseal_all(&mut exported, &entry_key)?;
+
+    encrypt_and_save_database(&FileStorage, &exported, output, &key)?;
+
+    println!("Exported {} account(s) to {}", exported.get_accounts().len(), output.display());
+    println!("It opens like any other database, with the export password instead of your passkey.");
+    Ok(())
+}
+
+// Writes the accounts out unencrypted, only after a warning and the user
+// typing a confirmation, into a file only they can read.
+fn export_plaintext(accounts: &[&Account], output: &Path, exporter: &dyn Exporter, entry_key: &EntryKey) -> Result<(), FerropassError> {
+    println!("WARNING: {} will hold {} password(s) UNENCRYPTED.", output.display(), accounts.len());
+    println!("Anyone who can read the file, or a backup, sync folder or disk it ends up on, gets every one of them.");
+    println!("Only export in plain text to move to another password manager or for an audit, and delete the file");
+    println!("securely as soon as you're done with it.");
+
+    let typed = Cli::prompt_input("Type 'plaintext' to write the unencrypted file: ")?;
+    if typed != "plaintext" {
+        return Err(FerropassError::Validation("Export cancelled".to_string()));
+    }
+
+    let count = write_plaintext(accounts, output, exporter, entry_key)?;
+    println!("Exported {} account(s) to {} in plain text, readable only by you.", count, output.display());
+    Ok(())
+}
+
+pub fn list_formats() {
+    for exporter in Registry::new().get_exporters() {
+        println!("{:<16} {}", exporter.name(), exporter.description());
+    }
+}
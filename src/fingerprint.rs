@@ -0,0 +1,30 @@
+use ferropass_core::config;
+use ferropass_core::error::FerropassError;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+const STATE_FILE: &str = "known-vaults.json";
+
+// The fingerprint this machine last saw for each vault path. A vault's
+// fingerprint comes from its data key, which no passkey change or re-save
+// alters, so a different one at the same path means a different vault was
+// put there.
+pub fn check(filepath: &Path, fingerprint: &str) -> Result<Option<String>, FerropassError> {
+    let vault = config::vault_id(filepath);
+    config::update_state(STATE_FILE, |known: &mut HashMap<String, String>| match known.get(&vault) {
+        Some(previous) if previous == fingerprint => None,
+        Some(previous) => Some(previous.clone()),
+        None => {
+            known.insert(vault, fingerprint.to_string());
+            None
+        }
+    })
+}
+
+// Accepts the vault now at this path as the one expected there.
+pub fn remember(filepath: &Path, fingerprint: &str) -> Result<(), FerropassError> {
+    config::update_state(STATE_FILE, |known: &mut HashMap<String, String>| {
+        known.insert(config::vault_id(filepath), fingerprint.to_string());
+    })
+}
@@ -0,0 +1,63 @@
+use crate::clipboard::copy_to_clipboard;
+
+use ferropass_core::config;
+use ferropass_core::error::FerropassError;
+use ferropass_core::password::{
+    generate_from_pattern, generate_memorable, generate_passphrase, generate_pin, generate_pronounceable, generate_random_password,
+    Capitalization, Generated, Language, PasswordOptions, Separator, Wordlist,
+};
+
+// Each prints only the password on stdout, so it can be piped into another
+// program, or with `copy` puts it on the clipboard instead; its strength goes
+// to stderr.
+pub fn run(options: &PasswordOptions, copy: bool) -> Result<(), FerropassError> {
+    output(generate_random_password(options)?, copy)
+}
+
+pub fn run_preset(name: &str, copy: bool) -> Result<(), FerropassError> {
+    output(config::current().preset(name)?.generate()?, copy)
+}
+
+pub fn run_pattern(pattern: &str, copy: bool) -> Result<(), FerropassError> {
+    output(generate_from_pattern(pattern)?, copy)
+}
+
+pub fn run_passphrase(
+    language: Language,
+    wordlist: Option<&str>,
+    word_count: Option<usize>,
+    separator: Separator,
+    capitalization: Capitalization,
+    copy: bool,
+) -> Result<(), FerropassError> {
+    let wordlist = match wordlist {
+        Some(name) => Wordlist::load(name)?,
+        None => Wordlist::builtin(language),
+    };
+    let word_count = word_count.unwrap_or(wordlist.default_word_count());
+    
+    output(generate_passphrase(&wordlist, word_count, separator, capitalization)?, copy)
+}
+
+pub fn run_memorable(word_count: usize, copy: bool) -> Result<(), FerropassError> {
+    output(generate_memorable(word_count)?, copy)
+}
+
+pub fn run_pronounceable(syllables: usize, copy: bool) -> Result<(), FerropassError> {
+    output(generate_pronounceable(syllables)?, copy)
+}
+
+pub fn run_pin(length: usize, copy: bool) -> Result<(), FerropassError> {
+    output(generate_pin(length)?, copy)
+}
+
+fn output(generated: Generated, copy: bool) -> Result<(), FerropassError> {
+    if copy {
+        copy_to_clipboard(&generated.password)?;
+        eprintln!("Password copied to clipboard.");
+    } else {
+        println!("{}", generated.password);
+    }
+    eprintln!("{}", generated.describe());
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use crate::agent;
+use crate::cli::Cli;
+use crate::clipboard::{self, copy_to_clipboard};
+
+use ferropass_core::config;
+use ferropass_core::encryption::load_and_decrypt_database;
+use ferropass_core::error::FerropassError;
+use ferropass_core::storage::FileStorage;
+use ferropass_core::vault::find;
+
+use std::path::Path;
+
+// Hands over one account's password without starting a session: on stdout
+// with `stdout`, where nothing else is written, or through the clipboard,
+// staying until it is cleared so an X11 clipboard keeps it that long.
+pub fn run(filepath: &Path, account: &str, stdout: bool, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    let read_passkey = || Cli::read_passkey_on_terminal(passkey_file, "Enter database passkey: ");
+    // A running agent answers without deriving the key again.
+    let (username, password) = match agent::get(filepath, account, read_passkey)? {
+        Some(found) => found,
+        None => {
+            let (database, entry_key) = load_and_decrypt_database(&FileStorage, filepath, &read_passkey()?)?;
+            let account = find(&database, account, filepath)?;
+            (account.get_username_or_email().to_string(), entry_key.open(account.get_password())?)
+        },
+    };
+
+    if stdout {
+        println!("{}", password);
+        return Ok(());
+    }
+
+    copy_to_clipboard(&password)?;
+    match config::current().clipboard_clear_after() {
+        Some(after) => {
+            eprintln!(
+                "Password for {} copied to clipboard; it is cleared in {} seconds.",
+                username, after.as_secs()
+            );
+            clipboard::clear_later(&password, after).join()
+                .map_err(|_| FerropassError::Io("Failed to clear clipboard".to_string()))
+        },
+        None => {
+            eprintln!("Password for {} copied to clipboard.", username);
+            Ok(())
+        }
+    }
+}
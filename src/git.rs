@@ -0,0 +1,101 @@
+use crate::cli::Cli;
+use crate::merge;
+
+use ferropass_core::encryption::{encrypt_and_save_database, unlock_database};
+use ferropass_core::error::FerropassError;
+use ferropass_core::git::{self, Pulled, VaultPaths};
+use ferropass_core::storage::{FileStorage, VaultStorage};
+
+use std::path::Path;
+
+// Commits the vault if needed, pulls, merges the two databases entry by entry
+// if both sides changed the vault, and pushes. Other files in the repository
+// are merged by git as usual.
+pub fn sync(filepath: &Path, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    // The passkey is only needed to merge the vault.
+    let report = git::sync_with(filepath, |paths, upstream, base| {
+        let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+        merge_vault(filepath, paths, upstream, base, &passkey)
+    })?;
+    let file = git::file_name(filepath);
+
+    if report.committed {
+        println!("Committed local changes to {}", file);
+    }
+    match report.pulled {
+        Pulled::FastForwarded(count) => println!("Pulled {} commit(s) from {}", count, report.upstream),
+        Pulled::Merged(count) => println!("Merged {} commit(s) from {}", count, report.upstream),
+        Pulled::VaultMerged | Pulled::Nothing => {},
+    }
+    if report.pushed {
+        println!("Pushed to {}", report.upstream);
+    } else if report.pulled == Pulled::Nothing {
+        println!("{} is up to date with {}", file, report.upstream);
+    }
+
+    Ok(())
+}
+
+// Both sides edited the vault, so its file is merged as a database rather
+// than as text: git merges everything else, then the vault is replaced with
+// the field-level merge of both versions, against the version in `base`, the
+// commit they have in common, before the merge is committed.
+fn merge_vault(filepath: &Path, paths: &VaultPaths, upstream: &str, base: &str, passkey: &str) -> Result<(), FerropassError> {
+    let (mut database, key) = unlock_database(&FileStorage, filepath, passkey)?;
+    let entry_key = key.entry_key();
+
+    let (mut theirs, their_key) = match paths.load_version("@{u}", passkey)? {
+        Err(FerropassError::InvalidPasskey) => {
+            let passkey = Cli::read_passkey(None, &format!("The passkey was changed on {}; enter that passkey: ", upstream))?;
+            paths.load_version("@{u}", &passkey)??
+        },
+        result => result?,
+    };
+    entry_key.reseal_all(&mut theirs, &their_key)?;
+
+    // A vault first committed on both sides separately, or whose passkey
+    // changed since, has no usable common version; the user then chooses
+    // between the two where entries differ.
+    let ancestor = match paths.load_version(base, passkey) {
+        Ok(Ok((mut ancestor, ancestor_key))) => entry_key.reseal_all(&mut ancestor, &ancestor_key).ok().map(|()| ancestor),
+        _ => None,
+    };
+
+    // git is expected to stop with the vault in conflict; anything else it
+    // couldn't merge is left to the user.
+    let _ = paths.git(&["merge", "-q", "--no-commit", "--no-ff", "@{u}"]);
+    let result = (|| {
+        let unmerged = paths.git(&["diff", "--name-only", "--diff-filter=U", "--relative"])?;
+        let others: Vec<&str> = unmerged.lines()
+            .filter(|name| *name != paths.file && *name != paths.signature)
+            .collect();
+        if !others.is_empty() {
+            return Err(FerropassError::Io(format!("git couldn't merge {}; merge them by hand, then sync again", others.join(", "))));
+        }
+
+        let description = match &ancestor {
+            Some(ancestor) => {
+                merge::merge_three_way(&mut database, ancestor, &theirs, &entry_key, "this copy", upstream)?.describe()
+            },
+            None => merge::merge_databases(&mut database, &theirs, &entry_key, "This copy", upstream)?.describe(),
+        };
+        // git left the vault with conflict markers in it; the save goes over
+        // this copy's version, the one it was opened from.
+        FileStorage.write_blob(filepath, &paths.git_bytes(&["show", &format!("HEAD:./{}", paths.file)])?)?;
+        encrypt_and_save_database(&FileStorage, &database, filepath, &key)?;
+
+        let mut add = vec!["add", "--"];
+        add.extend(paths.existing());
+        paths.git(&add)?;
+        let message = format!("Merge {} into {}: {}", upstream, paths.file, description);
+        paths.git(&["commit", "-q", "-m", &message])?;
+
+        println!("Merged {} with {}: {}.", paths.file, upstream, description);
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = paths.git(&["merge", "--abort"]);
+    }
+    result
+}
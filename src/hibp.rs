@@ -0,0 +1,60 @@
+use ferropass_core::config;
+use ferropass_core::error::FerropassError;
+use ferropass_core::paranoid;
+
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Looks passwords up in Have I Been Pwned's Pwned Passwords by k-anonymity:
+// only the first five hex digits of a password's SHA-1 hash are sent, and the
+// rest of the hash is looked for among the few hundred suffixes that come
+// back. Responses are padded with fake suffixes so their size doesn't give
+// the prefix away, and each prefix is only fetched once.
+pub struct Checker {
+    agent: ureq::Agent,
+    ranges: HashMap<String, HashMap<String, u64>>,
+}
+
+impl Checker {
+    pub fn new() -> Result<Self, FerropassError> {
+        paranoid::ensure_allowed("Network access")?;
+        
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .user_agent(concat!("ferropass/", env!("CARGO_PKG_VERSION")))
+            .build();
+        
+        Ok(Checker { agent, ranges: HashMap::new() })
+    }
+    
+    // How many times the password appears in known breaches; 0 if never.
+    pub fn breach_count(&mut self, password: &str) -> Result<u64, FerropassError> {
+        let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+        
+        if !self.ranges.contains_key(prefix) {
+            let range = self.fetch(prefix)?;
+            self.ranges.insert(prefix.to_string(), range);
+        }
+        
+        Ok(self.ranges[prefix].get(suffix).copied().unwrap_or(0))
+    }
+    
+    fn fetch(&self, prefix: &str) -> Result<HashMap<String, u64>, FerropassError> {
+        let url = format!("{}{}", config::current().hibp_url(), prefix);
+        let body = self.agent.get(&url)
+            .set("Add-Padding", "true")
+            .call()
+            .map_err(|e| FerropassError::Io(format!("Error querying Have I Been Pwned: {}", e)))?
+            .into_string()
+            .map_err(|e| FerropassError::Io(format!("Error reading the response from {}: {}", url, e)))?;
+        
+        // Padding entries have a count of 0 and are dropped.
+        Ok(body.lines()
+            .filter_map(|line| line.trim().split_once(':'))
+            .filter_map(|(suffix, count)| Some((suffix.to_uppercase(), count.parse::<u64>().ok()?)))
+            .filter(|&(_, count)| count > 0)
+            .collect())
+    }
+}
@@ -0,0 +1,64 @@
+use crate::cli::Cli;
+use crate::merge;
+use crate::pager;
+
+use ferropass_core::encryption::{EncryptedData, EntryKey};
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::{format_timestamp, Change, Database};
+use ferropass_core::storage::FileStorage;
+use ferropass_core::throttle;
+
+use std::path::Path;
+
+// Lists the saves recorded in a vault's journal since its last full save,
+// oldest first, with the entries each one added, changed or removed and the
+// fields that changed. Secrets are never shown.
+pub fn run(filepath: &Path, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    let encrypted_data = EncryptedData::read_signed(&FileStorage, filepath)?;
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    let key = throttle::guard(filepath, || encrypted_data.unlock_key(&passkey))?;
+    let entry_key = encrypted_data.entry_key(&key);
+
+    let mut database = encrypted_data.open_base(&key)?;
+    let history = encrypted_data.journal_history(&key)?;
+
+    let mut out = format!("History of {} (revision {})\n", filepath.display(), encrypted_data.revision());
+    out.push_str(&format!("  Last full save: {} entries\n", database.get_accounts().len()));
+    if history.is_empty() {
+        out.push_str("  No edits since. Turn on keeping the edit history in the database settings to record every save.\n");
+    }
+
+    for (index, (at, changes)) in history.into_iter().enumerate() {
+        let when = at.map_or_else(|| "unknown time".to_string(), format_timestamp);
+        let mut lines = Vec::new();
+        for change in changes {
+            lines.push(describe(&database, &change, &entry_key)?);
+            database.apply(change);
+        }
+        out.push_str(&format!("  #{:<4} {:<16}  {}\n", index + 1, when, lines.join("; ")));
+    }
+
+    pager::page("History", &out)
+}
+
+fn describe(database: &Database, change: &Change, entry_key: &EntryKey) -> Result<String, FerropassError> {
+    Ok(match change {
+        Change::Upsert(account) => match database.get_account_by_id(account.get_id()) {
+            Some(before) => {
+                let fields = merge::differences(before, account, entry_key)?;
+                if fields.is_empty() {
+                    format!("touched {}", merge::entry_name(account))
+                } else {
+                    format!("changed {} ({})", merge::entry_name(account), merge::field_names(&fields))
+                }
+            },
+            None => format!("added {}", merge::entry_name(account)),
+        },
+        Change::Remove(id) => match database.get_account_by_id(id) {
+            Some(account) => format!("removed {}", merge::entry_name(account)),
+            None => format!("removed entry {}", id),
+        },
+        Change::Settings(_) => "changed settings".to_string(),
+        Change::Logged(record) => format!("noted \"{}\"", record.get_text()),
+    })
+}
@@ -0,0 +1,228 @@
+use crate::cli::Cli;
+
+use ferropass_core::backup;
+use ferropass_core::encryption::{encrypt_and_save_database, load_and_decrypt_database, unlock_database, EntryKey};
+use ferropass_core::error::FerropassError;
+use ferropass_core::formats::Registry;
+use ferropass_core::importers::{self, ImportedEntry};
+use ferropass_core::models::{format_timestamp, Account, Database};
+use ferropass_core::storage::{FileStorage, VaultStorage};
+use ferropass_core::totp::Totp;
+
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportMode {
+    // Add entries that aren't in the vault yet, leave existing ones alone.
+    Merge,
+    // Like `Merge`, but imported entries replace existing ones with the same ID.
+    Overwrite,
+    // Discard every existing entry and keep only the imported ones.
+    Replace,
+}
+
+// What to do with an imported entry that looks like one already in the vault
+// (same description, username and URL) but has a different ID.
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum OnConflict {
+    Skip,
+    Overwrite,
+    Duplicate,
+}
+
+pub fn run(
+    filepath: &Path,
+    source: &Path,
+    format: Option<String>,
+    mode: ImportMode,
+    on_conflict: Option<OnConflict>,
+    passkey_file: Option<&Path>,
+) -> Result<(), FerropassError> {
+    let registry = Registry::new();
+    let importer = match format {
+        Some(name) => registry.importer(&name)?,
+        None => registry.detect(source)?,
+    };
+
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    let _vault_lock = FileStorage.lock(filepath)?;
+    let (mut database, key) = unlock_database(&FileStorage, filepath, &passkey)?;
+
+    let imported = if importer.name() == importers::FERROPASS {
+        let source_passkey = Cli::read_passkey(None, &format!("Enter passkey for {}: ", source.display()))?;
+        let (mut imported, source_key) = load_and_decrypt_database(&FileStorage, source, &source_passkey)?;
+        key.entry_key().reseal_all(&mut imported, &source_key)?;
+        imported
+    } else {
+        seal_entries(importer.read(source)?, &key.entry_key())?
+    };
+
+    let mut backed_up = false;
+    if mode != ImportMode::Merge {
+        confirm_overwrite(filepath, &database)?;
+        back_up(filepath)?;
+        backed_up = true;
+    }
+
+    let mut removed = 0;
+    if mode == ImportMode::Replace {
+        let ids: Vec<String> = database.get_accounts().iter()
+            .map(|account| account.get_id().to_string())
+            .collect();
+        for id in ids {
+            database.remove_account(&id);
+            removed += 1;
+        }
+    }
+
+    let (mut added, mut updated, mut duplicated, mut skipped) = (0, 0, 0, 0);
+    let mut for_all = on_conflict;
+    for account in imported.get_accounts() {
+        if let Some(existing) = database.get_account_by_id_mut(account.get_id()) {
+            if mode == ImportMode::Overwrite {
+                *existing = account.clone();
+                updated += 1;
+            } else {
+                skipped += 1;
+            }
+            continue;
+        }
+
+        let Some(index) = find_collision(&database, account) else {
+            database.add_account(account.clone());
+            added += 1;
+            continue;
+        };
+
+        let choice = match for_all {
+            Some(choice) => choice,
+            None => {
+                let (choice, remember) = ask_on_conflict(&database.get_accounts()[index])?;
+                if remember {
+                    for_all = Some(choice);
+                }
+                choice
+            },
+        };
+        match choice {
+            OnConflict::Skip => skipped += 1,
+            OnConflict::Overwrite => {
+                // A merge gets as far as its first overwrite before asking.
+                if !backed_up {
+                    confirm_overwrite(filepath, &database)?;
+                    back_up(filepath)?;
+                    backed_up = true;
+                }
+                database.get_accounts_mut()[index] = account.clone();
+                updated += 1;
+            },
+            OnConflict::Duplicate => {
+                database.add_account(account.clone());
+                duplicated += 1;
+            },
+        }
+    }
+
+    encrypt_and_save_database(&FileStorage, &database, filepath, &key)?;
+
+    println!("Imported {} into {}: {} added, {} overwritten, {} duplicated, {} skipped, {} removed.",
+        source.display(), filepath.display(), added, updated, duplicated, skipped, removed);
+    if importer.is_plain_text() {
+        println!("{} is a {} in plain text; delete it securely once you've checked the import.", source.display(), importer.description());
+    }
+    Ok(())
+}
+
+pub fn list_formats() {
+    for importer in Registry::new().get_importers() {
+        println!("{:<16} {}", importer.name(), importer.description());
+    }
+}
+
+fn back_up(filepath: &Path) -> Result<(), FerropassError> {
+    let backup_path = backup::snapshot(filepath)?;
+    println!("Backed up {} to {}", filepath.display(), backup_path.display());
+    Ok(())
+}
+
+pub fn find_collision(database: &Database, account: &Account) -> Option<usize> {
+    let same = |a: Option<&str>, b: Option<&str>| a.unwrap_or("").trim().eq_ignore_ascii_case(b.unwrap_or("").trim());
+
+    database.get_accounts().iter().position(|existing| {
+        same(Some(existing.get_username_or_email()), Some(account.get_username_or_email()))
+            && same(existing.get_description().as_deref(), account.get_description().as_deref())
+            && same(existing.get_url(), account.get_url())
+    })
+}
+
+// Asks about one collision; a capital letter applies the answer to every
+// collision still to come.
+pub fn ask_on_conflict(existing: &Account) -> Result<(OnConflict, bool), FerropassError> {
+    println!(
+        "{} ({}) is already in the vault, last edited {}.",
+        existing.get_description().as_deref().unwrap_or("An entry"),
+        existing.get_username_or_email(),
+        format_timestamp(existing.get_updated_at())
+    );
+
+    loop {
+        let choice = Cli::prompt_input("Skip, overwrite or duplicate it? (s/o/d, S/O/D for all remaining, default s): ")?;
+        let remember = choice.chars().next().is_some_and(|c| c.is_uppercase());
+        match choice.to_lowercase().as_str() {
+            "" | "s" => return Ok((OnConflict::Skip, remember)),
+            "o" => return Ok((OnConflict::Overwrite, remember)),
+            "d" => return Ok((OnConflict::Duplicate, remember)),
+            _ => println!("Please enter s, o or d."),
+        }
+    }
+}
+
+// Turns another password manager's logins into accounts, the title becoming
+// the description. Entries with neither a password nor notes are left out.
+fn seal_entries(entries: Vec<ImportedEntry>, entry_key: &EntryKey) -> Result<Database, FerropassError> {
+    let mut imported = Database::new();
+
+    for entry in entries {
+        if entry.password.is_empty() && entry.notes.is_none() {
+            continue;
+        }
+
+        let mut account = Account::new(entry.username, entry.title, entry_key.seal(&entry.password)?);
+        account.set_url(entry.url);
+        account.set_group(entry.group);
+        account.set_tags(entry.tags);
+        account.set_notes(entry.notes);
+
+        if let Some(secret) = entry.totp {
+            match Totp::parse(&secret) {
+                Ok(_) => account.set_totp(Some(entry_key.seal(&secret)?)),
+                Err(e) => eprintln!(
+                    "Warning: the TOTP secret of {} was not imported: {}",
+                    account.get_description().as_deref().unwrap_or(account.get_username_or_email()), e
+                ),
+            }
+        }
+
+        imported.add_account(account);
+    }
+
+    Ok(imported)
+}
+
+// Destructive modes show what is about to be overwritten and make the user
+// type the vault's name, so a mistyped path or flag doesn't go unnoticed.
+fn confirm_overwrite(filepath: &Path, database: &Database) -> Result<(), FerropassError> {
+    let name = filepath.file_stem()
+        .map_or_else(|| filepath.display().to_string(), |stem| stem.to_string_lossy().to_string());
+
+    println!("This import will overwrite entries in {}.", filepath.display());
+    println!("  Entries:  {}", database.get_accounts().len());
+    println!("  Checksum: {}", database.checksum());
+
+    let typed = Cli::prompt_input(&format!("Type the vault name '{}' to continue: ", name))?;
+    if typed != name {
+        return Err(FerropassError::Validation("Import cancelled".to_string()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use ferropass_core::encryption::{peek_header, upgrade_reason};
+use ferropass_core::error::FerropassError;
+use ferropass_core::signing;
+use ferropass_core::storage::FileStorage;
+
+use std::path::Path;
+
+// Shows what the header says about a vault, without asking for its passkey.
+pub fn run(filepath: &Path) -> Result<(), FerropassError> {
+    let header = peek_header(&FileStorage, filepath)?;
+
+    println!("{}", filepath.display());
+    println!("  Vault ID:        {}", header.vault_id.as_deref().unwrap_or("none yet, it gets one on the next full save"));
+    println!("  Format version:  {}", header.version);
+    println!("  Cipher:          {}", header.cipher);
+    match &header.kdf {
+        Some(kdf) => {
+            println!("  Key derivation:  {} m={} KiB, t={}, p={}", kdf.algorithm, kdf.m_cost, kdf.t_cost, kdf.p_cost);
+            println!("  Pepper:          {}", kdf.pepper.as_deref().unwrap_or("none"));
+        },
+        None => println!("  Key derivation:  not supported by this version of ferropass"),
+    }
+    println!("  Revision:        {}", header.revision);
+    println!("  Journal records: {}", header.journal_len);
+    match &header.signing_key {
+        Some(public_key) => println!("  Signed with:     key {}", signing::short_id(public_key)),
+        None => println!("  Signed with:     not signed"),
+    }
+    match header.recovery_shares {
+        Some((threshold, shares)) => println!("  Recovery shares: any {} of {}", threshold, shares),
+        None => println!("  Recovery shares: not set up"),
+    }
+    if let Some(reason) = upgrade_reason(&FileStorage, filepath) {
+        println!("It can be upgraded when next opened: {}.", reason);
+    }
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::Account;
+
+// A login step is free text that may contain placeholders:
+//   {username}     the account's username or email
+//   {chars:2,5,7}  only these character positions of the password (1-based)
+//   {chars}        positions chosen by the site at login time, asked for
+//                  while walking through the steps
+// Anything else in braces is left as written.
+pub fn render_step(
+    step: &str,
+    account: &Account,
+    open_password: &dyn Fn() -> Result<String, FerropassError>,
+    ask_positions: &mut dyn FnMut() -> Result<String, FerropassError>,
+) -> Result<String, FerropassError> {
+    let mut rendered = String::new();
+    let mut rest = step;
+
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let placeholder = &rest[start + 1..start + len];
+
+        rendered.push_str(&rest[..start]);
+        match placeholder {
+            "username" => rendered.push_str(account.get_username_or_email()),
+            "chars" => {
+                let positions = ask_positions()?;
+                rendered.push_str(&reveal_positions(&open_password()?, &positions)?);
+            },
+            _ => match placeholder.strip_prefix("chars:") {
+                Some(positions) => rendered.push_str(&reveal_positions(&open_password()?, positions)?),
+                None => rendered.push_str(&rest[start..=start + len]),
+            },
+        }
+        rest = &rest[start + len + 1..];
+    }
+
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+// Shows only the requested positions, e.g. "2,5,7" gives "[2]=o [5]=x [7]=q".
+pub fn reveal_positions(secret: &str, positions: &str) -> Result<String, FerropassError> {
+    let chars: Vec<char> = secret.chars().collect();
+
+    let revealed: Vec<String> = positions
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let position: usize = part.parse()
+                .map_err(|_| FerropassError::Validation(format!("Invalid character position '{}'", part)))?;
+
+            match position.checked_sub(1).and_then(|index| chars.get(index)) {
+                Some(c) => Ok(format!("[{}]={}", position, c)),
+                None => Err(FerropassError::Validation(format!("The password has no character {} (it is {} long)", position, chars.len()))),
+            }
+        })
+        .collect::<Result<_, FerropassError>>()?;
+
+    if revealed.is_empty() {
+        return Err(FerropassError::Validation("No character positions given".to_string()));
+    }
+
+    Ok(revealed.join(" "))
+}
+
+// Words that show a login step asks for something besides the password.
+const SECOND_FACTOR_WORDS: [&str; 10] = [
+    "code", "2fa", "otp", "totp", "authenticator", "two-factor", "security key", "yubikey", "sms", "token",
+];
+
+// Whether the recorded procedure includes a second factor, such as "enter the
+// code from the authenticator app".
+pub fn has_second_factor(steps: &[String]) -> bool {
+    steps.iter().any(|step| {
+        let step = step.to_lowercase();
+        SECOND_FACTOR_WORDS.iter().any(|word| step.contains(word))
+    })
+}
@@ -1,16 +1,173 @@
-mod models;
-mod encryption;
-mod password;
 mod cli;
 mod clipboard;
+mod args;
+mod summary;
+mod pager;
+mod verify;
+mod info;
+mod recover;
+mod terminal;
+mod save_queue;
+mod import;
+mod export;
+mod recovery_kit;
+mod login;
+mod recovery_shares;
+mod self_test;
+mod pepper;
+mod change_passkey;
+mod signing;
+mod fingerprint;
+mod generate;
+mod hibp;
+mod audit;
+mod username;
+mod autotype;
+mod get;
+mod phonetic;
+mod merge;
+mod share;
+mod pdf;
+mod git;
+mod restore_backup;
+mod transfer;
+mod history;
+mod qr;
+mod agent;
+mod ssh_agent;
+mod rpc;
 
-use cli::CLI;
+use args::{Args, Command};
+use clap::Parser;
+use cli::Cli;
+use ferropass_core::{entropy, notice, paranoid};
+use ferropass_core::export::Selection;
+use ferropass_core::password::{PasswordOptions, DEFAULT_MEMORABLE_WORDS};
+use ferropass_core::storage::{MemoryStorage, VaultStorage};
+use import::ImportMode;
 use std::process::exit;
+use std::sync::Arc;
 
 fn main() {
-    let mut cli = CLI::new();
+    let args = Args::parse();
+    notice::set_handler(|notice| eprintln!("{}", notice));
     
-    if let Err(e) = cli.run() {
+    if args.paranoid {
+        paranoid::enable();
+    }
+    
+    if args.ephemeral && args.command.is_some() {
+        eprintln!("Error: --ephemeral only applies to the interactive session");
+        exit(2);
+    }
+    
+    if let Err(e) = entropy::ensure() {
+        eprintln!("Warning: {}. New keys and passwords can't be generated until it is available.", e);
+    }
+    
+    let result = match args.command {
+        Some(Command::Summary { file, since, max_age, passkey_file, mail, sendmail }) => {
+            summary::run(&file, since, max_age, passkey_file.as_deref(), mail.as_deref(), &sendmail)
+        },
+        Some(Command::History { file, passkey_file }) => history::run(&file, passkey_file.as_deref()),
+        Some(Command::Info { file }) => info::run(&file),
+        Some(Command::Verify { file, passkey_file }) => verify::run(&file, passkey_file.as_deref()),
+        Some(Command::Recover { file, output, passkey_file }) => {
+            recover::run(&file, output.as_deref(), passkey_file.as_deref())
+        },
+        Some(Command::Import { file: Some(file), source: Some(source), format, replace, overwrite, on_conflict, list_formats: false, passkey_file }) => {
+            let mode = if replace {
+                ImportMode::Replace
+            } else if overwrite {
+                ImportMode::Overwrite
+            } else {
+                ImportMode::Merge
+            };
+            import::run(&file, &source, format, mode, on_conflict, passkey_file.as_deref())
+        },
+        Some(Command::Import { .. }) => {
+            import::list_formats();
+            Ok(())
+        },
+        Some(Command::ImportEntry { file, bundle, passkey_file }) => {
+            share::import_entry(&file, bundle.as_deref(), passkey_file.as_deref())
+        },
+        Some(Command::QrExport { file, entries, light_background, passkey_file }) => {
+            qr::export(&file, &entries, light_background, passkey_file.as_deref())
+        },
+        Some(Command::QrImport { file, passkey_file }) => qr::import(&file, passkey_file.as_deref()),
+        Some(Command::Merge { file, other, passkey_file }) => merge::run(&file, &other, passkey_file.as_deref()),
+        Some(Command::Sync { file, passkey_file }) => git::sync(&file, passkey_file.as_deref()),
+        Some(Command::Send { file, port }) => transfer::send(&file, port),
+        Some(Command::Receive { code, from, output }) => transfer::receive(code.as_deref(), from.as_deref(), output.as_deref()),
+        Some(Command::Export { file: Some(file), output: Some(output), ids, tags, groups, search, plaintext, list_formats: false, passkey_file }) => {
+            let selection = Selection { ids, tags, groups, search };
+            export::run(&file, &output, &selection, plaintext, passkey_file.as_deref())
+        },
+        Some(Command::Export { .. }) => {
+            export::list_formats();
+            Ok(())
+        },
+        Some(Command::RecoveryKit { file, output, passkey_file }) => {
+            recovery_kit::run(&file, output.as_deref(), passkey_file.as_deref())
+        },
+        Some(Command::RecoveryShares { file, threshold, shares, output_dir, passkey_file }) => {
+            recovery_shares::create(&file, threshold, shares, output_dir.as_deref(), passkey_file.as_deref())
+        },
+        Some(Command::ResetPasskey { file }) => recovery_shares::reset_passkey(&file),
+        Some(Command::ChangePasskey { file, passkey_file }) => change_passkey::run(&file, passkey_file.as_deref()),
+        Some(Command::Sign { file, remove, passkey_file }) => signing::run(&file, remove, passkey_file.as_deref()),
+        Some(Command::RestoreBackup { file, list, backup }) => restore_backup::run(&file, list, backup),
+        Some(Command::SelfTest) => self_test::run(),
+        Some(Command::Pepper { file, remove, passkey_file }) => pepper::run(&file, remove, passkey_file.as_deref()),
+        Some(Command::Audit { file, hibp, max_age, passkey_file }) => audit::run(&file, hibp, max_age, passkey_file.as_deref()),
+        Some(Command::Get { file, account, stdout, passkey_file }) => get::run(&file, &account, stdout, passkey_file.as_deref()),
+        Some(Command::Agent { status: true, .. }) => agent::status(),
+        Some(Command::Agent { stop: true, .. }) => agent::stop(),
+        Some(Command::Agent { foreground, lock_after, .. }) => agent::run(lock_after, foreground),
+        Some(Command::Unlock { file, passkey_file }) => agent::unlock_vault(&file, passkey_file.as_deref()),
+        Some(Command::Token { name: None, .. }) => rpc::list_tokens(),
+        Some(Command::Token { name: Some(name), revoke: true, .. }) => rpc::revoke_token(&name),
+        Some(Command::Token { name: Some(name), vaults, allow, .. }) => rpc::create_token(&name, &vaults, &allow),
+        Some(Command::Lock { file }) => agent::lock(file.as_deref()),
+        Some(Command::Gen { preset: Some(preset), copy, .. }) => generate::run_preset(&preset, copy),
+        Some(Command::Gen { pattern: Some(pattern), copy, .. }) => generate::run_pattern(&pattern, copy),
+        Some(Command::Gen { pin: true, pin_length, copy, .. }) => generate::run_pin(pin_length, copy),
+        Some(Command::Gen { memorable: true, words, copy, .. }) => generate::run_memorable(words.unwrap_or(DEFAULT_MEMORABLE_WORDS), copy),
+        Some(Command::Gen { pronounceable: true, syllables, copy, .. }) => generate::run_pronounceable(syllables, copy),
+        Some(Command::Gen { passphrase: true, words, language, wordlist, separator, capitalization, copy, .. }) => {
+            generate::run_passphrase(language, wordlist.as_deref(), words, separator, capitalization, copy)
+        },
+        Some(Command::Gen {
+            length, no_uppercase, no_digits, no_symbols, no_ambiguous, exclude,
+            min_lowercase, min_uppercase, min_digits, min_symbols, start_with_letter, max_repeat, copy, ..
+        }) => {
+            generate::run(&PasswordOptions {
+                length,
+                uppercase: !no_uppercase,
+                digits: !no_digits,
+                symbols: !no_symbols,
+                avoid_ambiguous: no_ambiguous,
+                exclude,
+                min_lowercase,
+                min_uppercase,
+                min_digits,
+                min_symbols,
+                start_with_letter,
+                max_repeat,
+            }, copy)
+        },
+        None => {
+            let storage: Arc<dyn VaultStorage> = if args.ephemeral {
+                Arc::new(MemoryStorage::new())
+            } else {
+                Arc::new(Cli::routed_storage())
+            };
+            Cli::new(storage).run()
+        },
+    };
+    
+    if let Err(e) = result {
         eprintln!("Error: {}", e);
         exit(1);
     }
@@ -0,0 +1,813 @@
+use crate::cli::Cli;
+
+use ferropass_core::backup;
+use ferropass_core::config;
+use ferropass_core::encryption::{encrypt_and_save_database, load_and_decrypt_database, unlock_database, EntryKey, SessionKey};
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::{format_timestamp, Account, Database, Field};
+use ferropass_core::storage::{FileStorage, VaultStorage};
+
+use std::path::{Path, PathBuf};
+
+enum Resolution {
+    Ours,
+    Theirs,
+    Both,
+    Fields(Vec<Field>),
+}
+
+// Values longer than this are cut short in the side-by-side view.
+const COLUMN_WIDTH: usize = 32;
+
+// How a merge went, by entry.
+#[derive(Default)]
+pub struct MergeSummary {
+    pub added: usize,
+    pub unchanged: usize,
+    pub kept: usize,
+    pub taken: usize,
+    pub both: usize,
+}
+
+impl MergeSummary {
+    pub fn changed_anything(&self) -> bool {
+        self.added + self.taken + self.both > 0
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} added, {} identical, {} conflict(s) ({} kept, {} replaced, {} kept both)",
+            self.added, self.unchanged, self.kept + self.taken + self.both, self.kept, self.taken, self.both
+        )
+    }
+}
+
+// How a merge against a common ancestor went.
+#[derive(Default)]
+pub struct ThreeWaySummary {
+    pub added: usize,
+    pub removed: usize,
+    pub updated: usize,
+    pub conflicts: usize,
+}
+
+impl ThreeWaySummary {
+    pub fn changed_anything(&self) -> bool {
+        self.added + self.removed + self.updated + self.conflicts > 0
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} added, {} removed, {} updated, {} conflict(s) resolved",
+            self.added, self.removed, self.updated, self.conflicts
+        )
+    }
+}
+
+// Merges another copy of a vault into `filepath`, e.g. two copies edited on
+// different machines. The other file is left unchanged. Each merge keeps the
+// other copy as it was merged in, which is what both copies have in common
+// from then on, so later merges only ask about fields both copies changed.
+pub fn run(filepath: &Path, other: &Path, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    let _vault_lock = FileStorage.lock(filepath)?;
+    let (mut database, key) = unlock_database(&FileStorage, filepath, &passkey)?;
+    let entry_key = key.entry_key();
+
+    let other_passkey = Cli::prompt_password(&format!("Enter passkey for {} (leave empty if it's the same): ", other.display()))?;
+    let other_passkey = if other_passkey.is_empty() { passkey.clone() } else { other_passkey };
+    let (mut theirs, other_key) = load_and_decrypt_database(&FileStorage, other, &other_passkey)?;
+    entry_key.reseal_all(&mut theirs, &other_key)?;
+
+    let (ours, other_name) = (filepath.display().to_string(), other.display().to_string());
+    let changed = match load_snapshot(&key, &passkey, &entry_key) {
+        Some(base) => {
+            let summary = merge_three_way(&mut database, &base, &theirs, &entry_key, &ours, &other_name)?;
+            if !summary.changed_anything() {
+                println!("{} already has everything in {}.", filepath.display(), other.display());
+            }
+            summary.changed_anything().then(|| summary.describe())
+        },
+        None => {
+            let summary = merge_databases(&mut database, &theirs, &entry_key, &ours, &other_name)?;
+            if !summary.changed_anything() {
+                println!(
+                    "{} already has everything in {}: {} identical, {} conflict(s) kept as they were.",
+                    filepath.display(), other.display(), summary.unchanged, summary.kept
+                );
+            }
+            summary.changed_anything().then(|| summary.describe())
+        },
+    };
+    save_snapshot(&theirs, &key);
+
+    let Some(description) = changed else {
+        return Ok(());
+    };
+
+    let backup_path = backup::snapshot(filepath)?;
+    println!("Backed up {} to {}", filepath.display(), backup_path.display());
+    encrypt_and_save_database(&FileStorage, &database, filepath, &key)?;
+
+    println!("Merged {} into {}: {}.", other.display(), filepath.display(), description);
+    Ok(())
+}
+
+// Brings the entries of `theirs`, already sealed under `entry_key`, into
+// `database`. Entries are matched by ID, or else by the same username and
+// URL. Entries only in one copy are kept, identical ones left alone, and for
+// each entry that differs the user picks a version, or picks field by field;
+// `ours` and `other` name the two copies when asking. Each choice is recorded
+// in the change log.
+pub fn merge_databases(
+    database: &mut Database,
+    theirs: &Database,
+    entry_key: &EntryKey,
+    ours: &str,
+    other: &str,
+) -> Result<MergeSummary, FerropassError> {
+    merge_databases_with(database, theirs, entry_key, ours, other, &mut choose)
+}
+
+// `merge_databases`, with `choose` answering each question: given a prompt,
+// the default and the number of choices, it returns the one picked.
+pub fn merge_databases_with(
+    database: &mut Database,
+    theirs: &Database,
+    entry_key: &EntryKey,
+    ours: &str,
+    other: &str,
+    choose: &mut impl FnMut(&str, usize, usize) -> Result<usize, FerropassError>,
+) -> Result<MergeSummary, FerropassError> {
+    let mut summary = MergeSummary::default();
+    database.merge_change_log(theirs);
+
+    for account in theirs.get_accounts() {
+        let Some(index) = find_match(database, account) else {
+            database.add_account(account.clone());
+            summary.added += 1;
+            continue;
+        };
+
+        let existing = &database.get_accounts()[index];
+        let differences = differences(existing, account, entry_key)?;
+        if differences.is_empty() {
+            summary.unchanged += 1;
+            continue;
+        }
+
+        let id = existing.get_id().to_string();
+        let resolution = resolve(existing, account, &differences, ours, other, choose)?;
+        let text = match &resolution {
+            Resolution::Ours => format!("Merged with {}: kept {}'s version", other, ours),
+            Resolution::Theirs => format!("Merged with {}: took its version", other),
+            Resolution::Both => format!("Merged with {}: kept both versions", other),
+            Resolution::Fields(taken) if taken.is_empty() => format!("Merged with {}: kept {}'s version of every field", other, ours),
+            Resolution::Fields(taken) => format!("Merged with {}: took its {}", other, field_names(taken)),
+        };
+
+        match resolution {
+            Resolution::Ours => summary.kept += 1,
+            Resolution::Theirs => {
+                database.get_accounts_mut()[index] = account.clone();
+                summary.taken += 1;
+            },
+            Resolution::Both => {
+                let copy = if account.get_id() == existing.get_id() { account.duplicate() } else { account.clone() };
+                database.add_account(copy);
+                summary.both += 1;
+            },
+            Resolution::Fields(taken) if taken.is_empty() => summary.kept += 1,
+            Resolution::Fields(taken) => {
+                let merged = &mut database.get_accounts_mut()[index];
+                for field in taken {
+                    merged.take_field(account, field);
+                }
+                summary.taken += 1;
+            },
+        }
+        database.log_change(&id, text);
+    }
+
+    Ok(summary)
+}
+
+// Merges `theirs` into `database` field by field, given `base`, the state
+// both copies started from. A field changed in only one copy takes that
+// change; for one changed in both, the user picks a version after seeing
+// the two side by side, as they do for an entry one copy deleted and the
+// other edited. Those choices go into the change log. Everything is sealed
+// under `entry_key`.
+pub fn merge_three_way(
+    database: &mut Database,
+    base: &Database,
+    theirs: &Database,
+    entry_key: &EntryKey,
+    ours: &str,
+    other: &str,
+) -> Result<ThreeWaySummary, FerropassError> {
+    merge_three_way_with(database, base, theirs, entry_key, ours, other, &mut choose)
+}
+
+// `merge_three_way`, with `choose` answering each question as for
+// `merge_databases_with`.
+pub fn merge_three_way_with(
+    database: &mut Database,
+    base: &Database,
+    theirs: &Database,
+    entry_key: &EntryKey,
+    ours: &str,
+    other: &str,
+    choose: &mut impl FnMut(&str, usize, usize) -> Result<usize, FerropassError>,
+) -> Result<ThreeWaySummary, FerropassError> {
+    let mut summary = ThreeWaySummary::default();
+    let mut log = Vec::new();
+    database.merge_change_log(theirs);
+
+    for account in theirs.get_accounts() {
+        let ancestor = base.get_account_by_id(account.get_id());
+        // Entries created separately in each copy since the last merge are
+        // matched like in a merge without an ancestor.
+        let index = database.get_accounts().iter().position(|existing| existing.get_id() == account.get_id())
+            .or_else(|| find_match(database, account).filter(|index| {
+                ancestor.is_none() && base.get_account_by_id(database.get_accounts()[*index].get_id()).is_none()
+            }));
+
+        let Some(index) = index else {
+            match ancestor {
+                None => {
+                    database.add_account(account.clone());
+                    summary.added += 1;
+                },
+                Some(ancestor) if !differences(ancestor, account, entry_key)?.is_empty() => {
+                    println!();
+                    println!("=== {} was deleted in {} but edited in {} ===", entry_name(account), ours, other);
+                    let prompt = format!("Keep it (1) deleted, or (2) restore {}'s version? (default 2): ", other);
+                    let text = if choose(&prompt, 2, 2)? == 2 {
+                        database.add_account(account.clone());
+                        format!("Merged with {}: restored after it was deleted here but edited there", other)
+                    } else {
+                        format!("Merged with {}: kept deleted though it was edited there", other)
+                    };
+                    log.push((account.get_id().to_string(), text));
+                    summary.conflicts += 1;
+                },
+                Some(_) => {},
+            }
+            continue;
+        };
+
+        let existing = &database.get_accounts()[index];
+        let mut merged = existing.clone();
+        let mut took_any = false;
+        let mut conflicting = Vec::new();
+
+        for field in differences(existing, account, entry_key)? {
+            let changed = |version: &Account| match ancestor {
+                Some(ancestor) => same(field, ancestor, version, entry_key).map(|same| !same),
+                None => Ok(true),
+            };
+            let (ours_changed, theirs_changed) = (changed(existing)?, changed(account)?);
+
+            if ours_changed && theirs_changed {
+                conflicting.push(field);
+            } else if theirs_changed {
+                merged.take_field(account, field);
+                took_any = true;
+            }
+        }
+
+        if !conflicting.is_empty() {
+            println!();
+            println!("=== {}: both copies changed it ===", entry_name(existing));
+            print_side_by_side(existing, account, &conflicting, ours, other);
+
+            let taken = pick_fields(existing, account, &conflicting, ours, other, choose)?;
+            for field in &taken {
+                merged.take_field(account, *field);
+                took_any = true;
+            }
+            log.push((existing.get_id().to_string(), describe_choice(&conflicting, &taken, ours, other)));
+            summary.conflicts += conflicting.len();
+        }
+
+        if took_any {
+            database.get_accounts_mut()[index] = merged;
+            summary.updated += 1;
+        }
+    }
+
+    // Deleted there: gone here too, unless it was edited here since.
+    for ancestor in base.get_accounts() {
+        if theirs.get_account_by_id(ancestor.get_id()).is_some() {
+            continue;
+        }
+        let Some(existing) = database.get_account_by_id(ancestor.get_id()) else {
+            continue;
+        };
+
+        if differences(ancestor, existing, entry_key)?.is_empty() {
+            database.remove_account(ancestor.get_id());
+            summary.removed += 1;
+            continue;
+        }
+
+        println!();
+        println!("=== {} was edited in {} but deleted in {} ===", entry_name(existing), ours, other);
+        let prompt = format!("Keep (1) {}'s version, or (2) delete it? (default 1): ", ours);
+        let text = if choose(&prompt, 1, 2)? == 2 {
+            database.remove_account(ancestor.get_id());
+            format!("Merged with {}: deleted as it was there, though it was edited here", other)
+        } else {
+            format!("Merged with {}: kept though it was deleted there", other)
+        };
+        log.push((ancestor.get_id().to_string(), text));
+        summary.conflicts += 1;
+    }
+
+    if database.get_settings() == base.get_settings() && theirs.get_settings() != base.get_settings() {
+        *database.get_settings_mut() = theirs.get_settings().clone();
+        summary.updated += 1;
+    }
+
+    for (id, text) in log {
+        database.log_change(&id, text);
+    }
+    Ok(summary)
+}
+
+// The other copy of a vault as this machine last merged it in, kept
+// encrypted under the vault's own key in the state directory and found by
+// the vault's fingerprint, which every copy of it shares. Both copies carry
+// on from it, whether or not the merged result was copied back, so it is the
+// common ancestor for the next merge.
+fn snapshot_path(key: &SessionKey) -> Option<PathBuf> {
+    Some(config::state_dir()?.join("merged").join(format!("{}.fp", key.fingerprint())))
+}
+
+// None without a snapshot, or if it no longer opens with the passkey; the
+// merge then asks about each entry that differs instead.
+fn load_snapshot(key: &SessionKey, passkey: &str, entry_key: &EntryKey) -> Option<Database> {
+    let path = snapshot_path(key)?;
+    if !path.exists() {
+        return None;
+    }
+
+    let opened = unlock_database(&FileStorage, &path, passkey)
+        .and_then(|(mut base, base_key)| entry_key.reseal_all(&mut base, &base_key.entry_key()).map(|()| base));
+    match opened {
+        Ok(base) => Some(base),
+        Err(e) => {
+            eprintln!("Warning: can't open the copy saved when this vault was last merged ({}); asking about each difference instead", e);
+            None
+        },
+    }
+}
+
+fn save_snapshot(database: &Database, key: &SessionKey) {
+    let result = snapshot_path(key)
+        .ok_or_else(|| FerropassError::Io("no state directory".to_string()))
+        .and_then(|path| {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| FerropassError::Io(format!("Failed to create {}: {}", dir.display(), e)))?;
+            }
+            encrypt_and_save_database(&FileStorage, database, &path, key)
+        });
+
+    if let Err(e) = result {
+        eprintln!("Warning: failed to keep a copy of the merged vault for the next merge: {}", e);
+    }
+}
+
+// Entries without a URL on either side match on their description instead, as
+// the same username is often used for many sites.
+fn find_match(database: &Database, account: &Account) -> Option<usize> {
+    let accounts = database.get_accounts();
+    let same_site = |existing: &Account| match (existing.get_url(), account.get_url()) {
+        (Some(existing_url), Some(url)) => existing_url.eq_ignore_ascii_case(url),
+        (None, None) => existing.get_description() == account.get_description(),
+        _ => false,
+    };
+
+    accounts.iter().position(|existing| existing.get_id() == account.get_id())
+        .or_else(|| accounts.iter().position(|existing| {
+            same_site(existing) && existing.get_username_or_email().eq_ignore_ascii_case(account.get_username_or_email())
+        }))
+}
+
+// What differs between two versions of an entry.
+pub fn differences(ours: &Account, theirs: &Account, entry_key: &EntryKey) -> Result<Vec<Field>, FerropassError> {
+    let mut differences = Vec::new();
+    for field in Field::ALL {
+        if !same(field, ours, theirs, entry_key)? {
+            differences.push(field);
+        }
+    }
+    Ok(differences)
+}
+
+// Sealed fields are compared once opened, since sealing the same password
+// twice never gives the same text.
+fn same(field: Field, ours: &Account, theirs: &Account, entry_key: &EntryKey) -> Result<bool, FerropassError> {
+    let open_totp = |account: &Account| account.get_totp().map(|totp| entry_key.open(totp)).transpose();
+    let open_ssh_key = |account: &Account| account.get_ssh_key().map(|ssh_key| entry_key.open(ssh_key)).transpose();
+
+    Ok(match field {
+        Field::Username => ours.get_username_or_email() == theirs.get_username_or_email(),
+        Field::Description => ours.get_description() == theirs.get_description(),
+        Field::Url => ours.get_url() == theirs.get_url(),
+        Field::Group => ours.get_group() == theirs.get_group(),
+        Field::Tags => ours.get_tags() == theirs.get_tags(),
+        Field::Password => entry_key.open(ours.get_password())? == entry_key.open(theirs.get_password())?,
+        Field::Notes => ours.get_notes() == theirs.get_notes(),
+        Field::LoginProcedure => ours.get_login_procedure() == theirs.get_login_procedure(),
+        Field::Policy => ours.get_policy() == theirs.get_policy(),
+        Field::Autotype => ours.get_autotype() == theirs.get_autotype(),
+        Field::Totp => open_totp(ours)? == open_totp(theirs)?,
+        Field::SshKey => open_ssh_key(ours)? == open_ssh_key(theirs)?,
+    })
+}
+
+// A field as shown when comparing versions; secrets and notes only say
+// whether they are set.
+fn shown(account: &Account, field: Field) -> String {
+    let or_none = |value: Option<&str>| value.filter(|value| !value.is_empty()).unwrap_or("(none)").to_string();
+
+    match field {
+        Field::Username => account.get_username_or_email().to_string(),
+        Field::Description => or_none(account.get_description().as_deref()),
+        Field::Url => or_none(account.get_url()),
+        Field::Group => or_none(account.get_group()),
+        Field::Tags => or_none(Some(&account.get_tags().join(", "))),
+        Field::Password if account.get_password_updated_at() == 0 => "(hidden)".to_string(),
+        Field::Password => format!("(hidden, set {})", format_timestamp(account.get_password_updated_at())),
+        Field::Notes => if account.get_notes().is_some() { "(hidden)" } else { "(none)" }.to_string(),
+        Field::LoginProcedure if account.get_login_procedure().is_empty() => "(none)".to_string(),
+        Field::LoginProcedure => format!("{} step(s)", account.get_login_procedure().len()),
+        Field::Policy => account.get_policy().map_or_else(|| "(default)".to_string(), |policy| policy.describe()),
+        Field::Autotype => account.get_autotype().unwrap_or("(default)").to_string(),
+        Field::Totp => if account.get_totp().is_some() { "(hidden)" } else { "(none)" }.to_string(),
+        Field::SshKey => if account.get_ssh_key().is_some() { "(hidden)" } else { "(none)" }.to_string(),
+    }
+}
+
+fn clip(text: &str) -> String {
+    if text.chars().count() <= COLUMN_WIDTH {
+        return text.to_string();
+    }
+    let mut clipped: String = text.chars().take(COLUMN_WIDTH - 1).collect();
+    clipped.push('…');
+    clipped
+}
+
+// The two versions of `fields` in columns, this copy's on the left, after
+// when each was last edited.
+fn print_side_by_side(ours: &Account, theirs: &Account, fields: &[Field], ours_name: &str, other_name: &str) {
+    let mut rows = vec![("Edited", format_timestamp(ours.get_updated_at()), format_timestamp(theirs.get_updated_at()))];
+    rows.extend(fields.iter().map(|field| (field.name(), shown(ours, *field), shown(theirs, *field))));
+    let rows: Vec<(&str, String, String)> = rows.into_iter().map(|(name, left, right)| (name, clip(&left), clip(&right))).collect();
+
+    let left_header = clip(&format!("1. {}", ours_name));
+    let width = rows.iter().map(|(_, left, _)| left.chars().count()).chain([left_header.chars().count()]).max().unwrap_or(0);
+    println!("  {:<16} {:<width$}  {}", "", left_header, clip(&format!("2. {}", other_name)));
+    for (name, left, right) in rows {
+        let marker = if left == right { " " } else { "*" };
+        println!("{} {:<16} {:<width$}  {}", marker, name, left, right);
+    }
+}
+
+// Asks which version of each field to keep, suggesting the more recently
+// edited entry's; returns the fields to take from `theirs`.
+fn pick_fields(
+    ours: &Account,
+    theirs: &Account,
+    fields: &[Field],
+    ours_name: &str,
+    other_name: &str,
+    choose: &mut impl FnMut(&str, usize, usize) -> Result<usize, FerropassError>,
+) -> Result<Vec<Field>, FerropassError> {
+    let newer = if theirs.get_updated_at() > ours.get_updated_at() { 2 } else { 1 };
+    let mut taken = Vec::new();
+    for field in fields {
+        let prompt = format!("{}: keep (1) {}'s or (2) {}'s? (default {}): ", field.name(), ours_name, other_name, newer);
+        if choose(&prompt, newer, 2)? == 2 {
+            taken.push(*field);
+        }
+    }
+    Ok(taken)
+}
+
+// A number from 1 to `count`, or `default` for an empty answer.
+fn choose(prompt: &str, default: usize, count: usize) -> Result<usize, FerropassError> {
+    loop {
+        let choice = Cli::prompt_input(prompt)?;
+        if choice.is_empty() {
+            return Ok(default);
+        }
+        match choice.parse() {
+            Ok(number) if (1..=count).contains(&number) => return Ok(number),
+            _ => println!("Please enter a number from 1 to {}.", count),
+        }
+    }
+}
+
+pub fn field_names(fields: &[Field]) -> String {
+    fields.iter().map(|field| field.name()).collect::<Vec<_>>().join(", ")
+}
+
+// For the change log: which copy's version of each conflicting field was kept.
+fn describe_choice(conflicting: &[Field], taken: &[Field], ours: &str, other: &str) -> String {
+    let kept: Vec<Field> = conflicting.iter().copied().filter(|field| !taken.contains(field)).collect();
+    let mut parts = Vec::new();
+    if !kept.is_empty() {
+        parts.push(format!("kept {}'s {}", ours, field_names(&kept)));
+    }
+    if !taken.is_empty() {
+        parts.push(format!("took {}'s {}", other, field_names(taken)));
+    }
+    format!("Merged with {}, both changed: {}", other, parts.join("; "))
+}
+
+pub fn entry_name(account: &Account) -> String {
+    account.get_description().clone().unwrap_or_else(|| account.get_username_or_email().to_string())
+}
+
+// Shows both versions side by side and suggests the more recently edited
+// one.
+fn resolve(
+    ours: &Account,
+    theirs: &Account,
+    differences: &[Field],
+    ours_name: &str,
+    other_name: &str,
+    choose: &mut impl FnMut(&str, usize, usize) -> Result<usize, FerropassError>,
+) -> Result<Resolution, FerropassError> {
+    println!();
+    println!("=== {} differs ===", entry_name(ours));
+    print_side_by_side(ours, theirs, differences, ours_name, other_name);
+
+    let newer = if theirs.get_updated_at() > ours.get_updated_at() { 2 } else { 1 };
+    let prompt = format!(
+        "Keep (1) this vault's version, (2) the other's, (3) both, or (4) choose field by field? (default {}): ",
+        newer
+    );
+    Ok(match choose(&prompt, newer, 4)? {
+        1 => Resolution::Ours,
+        2 => Resolution::Theirs,
+        3 => Resolution::Both,
+        _ => Resolution::Fields(pick_fields(ours, theirs, differences, ours_name, other_name, choose)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::OnceLock;
+
+    fn entry_key() -> &'static EntryKey {
+        static KEY: OnceLock<EntryKey> = OnceLock::new();
+        KEY.get_or_init(|| SessionKey::generate("correct horse battery staple").unwrap().entry_key())
+    }
+
+    fn account(username: &str, url: &str, password: &str) -> Account {
+        let mut account = Account::new(username.to_string(), None, entry_key().seal(password).unwrap());
+        account.set_url(Some(url.to_string()));
+        account
+    }
+
+    fn database(accounts: &[&Account]) -> Database {
+        let mut database = Database::new();
+        for account in accounts {
+            database.add_account((*account).clone());
+        }
+        database
+    }
+
+    fn password(database: &Database, id: &str) -> String {
+        entry_key().open(database.get_account_by_id(id).unwrap().get_password()).unwrap()
+    }
+
+    // Answers each question with the next of `answers`, failing the test if
+    // asked more than that.
+    fn answering(answers: &[usize]) -> impl FnMut(&str, usize, usize) -> Result<usize, FerropassError> + '_ {
+        let mut answers = answers.iter();
+        move |prompt, _, count| {
+            let answer = *answers.next().unwrap_or_else(|| panic!("unexpected question: {}", prompt));
+            assert!((1..=count).contains(&answer));
+            Ok(answer)
+        }
+    }
+
+    fn three_way(ours: &mut Database, base: &Database, theirs: &Database, answers: &[usize]) -> ThreeWaySummary {
+        merge_three_way_with(ours, base, theirs, entry_key(), "here", "there", &mut answering(answers)).unwrap()
+    }
+
+    #[test]
+    fn edits_to_different_fields_are_both_kept() {
+        let entry = account("alice", "https://mail.example.com", "s3cret");
+        let base = database(&[&entry]);
+        let (mut ours, mut theirs) = (base.clone(), base.clone());
+        ours.get_account_by_id_mut(entry.get_id()).unwrap().set_description(Some("Mail".to_string()));
+        theirs.get_account_by_id_mut(entry.get_id()).unwrap().set_password(entry_key().seal("rotated").unwrap());
+
+        let summary = three_way(&mut ours, &base, &theirs, &[]);
+        assert_eq!((summary.updated, summary.conflicts), (1, 0));
+        assert_eq!(ours.get_accounts().len(), 1);
+        assert_eq!(ours.get_account_by_id(entry.get_id()).unwrap().get_description().as_deref(), Some("Mail"));
+        assert_eq!(password(&ours, entry.get_id()), "rotated");
+    }
+
+    #[test]
+    fn edits_to_the_same_field_ask_which_to_keep() {
+        let entry = account("alice", "https://mail.example.com", "s3cret");
+        let base = database(&[&entry]);
+        let mut theirs = base.clone();
+        theirs.get_account_by_id_mut(entry.get_id()).unwrap().set_password(entry_key().seal("theirs").unwrap());
+
+        for (answer, expected) in [(1, "ours"), (2, "theirs")] {
+            let mut ours = base.clone();
+            ours.get_account_by_id_mut(entry.get_id()).unwrap().set_password(entry_key().seal("ours").unwrap());
+
+            let summary = three_way(&mut ours, &base, &theirs, &[answer]);
+            assert_eq!(summary.conflicts, 1);
+            assert_eq!(password(&ours, entry.get_id()), expected);
+            assert!(ours.get_change_log().iter().any(|record| record.get_entry_id() == entry.get_id()));
+        }
+    }
+
+    #[test]
+    fn the_same_edit_on_both_sides_is_no_conflict() {
+        let entry = account("alice", "https://mail.example.com", "s3cret");
+        let base = database(&[&entry]);
+        let (mut ours, mut theirs) = (base.clone(), base.clone());
+        for database in [&mut ours, &mut theirs] {
+            database.get_account_by_id_mut(entry.get_id()).unwrap().set_password(entry_key().seal("rotated").unwrap());
+        }
+
+        let summary = three_way(&mut ours, &base, &theirs, &[]);
+        assert!(!summary.changed_anything());
+        assert_eq!(password(&ours, entry.get_id()), "rotated");
+    }
+
+    #[test]
+    fn deletion_of_an_unedited_entry_is_taken() {
+        let (kept, deleted) = (account("alice", "https://a.example.com", "a"), account("bob", "https://b.example.com", "b"));
+        let base = database(&[&kept, &deleted]);
+
+        let mut ours = base.clone();
+        let summary = three_way(&mut ours, &base, &database(&[&kept]), &[]);
+        assert_eq!(summary.removed, 1);
+        assert!(ours.get_account_by_id(deleted.get_id()).is_none());
+
+        let mut ours = database(&[&kept]);
+        let summary = three_way(&mut ours, &base, &base, &[]);
+        assert!(!summary.changed_anything());
+        assert!(ours.get_account_by_id(deleted.get_id()).is_none());
+    }
+
+    #[test]
+    fn deletion_against_an_edit_asks() {
+        let entry = account("alice", "https://mail.example.com", "s3cret");
+        let base = database(&[&entry]);
+        let mut edited = base.clone();
+        edited.get_account_by_id_mut(entry.get_id()).unwrap().set_password(entry_key().seal("edited").unwrap());
+
+        // Deleted here, edited there: 1 keeps it deleted, 2 restores it.
+        for (answer, restored) in [(1, false), (2, true)] {
+            let mut ours = Database::new();
+            let summary = three_way(&mut ours, &base, &edited, &[answer]);
+            assert_eq!(summary.conflicts, 1);
+            assert_eq!(ours.get_account_by_id(entry.get_id()).is_some(), restored);
+            if restored {
+                assert_eq!(password(&ours, entry.get_id()), "edited");
+            }
+        }
+
+        // Edited here, deleted there: 1 keeps the edit, 2 deletes it.
+        for (answer, kept) in [(1, true), (2, false)] {
+            let mut ours = edited.clone();
+            let summary = three_way(&mut ours, &base, &Database::new(), &[answer]);
+            assert_eq!(summary.conflicts, 1);
+            assert_eq!(ours.get_account_by_id(entry.get_id()).is_some(), kept);
+        }
+    }
+
+    #[test]
+    fn entries_added_on_both_sides_are_all_kept() {
+        let base = Database::new();
+        let mine = account("alice", "https://a.example.com", "a");
+        let yours = account("bob", "https://b.example.com", "b");
+
+        let mut ours = database(&[&mine]);
+        let summary = three_way(&mut ours, &base, &database(&[&yours]), &[]);
+        assert_eq!(summary.added, 1);
+        assert_eq!(ours.get_accounts().len(), 2);
+        assert_eq!(password(&ours, yours.get_id()), "b");
+    }
+
+    #[test]
+    fn the_same_entry_added_on_both_sides_is_matched() {
+        let base = Database::new();
+        let mine = account("alice", "https://mail.example.com", "s3cret");
+        let same = account("ALICE", "https://MAIL.example.com", "s3cret");
+        let mut ours = database(&[&mine]);
+        let summary = three_way(&mut ours, &base, &database(&[&same]), &[1, 1]);
+        assert_eq!(summary.conflicts, 2);
+        assert_eq!(ours.get_accounts().len(), 1);
+        assert_eq!(ours.get_accounts()[0].get_username_or_email(), "alice");
+
+        let different = account("alice", "https://mail.example.com", "other");
+        let mut ours = database(&[&mine]);
+        let summary = three_way(&mut ours, &base, &database(&[&different]), &[2]);
+        assert_eq!(summary.conflicts, 1);
+        assert_eq!(ours.get_accounts().len(), 1);
+        assert_eq!(password(&ours, mine.get_id()), "other");
+    }
+
+    #[test]
+    fn deletions_older_than_the_base_change_nothing() {
+        // Deleted there before the last merge, and kept or added back here
+        // since: the base no longer has it, so it isn't deleted again.
+        let entry = account("alice", "https://mail.example.com", "s3cret");
+        let other = account("bob", "https://b.example.com", "b");
+        let base = database(&[&other]);
+
+        let mut ours = database(&[&entry, &other]);
+        let summary = three_way(&mut ours, &base, &base, &[]);
+        assert!(!summary.changed_anything());
+        assert_eq!(password(&ours, entry.get_id()), "s3cret");
+
+        // Deleted here before the last merge and still gone there: nothing
+        // comes back.
+        let mut ours = Database::new();
+        let summary = three_way(&mut ours, &base, &Database::new(), &[]);
+        assert!(ours.get_accounts().is_empty());
+        assert_eq!(summary.conflicts, 0);
+    }
+
+    #[test]
+    fn settings_changed_only_there_are_taken() {
+        let base = Database::new();
+        let mut theirs = base.clone();
+        theirs.get_settings_mut().set_keep_history(true);
+
+        let mut ours = base.clone();
+        three_way(&mut ours, &base, &theirs, &[]);
+        assert!(ours.get_settings().get_keep_history());
+    }
+
+    #[test]
+    fn two_way_conflicts_resolve_as_chosen() {
+        let entry = account("alice", "https://mail.example.com", "ours");
+        let mut changed = entry.clone();
+        changed.set_password(entry_key().seal("theirs").unwrap());
+        changed.set_notes(Some("from there".to_string()));
+        let theirs = database(&[&changed]);
+
+        let merge = |answers: &[usize]| {
+            let mut ours = database(&[&entry]);
+            let summary = merge_databases_with(&mut ours, &theirs, entry_key(), "here", "there", &mut answering(answers)).unwrap();
+            (ours, summary)
+        };
+
+        let (ours, summary) = merge(&[1]);
+        assert_eq!((summary.kept, summary.changed_anything()), (1, false));
+        assert_eq!(password(&ours, entry.get_id()), "ours");
+
+        let (ours, summary) = merge(&[2]);
+        assert_eq!(summary.taken, 1);
+        assert_eq!(password(&ours, entry.get_id()), "theirs");
+
+        let (ours, summary) = merge(&[3]);
+        assert_eq!(summary.both, 1);
+        assert_eq!(ours.get_accounts().len(), 2);
+        assert_ne!(ours.get_accounts()[0].get_id(), ours.get_accounts()[1].get_id());
+
+        // Field by field: the differing fields are asked about in order,
+        // password before notes.
+        let (ours, summary) = merge(&[4, 2, 1]);
+        assert_eq!(summary.taken, 1);
+        assert_eq!(password(&ours, entry.get_id()), "theirs");
+        assert_eq!(ours.get_account_by_id(entry.get_id()).unwrap().get_notes(), &None);
+
+        let (_, summary) = merge(&[4, 1, 1]);
+        assert_eq!(summary.kept, 1);
+    }
+
+    #[test]
+    fn two_way_merge_adds_and_leaves_identical_entries() {
+        let entry = account("alice", "https://mail.example.com", "s3cret");
+        let resealed = {
+            let mut copy = entry.clone();
+            copy.reseal_password(entry_key().seal("s3cret").unwrap());
+            copy
+        };
+        let added = account("bob", "https://b.example.com", "b");
+
+        let mut ours = database(&[&entry]);
+        let summary = merge_databases_with(&mut ours, &database(&[&resealed, &added]), entry_key(), "here", "there", &mut answering(&[])).unwrap();
+        assert_eq!((summary.added, summary.unchanged), (1, 1));
+        assert_eq!(ours.get_accounts().len(), 2);
+    }
+}
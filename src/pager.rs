@@ -0,0 +1,235 @@
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use crate::terminal as screen;
+use std::io::{self, Write};
+
+use ferropass_core::error::FerropassError;
+
+// Shows `text` in a less-style pager on the alternate screen and wipes the
+// scrollback on exit, so secrets don't linger in the terminal history. Falls
+// back to plain printing on terminals that can't go full screen.
+pub fn page(title: &str, text: &str) -> Result<(), FerropassError> {
+    let mut stdout = io::stdout();
+
+    if !screen::current().supports_full_screen() {
+        println!("{}", text);
+        return Ok(());
+    }
+
+    terminal::enable_raw_mode().map_err(|e| FerropassError::Io(format!("Failed to open pager: {}", e)))?;
+
+    let result = execute!(stdout, EnterAlternateScreen, Hide)
+        .map_err(|e| FerropassError::Io(format!("Failed to open pager: {}", e)))
+        .and_then(|_| Pager::new(title, text).run(&mut stdout));
+
+    let _ = execute!(stdout, Show, LeaveAlternateScreen, Clear(ClearType::Purge), MoveTo(0, 0));
+    let _ = terminal::disable_raw_mode();
+
+    result
+}
+
+struct Pager {
+    title: String,
+    lines: Vec<String>,
+    rows: Vec<String>,
+    width: usize,
+    height: usize,
+    top: usize,
+    query: Option<String>,
+    prompt: Option<String>,
+    message: Option<String>,
+}
+
+impl Pager {
+    fn new(title: &str, text: &str) -> Self {
+        Pager {
+            title: title.to_string(),
+            lines: text.lines().map(|line| line.replace('\t', "    ")).collect(),
+            rows: Vec::new(),
+            width: 80,
+            height: 24,
+            top: 0,
+            query: None,
+            prompt: None,
+            message: None,
+        }
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> Result<(), FerropassError> {
+        let (width, height) = terminal::size().map_err(|e| FerropassError::Io(format!("Failed to read terminal size: {}", e)))?;
+        self.resize(width, height);
+
+        loop {
+            self.draw(out).map_err(|e| FerropassError::Io(format!("Failed to draw pager: {}", e)))?;
+
+            let event = screen::wait_for_event().and_then(|()| event::read());
+            match event.map_err(|e| FerropassError::Io(format!("Failed to read input: {}", e)))? {
+                Event::Key(key) if key.kind != KeyEventKind::Release && !self.handle_key(key) => break,
+                Event::Resize(width, height) => self.resize(width, height),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u16, height: u16) {
+        self.width = (width as usize).max(1);
+        self.height = (height as usize).max(2);
+
+        self.rows.clear();
+        for line in &self.lines {
+            let chars: Vec<char> = line.chars().collect();
+            if chars.is_empty() {
+                self.rows.push(String::new());
+            }
+            for chunk in chars.chunks(self.width) {
+                self.rows.push(chunk.iter().collect());
+            }
+        }
+
+        self.top = self.top.min(self.max_top());
+    }
+
+    fn page_size(&self) -> usize {
+        self.height - 1
+    }
+
+    fn max_top(&self) -> usize {
+        self.rows.len().saturating_sub(self.page_size())
+    }
+
+    // Returns false when the pager should close.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if let Some(prompt) = &mut self.prompt {
+            match key.code {
+                KeyCode::Enter => {
+                    let query = prompt.clone();
+                    self.prompt = None;
+                    if !query.is_empty() {
+                        self.query = Some(query.to_ascii_lowercase());
+                        self.search(true, true);
+                    }
+                },
+                KeyCode::Esc => self.prompt = None,
+                KeyCode::Backspace => {
+                    prompt.pop();
+                },
+                KeyCode::Char(c) => prompt.push(c),
+                _ => {}
+            }
+            return true;
+        }
+
+        self.message = None;
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('j') | KeyCode::Down | KeyCode::Enter => self.scroll_down(1),
+            KeyCode::Char('k') | KeyCode::Up => self.scroll_up(1),
+            KeyCode::Char(' ') | KeyCode::Char('f') | KeyCode::PageDown => self.scroll_down(self.page_size()),
+            KeyCode::Char('b') | KeyCode::PageUp => self.scroll_up(self.page_size()),
+            KeyCode::Char('g') | KeyCode::Home => self.top = 0,
+            KeyCode::Char('G') | KeyCode::End => self.top = self.max_top(),
+            KeyCode::Char('/') => self.prompt = Some(String::new()),
+            KeyCode::Char('n') => self.search(true, false),
+            KeyCode::Char('N') => self.search(false, false),
+            _ => {}
+        }
+
+        true
+    }
+
+    fn scroll_down(&mut self, amount: usize) {
+        self.top = (self.top + amount).min(self.max_top());
+    }
+
+    fn scroll_up(&mut self, amount: usize) {
+        self.top = self.top.saturating_sub(amount);
+    }
+
+    fn search(&mut self, forward: bool, include_current: bool) {
+        let query = match &self.query {
+            Some(query) => query,
+            None => {
+                self.message = Some("No previous search".to_string());
+                return;
+            }
+        };
+
+        let matches = |row: &String| row.to_ascii_lowercase().contains(query.as_str());
+        let found = if forward {
+            let start = if include_current { self.top } else { self.top + 1 };
+            (start..self.rows.len()).find(|&i| matches(&self.rows[i]))
+        } else {
+            (0..self.top).rev().find(|&i| matches(&self.rows[i]))
+        };
+
+        match found {
+            Some(index) => self.top = index.min(self.max_top()),
+            None => self.message = Some("Pattern not found".to_string()),
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        queue!(out, Clear(ClearType::All))?;
+
+        for (screen_row, row) in self.rows.iter().skip(self.top).take(self.page_size()).enumerate() {
+            queue!(out, MoveTo(0, screen_row as u16))?;
+            self.draw_row(out, row)?;
+        }
+
+        let status = if let Some(prompt) = &self.prompt {
+            format!("/{}", prompt)
+        } else if let Some(message) = &self.message {
+            message.clone()
+        } else {
+            let last = (self.top + self.page_size()).min(self.rows.len());
+            let percent = if self.rows.is_empty() { 100 } else { last * 100 / self.rows.len() };
+            format!(" {} | lines {}-{} of {} ({}%) | q quit, / search, n/N next/prev ",
+                self.title, self.top + 1, last, self.rows.len(), percent)
+        };
+        let status: String = status.chars().take(self.width).collect();
+
+        queue!(
+            out,
+            MoveTo(0, (self.height - 1) as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(status),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        out.flush()
+    }
+
+    fn draw_row(&self, out: &mut impl Write, row: &str) -> io::Result<()> {
+        let query = match &self.query {
+            Some(query) => query,
+            None => return queue!(out, Print(row)),
+        };
+
+        let lower = row.to_ascii_lowercase();
+        let mut position = 0;
+
+        while let Some(offset) = lower[position..].find(query.as_str()) {
+            let start = position + offset;
+            let end = start + query.len();
+            queue!(
+                out,
+                Print(&row[position..start]),
+                SetAttribute(Attribute::Reverse),
+                Print(&row[start..end]),
+                SetAttribute(Attribute::Reset)
+            )?;
+            position = end;
+        }
+
+        queue!(out, Print(&row[position..]))
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt::Write as _;
+
+// A4 in points, with 10-point Courier on 12-point lines.
+const PAGE_WIDTH: u32 = 595;
+const PAGE_HEIGHT: u32 = 842;
+const MARGIN: u32 = 56;
+const FONT_SIZE: u32 = 10;
+const LINE_HEIGHT: u32 = 12;
+// Courier is 0.6 em wide, so 80 columns fit between the margins.
+const COLUMNS: usize = 80;
+
+// Lays plain text out as a PDF in a monospaced font, so a sheet that lines up
+// on the terminal lines up on paper too. Only the standard Courier font is
+// used, with nothing embedded, so any PDF viewer or printer can show it;
+// characters outside Latin-1 are printed as '?'.
+pub fn from_text(title: &str, text: &str) -> Vec<u8> {
+    let lines_per_page = ((PAGE_HEIGHT - 2 * MARGIN) / LINE_HEIGHT) as usize;
+    let lines: Vec<String> = text.lines().flat_map(wrap).collect();
+    let pages: Vec<&[String]> = if lines.is_empty() { vec![&[]] } else { lines.chunks(lines_per_page).collect() };
+
+    // Objects 1-3 are the catalog, the page tree and the font, and 4 the
+    // document info; each page then takes two, itself and its contents.
+    let page_ids: Vec<usize> = (0..pages.len()).map(|index| 5 + 2 * index).collect();
+    let mut objects = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            page_ids.iter().map(|id| format!("{} 0 R", id)).collect::<Vec<_>>().join(" "),
+            pages.len()
+        ).into_bytes(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>".to_vec(),
+        [b"<< /Title ".as_slice(), &literal(title), b" /Producer (ferropass) >>"].concat(),
+    ];
+
+    for (page, id) in pages.iter().zip(&page_ids) {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH, PAGE_HEIGHT, id + 1
+        ).into_bytes());
+
+        let mut content = format!("BT /F1 {} Tf {} TL {} {} Td\n", FONT_SIZE, LINE_HEIGHT, MARGIN, PAGE_HEIGHT - MARGIN).into_bytes();
+        for line in page.iter() {
+            content.extend(literal(line));
+            content.extend(b" '\n");
+        }
+        content.extend(b"ET");
+
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend(content);
+        stream.extend(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", index + 1).into_bytes());
+        pdf.extend(object);
+        pdf.extend(b"\nendobj\n");
+    }
+
+    let xref_offset = pdf.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R /Info 4 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1, xref_offset
+    );
+    pdf.extend(trailer.into_bytes());
+    pdf
+}
+
+// Breaks a line too long for the page at spaces, continuing under the start
+// of its text, or of a numbered item's text.
+fn wrap(line: &str) -> Vec<String> {
+    let text = line.trim_start();
+    let number = text.find(". ")
+        .filter(|end| *end > 0 && text[..*end].chars().all(|c| c.is_ascii_digit()))
+        .map_or(0, |end| end + 2);
+    let indent = " ".repeat(line.len() - text.len() + number);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split(' ') {
+        let width = current.chars().count();
+        if width > indent.len() && width + 1 + word.chars().count() > COLUMNS {
+            lines.push(current);
+            current = String::new();
+        }
+        if current.is_empty() {
+            current.push_str(&indent);
+        } else {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    lines.push(current);
+    lines
+}
+
+// A PDF string literal in WinAnsi, which matches Latin-1 for the printable
+// characters a kit uses.
+fn literal(text: &str) -> Vec<u8> {
+    let mut bytes = vec![b'('];
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => bytes.extend([b'\\', c as u8]),
+            ' '..='~' | '\u{a0}'..='\u{ff}' => bytes.push(c as u32 as u8),
+            _ => bytes.push(b'?'),
+        }
+    }
+    bytes.push(b')');
+    bytes
+}
@@ -0,0 +1,48 @@
+use crate::cli::Cli;
+
+use ferropass_core::encryption::{rewrap_and_save, unlock_database};
+use ferropass_core::error::FerropassError;
+use ferropass_core::pepper::load_or_create;
+use ferropass_core::storage::{FileStorage, VaultStorage};
+
+use std::path::Path;
+
+// Re-keys the vault so that deriving its key needs this machine's pepper as
+// well as the passkey, or no longer needs it with `remove`.
+pub fn run(filepath: &Path, remove: bool, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    let _vault_lock = FileStorage.lock(filepath)?;
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    let (database, key) = unlock_database(&FileStorage, filepath, &passkey)?;
+
+    if remove {
+        let Some(id) = key.get_pepper().map(str::to_string) else {
+            return Err(FerropassError::Validation(format!("{} doesn't use a pepper", filepath.display())));
+        };
+
+        let new_key = key.rekey_with_pepper(&passkey, None)?;
+        rewrap_and_save(&FileStorage, &database, filepath, &new_key)?;
+
+        println!("{} no longer needs pepper {}; the passkey alone opens it.", filepath.display(), id);
+        return Ok(());
+    }
+
+    if let Some(id) = key.get_pepper() {
+        return Err(FerropassError::Validation(format!("{} already uses pepper {}", filepath.display(), id)));
+    }
+
+    let (pepper, path, created) = load_or_create()?;
+    if created {
+        println!("Created a new pepper at {}.", path.display());
+    }
+
+    let new_key = key.rekey_with_pepper(&passkey, Some(pepper.id()))?;
+    rewrap_and_save(&FileStorage, &database, filepath, &new_key)?;
+
+    println!("{} now needs pepper {} from {} as well as the passkey.", filepath.display(), pepper.id(), path.display());
+    println!("Keep a copy of the pepper file somewhere safe and offline, and don't sync it with the vault:");
+    println!("without it the vault can't be opened, even with the right passkey.");
+    if FileStorage.latest_version(filepath).is_some() {
+        println!("Backups next to the vault were made before and open without the pepper; delete them if they are synced too.");
+    }
+    Ok(())
+}
@@ -0,0 +1,72 @@
+// The ICAO/NATO spelling alphabet, with its own spellings of "Alfa" and
+// "Juliett", so they are said the same way in any language.
+const LETTERS: [&str; 26] = [
+    "Alfa", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel", "India", "Juliett", "Kilo", "Lima", "Mike",
+    "November", "Oscar", "Papa", "Quebec", "Romeo", "Sierra", "Tango", "Uniform", "Victor", "Whiskey", "X-ray",
+    "Yankee", "Zulu",
+];
+
+const DIGITS: [&str; 10] = ["zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine"];
+
+// Spells `secret` out one character per line for reading it over the phone,
+// e.g. "  3  k  lowercase kilo". Every character says what kind it is, so a
+// listener never has to guess at case, or at whether "oh" was a letter or a
+// digit.
+pub fn spell(secret: &str) -> String {
+    secret.chars()
+        .enumerate()
+        .map(|(index, c)| format!("{:>3}  {}  {}", index + 1, if c == ' ' { '␣' } else { c }, describe(c)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn describe(c: char) -> String {
+    match c {
+        'A'..='Z' => format!("capital {}", LETTERS[c as usize - 'A' as usize].to_uppercase()),
+        'a'..='z' => format!("lowercase {}", LETTERS[c as usize - 'a' as usize].to_lowercase()),
+        '0'..='9' => format!("digit {}", DIGITS[c as usize - '0' as usize]),
+        _ => match symbol_name(c) {
+            Some(name) => format!("symbol: {}", name),
+            None => format!("character U+{:04X}", c as u32),
+        },
+    }
+}
+
+fn symbol_name(c: char) -> Option<&'static str> {
+    Some(match c {
+        ' ' => "space",
+        '!' => "exclamation mark",
+        '"' => "double quote",
+        '#' => "hash",
+        '$' => "dollar sign",
+        '%' => "percent sign",
+        '&' => "ampersand",
+        '\'' => "single quote",
+        '(' => "opening parenthesis",
+        ')' => "closing parenthesis",
+        '*' => "asterisk",
+        '+' => "plus sign",
+        ',' => "comma",
+        '-' => "hyphen",
+        '.' => "full stop",
+        '/' => "slash",
+        ':' => "colon",
+        ';' => "semicolon",
+        '<' => "less-than sign",
+        '=' => "equals sign",
+        '>' => "greater-than sign",
+        '?' => "question mark",
+        '@' => "at sign",
+        '[' => "opening square bracket",
+        '\\' => "backslash",
+        ']' => "closing square bracket",
+        '^' => "caret",
+        '_' => "underscore",
+        '`' => "backtick",
+        '{' => "opening curly brace",
+        '|' => "vertical bar",
+        '}' => "closing curly brace",
+        '~' => "tilde",
+        _ => return None,
+    })
+}
@@ -0,0 +1,173 @@
+use crate::cli::Cli;
+use crate::share;
+
+use ferropass_core::encryption::load_and_decrypt_database;
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::Account;
+use ferropass_core::password::{generate_passphrase, Capitalization, Language, Separator, Wordlist};
+use ferropass_core::storage::FileStorage;
+
+use base64::{Engine as _, engine::general_purpose};
+use qrcode::{EcLevel, QrCode};
+use qrcode::render::unicode::Dense1x2;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+const PREFIX: &str = "FPQR";
+// Base64 characters per code. With the header, each code stays at version
+// 13 or below: 77 columns with its quiet zone, so it fits an 80-column
+// terminal.
+const FRAME_DATA: usize = 320;
+
+// One code's text, e.g. "FPQR:3f9a61c2:2/5:<base64>". The transfer ID is
+// the start of the bundle's SHA-256, so the reassembled bundle can be checked
+// and codes from another transfer are told apart.
+struct Frame {
+    transfer: String,
+    index: usize,
+    count: usize,
+    data: String,
+}
+
+impl Frame {
+    fn parse(text: &str) -> Result<Self, FerropassError> {
+        let unreadable = || FerropassError::Format("That isn't a code from `ferropass qr-export`".to_string());
+        let mut parts = text.trim().splitn(4, ':');
+        if parts.next() != Some(PREFIX) {
+            return Err(unreadable());
+        }
+        let (Some(transfer), Some(position), Some(data)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(unreadable());
+        };
+        let (index, count) = position.split_once('/').ok_or_else(unreadable)?;
+        let (index, count): (usize, usize) = (index.parse().map_err(|_| unreadable())?, count.parse().map_err(|_| unreadable())?);
+        if index == 0 || index > count {
+            return Err(unreadable());
+        }
+
+        Ok(Frame { transfer: transfer.to_string(), index, count, data: data.to_string() })
+    }
+}
+
+// Seals the chosen entries, or all of them, into a bundle under a generated
+// one-off password, and shows it as a series of QR codes, one at a time.
+pub fn export(filepath: &Path, entries: &[String], light_background: bool, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    let (database, entry_key) = load_and_decrypt_database(&FileStorage, filepath, &passkey)?;
+
+    let accounts: Vec<&Account> = if entries.is_empty() {
+        database.get_accounts().iter().collect()
+    } else {
+        entries.iter()
+            .map(|id| database.get_account_by_id(id).ok_or_else(|| FerropassError::Validation(format!("{} has no entry with ID {}", filepath.display(), id))))
+            .collect::<Result<_, _>>()?
+    };
+    if accounts.is_empty() {
+        return Err(FerropassError::Validation(format!("{} has no entries to export", filepath.display())));
+    }
+
+    let password = generate_passphrase(&Wordlist::builtin(Language::English), 6, Separator::Hyphen, Capitalization::Lower)?.password;
+    let bundle = share::seal(&accounts, &entry_key, &password)?;
+    let frames = frames(&bundle);
+
+    println!("{} entries in {} codes.", accounts.len(), frames.len());
+    println!("On the other machine, run `ferropass qr-import <vault>` and scan or paste the codes in any order.");
+    println!("When it asks for the bundle's password, type: {}", password);
+    Cli::prompt_input("Press Enter to show the first code...")?;
+
+    let mut index = 0;
+    while index < frames.len() {
+        Cli::clear_screen();
+        println!("Code {} of {}", index + 1, frames.len());
+        println!("{}", render(&frames[index], light_background)?);
+        match Cli::prompt_input("Enter for the next code, b to go back, q to stop: ")?.to_lowercase().as_str() {
+            "q" => break,
+            "b" => index = index.saturating_sub(1),
+            _ => index += 1,
+        }
+    }
+
+    // Neither the password nor the codes stay on screen.
+    Cli::clear_screen();
+    println!("Done.");
+    Ok(())
+}
+
+// Reads codes, as text from a scanner or pasted, until the bundle is whole,
+// then adds its entries to the vault.
+pub fn import(filepath: &Path, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    println!("Scan or paste the codes from `ferropass qr-export`, one per line, in any order. An empty line stops.");
+
+    let mut transfer: Option<(String, usize)> = None;
+    let mut parts = BTreeMap::new();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|e| FerropassError::Io(format!("Failed to read input: {}", e)))?;
+        if line.trim().is_empty() {
+            break;
+        }
+
+        let frame = match Frame::parse(&line) {
+            Ok(frame) => frame,
+            Err(e) => {
+                println!("{}; skipped.", e);
+                continue;
+            }
+        };
+        if let Some((id, count)) = &transfer && (*id != frame.transfer || *count != frame.count) {
+            println!("That code is from another transfer; skipped.");
+            continue;
+        }
+        transfer = Some((frame.transfer.clone(), frame.count));
+        parts.insert(frame.index, frame.data);
+
+        println!("Got {} of {}", parts.len(), frame.count);
+        if parts.len() == frame.count {
+            break;
+        }
+    }
+
+    let Some((id, count)) = transfer else {
+        return Err(FerropassError::Format("No codes were read".to_string()));
+    };
+    let missing: Vec<String> = (1..=count).filter(|index| !parts.contains_key(index)).map(|index| index.to_string()).collect();
+    if !missing.is_empty() {
+        return Err(FerropassError::Format(format!("Code(s) {} of {} are missing; run the import again with all of them", missing.join(", "), count)));
+    }
+
+    let encoded: String = parts.into_values().collect();
+    let bundle = general_purpose::STANDARD.decode(encoded)
+        .ok()
+        .filter(|bundle| transfer_id(bundle) == id)
+        .ok_or_else(|| FerropassError::Format("The codes don't add up to the bundle they came from; scan them again".to_string()))?;
+
+    share::add_bundle(filepath, &bundle, passkey_file)
+}
+
+fn frames(bundle: &[u8]) -> Vec<String> {
+    let id = transfer_id(bundle);
+    let encoded = general_purpose::STANDARD.encode(bundle);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(FRAME_DATA).collect();
+
+    chunks.iter()
+        .enumerate()
+        .map(|(index, chunk)| format!("{}:{}:{}/{}:{}", PREFIX, id, index + 1, chunks.len(), String::from_utf8_lossy(chunk)))
+        .collect()
+}
+
+fn transfer_id(bundle: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bundle))[..8].to_string()
+}
+
+// Terminals usually draw light text on a dark background, so by default the
+// code's light modules are the ones drawn, to keep it the right way round.
+fn render(frame: &str, light_background: bool) -> Result<String, FerropassError> {
+    let code = QrCode::with_error_correction_level(frame, EcLevel::L)
+        .map_err(|e| FerropassError::Validation(format!("Failed to make a QR code: {}", e)))?;
+    let mut renderer = code.render::<Dense1x2>();
+    if !light_background {
+        renderer.dark_color(Dense1x2::Light).light_color(Dense1x2::Dark);
+    }
+    Ok(renderer.quiet_zone(true).build())
+}
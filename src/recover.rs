@@ -0,0 +1,435 @@
+use crate::cli::Cli;
+
+use ferropass_core::algorithms::DEFAULT_CIPHER;
+use ferropass_core::encryption::{encrypt_and_save_database, EncryptedData, EntryKey, JournalRecord, KdfParams, SessionKey, CURRENT_FORMAT_VERSION, HEADER_FORMAT_VERSION};
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::{Account, Database};
+use ferropass_core::storage::FileStorage;
+use ferropass_core::throttle;
+
+use base64::{Engine as _, engine::general_purpose};
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+
+// What could be read back out of the base snapshot line.
+struct RecoveredHeader {
+    version: u32,
+    kdf: Option<KdfParams>,
+    cipher: String,
+    chunk_size: Option<u32>,
+    salt: String,
+    wrapped_key: Option<String>,
+    signing_key: Option<String>,
+    nonce: String,
+    data: Vec<u8>,
+    data_complete: bool,
+}
+
+pub fn run(filepath: &Path, output: Option<&Path>, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    if let Some(output) = output
+        && output.exists()
+    {
+        return Err(FerropassError::Validation(format!("{} already exists", output.display())));
+    }
+
+    println!("Recovering {}", filepath.display());
+
+    let bytes = fs::read(filepath).map_err(|e| FerropassError::Io(format!("Error reading file: {}", e)))?;
+    let content = String::from_utf8_lossy(&bytes);
+    if let Cow::Owned(_) = content {
+        warn("File contains bytes that aren't valid UTF-8; they were replaced before parsing");
+    }
+
+    let mut lines = content.split('\n').filter(|line| !line.trim().is_empty());
+    let base = lines.next().ok_or_else(|| FerropassError::Format("File is empty, nothing can be recovered".to_string()))?;
+    let journal_lines: Vec<&str> = lines.collect();
+
+    let header = recover_header(base)?;
+    let journal = recover_journal(&journal_lines, content.ends_with('\n'));
+
+    let mut encrypted_data = EncryptedData::from_parts(
+        header.version, header.kdf, header.cipher, header.salt, header.nonce, &header.data, journal
+    );
+    encrypted_data.set_chunk_size(header.chunk_size);
+    encrypted_data.set_wrapped_key(header.wrapped_key);
+    encrypted_data.set_signing_key(header.signing_key);
+    let journal_len = encrypted_data.journal_len();
+
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    let key = throttle::guard(filepath, || encrypted_data.unlock_key(&passkey))?;
+
+    let mut database = recover_base(&encrypted_data, &key, header.data_complete)?;
+
+    for index in 0..journal_len {
+        match encrypted_data.decrypt_journal_record(&key, index) {
+            Ok(changes) => {
+                for change in changes {
+                    database.apply(change);
+                }
+            },
+            Err(e) => {
+                fail(&e.to_string());
+                warn(&format!("Stopped replaying the journal at record {}, later records build on it", index + 1));
+                break;
+            }
+        }
+    }
+
+    println!("Recovered {} account(s).", database.get_accounts().len());
+
+    match output {
+        Some(output) => {
+            let session_key = SessionKey::generate(&passkey)?;
+            reseal_passwords(&mut database, &encrypted_data.entry_key(&key), &session_key.entry_key());
+            encrypt_and_save_database(&FileStorage, &database, output, &session_key)?;
+            println!("Wrote the recovered database to {}. Check it before replacing the damaged file.", output.display());
+        },
+        None => println!("Run again with --output <file> to save what was recovered."),
+    }
+
+    Ok(())
+}
+
+fn recover_header(base: &str) -> Result<RecoveredHeader, FerropassError> {
+    let fields = match serde_json::from_str::<serde_json::Value>(base) {
+        Ok(value) => {
+            ok("Header is valid JSON");
+            HeaderFields::from_json(&value)
+        },
+        Err(e) => {
+            fail(&format!("Header is not valid JSON ({}), recovering fields one by one", e));
+            HeaderFields::scan(base)
+        }
+    };
+
+    let version = match fields.version {
+        Some(version) => {
+            ok(&format!("Format version {}", version));
+            version
+        },
+        None if fields.wrapped_key.is_some() => {
+            warn(&format!("Format version is missing, assuming {} since a wrapped data key is present", CURRENT_FORMAT_VERSION));
+            CURRENT_FORMAT_VERSION
+        },
+        None if fields.kdf_present => {
+            warn(&format!("Format version is missing, assuming {} since key derivation parameters are present", HEADER_FORMAT_VERSION));
+            HEADER_FORMAT_VERSION
+        },
+        None => {
+            warn("Format version is missing, assuming a legacy version 1 file");
+            1
+        }
+    };
+
+    let kdf = match fields.kdf {
+        Some(kdf) => {
+            ok(&format!("Key derivation parameters: {} m={} t={} p={}", kdf.algorithm, kdf.m_cost, kdf.t_cost, kdf.p_cost));
+            Some(kdf)
+        },
+        None if version >= HEADER_FORMAT_VERSION => {
+            fail("Key derivation parameters are missing or damaged, trying the defaults");
+            Some(KdfParams::current())
+        },
+        None => None,
+    };
+
+    let cipher = match fields.cipher {
+        Some((cipher, true)) => {
+            ok(&format!("Cipher: {}", cipher));
+            cipher
+        },
+        Some(_) => {
+            fail(&format!("Cipher name is cut off, trying {}", DEFAULT_CIPHER));
+            DEFAULT_CIPHER.to_string()
+        },
+        None => DEFAULT_CIPHER.to_string(),
+    };
+
+    if let Some(chunk_size) = fields.chunk_size {
+        ok(&format!("Data is encrypted in chunks of {} bytes", chunk_size));
+    }
+
+    let wrapped_key = match fields.wrapped_key {
+        Some((wrapped_key, true)) => {
+            ok("Wrapped data key is intact");
+            Some(wrapped_key)
+        },
+        _ if version >= CURRENT_FORMAT_VERSION => {
+            fail("Wrapped data key is missing or cut off");
+            return Err(FerropassError::Format("Without the wrapped data key the data cannot be decrypted, nothing can be recovered".to_string()));
+        },
+        _ => None,
+    };
+
+    // The signature isn't checked here, since the file is known to be
+    // damaged, but the key it names is part of what the data authenticates.
+    let signing_key = match fields.signing_key {
+        Some((signing_key, true)) => {
+            ok("Signing key is intact, the signature is not checked while recovering");
+            Some(signing_key)
+        },
+        Some(_) => {
+            fail("Signing key is cut off, the data won't authenticate");
+            None
+        },
+        None => None,
+    };
+
+    let salt = match fields.salt {
+        Some((salt, true)) => {
+            ok("Salt is intact");
+            salt
+        },
+        _ => {
+            fail("Salt is missing or cut off");
+            return Err(FerropassError::Format("Without the salt the key cannot be derived, nothing can be recovered".to_string()));
+        }
+    };
+
+    let nonce = match fields.nonce {
+        Some((nonce, true)) if general_purpose::STANDARD.decode(&nonce).is_ok_and(|bytes| bytes.len() == 12) => {
+            ok("Nonce is intact");
+            nonce
+        },
+        _ => {
+            fail("Nonce is missing or damaged");
+            return Err(FerropassError::Format("Without the nonce the data cannot be decrypted, nothing can be recovered".to_string()));
+        }
+    };
+
+    let (data, data_complete) = match fields.data {
+        Some((data, complete)) => {
+            let (bytes, clean) = decode_lenient(&data);
+            if complete && clean {
+                ok(&format!("Encrypted data decodes ({} bytes)", bytes.len()));
+            } else {
+                fail(&format!("Encrypted data is cut off or damaged, {} bytes could be decoded", bytes.len()));
+            }
+            (bytes, complete && clean)
+        },
+        None => {
+            fail("Encrypted data is missing");
+            return Err(FerropassError::Format("The file holds no encrypted data, nothing can be recovered".to_string()));
+        }
+    };
+
+    Ok(RecoveredHeader { version, kdf, cipher, chunk_size: fields.chunk_size, salt, wrapped_key, signing_key, nonce, data, data_complete })
+}
+
+fn recover_journal(lines: &[&str], terminated: bool) -> Vec<JournalRecord> {
+    let mut journal = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let last = index + 1 == lines.len();
+
+        match serde_json::from_str::<JournalRecord>(line) {
+            Ok(record) => journal.push(record),
+            Err(_) if last && !terminated => {
+                warn(&format!("Journal record {} was never finished writing and is skipped", index + 1));
+                break;
+            },
+            Err(e) => {
+                fail(&format!("Journal record {} can't be parsed ({}), later records are skipped", index + 1, e));
+                break;
+            }
+        }
+    }
+
+    if !journal.is_empty() {
+        ok(&format!("Journal holds {} readable record(s)", journal.len()));
+    }
+
+    journal
+}
+
+fn recover_base(encrypted_data: &EncryptedData, key: &[u8; 32], data_complete: bool) -> Result<Database, FerropassError> {
+    if let Ok(plaintext) = encrypted_data.decrypt_base(key) {
+        ok("Base snapshot authenticates with the passkey");
+
+        return match serde_json::from_slice::<Database>(&plaintext) {
+            Ok(database) => Ok(database),
+            Err(e) => {
+                fail(&format!("Base snapshot decrypts but isn't a valid database ({}), salvaging accounts", e));
+                Ok(salvage_accounts(&plaintext))
+            }
+        };
+    }
+
+    fail("Base snapshot does not authenticate (damaged data or wrong passkey)");
+
+    let plaintext = encrypted_data.decrypt_base_unauthenticated(key, data_complete)?;
+    let database = salvage_accounts(&plaintext);
+
+    if database.get_accounts().is_empty() {
+        warn("No accounts could be salvaged from the base snapshot; if the passkey is right, the damage starts too early");
+    } else {
+        warn(&format!(
+            "Salvaged {} account(s) from unauthenticated data; they can't be verified, check each one",
+            database.get_accounts().len()
+        ));
+    }
+
+    Ok(database)
+}
+
+// Reads every complete account object out of a plaintext that may be cut
+// off or have damaged bytes. Damage only garbles the bytes it hits, so each
+// account is looked for on its own rather than by parsing the whole list.
+fn salvage_accounts(plaintext: &[u8]) -> Database {
+    let text = String::from_utf8_lossy(plaintext);
+    let mut database = Database::new();
+
+    let mut rest = &text[..];
+    while let Some(start) = rest.find("{\"id\"") {
+        rest = &rest[start..];
+
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<Account>();
+        match stream.next() {
+            Some(Ok(account)) => {
+                database.add_account(account);
+                rest = &rest[stream.byte_offset()..];
+            },
+            _ => rest = &rest[1..],
+        }
+    }
+
+    database
+}
+
+// The recovered file gets a new data key, so each password, TOTP secret and
+// SSH key is sealed again under it. Accounts whose password no longer opens
+// are left out; a TOTP secret or SSH key that doesn't open is dropped from
+// its account.
+fn reseal_passwords(database: &mut Database, from: &EntryKey, to: &EntryKey) {
+    database.get_accounts_mut().retain_mut(|account| match to.reseal(account.get_password(), from) {
+        Ok(password) => {
+            account.reseal_password(password);
+            match account.get_totp().map(|totp| to.reseal(totp, from)) {
+                Some(Ok(totp)) => account.reseal_totp(totp),
+                Some(Err(e)) => {
+                    fail(&format!("Left out the TOTP secret of account {} ({}): {}", account.get_id(), account.get_username_or_email(), e));
+                    account.set_totp(None);
+                },
+                None => {},
+            }
+            match account.get_ssh_key().map(|ssh_key| to.reseal(ssh_key, from)) {
+                Some(Ok(ssh_key)) => account.reseal_ssh_key(ssh_key),
+                Some(Err(e)) => {
+                    fail(&format!("Left out the SSH key of account {} ({}): {}", account.get_id(), account.get_username_or_email(), e));
+                    account.set_ssh_key(None);
+                },
+                None => {},
+            }
+            true
+        },
+        Err(e) => {
+            fail(&format!("Left out account {} ({}): {}", account.get_id(), account.get_username_or_email(), e));
+            false
+        }
+    });
+}
+
+// Base64 decoding that keeps whatever decodes up to the point of damage.
+// Returns the bytes and whether the input was intact.
+fn decode_lenient(data: &str) -> (Vec<u8>, bool) {
+    if let Ok(bytes) = general_purpose::STANDARD.decode(data) {
+        return (bytes, true);
+    }
+
+    let valid: String = data.chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '+' || *c == '/')
+        .collect();
+    let usable = &valid[..valid.len() - valid.len() % 4];
+
+    (general_purpose::STANDARD_NO_PAD.decode(usable).unwrap_or_default(), false)
+}
+
+#[derive(Default)]
+struct HeaderFields {
+    version: Option<u32>,
+    kdf: Option<KdfParams>,
+    kdf_present: bool,
+    cipher: Option<(String, bool)>,
+    chunk_size: Option<u32>,
+    wrapped_key: Option<(String, bool)>,
+    signing_key: Option<(String, bool)>,
+    // The value, and whether its closing quote was found.
+    salt: Option<(String, bool)>,
+    nonce: Option<(String, bool)>,
+    data: Option<(String, bool)>,
+}
+
+impl HeaderFields {
+    fn from_json(value: &serde_json::Value) -> Self {
+        let string = |name: &str| value.get(name).and_then(|v| v.as_str()).map(|s| (s.to_string(), true));
+
+        HeaderFields {
+            version: value.get("version").and_then(|v| v.as_u64()).map(|v| v as u32),
+            kdf: value.get("kdf").and_then(|kdf| serde_json::from_value(kdf.clone()).ok()),
+            kdf_present: value.get("kdf").is_some(),
+            cipher: string("cipher"),
+            chunk_size: value.get("chunk_size").and_then(|v| v.as_u64()).map(|v| v as u32),
+            wrapped_key: string("wrapped_key"),
+            signing_key: string("signing_key"),
+            salt: string("salt"),
+            nonce: string("nonce"),
+            data: string("data"),
+        }
+    }
+
+    // Picks fields out of a header line that no longer parses as a whole,
+    // typically because it was cut off partway through.
+    fn scan(line: &str) -> Self {
+        let number = |name: &str| field_value(line, name).and_then(|value| {
+            let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().ok()
+        });
+
+        let kdf_text = field_value(line, "kdf");
+        let kdf = kdf_text
+            .and_then(|value| value.find('}').map(|end| &value[..=end]))
+            .and_then(|object| serde_json::from_str(object).ok());
+
+        HeaderFields {
+            version: number("version"),
+            kdf,
+            kdf_present: kdf_text.is_some(),
+            cipher: string_value(line, "cipher"),
+            chunk_size: number("chunk_size"),
+            wrapped_key: string_value(line, "wrapped_key"),
+            signing_key: string_value(line, "signing_key"),
+            salt: string_value(line, "salt"),
+            nonce: string_value(line, "nonce"),
+            data: string_value(line, "data"),
+        }
+    }
+}
+
+fn field_value<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let key = format!("\"{}\"", name);
+    let after_key = &line[line.find(&key)? + key.len()..];
+    let value = after_key.trim_start().strip_prefix(':')?;
+    Some(value.trim_start())
+}
+
+fn string_value(line: &str, name: &str) -> Option<(String, bool)> {
+    let value = field_value(line, name)?.strip_prefix('"')?;
+
+    Some(match value.find('"') {
+        Some(end) => (value[..end].to_string(), true),
+        None => (value.to_string(), false),
+    })
+}
+
+fn ok(message: &str) {
+    println!("  [ OK ] {}", message);
+}
+
+fn warn(message: &str) {
+    println!("  [WARN] {}", message);
+}
+
+fn fail(message: &str) {
+    println!("  [FAIL] {}", message);
+}
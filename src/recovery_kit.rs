@@ -0,0 +1,177 @@
+use crate::cli::Cli;
+use crate::pdf;
+
+use ferropass_core::config;
+use ferropass_core::encryption::{key_fingerprint, EncryptedData};
+use ferropass_core::error::FerropassError;
+use ferropass_core::pepper;
+use ferropass_core::signing;
+use ferropass_core::storage::{FileStorage, VaultStorage};
+use ferropass_core::throttle;
+
+use chrono::Local;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::path::Path;
+
+pub fn run(filepath: &Path, output: Option<&Path>, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    if let Some(path) = output.filter(|path| path.exists()) {
+        return Err(FerropassError::Validation(format!("{} already exists", path.display())));
+    }
+
+    let encrypted_data = EncryptedData::read(&FileStorage, filepath)?;
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+
+    let (key, database) = throttle::guard(filepath, || {
+        let key = encrypted_data.unlock_key(&passkey)?;
+        let database = encrypted_data.open(&key)?;
+        Ok((key, database))
+    })?;
+
+    let location = fs::canonicalize(filepath).unwrap_or_else(|_| filepath.to_path_buf());
+    let kdf = encrypted_data.kdf_params()?;
+    let salt = encrypted_data.salt()?;
+    let pepper_location = pepper::path().map(|path| path.display().to_string()).unwrap_or_default();
+    let signing_key_location = signing::path().map(|path| path.display().to_string()).unwrap_or_default();
+    let signing_key = encrypted_data.get_signing_key().map(signing::short_id);
+
+    let kit = build_kit(&KitDetails {
+        location: &location,
+        fingerprint: &key_fingerprint(&key),
+        version: encrypted_data.version(),
+        algorithm: &kdf.algorithm,
+        m_cost: kdf.m_cost,
+        t_cost: kdf.t_cost,
+        p_cost: kdf.p_cost,
+        salt: salt.as_str(),
+        pepper: kdf.pepper.as_deref().map(|id| (id, pepper_location.as_str())),
+        signing_key: signing_key.as_deref().map(|id| (id, signing_key_location.as_str())),
+        accounts: database.get_accounts().len(),
+        auto_lock_minutes: database.get_settings().get_auto_lock_minutes(),
+        recovery_shares: encrypted_data.recovery().map(|slot| (slot.get_threshold(), slot.get_shares())),
+    }).map_err(|e| FerropassError::Io(format!("Failed to build recovery kit: {}", e)))?;
+
+    match output {
+        Some(path) => {
+            let is_pdf = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"));
+            let contents = if is_pdf { pdf::from_text("FerroPass recovery kit", &kit) } else { kit.into_bytes() };
+            config::write_private_file(path, contents)?;
+            println!("Recovery kit for {} written to {}", filepath.display(), path.display());
+        },
+        None => print!("{}", kit),
+    }
+
+    Ok(())
+}
+
+struct KitDetails<'a> {
+    location: &'a Path,
+    fingerprint: &'a str,
+    version: u32,
+    algorithm: &'a str,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: &'a str,
+    // The pepper's ID and where it is kept on this machine.
+    pepper: Option<(&'a str, &'a str)>,
+    // The same for the key the vault is signed with.
+    signing_key: Option<(&'a str, &'a str)>,
+    accounts: usize,
+    auto_lock_minutes: Option<u64>,
+    recovery_shares: Option<(u8, u8)>,
+}
+
+fn build_kit(details: &KitDetails) -> Result<String, fmt::Error> {
+    let mut out = String::new();
+    let name = details.location.file_name()
+        .map_or_else(|| details.location.display().to_string(), |name| name.to_string_lossy().to_string());
+
+    writeln!(out, "FERROPASS RECOVERY KIT")?;
+    writeln!(out, "======================")?;
+    writeln!(out)?;
+    writeln!(out, "Vault:        {}", name)?;
+    writeln!(out, "Fingerprint:  {}", details.fingerprint)?;
+    writeln!(out, "Accounts:     {}", details.accounts)?;
+    writeln!(out, "Printed:      {}", Local::now().format("%Y-%m-%d %H:%M"))?;
+    writeln!(out)?;
+
+    writeln!(out, "Storage locations")?;
+    writeln!(out, "  Vault file:     {}", details.location.display())?;
+    match FileStorage.latest_version(details.location) {
+        Some((path, _)) => writeln!(out, "  Latest backup:  {}", path.display())?,
+        None => writeln!(out, "  Latest backup:  none found next to the vault")?,
+    }
+    writeln!(out, "  Other copies:   ________________________________________")?;
+    writeln!(out)?;
+
+    writeln!(out, "Key derivation")?;
+    writeln!(out, "  Format version: {}", details.version)?;
+    writeln!(out, "  Algorithm:      {}", details.algorithm)?;
+    writeln!(out, "  Memory:         {} KiB", details.m_cost)?;
+    writeln!(out, "  Iterations:     {}", details.t_cost)?;
+    writeln!(out, "  Parallelism:    {}", details.p_cost)?;
+    writeln!(out, "  Salt:           {}", details.salt)?;
+    writeln!(out)?;
+
+    writeln!(out, "Unlocking")?;
+    match details.pepper {
+        Some((id, location)) => {
+            writeln!(out, "  Pepper file:     required, pepper {}", id)?;
+            writeln!(out, "                   kept at {}", location)?;
+        },
+        None => writeln!(out, "  Pepper file:     not used by this vault")?,
+    }
+    match details.signing_key {
+        Some((id, location)) => {
+            writeln!(out, "  Signing key:     key {}, needed to save the vault", id)?;
+            writeln!(out, "                   kept at {}", location)?;
+        },
+        None => writeln!(out, "  Signing key:     not used by this vault")?,
+    }
+    match details.recovery_shares {
+        Some((threshold, shares)) => writeln!(out, "  Recovery shares: any {} of {} reset the passkey", threshold, shares)?,
+        None => writeln!(out, "  Recovery shares: not set up for this vault")?,
+    }
+    match details.auto_lock_minutes {
+        Some(minutes) => writeln!(out, "  Auto-lock:       after {} minute(s) of inactivity", minutes)?,
+        None => writeln!(out, "  Auto-lock:       off")?,
+    }
+    writeln!(out)?;
+
+    writeln!(out, "Passkey")?;
+    writeln!(out, "  ______________________________________________________")?;
+    writeln!(out)?;
+    writeln!(out, "  ______________________________________________________")?;
+    writeln!(out)?;
+
+    // Shares are never printed on the kit; it only records who holds them.
+    if let Some((_, shares)) = details.recovery_shares {
+        writeln!(out, "Recovery share holders")?;
+        for share in 1..=shares {
+            writeln!(out, "  {:>2}. ___________________________________________________", share)?;
+        }
+        writeln!(out)?;
+    }
+
+    writeln!(out, "To regain access")?;
+    writeln!(out, "  1. Install ferropass and copy the vault file or a backup from the locations above.")?;
+    if let Some((id, _)) = details.pepper {
+        writeln!(out, "     Restore the pepper file (pepper {}) from your offline copy as well.", id)?;
+    }
+    if details.signing_key.is_some() {
+        writeln!(out, "     Copy its signature file (the vault's name followed by .sig) with it, and the signing key to save it.")?;
+    }
+    writeln!(out, "  2. Run `ferropass verify <file>` and enter the passkey written on this sheet.")?;
+    writeln!(out, "  3. Check that the fingerprint it prints matches {}.", details.fingerprint)?;
+    writeln!(out, "     A different fingerprint means the file is not this vault.")?;
+    writeln!(out, "  4. Open the vault from the main menu with the same passkey.")?;
+    if let Some((threshold, _)) = details.recovery_shares {
+        writeln!(out, "  If the passkey is lost, collect {} recovery shares and run", threshold)?;
+        writeln!(out, "  `ferropass reset-passkey <file>` to set a new one.")?;
+    }
+    writeln!(out)?;
+    writeln!(out, "Store this sheet offline, somewhere only you or your trusted contacts can reach.")?;
+
+    Ok(out)
+}
@@ -0,0 +1,109 @@
+use crate::cli::Cli;
+
+use ferropass_core::config;
+use ferropass_core::encryption::{rewrap_and_save, unlock_database, unlock_with_recovery_key, EncryptedData};
+use ferropass_core::error::FerropassError;
+use ferropass_core::shamir::{self, Share};
+use ferropass_core::storage::{FileStorage, VaultStorage};
+
+use std::path::Path;
+
+// Sets up a new recovery key for the vault and prints (or writes) the shares
+// it is split into. Shares from an earlier run stop working.
+pub fn create(filepath: &Path, threshold: u8, count: u8, output_dir: Option<&Path>, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    let _vault_lock = FileStorage.lock(filepath)?;
+    let (database, mut key) = unlock_database(&FileStorage, filepath, &passkey)?;
+
+    let replacing = EncryptedData::read(&FileStorage, filepath)?.recovery().is_some();
+
+    let mut recovery_key = key.set_up_recovery(threshold, count)?;
+    let shares = shamir::split(&recovery_key, threshold, count);
+    recovery_key.fill(0);
+    let shares = shares?;
+
+    let stem = filepath.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    if let Some(dir) = output_dir {
+        for share in &shares {
+            let path = dir.join(format!("{}.share-{}.txt", stem, share.get_index()));
+            if path.exists() {
+                return Err(FerropassError::Validation(format!("{} already exists", path.display())));
+            }
+        }
+    }
+
+    rewrap_and_save(&FileStorage, &database, filepath, &key)?;
+
+    if replacing {
+        println!("The vault's earlier recovery shares no longer work.");
+    }
+    println!("Any {} of these {} shares can set a new passkey for {}:", threshold, count, filepath.display());
+    println!();
+
+    for share in &shares {
+        let text = format!(
+            "FerroPass recovery share {} of {} for {} ({} needed)\n{}\n",
+            share.get_index(), count, stem, threshold, share.encode()
+        );
+
+        match output_dir {
+            Some(dir) => {
+                let path = dir.join(format!("{}.share-{}.txt", stem, share.get_index()));
+                config::write_private_file(&path, &text)?;
+                println!("  Share {} written to {}", share.get_index(), path.display());
+            },
+            None => println!("  Share {}: {}", share.get_index(), share.encode()),
+        }
+    }
+
+    println!();
+    println!("Give each share to a different person or keep them in different places.");
+    println!("If the passkey is lost, run `ferropass reset-passkey {}` with {} of them.", filepath.display(), threshold);
+    Ok(())
+}
+
+// Rebuilds the recovery key from shares and uses it to set a new passkey.
+pub fn reset_passkey(filepath: &Path) -> Result<(), FerropassError> {
+    let _vault_lock = FileStorage.lock(filepath)?;
+    let encrypted_data = EncryptedData::read(&FileStorage, filepath)?;
+    let slot = encrypted_data.recovery()
+        .ok_or_else(|| FerropassError::Validation("Recovery shares are not set up for this vault".to_string()))?;
+    let threshold = slot.get_threshold();
+
+    println!("Enter {} of the {} recovery shares for {}.", threshold, slot.get_shares(), filepath.display());
+
+    let mut shares: Vec<Share> = Vec::new();
+    while shares.len() < threshold as usize {
+        let text = Cli::prompt_password(&format!("Share {} of {}: ", shares.len() + 1, threshold))?;
+
+        match Share::parse(&text) {
+            Ok(share) if share.get_threshold() != threshold => {
+                println!("That share is from a set needing {} shares, this vault needs {}.", share.get_threshold(), threshold);
+            },
+            Ok(share) if share.get_index() > slot.get_shares() => {
+                println!("This vault's key was split into {} shares, so there is no share {}.", slot.get_shares(), share.get_index());
+            },
+            Ok(share) if shares.iter().any(|earlier| earlier.get_index() == share.get_index()) => {
+                println!("Share {} was already entered.", share.get_index());
+            },
+            Ok(share) => shares.push(share),
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    let recovery_key: [u8; 32] = shamir::combine(&shares)?
+        .try_into()
+        .map_err(|_| FerropassError::Crypto("The shares don't rebuild this vault's recovery key".to_string()))?;
+    let (database, key) = unlock_with_recovery_key(&FileStorage, filepath, &recovery_key)?;
+    println!("Shares accepted ({} accounts).", database.get_accounts().len());
+
+    let passkey = Cli::prompt_for_valid_passkey("Enter a new passkey")?;
+    let new_key = key.rekey(&passkey)?;
+
+    let backup = FileStorage.snapshot(filepath)?;
+    rewrap_and_save(&FileStorage, &database, filepath, &new_key)?;
+
+    println!("The passkey for {} was reset. The previous file was kept at {}.", filepath.display(), backup.display());
+    println!("The same recovery shares keep working with the new passkey.");
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use crate::cli::Cli;
+
+use ferropass_core::encryption::{self, EncryptedData};
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::format_timestamp;
+use ferropass_core::signing;
+use ferropass_core::storage::VaultStorage;
+
+use std::cmp::Reverse;
+use std::path::Path;
+
+// Puts an earlier version of a vault back: one of the backups kept next to
+// it, or for a vault in a versioned bucket, an earlier version of the object.
+// What is there now is backed up first, so a restore can itself be undone.
+pub fn run(filepath: &Path, list: bool, backup: Option<usize>) -> Result<(), FerropassError> {
+    let storage = Cli::routed_storage();
+    let mut versions = storage.list_versions(filepath)?;
+    versions.sort_by_key(|(_, saved_at)| Reverse(*saved_at));
+
+    if versions.is_empty() {
+        return Err(FerropassError::Validation(format!("No backups of {} found", filepath.display())));
+    }
+
+    println!("Backups of {}, newest first:", filepath.display());
+    for (index, (path, saved_at)) in versions.iter().enumerate() {
+        println!("  {}. {}  {}", index + 1, format_timestamp(*saved_at), path.display());
+    }
+    if list {
+        return Ok(());
+    }
+
+    let choice = match backup {
+        Some(choice) => choice,
+        None => {
+            let answer = Cli::prompt_input(&format!("Restore which backup? (1-{}, leave empty to cancel): ", versions.len()))?;
+            if answer.is_empty() {
+                println!("Nothing was restored.");
+                return Ok(());
+            }
+            answer.parse().map_err(|_| FerropassError::Validation(format!("'{}' is not a backup number", answer)))?
+        },
+    };
+    let Some((path, saved_at)) = choice.checked_sub(1).and_then(|index| versions.get(index)) else {
+        return Err(FerropassError::Validation(format!("There is no backup {}; pick one from 1 to {}", choice, versions.len())));
+    };
+
+    // Only the file's structure is checked; restoring doesn't need the passkey.
+    let contents = storage.read_blob(path)?;
+    let text = String::from_utf8(contents.clone()).map_err(|_| FerropassError::Format(format!("{} is not a vault file", path.display())))?;
+    EncryptedData::parse(&text).map_err(|e| e.context(format!("{} is not a vault file", path.display())))?;
+
+    let _vault_lock = storage.lock(filepath)?;
+    if storage.exists(filepath) {
+        // Read first, so a remote vault is replaced only if nobody changed it
+        // in the meantime.
+        storage.read_blob(filepath)?;
+        let current = storage.snapshot(filepath)?;
+        println!("Backed up the current vault as {}", current.display());
+    }
+    storage.write_blob(filepath, &contents)?;
+    encryption::forget_journal(filepath)?;
+
+    println!("Restored {} from the backup of {}.", filepath.display(), format_timestamp(*saved_at));
+    if storage.exists(&signing::signature_path(filepath)) {
+        println!("The vault is signed; check it with `ferropass verify`, then run `ferropass sign` to sign the restored file.");
+    }
+    Ok(())
+}
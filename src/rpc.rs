@@ -0,0 +1,347 @@
+use crate::agent::{self, UnlockedVaults};
+
+use ferropass_core::config;
+use ferropass_core::entropy;
+use ferropass_core::error::FerropassError;
+use ferropass_core::encryption::EntryKey;
+use ferropass_core::models::{current_timestamp, format_timestamp, Database};
+use ferropass_core::password::{generate_random_password, PasswordOptions, DEFAULT_LENGTH, MAX_LENGTH};
+use ferropass_core::query::SortBy;
+use ferropass_core::vault::find;
+
+use clap::ValueEnum;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Only hashes of the tokens are kept, so the file itself gives nothing away.
+const TOKENS_FILE: &str = "agent-tokens.json";
+
+// Error codes from the JSON-RPC 2.0 specification, then the agent's own.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const FAILED: i64 = -32000;
+const UNAUTHORIZED: i64 = -32001;
+const LOCKED: i64 = -32002;
+
+type Failure = (i64, String);
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Method {
+    List,
+    Get,
+    Generate,
+    Lock,
+}
+
+impl Method {
+    const ALL: [Method; 4] = [Method::List, Method::Get, Method::Generate, Method::Lock];
+
+    fn name(self) -> &'static str {
+        match self {
+            Method::List => "list",
+            Method::Get => "get",
+            Method::Generate => "generate",
+            Method::Lock => "lock",
+        }
+    }
+}
+
+// What a tool holding the token may do: call `methods`, on `vaults` alone
+// unless there are none.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct Token {
+    hash: String,
+    methods: Vec<Method>,
+    #[serde(default)]
+    vaults: Vec<PathBuf>,
+    created_at: u64,
+}
+
+impl Token {
+    fn allows(&self, vault: &Path) -> bool {
+        self.vaults.is_empty() || self.vaults.iter().any(|allowed| allowed == vault)
+    }
+}
+
+type Tokens = BTreeMap<String, Token>;
+
+// The token is only shown here; after that the agent knows its hash alone.
+pub fn create_token(name: &str, vaults: &[PathBuf], methods: &[Method]) -> Result<(), FerropassError> {
+    let taken = || FerropassError::Validation(format!("There is already a token named {}; revoke it first with `ferropass token {} --revoke`", name, name));
+    let tokens: Tokens = config::load_state(TOKENS_FILE)?;
+    if tokens.contains_key(name) {
+        return Err(taken());
+    }
+    let vaults = vaults.iter()
+        .map(|vault| fs::canonicalize(vault).map_err(|e| FerropassError::Io(format!("Error reading file: {}: {}", vault.display(), e))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    entropy::ensure()?;
+    let mut secret = [0u8; 24];
+    OsRng.fill_bytes(&mut secret);
+    let token = format!("fpt_{}", secret.iter().map(|byte| format!("{:02x}", byte)).collect::<String>());
+    secret.fill(0);
+
+    let methods = if methods.is_empty() { Method::ALL.to_vec() } else { methods.to_vec() };
+    let created = Token { hash: hash(&token), methods, vaults, created_at: current_timestamp() };
+    let added = config::update_state(TOKENS_FILE, |tokens: &mut Tokens| {
+        !tokens.contains_key(name) && tokens.insert(name.to_string(), created).is_none()
+    })?;
+    // Another process may have made one by the same name meanwhile.
+    if !added {
+        return Err(taken());
+    }
+
+    println!("{}", token);
+    eprintln!("Give this token to {}; it is not shown again. Revoke it with `ferropass token {} --revoke`.", name, name);
+    Ok(())
+}
+
+pub fn revoke_token(name: &str) -> Result<(), FerropassError> {
+    if config::update_state(TOKENS_FILE, |tokens: &mut Tokens| tokens.remove(name))?.is_none() {
+        return Err(FerropassError::Validation(format!("There is no token named {}", name)));
+    }
+    println!("Token {} revoked.", name);
+    Ok(())
+}
+
+pub fn list_tokens() -> Result<(), FerropassError> {
+    let tokens: Tokens = config::load_state(TOKENS_FILE)?;
+    if tokens.is_empty() {
+        println!("No tokens; create one with `ferropass token <name>`.");
+    }
+    for (name, token) in &tokens {
+        let methods: Vec<&str> = token.methods.iter().map(|method| method.name()).collect();
+        let vaults = if token.vaults.is_empty() {
+            "every vault".to_string()
+        } else {
+            token.vaults.iter().map(|vault| vault.display().to_string()).collect::<Vec<_>>().join(", ")
+        };
+        println!("{}: {} on {}, created {}", name, methods.join(", "), vaults, format_timestamp(token.created_at));
+    }
+    Ok(())
+}
+
+fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub fn parse_error() -> Value {
+    json!({ "jsonrpc": "2.0", "id": null, "error": { "code": PARSE_ERROR, "message": "Parse error" } })
+}
+
+// Answers one JSON-RPC 2.0 request. Notifications, which have no ID, get no
+// answer; batches aren't supported.
+pub fn answer(message: &Value, vaults: &mut UnlockedVaults) -> Option<Value> {
+    let id = message.get("id").cloned();
+    let result = match (message.get("jsonrpc").and_then(Value::as_str), message.get("method").and_then(Value::as_str)) {
+        (Some("2.0"), Some(method)) => call(method, message.get("params").unwrap_or(&Value::Null), vaults),
+        _ => Err((INVALID_REQUEST, "Invalid request".to_string())),
+    };
+
+    let id = match (id, &result) {
+        (Some(id), _) => id,
+        (None, Err((INVALID_REQUEST, _))) => Value::Null,
+        (None, _) => return None,
+    };
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+    })
+}
+
+fn call(method: &str, params: &Value, vaults: &mut UnlockedVaults) -> Result<Value, Failure> {
+    let method = Method::ALL.into_iter()
+        .find(|known| known.name() == method)
+        .ok_or_else(|| (METHOD_NOT_FOUND, format!("There is no method {}", method)))?;
+    if !params.is_object() && !params.is_null() {
+        return Err((INVALID_PARAMS, "Params must be an object".to_string()));
+    }
+    let text = |name: &str| params.get(name).and_then(Value::as_str);
+
+    let token = text("token")
+        .ok_or_else(|| (UNAUTHORIZED, "A token is needed; create one with `ferropass token <name>`".to_string()))?;
+    let tokens: Tokens = config::load_state(TOKENS_FILE).map_err(|e| (FAILED, e.to_string()))?;
+    let token = tokens.values()
+        .find(|known| known.hash == hash(token))
+        .ok_or_else(|| (UNAUTHORIZED, "The token is not valid".to_string()))?;
+    if !token.methods.contains(&method) {
+        return Err((UNAUTHORIZED, format!("The token doesn't allow {}", method.name())));
+    }
+
+    let vault = text("vault")
+        .map(|vault| {
+            // The vaults a token lists are stored resolved, so one named the
+            // same way is let through without looking at the file system;
+            // anything else gets the same answer whether it exists or not.
+            let refused = || (UNAUTHORIZED, format!("The token doesn't allow {}", vault));
+            if !token.vaults.is_empty() && token.allows(Path::new(vault)) {
+                return Ok(PathBuf::from(vault));
+            }
+            let path = fs::canonicalize(vault).map_err(|_| {
+                if token.vaults.is_empty() {
+                    (UNAUTHORIZED, format!("There is no vault at {}", vault))
+                } else {
+                    refused()
+                }
+            })?;
+            if !token.allows(&path) {
+                return Err(refused());
+            }
+            Ok(path)
+        })
+        .transpose()?;
+
+    match method {
+        Method::List => {
+            let named = vault.is_some();
+            let listed: Vec<PathBuf> = match vault {
+                Some(vault) => vec![vault],
+                None => vaults.keys().filter(|vault| token.allows(vault)).cloned().collect(),
+            };
+            let mut entries = Vec::new();
+            for vault in listed {
+                let (database, _) = match opened(vaults, &vault) {
+                    Ok(opened) => opened,
+                    // Locked since the list was taken.
+                    Err((LOCKED, _)) if !named => continue,
+                    Err(failure) => return Err(failure),
+                };
+                let mut query = database.query().sorted_by(SortBy::Title);
+                if let Some(search) = text("search") {
+                    query = query.search(search);
+                }
+                if let Some(tag) = text("tag") {
+                    query = query.tag(tag);
+                }
+                if let Some(group) = text("group") {
+                    query = query.group(group);
+                }
+                if let Some(url) = text("url") {
+                    query = query.url_contains(url);
+                }
+                for account in query {
+                    entries.push(json!({
+                        "vault": vault,
+                        "id": account.get_id(),
+                        "username": account.get_username_or_email(),
+                        "description": account.get_description(),
+                        "url": account.get_url(),
+                        "group": account.get_group(),
+                        "tags": account.get_tags(),
+                    }));
+                }
+            }
+            Ok(Value::Array(entries))
+        },
+        Method::Get => {
+            let vault = vault.ok_or_else(|| (INVALID_PARAMS, "get needs a vault".to_string()))?;
+            let term = text("account").ok_or_else(|| (INVALID_PARAMS, "get needs an account".to_string()))?;
+            let (database, entry_key) = opened(vaults, &vault)?;
+            let account = find(&database, term, &vault).map_err(|e| (FAILED, e.to_string()))?;
+            let password = entry_key.open(account.get_password()).map_err(|e| (FAILED, e.to_string()))?;
+            Ok(json!({ "id": account.get_id(), "username": account.get_username_or_email(), "password": password }))
+        },
+        Method::Generate => {
+            // Checked before anything is generated, while the vaults are locked.
+            let length = params.get("length")
+                .map(|length| length.as_u64()
+                    .and_then(|length| usize::try_from(length).ok())
+                    .filter(|length| (1..=MAX_LENGTH).contains(length))
+                    .ok_or_else(|| (INVALID_PARAMS, format!("length must be a whole number from 1 to {}", MAX_LENGTH))))
+                .transpose()?;
+            let generated = match (text("preset"), length) {
+                (Some(preset), _) => config::current().preset(preset).and_then(|preset| preset.generate()),
+                (None, length) => generate_random_password(&PasswordOptions {
+                    length: length.unwrap_or(DEFAULT_LENGTH),
+                    ..PasswordOptions::default()
+                }),
+            }.map_err(|e| (FAILED, e.to_string()))?;
+            Ok(json!({ "password": generated.password, "bits": generated.bits }))
+        },
+        Method::Lock => {
+            let before = vaults.len();
+            match vault {
+                Some(vault) => {
+                    vaults.remove(&vault);
+                },
+                None => vaults.retain(|vault, _| !token.allows(vault)),
+            }
+            Ok(json!({ "locked": before - vaults.len() }))
+        },
+    }
+}
+
+fn opened(vaults: &mut UnlockedVaults, vault: &Path) -> Result<(Database, EntryKey), Failure> {
+    agent::use_vault(vaults, vault)
+        .map_err(|e| (FAILED, e.to_string()))?
+        .ok_or_else(|| (LOCKED, format!("{} is locked; unlock it with `ferropass unlock`", vault.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Once;
+
+    // Tokens are kept in the state directory, shared by every test here.
+    fn dir() -> PathBuf {
+        static SETUP: Once = Once::new();
+        let root = std::env::temp_dir().join(format!("ferropass-rpc-{}", std::process::id()));
+        SETUP.call_once(|| {
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            unsafe {
+                std::env::set_var("XDG_STATE_HOME", root.join("state"));
+            }
+        });
+        root
+    }
+
+    fn add_token(name: &str, vaults: Vec<PathBuf>) {
+        let token = Token { hash: hash(name), methods: vec![Method::List], vaults, created_at: 0 };
+        config::update_state(TOKENS_FILE, |tokens: &mut Tokens| tokens.insert(name.to_string(), token)).unwrap();
+    }
+
+    fn list(token: &str, vault: &Path) -> Failure {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "list", "params": { "token": token, "vault": vault } });
+        let answer = answer(&request, &mut UnlockedVaults::new()).unwrap();
+        let error = &answer["error"];
+        (error["code"].as_i64().unwrap(), error["message"].as_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn limited_tokens_learn_nothing_about_other_paths() {
+        let dir = dir();
+        let allowed = dir.join("allowed.fp");
+        let other = dir.join("other.fp");
+        fs::write(&allowed, b"").unwrap();
+        fs::write(&other, b"").unwrap();
+        add_token("limited", vec![fs::canonicalize(&allowed).unwrap()]);
+
+        assert_eq!(list("limited", &fs::canonicalize(&allowed).unwrap()).0, LOCKED);
+        assert_eq!(list("limited", &allowed.parent().unwrap().join(".").join("allowed.fp")).0, LOCKED);
+
+        let missing = dir.join("missing.fp");
+        assert_eq!(list("limited", &other), (UNAUTHORIZED, format!("The token doesn't allow {}", other.display())));
+        assert_eq!(list("limited", &missing), (UNAUTHORIZED, format!("The token doesn't allow {}", missing.display())));
+    }
+
+    #[test]
+    fn unresolvable_paths_are_unauthorized() {
+        let missing = dir().join("nowhere").join("vault.fp");
+        add_token("unlimited", Vec::new());
+
+        assert_eq!(list("unlimited", &missing).0, UNAUTHORIZED);
+        assert_eq!(list("unknown", &missing).0, UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,168 @@
+use ferropass_core::encryption::{encrypt_and_save_database, save_changes, SessionKey};
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::Database;
+use ferropass_core::storage::VaultStorage;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+struct SaveJob {
+    database: Database,
+    path: PathBuf,
+    key: SessionKey,
+}
+
+enum Message {
+    Track(PathBuf, Database),
+    Forget(PathBuf),
+    Save(Box<SaveJob>),
+    Flush(Sender<()>),
+}
+
+// Runs saves on a single background thread so the menu doesn't block on key
+// derivation and encryption after every edit. Jobs are handled in the order
+// they were queued; failures are collected for the caller to report. Once a
+// file's on-disk state is known (see `track`), only the differences are
+// appended to its journal.
+pub struct SaveQueue {
+    sender: Option<Sender<Message>>,
+    worker: Option<JoinHandle<()>>,
+    errors: Arc<Mutex<Vec<String>>>,
+}
+
+impl SaveQueue {
+    pub fn new(storage: Arc<dyn VaultStorage>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+
+        let worker_errors = Arc::clone(&errors);
+        let worker = thread::spawn(move || run_worker(storage, receiver, worker_errors));
+
+        SaveQueue {
+            sender: Some(sender),
+            worker: Some(worker),
+            errors,
+        }
+    }
+
+    // Records `database` as what is currently stored at `path`.
+    pub fn track(&self, path: &Path, database: &Database) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(Message::Track(path.to_path_buf(), database.clone()));
+        }
+    }
+
+    // Drops the worker's copy of the decrypted state, e.g. when the database
+    // is locked.
+    pub fn forget(&self, path: &Path) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(Message::Forget(path.to_path_buf()));
+        }
+    }
+    
+    pub fn enqueue(&self, database: &Database, path: &Path, key: &SessionKey) {
+        let job = SaveJob {
+            database: database.clone(),
+            path: path.to_path_buf(),
+            key: key.clone(),
+        };
+
+        let sent = self.sender.as_ref()
+            .is_some_and(|sender| sender.send(Message::Save(Box::new(job))).is_ok());
+
+        if !sent {
+            self.record_error("Save worker is not running, changes were not saved".to_string());
+        }
+    }
+
+    pub fn take_errors(&self) -> Vec<String> {
+        self.errors.lock()
+            .map(|mut errors| errors.drain(..).collect())
+            .unwrap_or_default()
+    }
+
+    // Blocks until every save queued so far has been written.
+    pub fn flush(&self) -> Result<(), FerropassError> {
+        let (reply_sender, reply_receiver) = mpsc::channel();
+
+        if let Some(sender) = &self.sender
+            && sender.send(Message::Flush(reply_sender)).is_ok()
+        {
+            let _ = reply_receiver.recv();
+        }
+
+        let errors = self.take_errors();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(FerropassError::Io(format!("Failed to save changes: {}", errors.join("; "))))
+        }
+    }
+
+    fn record_error(&self, error: String) {
+        if let Ok(mut errors) = self.errors.lock() {
+            errors.push(error);
+        }
+    }
+}
+
+impl Drop for SaveQueue {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_worker(storage: Arc<dyn VaultStorage>, receiver: Receiver<Message>, errors: Arc<Mutex<Vec<String>>>) {
+    let mut saved_states: HashMap<PathBuf, Database> = HashMap::new();
+
+    while let Ok(message) = receiver.recv() {
+        let mut pending = vec![message];
+        pending.extend(receiver.try_iter());
+
+        for (index, message) in pending.iter().enumerate() {
+            match message {
+                Message::Track(path, database) => {
+                    saved_states.insert(path.clone(), database.clone());
+                },
+                Message::Forget(path) => {
+                    saved_states.remove(path);
+                },
+                Message::Save(job) => {
+                    // A later save of the same file, queued before the next flush,
+                    // already contains these changes.
+                    let superseded = pending[index + 1..].iter()
+                        .take_while(|later| !matches!(later, Message::Flush(_)))
+                        .any(|later| matches!(later, Message::Save(later) if later.path == job.path));
+                    if superseded {
+                        continue;
+                    }
+
+                    let result = match saved_states.remove(&job.path) {
+                        Some(previous) => save_changes(storage.as_ref(), &previous, &job.database, &job.path, &job.key),
+                        None => encrypt_and_save_database(storage.as_ref(), &job.database, &job.path, &job.key),
+                    };
+
+                    match result {
+                        Ok(()) => {
+                            saved_states.insert(job.path.clone(), job.database.clone());
+                        },
+                        Err(e) => {
+                            if let Ok(mut errors) = errors.lock() {
+                                errors.push(format!("{}: {}", job.path.display(), e));
+                            }
+                        }
+                    }
+                },
+                Message::Flush(reply) => {
+                    let _ = reply.send(());
+                }
+            }
+        }
+    }
+}
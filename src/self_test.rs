@@ -0,0 +1,344 @@
+use crate::clipboard;
+use crate::terminal;
+
+use ferropass_core::algorithms::{self, DEFAULT_CIPHER};
+use ferropass_core::encryption::{encrypt_and_save_database, rewrap_and_save, save_changes, unlock_database, SessionKey};
+use ferropass_core::entropy;
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::{Account, Database};
+use ferropass_core::password::{generate_pin_with, generate_random_password_with, PasswordOptions};
+use ferropass_core::shamir::{self, Share};
+use ferropass_core::storage::MemoryStorage;
+use ferropass_core::stream::{ChunkReader, ChunkWriter};
+use ferropass_core::totp::Totp;
+
+use argon2::{Algorithm, Argon2, AssociatedData, ParamsBuilder, Version};
+use ed25519_dalek::{Signer, SigningKey, Verifier};
+use rand::{RngCore, SeedableRng};
+use rand::rngs::{OsRng, StdRng};
+use sha2::{Digest, Sha256};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::Path;
+
+// Argon2id test vector from RFC 9106, section 5.3.
+const ARGON2ID_TAG: &str = "0d640df58d78766c08c037a34a8b53c9d01ef0452d75b65eb52520e96b01e659";
+
+// AES-256-GCM test case 14 from the GCM specification: zero key, zero IV,
+// one zero block.
+const AES_GCM_CIPHERTEXT: &str = "cea7403d4d606b6e074ec5d3baf39d18";
+const AES_GCM_TAG: &str = "d0d1c8a799996bf0265b98b5d48ab919";
+
+// Ed25519 test 1 from RFC 8032, section 7.1: an empty message.
+const ED25519_SECRET: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+const ED25519_PUBLIC: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+const ED25519_SIGNATURE: &str = "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b";
+
+// TOTP test vectors from RFC 6238, appendix B, at 59 seconds past the epoch.
+const TOTP_VECTORS: [(&str, &str); 3] = [
+    ("otpauth://totp/rfc?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&digits=8", "94287082"),
+    ("otpauth://totp/rfc?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA&algorithm=SHA256&digits=8", "46119246"),
+    (
+        "otpauth://totp/rfc?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA&algorithm=SHA512&digits=8",
+        "90693936",
+    ),
+];
+
+const SHA256_ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+type Check = fn() -> Result<(), FerropassError>;
+
+pub fn run() -> Result<(), FerropassError> {
+    println!("FerroPass self-test");
+
+    let checks: [(&str, Check); 10] = [
+        ("Random number source is available and not stuck", check_rng),
+        ("Password generators draw only from the random number source they are given and follow their options", check_generators),
+        ("Argon2id matches the RFC 9106 test vector", check_argon2),
+        ("AES-256-GCM matches the GCM specification test vector", check_aes_gcm),
+        ("SHA-256 matches the FIPS 180-2 test vector", check_sha256),
+        ("Ed25519 matches the RFC 8032 test vector", check_ed25519),
+        ("TOTP codes match the RFC 6238 test vectors", check_totp),
+        ("Chunked encryption round-trips and refuses reordered or cut-off chunks", check_chunks),
+        ("Database container round-trips through a full save, a journal append, a passkey change and an unlock", check_container),
+        ("Recovery shares rebuild the key from any threshold of them", check_shamir),
+    ];
+
+    let mut failures = 0;
+    for (label, check) in checks {
+        match check() {
+            Ok(()) => println!("  [ OK ] {}", label),
+            Err(e) => {
+                println!("  [FAIL] {}: {}", label, e);
+                failures += 1;
+            }
+        }
+    }
+
+    match clipboard::check_backend() {
+        Ok(backend) => println!("  [ OK ] Clipboard backend is reachable: {}", backend),
+        Err(e) => println!("  [WARN] Clipboard backend is not available, copying passwords won't work: {}", e),
+    }
+
+    println!("  [INFO] Terminal: {}", terminal::current().describe());
+    println!("  [INFO] Input is {}", if io::stdin().is_terminal() { "a terminal" } else { "not a terminal" });
+    println!("  [INFO] Waiting for input with a timeout (auto-lock) is {}",
+        if cfg!(unix) { "supported" } else { "not supported on this platform" });
+
+    if failures > 0 {
+        return Err(FerropassError::Crypto(format!("{} self-test check(s) failed, don't trust this build with real secrets", failures)));
+    }
+
+    println!("All checks passed.");
+    Ok(())
+}
+
+fn check_rng() -> Result<(), FerropassError> {
+    entropy::ensure()?;
+
+    let mut first = [0u8; 32];
+    let mut second = [0u8; 32];
+    OsRng.try_fill_bytes(&mut first).map_err(|e| FerropassError::Crypto(e.to_string()))?;
+    OsRng.try_fill_bytes(&mut second).map_err(|e| FerropassError::Crypto(e.to_string()))?;
+
+    if first == second {
+        return Err(FerropassError::Crypto("two draws returned the same bytes".to_string()));
+    }
+    if first.iter().all(|&byte| byte == first[0]) {
+        return Err(FerropassError::Crypto("a draw returned one byte repeated".to_string()));
+    }
+
+    Ok(())
+}
+
+// The same seed must give the same passwords, which it wouldn't if a
+// generator drew from anywhere else.
+fn check_generators() -> Result<(), FerropassError> {
+    let options = PasswordOptions { length: 8, exclude: "aeiou&".to_string(), ..PasswordOptions::default() };
+    let first = generate_random_password_with(&options, &mut StdRng::seed_from_u64(1))?;
+    let second = generate_random_password_with(&options, &mut StdRng::seed_from_u64(1))?;
+    if first.password != second.password {
+        return Err(FerropassError::Crypto("the same seed gave different passwords".to_string()));
+    }
+
+    let mut rng = StdRng::seed_from_u64(2);
+    for _ in 0..100 {
+        let password = generate_random_password_with(&options, &mut rng)?.password;
+        let has = |class: fn(&char) -> bool| password.chars().any(|c| class(&c));
+
+        if password.chars().count() != options.length {
+            return Err(FerropassError::Crypto(format!("a password of {} characters was asked for, got {:?}", options.length, password)));
+        }
+        if !has(char::is_ascii_lowercase) || !has(char::is_ascii_uppercase) || !has(char::is_ascii_digit) || !has(char::is_ascii_punctuation) {
+            return Err(FerropassError::Crypto(format!("{:?} is missing a character class", password)));
+        }
+        if password.chars().any(|c| options.exclude.contains(c)) {
+            return Err(FerropassError::Crypto(format!("{:?} contains an excluded character", password)));
+        }
+
+        let pin = generate_pin_with(4, &mut rng)?.password;
+        if pin.len() != 4 || !pin.chars().all(|c| c.is_ascii_digit()) {
+            return Err(FerropassError::Crypto(format!("{:?} is not a 4-digit PIN", pin)));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_argon2() -> Result<(), FerropassError> {
+    let data = AssociatedData::new(&[0x04; 12]).map_err(|e| FerropassError::Crypto(e.to_string()))?;
+    let params = ParamsBuilder::new()
+        .m_cost(32)
+        .t_cost(3)
+        .p_cost(4)
+        .data(data)
+        .output_len(32)
+        .build()
+        .map_err(|e| FerropassError::Crypto(e.to_string()))?;
+    let argon2 = Argon2::new_with_secret(&[0x03; 8], Algorithm::Argon2id, Version::V0x13, params)
+        .map_err(|e| FerropassError::Crypto(e.to_string()))?;
+
+    let mut tag = [0u8; 32];
+    argon2.hash_password_into(&[0x01; 32], &[0x02; 16], &mut tag).map_err(|e| FerropassError::Crypto(e.to_string()))?;
+
+    expect_hex(&tag, ARGON2ID_TAG)
+}
+
+fn check_aes_gcm() -> Result<(), FerropassError> {
+    let cipher = algorithms::cipher(DEFAULT_CIPHER)?;
+    let output = cipher.encrypt(&[0u8; 32], &[0u8; 12], &[0u8; 16], &[])?;
+
+    let (ciphertext, tag) = output.split_at(16);
+    expect_hex(ciphertext, AES_GCM_CIPHERTEXT)?;
+    expect_hex(tag, AES_GCM_TAG)?;
+
+    let plaintext = cipher.decrypt(&[0u8; 32], &[0u8; 12], &output, &[])
+        .map_err(|_| FerropassError::Crypto("decrypting the test vector failed".to_string()))?;
+    if plaintext != [0u8; 16] {
+        return Err(FerropassError::Crypto("decrypting the test vector gave the wrong plaintext".to_string()));
+    }
+
+    let mut tampered = output.clone();
+    tampered[0] ^= 1;
+    if cipher.decrypt(&[0u8; 32], &[0u8; 12], &tampered, &[]).is_ok() {
+        return Err(FerropassError::Crypto("a modified ciphertext still authenticated".to_string()));
+    }
+
+    Ok(())
+}
+
+fn check_sha256() -> Result<(), FerropassError> {
+    let digest = format!("{:x}", Sha256::digest(b"abc"));
+
+    if digest != SHA256_ABC {
+        return Err(FerropassError::Crypto(format!("got {}, expected {}", digest, SHA256_ABC)));
+    }
+
+    Ok(())
+}
+
+fn check_totp() -> Result<(), FerropassError> {
+    for (uri, expected) in TOTP_VECTORS {
+        let code = Totp::parse(uri)?.code_at(59);
+        if code != expected {
+            return Err(FerropassError::Crypto(format!("got {}, expected {}", code, expected)));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_ed25519() -> Result<(), FerropassError> {
+    let secret: [u8; 32] = (0..32)
+        .map(|index| u8::from_str_radix(&ED25519_SECRET[index * 2..index * 2 + 2], 16).unwrap_or(0))
+        .collect::<Vec<u8>>()
+        .try_into()
+        .map_err(|_| FerropassError::Crypto("the test key has the wrong length".to_string()))?;
+    let signing_key = SigningKey::from_bytes(&secret);
+
+    expect_hex(signing_key.verifying_key().as_bytes(), ED25519_PUBLIC)?;
+    let signature = signing_key.sign(b"");
+    expect_hex(&signature.to_bytes(), ED25519_SIGNATURE)?;
+
+    if signing_key.verifying_key().verify(b"x", &signature).is_ok() {
+        return Err(FerropassError::Crypto("a signature verified for a different message".to_string()));
+    }
+
+    Ok(())
+}
+
+// Uses a tiny chunk size, so lengths on and around chunk boundaries are
+// covered without large inputs.
+fn check_chunks() -> Result<(), FerropassError> {
+    let cipher = algorithms::cipher(DEFAULT_CIPHER)?;
+    let key = [7u8; 32];
+    let nonce = [9u8; 12];
+    let chunk_size = 4;
+    let tag_len = cipher.tag_len();
+
+    let encrypt = |plaintext: &[u8]| -> Result<Vec<u8>, FerropassError> {
+        let mut writer = ChunkWriter::new(cipher.as_ref(), &key, &nonce, b"self-test", chunk_size, Vec::new());
+        writer.write_all(plaintext).map_err(|e| FerropassError::Crypto(e.to_string()))?;
+        writer.finish().map_err(|e| FerropassError::Crypto(e.to_string()))
+    };
+    let decrypt = |ciphertext: &[u8]| -> Result<Vec<u8>, FerropassError> {
+        let mut plaintext = Vec::new();
+        ChunkReader::new(cipher.as_ref(), &key, &nonce, b"self-test", chunk_size, ciphertext)
+            .read_to_end(&mut plaintext)
+            .map_err(|e| FerropassError::Crypto(e.to_string()))?;
+        Ok(plaintext)
+    };
+
+    for len in [0, 3, 4, 5, 8, 11] {
+        let plaintext: Vec<u8> = (0..len as u8).collect();
+        if decrypt(&encrypt(&plaintext)?)? != plaintext {
+            return Err(FerropassError::Crypto(format!("{} bytes came back different", len)));
+        }
+    }
+
+    let ciphertext = encrypt(b"0123456789")?;
+    let full_len = chunk_size + tag_len;
+    if decrypt(&ciphertext[..2 * full_len]).is_ok() {
+        return Err(FerropassError::Crypto("data cut off at a chunk boundary decrypted".to_string()));
+    }
+
+    let mut swapped = ciphertext.clone();
+    swapped[..full_len].copy_from_slice(&ciphertext[full_len..2 * full_len]);
+    swapped[full_len..2 * full_len].copy_from_slice(&ciphertext[..full_len]);
+    if decrypt(&swapped).is_ok() {
+        return Err(FerropassError::Crypto("reordered chunks decrypted".to_string()));
+    }
+
+    Ok(())
+}
+
+// Runs the real save and unlock code against in-memory storage.
+fn check_container() -> Result<(), FerropassError> {
+    let storage = MemoryStorage::new();
+    let path = Path::new("self-test.fp");
+    let passkey = "Self-Test-Passkey-0!";
+
+    let key = SessionKey::generate(passkey)?;
+    let entry_key = key.entry_key();
+
+    let mut database = Database::new();
+    database.add_account(Account::new("first@example.com".to_string(), None, entry_key.seal("Hunter2-Hunter2!")?));
+    encrypt_and_save_database(&storage, &database, path, &key)?;
+
+    let previous = database.clone();
+    database.add_account(Account::new("second@example.com".to_string(), Some("Journal".to_string()), entry_key.seal("Hunter3-Hunter3!")?));
+    save_changes(&storage, &previous, &database, path, &key)?;
+
+    let (unlocked, key) = unlock_database(&storage, path, passkey)?;
+    if unlocked.checksum() != database.checksum() {
+        return Err(FerropassError::Crypto("the unlocked database differs from what was saved".to_string()));
+    }
+
+    let new_passkey = "Self-Test-Passkey-1!";
+    rewrap_and_save(&storage, &database, path, &key.rekey(new_passkey)?)?;
+
+    let (unlocked, key) = unlock_database(&storage, path, new_passkey)?;
+    if unlocked.checksum() != database.checksum() {
+        return Err(FerropassError::Crypto("the database differs after changing the passkey".to_string()));
+    }
+    if key.entry_key().open(unlocked.get_accounts()[1].get_password())? != "Hunter3-Hunter3!" {
+        return Err(FerropassError::Crypto("a password opened to something else after changing the passkey".to_string()));
+    }
+
+    if unlock_database(&storage, path, passkey).is_ok() {
+        return Err(FerropassError::Crypto("the old passkey still unlocked the database".to_string()));
+    }
+    if unlock_database(&storage, path, "Wrong-Passkey-000!").is_ok() {
+        return Err(FerropassError::Crypto("a wrong passkey unlocked the database".to_string()));
+    }
+
+    Ok(())
+}
+
+fn check_shamir() -> Result<(), FerropassError> {
+    let mut secret = [0u8; 32];
+    OsRng.try_fill_bytes(&mut secret).map_err(|e| FerropassError::Crypto(e.to_string()))?;
+
+    let encoded: Vec<String> = shamir::split(&secret, 3, 5)?.iter().map(Share::encode).collect();
+
+    for set in [[0, 2, 4], [1, 3, 4]] {
+        let shares = set.iter()
+            .map(|&index| Share::parse(&encoded[index]))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if shamir::combine(&shares)? != secret {
+            return Err(FerropassError::Crypto("a set of shares rebuilt the wrong key".to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+fn expect_hex(actual: &[u8], expected: &str) -> Result<(), FerropassError> {
+    let actual: String = actual.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    if actual != expected {
+        return Err(FerropassError::Crypto(format!("got {}, expected {}", actual, expected)));
+    }
+
+    Ok(())
+}
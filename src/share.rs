@@ -0,0 +1,147 @@
+use crate::cli::Cli;
+use crate::import::{ask_on_conflict, find_collision, OnConflict};
+
+use ferropass_core::encryption::{encrypt_and_save_database, load_and_decrypt_database, unlock_database, EntryKey, SessionKey};
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::{Account, Database};
+use ferropass_core::storage::{FileStorage, MemoryStorage, VaultStorage};
+
+use base64::{Engine as _, engine::general_purpose};
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+const BEGIN: &str = "-----BEGIN FERROPASS ENTRY-----";
+const END: &str = "-----END FERROPASS ENTRY-----";
+const BUNDLE_PATH: &str = "bundle.fp";
+
+// A single account sealed under a password of its own, as ASCII armour small
+// enough to paste into a message.
+pub fn create(account: &Account, entry_key: &EntryKey, password: &str) -> Result<String, FerropassError> {
+    let encoded = general_purpose::STANDARD.encode(seal(&[account], entry_key, password)?);
+
+    let mut armored = vec![BEGIN.to_string()];
+    armored.extend(encoded.as_bytes().chunks(64).map(|line| String::from_utf8_lossy(line).to_string()));
+    armored.push(END.to_string());
+    Ok(armored.join("\n"))
+}
+
+// Accounts sealed under a password of their own. Inside is an ordinary
+// database file, so they are protected exactly like a vault.
+pub fn seal(accounts: &[&Account], entry_key: &EntryKey, password: &str) -> Result<Vec<u8>, FerropassError> {
+    let mut bundle = Database::new();
+    for account in accounts {
+        bundle.add_account((*account).clone());
+    }
+
+    let key = SessionKey::generate(password)?;
+    key.entry_key().reseal_all(&mut bundle, entry_key)?;
+
+    let storage = MemoryStorage::new();
+    encrypt_and_save_database(&storage, &bundle, Path::new(BUNDLE_PATH), &key)?;
+    storage.read_blob(Path::new(BUNDLE_PATH))
+}
+
+// Adds the account in a bundle to a vault, reading the bundle from `bundle`
+// or, without one, from what is pasted on stdin.
+pub fn import_entry(filepath: &Path, bundle: Option<&Path>, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    let armored = match bundle {
+        Some(path) => fs::read_to_string(path).map_err(|e| FerropassError::Io(format!("Failed to read {}: {}", path.display(), e)))?,
+        None => read_pasted()?,
+    };
+    add_bundle(filepath, &dearmor(&armored)?, passkey_file)
+}
+
+// Adds the accounts sealed in `contents` to a vault, asking what to do with
+// each one that is already there.
+pub fn add_bundle(filepath: &Path, contents: &[u8], passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    let _vault_lock = FileStorage.lock(filepath)?;
+    let (mut database, key) = unlock_database(&FileStorage, filepath, &passkey)?;
+
+    let storage = MemoryStorage::new();
+    storage.write_blob(Path::new(BUNDLE_PATH), contents)?;
+    let password = Cli::read_passkey(None, "Enter the bundle's password: ")?;
+    let (mut bundle, bundle_key) = load_and_decrypt_database(&storage, Path::new(BUNDLE_PATH), &password)
+        .map_err(|e| match e {
+            FerropassError::InvalidPasskey => FerropassError::Crypto("Wrong password, or the bundle is damaged".to_string()),
+            e => e,
+        })?;
+    key.entry_key().reseal_all(&mut bundle, &bundle_key)?;
+
+    if bundle.get_accounts().is_empty() {
+        return Err(FerropassError::Format("The bundle holds no entries".to_string()));
+    }
+
+    let mut imported = Vec::new();
+    let mut for_all = None;
+    for account in bundle.get_accounts() {
+        let existing = database.get_accounts().iter().position(|existing| existing.get_id() == account.get_id())
+            .or_else(|| find_collision(&database, account));
+        let name = account.get_description().clone().unwrap_or_else(|| account.get_username_or_email().to_string());
+
+        let Some(index) = existing else {
+            database.add_account(account.clone());
+            imported.push(name);
+            continue;
+        };
+        let choice = match for_all {
+            Some(choice) => choice,
+            None => {
+                let (choice, remember) = ask_on_conflict(&database.get_accounts()[index])?;
+                if remember {
+                    for_all = Some(choice);
+                }
+                choice
+            },
+        };
+        match choice {
+            OnConflict::Skip => {
+                println!("{} was not imported.", name);
+                continue;
+            },
+            OnConflict::Overwrite => database.get_accounts_mut()[index] = account.clone(),
+            OnConflict::Duplicate => database.add_account(account.duplicate()),
+        }
+        imported.push(name);
+    }
+
+    if imported.is_empty() {
+        return Ok(());
+    }
+    encrypt_and_save_database(&FileStorage, &database, filepath, &key)?;
+    match imported.as_slice() {
+        [name] => println!("Imported {} into {}.", name, filepath.display()),
+        _ => println!("Imported {} entries into {}.", imported.len(), filepath.display()),
+    }
+    Ok(())
+}
+
+fn read_pasted() -> Result<String, FerropassError> {
+    println!("Paste the bundle:");
+
+    let mut armored = String::new();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|e| FerropassError::Io(format!("Failed to read input: {}", e)))?;
+        let done = line.trim() == END;
+        armored.push_str(&line);
+        armored.push('\n');
+        if done {
+            break;
+        }
+    }
+    Ok(armored)
+}
+
+// Anything around the armour, such as the rest of an email, is ignored.
+fn dearmor(armored: &str) -> Result<Vec<u8>, FerropassError> {
+    let (Some(start), Some(end)) = (armored.find(BEGIN), armored.find(END)) else {
+        return Err(FerropassError::Format("No ferropass entry bundle found".to_string()));
+    };
+    if end < start {
+        return Err(FerropassError::Format("No ferropass entry bundle found".to_string()));
+    }
+
+    let encoded: String = armored[start + BEGIN.len()..end].chars().filter(|c| !c.is_whitespace()).collect();
+    general_purpose::STANDARD.decode(encoded).map_err(|_| FerropassError::Format("The bundle is damaged".to_string()))
+}
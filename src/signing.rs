@@ -0,0 +1,56 @@
+use crate::cli::Cli;
+
+use ferropass_core::encryption::{encrypt_and_save_database, unlock_ignoring_signature};
+use ferropass_core::error::FerropassError;
+use ferropass_core::signing::{key_id, load, load_or_create, path, short_id, signature_path};
+use ferropass_core::storage::{FileStorage, VaultStorage};
+
+use std::fs;
+use std::path::Path;
+
+// Signs the vault with this machine's key, or signs it again once the
+// passkey has shown its contents are intact.
+pub fn run(filepath: &Path, remove: bool, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    let _vault_lock = FileStorage.lock(filepath)?;
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+    let (database, mut key) = unlock_ignoring_signature(&FileStorage, filepath, &passkey)?;
+
+    if remove {
+        if key.get_signing_key().is_none() {
+            return Err(FerropassError::Validation(format!("{} isn't signed", filepath.display())));
+        }
+
+        key.set_signing_key(None);
+        encrypt_and_save_database(&FileStorage, &database, filepath, &key)?;
+        let _ = fs::remove_file(signature_path(filepath));
+
+        println!("{} is no longer signed.", filepath.display());
+        return Ok(());
+    }
+
+    let resigning = key.get_signing_key().is_some();
+    let (signing_key, path) = match key.get_signing_key() {
+        Some(public_key) => (load(public_key)?, path()?),
+        None => {
+            let (signing_key, path, created) = load_or_create()?;
+            if created {
+                println!("Created a new signing key at {}.", path.display());
+            }
+            (signing_key, path)
+        }
+    };
+
+    let public_key = key_id(&signing_key.verifying_key());
+    key.set_signing_key(Some(public_key.clone()));
+    encrypt_and_save_database(&FileStorage, &database, filepath, &key)?;
+
+    if resigning {
+        println!("{} was checked with the passkey and signed again.", filepath.display());
+        return Ok(());
+    }
+
+    println!("{} is now signed with key {} from {}.", filepath.display(), short_id(&public_key), path.display());
+    println!("Opening it checks the signature before anything is decrypted, and every save signs it again.");
+    println!("Saving it on another machine needs a copy of the signing key there.");
+    Ok(())
+}
@@ -0,0 +1,315 @@
+use crate::agent;
+use crate::cli::Cli;
+use crate::pager;
+
+use ferropass_core::config;
+use ferropass_core::encryption::{load_and_decrypt_database, EntryKey};
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::{current_timestamp, format_timestamp, Account, Database, LogRecord};
+use ferropass_core::paranoid;
+use ferropass_core::password::is_password_valid;
+use ferropass_core::storage::{FileStorage, VaultStorage};
+
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+// The audit from each vault's last summary, by path, to report what changed
+// since.
+const AUDITS_FILE: &str = "summary-audits.json";
+
+pub fn run(
+    filepath: &Path,
+    since: Duration,
+    max_age: Duration,
+    passkey_file: Option<&Path>,
+    mail: Option<&str>,
+    sendmail: &str,
+) -> Result<(), FerropassError> {
+    check_period(since, max_age)?;
+    if let Some(address) = mail {
+        check_address(address)?;
+    }
+    let read_passkey = || Cli::read_passkey(passkey_file, "Enter database passkey: ");
+    // A running agent with the vault unlocked builds it without a passkey.
+    let digest = match agent::summary(filepath, since, max_age, read_passkey)? {
+        Some(digest) => digest,
+        None => {
+            let (database, entry_key) = load_and_decrypt_database(&FileStorage, filepath, &read_passkey()?)?;
+            digest(filepath, &database, &entry_key, since, max_age)?
+        },
+    };
+
+    match mail {
+        Some(address) => {
+            let subject = format!("FerroPass summary for {}", display_name(filepath));
+            send_mail(sendmail, address, &subject, &digest)
+        },
+        None => pager::page("Summary", &digest),
+    }
+}
+
+// A line break would end the To header and let the rest of the address add
+// headers of its own, or start the body.
+fn check_address(address: &str) -> Result<(), FerropassError> {
+    if address.contains(['\r', '\n']) {
+        return Err(FerropassError::Validation("--mail must not contain line breaks".to_string()));
+    }
+    Ok(())
+}
+
+// Both are added to the current time, which has to stay a valid timestamp.
+fn check_period(since: Duration, max_age: Duration) -> Result<(), FerropassError> {
+    let now = current_timestamp();
+    if now.checked_add(since.as_secs()).is_none() {
+        return Err(FerropassError::Validation("--since is too long".to_string()));
+    }
+    if now.checked_add(max_age.as_secs()).is_none() {
+        return Err(FerropassError::Validation("--max-age is too long".to_string()));
+    }
+    Ok(())
+}
+
+pub fn digest(filepath: &Path, database: &Database, entry_key: &EntryKey, since: Duration, max_age: Duration) -> Result<String, FerropassError> {
+    let checks = check_passwords(database, entry_key)?;
+    let audit = Audit::of(&checks);
+    let vault = fs::canonicalize(filepath).unwrap_or_else(|_| filepath.to_path_buf()).display().to_string();
+    let previous = config::update_state(AUDITS_FILE, |audits: &mut Audits| audits.insert(vault, audit.clone()))?;
+
+    build_digest(filepath, database, &audit, previous.as_ref(), since, max_age)
+        .map_err(|e| FerropassError::Io(format!("Error building summary: {}", e)))
+}
+
+// What the audit needs from each password, worked out one password at a time
+// so they are never all open together.
+struct PasswordCheck {
+    valid: bool,
+    hash: String,
+}
+
+fn check_passwords(database: &Database, entry_key: &EntryKey) -> Result<HashMap<String, PasswordCheck>, FerropassError> {
+    let mut checks = HashMap::new();
+    for account in database.get_accounts() {
+        let password = entry_key.open(account.get_password())
+            .map_err(|e| e.context(format!("Account {}", account.get_id())))?;
+
+        checks.insert(account.get_id().to_string(), PasswordCheck {
+            valid: is_password_valid(&password),
+            hash: format!("{:x}", Sha256::digest(password.as_bytes())),
+        });
+    }
+
+    Ok(checks)
+}
+
+// Which accounts of the whole vault have a weak or a reused password, by ID.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+struct Audit {
+    at: u64,
+    weak: BTreeSet<String>,
+    reused: BTreeSet<String>,
+}
+
+type Audits = BTreeMap<String, Audit>;
+
+impl Audit {
+    fn of(checks: &HashMap<String, PasswordCheck>) -> Self {
+        let mut password_counts: HashMap<&str, usize> = HashMap::new();
+        for check in checks.values() {
+            *password_counts.entry(&check.hash).or_insert(0) += 1;
+        }
+
+        Audit {
+            at: current_timestamp(),
+            weak: checks.iter().filter(|(_, check)| !check.valid).map(|(id, _)| id.clone()).collect(),
+            reused: checks.iter().filter(|(_, check)| password_counts[check.hash.as_str()] > 1).map(|(id, _)| id.clone()).collect(),
+        }
+    }
+}
+
+fn build_digest(
+    filepath: &Path,
+    database: &Database,
+    audit: &Audit,
+    previous: Option<&Audit>,
+    since: Duration,
+    max_age: Duration,
+) -> Result<String, fmt::Error> {
+    let now = current_timestamp();
+    let cutoff = now.saturating_sub(since.as_secs());
+    let accounts = database.get_accounts();
+
+    let added: Vec<&Account> = accounts.iter()
+        .filter(|acc| acc.get_created_at() >= cutoff && acc.get_created_at() != 0)
+        .collect();
+    let changed: Vec<&Account> = accounts.iter()
+        .filter(|acc| acc.get_updated_at() >= cutoff && acc.get_created_at() < cutoff)
+        .collect();
+
+    let mut out = String::new();
+    writeln!(out, "=== FerroPass summary for {} ===", display_name(filepath))?;
+    writeln!(out, "Period: {} to {}", format_timestamp(cutoff), format_timestamp(now))?;
+    writeln!(out, "Total accounts: {}", accounts.len())?;
+    writeln!(out)?;
+
+    writeln!(out, "Added ({}):", added.len())?;
+    write_account_lines(&mut out, &added)?;
+    writeln!(out)?;
+
+    writeln!(out, "Changed ({}):", changed.len())?;
+    write_account_lines(&mut out, &changed)?;
+    writeln!(out)?;
+
+    let logged: Vec<&LogRecord> = database.get_change_log().iter()
+        .filter(|record| record.get_at() >= cutoff)
+        .collect();
+    if !logged.is_empty() {
+        writeln!(out, "Change log ({}):", logged.len())?;
+        for record in logged {
+            writeln!(out, "  {} {:<10} {}", format_timestamp(record.get_at()), record.get_entry_id(), record.get_text())?;
+        }
+        writeln!(out)?;
+    }
+
+    let recent: Vec<&Account> = added.iter().chain(changed.iter()).copied().collect();
+    let weak: Vec<&Account> = recent.iter()
+        .filter(|acc| audit.weak.contains(acc.get_id()))
+        .copied()
+        .collect();
+    let reused: Vec<&Account> = recent.iter()
+        .filter(|acc| audit.reused.contains(acc.get_id()))
+        .copied()
+        .collect();
+
+    writeln!(out, "Audit of added/changed accounts:")?;
+    writeln!(out, "  Weak passwords: {}", weak.len())?;
+    write_account_lines(&mut out, &weak)?;
+    writeln!(out, "  Reused passwords: {}", reused.len())?;
+    write_account_lines(&mut out, &reused)?;
+    writeln!(out)?;
+
+    match previous {
+        Some(previous) => {
+            writeln!(out, "Audit changes since the last summary ({}):", format_timestamp(previous.at))?;
+            write_audit_change(&mut out, "Weak passwords", accounts, &audit.weak, &previous.weak)?;
+            write_audit_change(&mut out, "Reused passwords", accounts, &audit.reused, &previous.reused)?;
+        },
+        None => {
+            writeln!(out, "Audit of the whole vault (the next summary reports what changed):")?;
+            writeln!(out, "  Weak passwords: {}", audit.weak.len())?;
+            writeln!(out, "  Reused passwords: {}", audit.reused.len())?;
+        },
+    }
+    writeln!(out)?;
+
+    let horizon = now.saturating_add(since.as_secs());
+    let mut expired = 0;
+    let mut expiring: Vec<(&Account, u64)> = Vec::new();
+    for account in accounts {
+        if account.get_password_updated_at() == 0 {
+            continue;
+        }
+
+        let expires_at = account.get_password_updated_at().saturating_add(max_age.as_secs());
+        if expires_at < now {
+            expired += 1;
+        } else if expires_at <= horizon {
+            expiring.push((account, expires_at));
+        }
+    }
+    expiring.sort_by_key(|(_, expires_at)| *expires_at);
+
+    writeln!(out, "Upcoming expirations (max password age {} days):", max_age.as_secs() / 86400)?;
+    if expiring.is_empty() {
+        writeln!(out, "  None")?;
+    }
+    for (account, expires_at) in &expiring {
+        writeln!(out, "  {:<10} {:<30} expires {}",
+            account.get_id(),
+            account.get_username_or_email(),
+            format_timestamp(*expires_at)
+        )?;
+    }
+    if expired > 0 {
+        writeln!(out, "  {} password(s) already past the maximum age", expired)?;
+    }
+    writeln!(out)?;
+
+    writeln!(out, "Backups:")?;
+    match FileStorage.latest_version(filepath) {
+        Some((path, modified)) => {
+            writeln!(out, "  Latest: {} ({})", path.display(), format_timestamp(modified))?;
+        },
+        None => {
+            writeln!(out, "  No backups found next to {}", filepath.display())?;
+        }
+    }
+
+    Ok(out)
+}
+
+fn write_account_lines(out: &mut String, accounts: &[&Account]) -> fmt::Result {
+    for account in accounts {
+        let desc = account.get_description()
+            .as_ref()
+            .map_or("", |s| s.as_str());
+
+        writeln!(out, "  {:<10} {:<30} {:<20}",
+            account.get_id(),
+            account.get_username_or_email(),
+            desc
+        )?;
+    }
+
+    Ok(())
+}
+
+// The count now and at the last summary, and the accounts that weren't
+// flagged then.
+fn write_audit_change(out: &mut String, label: &str, accounts: &[Account], now: &BTreeSet<String>, before: &BTreeSet<String>) -> fmt::Result {
+    let new: Vec<&Account> = accounts.iter().filter(|acc| now.contains(acc.get_id()) && !before.contains(acc.get_id())).collect();
+    let resolved = before.difference(now).count();
+
+    writeln!(out, "  {}: {} (was {}; {} new, {} resolved)", label, now.len(), before.len(), new.len(), resolved)?;
+    write_account_lines(out, &new)
+}
+
+fn send_mail(sendmail: &str, to: &str, subject: &str, body: &str) -> Result<(), FerropassError> {
+    paranoid::ensure_allowed("The mail hook")?;
+    
+    let mut parts = sendmail.split_whitespace();
+    let program = parts.next().ok_or_else(|| FerropassError::Io("Mail command is empty".to_string()))?;
+
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| FerropassError::Io(format!("Failed to run mail command '{}': {}", sendmail, e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The subject comes from the file name, which may hold line breaks.
+        let subject = subject.replace(['\r', '\n'], " ");
+        write!(stdin, "To: {}\nSubject: {}\n\n{}", to, subject, body)
+            .map_err(|e| FerropassError::Io(format!("Failed to write to mail command: {}", e)))?;
+    }
+
+    let status = child.wait()
+        .map_err(|e| FerropassError::Io(format!("Failed to wait for mail command: {}", e)))?;
+
+    if !status.success() {
+        return Err(FerropassError::Io(format!("Mail command exited with {}", status)));
+    }
+
+    Ok(())
+}
+
+fn display_name(filepath: &Path) -> String {
+    filepath.file_name()
+        .map_or_else(|| filepath.display().to_string(), |name| name.to_string_lossy().to_string())
+}
@@ -0,0 +1,213 @@
+use ferropass_core::config;
+
+use crossterm::{
+    cursor::MoveTo,
+    event, execute,
+    terminal::{self, Clear, ClearType},
+};
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+#[cfg(unix)]
+use std::time::Instant;
+
+pub trait Terminal: Send + Sync {
+    fn clear_screen(&self) -> io::Result<()>;
+    fn supports_full_screen(&self) -> bool;
+    fn describe(&self) -> &'static str;
+}
+
+// Full terminal control through crossterm, used whenever the terminal answers
+// a size query.
+pub struct CrosstermTerminal;
+
+impl Terminal for CrosstermTerminal {
+    fn clear_screen(&self) -> io::Result<()> {
+        execute!(io::stdout(), Clear(ClearType::All), MoveTo(0, 0))
+    }
+
+    fn supports_full_screen(&self) -> bool {
+        true
+    }
+
+    fn describe(&self) -> &'static str {
+        "full terminal control"
+    }
+}
+
+// Raw escape codes for terminals that render ANSI but can't be queried.
+pub struct AnsiTerminal;
+
+impl Terminal for AnsiTerminal {
+    fn clear_screen(&self) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b[2J\x1b[H")?;
+        stdout.flush()
+    }
+
+    fn supports_full_screen(&self) -> bool {
+        false
+    }
+
+    fn describe(&self) -> &'static str {
+        "ANSI escape codes, size unknown"
+    }
+}
+
+// Emacs shells, CI logs, pipes and users who turn off screen clearing in the
+// config: never emit control sequences, just keep appending output.
+pub struct DumbTerminal;
+
+impl Terminal for DumbTerminal {
+    fn clear_screen(&self) -> io::Result<()> {
+        println!();
+        Ok(())
+    }
+
+    fn supports_full_screen(&self) -> bool {
+        false
+    }
+
+    fn describe(&self) -> &'static str {
+        "plain output, no control sequences"
+    }
+}
+
+static TERMINAL: OnceLock<Box<dyn Terminal>> = OnceLock::new();
+
+pub fn current() -> &'static dyn Terminal {
+    TERMINAL.get_or_init(detect).as_ref()
+}
+
+fn detect() -> Box<dyn Terminal> {
+    let term = env::var("TERM").unwrap_or_default();
+
+    if config::current().linear_output()
+        || !io::stdout().is_terminal()
+        || term == "dumb"
+        || env::var_os("INSIDE_EMACS").is_some()
+    {
+        return Box::new(DumbTerminal);
+    }
+
+    if terminal::size().is_ok() {
+        Box::new(CrosstermTerminal)
+    } else {
+        Box::new(AnsiTerminal)
+    }
+}
+
+pub fn clear_screen() {
+    if current().clear_screen().is_err() {
+        let _ = DumbTerminal.clear_screen();
+    }
+}
+
+// How long each read from the terminal waits for input while a vault with
+// auto-lock is open, in seconds; 0 waits forever. Once a read has given up,
+// every read fails straight away until the timeout is turned off.
+static IDLE_TIMEOUT: AtomicU64 = AtomicU64::new(0);
+static TIMED_OUT: AtomicBool = AtomicBool::new(false);
+
+// Lines are only waited for with poll(2); elsewhere only the pager can give up.
+pub const IDLE_TIMEOUT_SUPPORTED: bool = cfg!(unix);
+
+pub fn set_idle_timeout(timeout: Option<Duration>) {
+    IDLE_TIMEOUT.store(timeout.map_or(0, |timeout| timeout.as_secs().max(1)), Ordering::SeqCst);
+    if timeout.is_none() {
+        TIMED_OUT.store(false, Ordering::SeqCst);
+    }
+}
+
+// Whether a read gave up since the timeout was last turned off.
+pub fn timed_out() -> bool {
+    TIMED_OUT.load(Ordering::SeqCst)
+}
+
+fn wait_idle(wait: impl FnOnce(Duration) -> io::Result<bool>) -> io::Result<()> {
+    let secs = IDLE_TIMEOUT.load(Ordering::SeqCst);
+    if !timed_out() && (secs == 0 || wait(Duration::from_secs(secs))?) {
+        return Ok(());
+    }
+    TIMED_OUT.store(true, Ordering::SeqCst);
+    Err(io::Error::new(io::ErrorKind::TimedOut, "no input before the vault's auto-lock time"))
+}
+
+// Waits for input on stdin for up to the idle timeout, for reads that don't
+// go through `read_line`, such as passwords.
+pub fn wait_for_line() -> io::Result<()> {
+    wait_idle(|timeout| Ok(wait_for_input(timeout)))
+}
+
+// The same for keys read through crossterm.
+pub fn wait_for_event() -> io::Result<()> {
+    wait_idle(event::poll)
+}
+
+// Reads a line like `Stdin::read_line`, newline and all, once the idle
+// timeout allows. It reads straight from the file descriptor a byte at a
+// time, since poll(2) can't see lines typed ahead into Stdin's buffer.
+#[cfg(unix)]
+pub fn read_line() -> io::Result<String> {
+    wait_for_line()?;
+
+    let mut line = Vec::new();
+    loop {
+        let mut byte = 0u8;
+        match unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) } {
+            0 => break,
+            1 => {
+                line.push(byte);
+                if byte == b'\n' {
+                    break;
+                }
+            },
+            _ if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            _ => return Err(io::Error::last_os_error()),
+        }
+    }
+    String::from_utf8(line).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "input is not valid UTF-8"))
+}
+
+#[cfg(not(unix))]
+pub fn read_line() -> io::Result<String> {
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line)
+}
+
+// Waits until a line of input is ready on stdin, or the timeout passes.
+#[cfg(unix)]
+pub fn wait_for_input(timeout: Duration) -> bool {
+    let deadline = Instant::now().checked_add(timeout);
+    let mut poll_fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    
+    loop {
+        let millis = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                remaining.as_millis().min(libc::c_int::MAX as u128) as libc::c_int
+            },
+            None => -1,
+        };
+        
+        match unsafe { libc::poll(&mut poll_fd, 1, millis) } {
+            0 => return false,
+            result if result > 0 => return true,
+            _ if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            _ => return true,
+        }
+    }
+}
+
+// Without poll(2) input always counts as ready.
+#[cfg(not(unix))]
+pub fn wait_for_input(_timeout: Duration) -> bool {
+    true
+}
@@ -0,0 +1,560 @@
+use crate::cli::Cli;
+
+use ferropass_core::algorithms::{AeadCipher, Aes256GcmCipher};
+use ferropass_core::backup;
+use ferropass_core::error::FerropassError;
+use ferropass_core::paranoid;
+use ferropass_core::password::Language;
+use ferropass_core::signing;
+use ferropass_core::storage::{FileStorage, VaultStorage};
+
+use base64::{Engine as _, engine::general_purpose};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use subtle::ConstantTimeEq;
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use rand::rngs::OsRng;
+use rand::{Rng, RngCore};
+use serde::{Serialize, Deserialize};
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+// Receivers ask for a code's nameplate on this port; the sender answers with
+// the TCP port it is listening on.
+const DISCOVERY_PORT: u16 = 47331;
+const DISCOVERY_ATTEMPTS: usize = 10;
+const PROTOCOL: &str = "ferropass-transfer-1";
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_PAYLOAD: u32 = 256 * 1024 * 1024;
+const CODE_WORDS: usize = 2;
+
+// What is sent: the vault file as it is on disk, still encrypted under its
+// passkey, and its signature if it has one.
+#[derive(Serialize, Deserialize)]
+struct Payload {
+    name: String,
+    vault: String,
+    signature: Option<String>,
+}
+
+// The two sides of SPAKE2. The sender blinds its share with M and the
+// receiver with N, so neither can replay the other's message.
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Sender,
+    Receiver,
+}
+
+impl Side {
+    fn blinding(self) -> RistrettoPoint {
+        let label = match self {
+            Side::Sender => "ferropass spake2 M",
+            Side::Receiver => "ferropass spake2 N",
+        };
+        RistrettoPoint::from_uniform_bytes(&sha512(&[label.as_bytes()]))
+    }
+
+    fn other(self) -> Side {
+        match self {
+            Side::Sender => Side::Receiver,
+            Side::Receiver => Side::Sender,
+        }
+    }
+}
+
+// The keys both sides agree on once the code checks out.
+struct SessionKeys {
+    cipher: [u8; 32],
+    sender_proof: Vec<u8>,
+    receiver_proof: Vec<u8>,
+}
+
+// Serves the vault at `filepath` to one receiver on the local network. The
+// code printed is the only secret: anyone who has it may receive the file,
+// and a wrong guess ends the transfer, so it can't be tried more than once.
+pub fn send(filepath: &Path, port: u16) -> Result<(), FerropassError> {
+    paranoid::ensure_allowed("LAN transfer")?;
+
+    let payload = {
+        let _vault_lock = FileStorage.lock(filepath)?;
+        let signature_path = signing::signature_path(filepath);
+        Payload {
+            name: filepath.file_name()
+                .ok_or_else(|| FerropassError::Io("Database path has no file name".to_string()))?
+                .to_string_lossy()
+                .to_string(),
+            vault: general_purpose::STANDARD.encode(FileStorage.read_blob(filepath)?),
+            signature: if FileStorage.exists(&signature_path) {
+                Some(general_purpose::STANDARD.encode(FileStorage.read_blob(&signature_path)?))
+            } else {
+                None
+            },
+        }
+    };
+    let payload = serde_json::to_vec(&payload).map_err(|e| FerropassError::Io(format!("Failed to prepare the transfer: {}", e)))?;
+
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))
+        .map_err(|e| FerropassError::Io(format!("Failed to listen on port {}: {}", port, e)))?;
+    let port = listener.local_addr().map_err(|e| FerropassError::Io(format!("Failed to listen: {}", e)))?.port();
+
+    let code = generate_code();
+    let nameplate = code.split('-').next().unwrap_or_default().to_string();
+    if let Err(e) = answer_discovery(nameplate, port) {
+        eprintln!("Warning: receivers can't find this machine by themselves ({}); give them --from as below", e);
+    }
+
+    println!("On the other machine, run:");
+    println!("  ferropass receive {}", code);
+    match local_address() {
+        Some(address) => println!("or, if it can't find this one: ferropass receive {} --from {}:{}", code, address, port),
+        None => println!("or, if it can't find this one: ferropass receive {} --from <this machine's address>:{}", code, port),
+    }
+    println!("Waiting for the receiver (Ctrl-C to cancel)...");
+
+    let (mut stream, peer) = listener.accept().map_err(|e| FerropassError::Io(format!("Failed to accept a connection: {}", e)))?;
+    set_timeouts(&stream)?;
+
+    let keys = handshake(&mut stream, Side::Sender, &code)?;
+    if !confirm_as_sender(&mut stream, &keys)? {
+        return Err(FerropassError::Io(format!("{} didn't have the right code; nothing was sent. Start again with a new code", peer.ip())));
+    }
+
+    write_frame(&mut stream, &encrypt(&keys.cipher, &payload)?)?;
+    // The receiver may be asked whether to replace a vault before it answers.
+    stream.set_read_timeout(None).map_err(|e| FerropassError::Io(format!("The connection failed: {}", e)))?;
+    match read_frame(&mut stream, 1)?.as_slice() {
+        [1] => {
+            println!("Sent {} to {}.", filepath.display(), peer.ip());
+            Ok(())
+        },
+        _ => Err(FerropassError::Io(format!("{} received the vault but didn't save it", peer.ip()))),
+    }
+}
+
+// Fetches a vault from `ferropass send` on another machine, found on the
+// local network by the code's nameplate or given by `from`, and saves it as
+// `output` or under its own name in the current directory.
+pub fn receive(code: Option<&str>, from: Option<&str>, output: Option<&Path>) -> Result<(), FerropassError> {
+    paranoid::ensure_allowed("LAN transfer")?;
+
+    let code = match code {
+        Some(code) => code.to_string(),
+        None => Cli::prompt_input("Enter the code shown by `ferropass send`: ")?,
+    };
+    let code = normalize_code(&code)?;
+
+    let address = match from {
+        Some(from) => from.parse::<SocketAddr>()
+            .map_err(|_| FerropassError::Io(format!("'{}' is not an address like 192.168.1.20:40123", from)))?,
+        None => discover(code.split('-').next().unwrap_or_default())?,
+    };
+
+    let mut stream = TcpStream::connect_timeout(&address, IO_TIMEOUT)
+        .map_err(|e| FerropassError::Io(format!("Failed to connect to {}: {}", address, e)))?;
+    set_timeouts(&stream)?;
+
+    let keys = handshake(&mut stream, Side::Receiver, &code)?;
+    confirm_as_receiver(&mut stream, &keys)?;
+
+    let payload = decrypt(&keys.cipher, &read_frame(&mut stream, MAX_PAYLOAD)?)?;
+    let payload: Payload = serde_json::from_slice(&payload).map_err(|e| FerropassError::Format(format!("Received a damaged transfer: {}", e)))?;
+    let result = save(&payload, output);
+    let _ = write_frame(&mut stream, &[u8::from(result.is_ok())]);
+
+    let path = result?;
+    println!("Received {} from {}.", path.display(), address.ip());
+    Ok(())
+}
+
+fn save(payload: &Payload, output: Option<&Path>) -> Result<PathBuf, FerropassError> {
+    let decode = |text: &str| general_purpose::STANDARD.decode(text).map_err(|e| FerropassError::Format(format!("Received a damaged transfer: {}", e)));
+    let vault = decode(&payload.vault)?;
+
+    // Only a plain file name is taken from the sender.
+    let path = match output {
+        Some(output) => output.to_path_buf(),
+        None => PathBuf::from(Path::new(&payload.name).file_name()
+            .ok_or_else(|| FerropassError::Io("The sender gave no file name; choose one with --output".to_string()))?),
+    };
+
+    let _vault_lock = FileStorage.lock(&path)?;
+    if FileStorage.exists(&path) {
+        let answer = Cli::prompt_input(&format!("{} already exists. Replace it? It is backed up first. (y/N): ", path.display()))?;
+        if answer.to_lowercase() != "y" {
+            return Err(FerropassError::Io(format!("{} was left as it was", path.display())));
+        }
+        let backup_path = backup::snapshot(&path)?;
+        println!("Backed up {} to {}", path.display(), backup_path.display());
+    }
+
+    FileStorage.write_blob(&path, &vault)?;
+    let signature_path = signing::signature_path(&path);
+    match &payload.signature {
+        Some(signature) => FileStorage.write_blob(&signature_path, &decode(signature)?)?,
+        // A signature left over from the replaced file would only fail.
+        None if signature_path.exists() => {
+            std::fs::remove_file(&signature_path).map_err(|e| FerropassError::Io(format!("Failed to remove {}: {}", signature_path.display(), e)))?;
+        },
+        None => {},
+    }
+
+    Ok(path)
+}
+
+// A short number to find the sender by, then words from the passphrase
+// list, such as "17-gravity-uncle".
+fn generate_code() -> String {
+    let words: Vec<&str> = Language::English.words().into_iter()
+        .filter(|word| word.chars().all(|c| c.is_ascii_lowercase()))
+        .collect();
+
+    let mut parts = vec![OsRng.gen_range(1..100u32).to_string()];
+    for _ in 0..CODE_WORDS {
+        parts.push(words[OsRng.gen_range(0..words.len())].to_string());
+    }
+    parts.join("-")
+}
+
+fn normalize_code(code: &str) -> Result<String, FerropassError> {
+    let code = code.trim().to_lowercase().split_whitespace().collect::<Vec<_>>().join("-");
+    let mut parts = code.split('-');
+    let nameplate_ok = parts.next().is_some_and(|nameplate| !nameplate.is_empty() && nameplate.chars().all(|c| c.is_ascii_digit()));
+    if !nameplate_ok || parts.count() != CODE_WORDS {
+        return Err(FerropassError::Io(format!("'{}' is not a transfer code like 17-gravity-uncle", code)));
+    }
+    Ok(code)
+}
+
+// Answers receivers asking for `nameplate` from a thread that lives as long
+// as the process.
+fn answer_discovery(nameplate: String, port: u16) -> Result<(), FerropassError> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT))
+        .map_err(|e| FerropassError::Io(format!("port {} is in use: {}", DISCOVERY_PORT, e)))?;
+    let question = format!("{} who-has {}", PROTOCOL, nameplate);
+    let answer = format!("{} at {}", PROTOCOL, port);
+
+    thread::spawn(move || {
+        let mut buffer = [0u8; 128];
+        while let Ok((length, peer)) = socket.recv_from(&mut buffer) {
+            if buffer[..length] == *question.as_bytes() {
+                let _ = socket.send_to(answer.as_bytes(), peer);
+            }
+        }
+    });
+    Ok(())
+}
+
+// Asks the whole network, and this machine, for the sender of `nameplate`.
+fn discover(nameplate: &str) -> Result<SocketAddr, FerropassError> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).map_err(|e| FerropassError::Io(format!("Failed to look for the sender: {}", e)))?;
+    socket.set_broadcast(true).map_err(|e| FerropassError::Io(format!("Failed to look for the sender: {}", e)))?;
+    socket.set_read_timeout(Some(Duration::from_secs(1))).map_err(|e| FerropassError::Io(format!("Failed to look for the sender: {}", e)))?;
+
+    let question = format!("{} who-has {}", PROTOCOL, nameplate);
+    let prefix = format!("{} at ", PROTOCOL);
+    println!("Looking for the sender on the local network...");
+
+    let mut buffer = [0u8; 128];
+    for _ in 0..DISCOVERY_ATTEMPTS {
+        for target in [Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST] {
+            let _ = socket.send_to(question.as_bytes(), (target, DISCOVERY_PORT));
+        }
+
+        while let Ok((length, peer)) = socket.recv_from(&mut buffer) {
+            let port = std::str::from_utf8(&buffer[..length]).ok()
+                .and_then(|answer| answer.strip_prefix(&prefix))
+                .and_then(|port| port.parse::<u16>().ok());
+            if let Some(port) = port {
+                return Ok(SocketAddr::new(peer.ip(), port));
+            }
+        }
+    }
+
+    Err(FerropassError::Io("No sender with that code answered; check the code, or give its address with --from".to_string()))
+}
+
+// The address other machines reach this one by. Connecting a UDP socket
+// sends nothing; it only picks the outgoing interface.
+fn local_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9)).ok()?;
+    socket.local_addr().ok().map(|address| address.ip()).filter(|ip| !ip.is_unspecified())
+}
+
+// SPAKE2 over Ristretto255, keyed by the whole code. An eavesdropper learns
+// nothing about the code, and someone in the middle gets a single guess.
+fn handshake(stream: &mut (impl Read + Write), side: Side, code: &str) -> Result<SessionKeys, FerropassError> {
+    let password = Scalar::from_bytes_mod_order_wide(&sha512(&[PROTOCOL.as_bytes(), code.as_bytes()]));
+
+    let mut secret = [0u8; 64];
+    OsRng.fill_bytes(&mut secret);
+    let secret = Scalar::from_bytes_mod_order_wide(&secret);
+    let share = (secret * RISTRETTO_BASEPOINT_POINT + password * side.blinding()).compress();
+
+    // The receiver speaks first.
+    let peer_share = if side == Side::Receiver {
+        write_frame(stream, share.as_bytes())?;
+        read_frame(stream, 32)?
+    } else {
+        let peer_share = read_frame(stream, 32)?;
+        write_frame(stream, share.as_bytes())?;
+        peer_share
+    };
+    let peer_point = CompressedRistretto::from_slice(&peer_share).ok()
+        .and_then(|compressed| compressed.decompress())
+        .ok_or_else(|| FerropassError::Io("The other machine sent an invalid handshake".to_string()))?;
+
+    let shared = (secret * (peer_point - password * side.other().blinding())).compress();
+    let (sender_share, receiver_share) = match side {
+        Side::Sender => (share.to_bytes().to_vec(), peer_share),
+        Side::Receiver => (peer_share, share.to_bytes().to_vec()),
+    };
+    let session = sha512(&[PROTOCOL.as_bytes(), &sender_share, &receiver_share, shared.as_bytes(), password.as_bytes()]);
+
+    let mut cipher = [0u8; 32];
+    cipher.copy_from_slice(&hmac_sha256(&session, b"cipher"));
+    Ok(SessionKeys {
+        cipher,
+        sender_proof: hmac_sha256(&session, b"sender"),
+        receiver_proof: hmac_sha256(&session, b"receiver"),
+    })
+}
+
+// The sender proves it has the code first, then checks the receiver's
+// proof. A receiver with the wrong code hangs up instead of answering.
+fn confirm_as_sender(stream: &mut (impl Read + Write), keys: &SessionKeys) -> Result<bool, FerropassError> {
+    write_frame(stream, &keys.sender_proof)?;
+    let proof = read_frame(stream, 64).ok();
+    Ok(proof.is_some_and(|proof| bool::from(proof.ct_eq(&keys.receiver_proof))))
+}
+
+// The receiver only answers once the sender's proof checks out.
+fn confirm_as_receiver(stream: &mut (impl Read + Write), keys: &SessionKeys) -> Result<(), FerropassError> {
+    if !bool::from(read_frame(stream, 64)?.ct_eq(&keys.sender_proof)) {
+        return Err(FerropassError::Io("The code doesn't match the one on the sending machine; nothing was received".to_string()));
+    }
+    write_frame(stream, &keys.receiver_proof)
+}
+
+fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, FerropassError> {
+    let cipher = Aes256GcmCipher;
+    let mut nonce = vec![0u8; cipher.nonce_len()];
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut sealed = nonce.clone();
+    sealed.extend(cipher.encrypt(key, &nonce, plaintext, PROTOCOL.as_bytes())?);
+    Ok(sealed)
+}
+
+fn decrypt(key: &[u8; 32], sealed: &[u8]) -> Result<Vec<u8>, FerropassError> {
+    let cipher = Aes256GcmCipher;
+    if sealed.len() < cipher.nonce_len() + cipher.tag_len() {
+        return Err(FerropassError::Io("Received a damaged transfer".to_string()));
+    }
+    let (nonce, ciphertext) = sealed.split_at(cipher.nonce_len());
+    cipher.decrypt(key, nonce, ciphertext, PROTOCOL.as_bytes()).map_err(|_| FerropassError::Io("Received a damaged transfer".to_string()))
+}
+
+fn set_timeouts(stream: &TcpStream) -> Result<(), FerropassError> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))
+        .and_then(|()| stream.set_write_timeout(Some(IO_TIMEOUT)))
+        .map_err(|e| FerropassError::Io(format!("Failed to set up the connection: {}", e)))
+}
+
+fn write_frame(stream: &mut impl Write, data: &[u8]) -> Result<(), FerropassError> {
+    let length = u32::try_from(data.len()).map_err(|_| FerropassError::Io("The vault is too large to send".to_string()))?;
+    stream.write_all(&length.to_be_bytes())
+        .and_then(|()| stream.write_all(data))
+        .map_err(|e| FerropassError::Io(format!("The connection failed: {}", e)))
+}
+
+// Frames longer than `max` are refused before anything is read into memory.
+fn read_frame(stream: &mut impl Read, max: u32) -> Result<Vec<u8>, FerropassError> {
+    let mut length = [0u8; 4];
+    stream.read_exact(&mut length).map_err(|e| FerropassError::Io(format!("The connection failed: {}", e)))?;
+    let length = u32::from_be_bytes(length);
+    if length > max {
+        return Err(FerropassError::Io("The other machine sent more than expected".to_string()));
+    }
+
+    let mut data = vec![0u8; length as usize];
+    stream.read_exact(&mut data).map_err(|e| FerropassError::Io(format!("The connection failed: {}", e)))?;
+    Ok(data)
+}
+
+fn sha512(parts: &[&[u8]]) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        // Length-prefixed, so the parts can't run into each other.
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::{self, Receiver, Sender};
+
+    const CODE: &str = "17-gravity-uncle";
+
+    // One end of an in-memory connection; dropping it hangs up.
+    struct Pipe {
+        incoming: Receiver<Vec<u8>>,
+        outgoing: Sender<Vec<u8>>,
+        buffer: Vec<u8>,
+    }
+
+    fn pipe() -> (Pipe, Pipe) {
+        let (to_second, from_first) = mpsc::channel();
+        let (to_first, from_second) = mpsc::channel();
+        (
+            Pipe { incoming: from_second, outgoing: to_second, buffer: Vec::new() },
+            Pipe { incoming: from_first, outgoing: to_first, buffer: Vec::new() },
+        )
+    }
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.buffer.is_empty() {
+                match self.incoming.recv() {
+                    Ok(data) => self.buffer = data,
+                    Err(_) => return Ok(0),
+                }
+            }
+            let length = buf.len().min(self.buffer.len());
+            buf[..length].copy_from_slice(&self.buffer[..length]);
+            self.buffer.drain(..length);
+            Ok(length)
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.outgoing.send(buf.to_vec()).map_err(|_| std::io::ErrorKind::BrokenPipe)?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Runs the sender's side of the handshake and confirmation on a thread
+    // of its own and the receiver's here, each side's keys going through its
+    // `tamper_*` first; returns what each side made of it.
+    fn transfer(
+        sender_code: &str,
+        receiver_code: &str,
+        tamper_sender: fn(&mut SessionKeys),
+        tamper_receiver: fn(&mut SessionKeys),
+    ) -> (Result<bool, FerropassError>, Result<SessionKeys, FerropassError>) {
+        let (mut sender_end, mut receiver_end) = pipe();
+        let sender_code = sender_code.to_string();
+        let sender = thread::spawn(move || {
+            let mut keys = handshake(&mut sender_end, Side::Sender, &sender_code)?;
+            tamper_sender(&mut keys);
+            confirm_as_sender(&mut sender_end, &keys)
+        });
+
+        let received = handshake(&mut receiver_end, Side::Receiver, receiver_code).and_then(|mut keys| {
+            tamper_receiver(&mut keys);
+            confirm_as_receiver(&mut receiver_end, &keys).map(|()| keys)
+        });
+        drop(receiver_end);
+        (sender.join().unwrap(), received)
+    }
+
+    fn untouched(_: &mut SessionKeys) {}
+
+    fn flip_sender_proof(keys: &mut SessionKeys) {
+        keys.sender_proof[0] ^= 1;
+    }
+
+    fn flip_receiver_proof(keys: &mut SessionKeys) {
+        keys.receiver_proof[0] ^= 1;
+    }
+
+    #[test]
+    fn same_code_agrees_on_a_key() {
+        let (mut sender_end, mut receiver_end) = pipe();
+        let sender = thread::spawn(move || {
+            let keys = handshake(&mut sender_end, Side::Sender, CODE).unwrap();
+            assert!(confirm_as_sender(&mut sender_end, &keys).unwrap());
+            write_frame(&mut sender_end, &encrypt(&keys.cipher, b"the vault").unwrap()).unwrap();
+            keys.cipher
+        });
+
+        let keys = handshake(&mut receiver_end, Side::Receiver, CODE).unwrap();
+        confirm_as_receiver(&mut receiver_end, &keys).unwrap();
+        let sealed = read_frame(&mut receiver_end, MAX_PAYLOAD).unwrap();
+        assert_eq!(decrypt(&keys.cipher, &sealed).unwrap(), b"the vault");
+        assert_eq!(sender.join().unwrap(), keys.cipher);
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&keys.cipher, &tampered).is_err());
+        assert!(decrypt(&keys.cipher, &sealed[..8]).is_err());
+    }
+
+    #[test]
+    fn wrong_code_fails_on_both_sides() {
+        let (sent, received) = transfer(CODE, "17-gravity-uncles", untouched, untouched);
+
+        assert!(!sent.unwrap());
+        assert!(received.is_err());
+    }
+
+    #[test]
+    fn tampered_sender_proof_is_refused() {
+        let (sent, received) = transfer(CODE, CODE, flip_sender_proof, untouched);
+
+        assert!(received.is_err());
+        assert!(!sent.unwrap());
+    }
+
+    #[test]
+    fn tampered_receiver_proof_is_refused() {
+        let (sent, received) = transfer(CODE, CODE, untouched, flip_receiver_proof);
+
+        assert!(received.is_ok());
+        assert!(!sent.unwrap());
+    }
+
+    #[test]
+    fn invalid_share_is_refused() {
+        let (mut sender_end, mut receiver_end) = pipe();
+        write_frame(&mut receiver_end, &[0xff; 32]).unwrap();
+
+        assert!(handshake(&mut sender_end, Side::Sender, CODE).is_err());
+    }
+
+    #[test]
+    fn oversized_frames_are_refused() {
+        let (mut first, mut second) = pipe();
+        write_frame(&mut first, &[0u8; 65]).unwrap();
+
+        assert!(read_frame(&mut second, 64).is_err());
+    }
+
+    #[test]
+    fn codes_are_normalized() {
+        assert_eq!(normalize_code(" 17 Gravity  uncle ").unwrap(), CODE);
+        assert!(normalize_code("gravity-uncle").is_err());
+        assert!(normalize_code("17-gravity").is_err());
+        assert_eq!(normalize_code(&generate_code()).unwrap().split('-').count(), CODE_WORDS + 1);
+    }
+}
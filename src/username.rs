@@ -0,0 +1,123 @@
+use ferropass_core::entropy;
+use ferropass_core::error::FerropassError;
+use ferropass_core::password::Language;
+
+use rand::{CryptoRng, Rng};
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+
+const ALIAS_TAG_CHARS: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+const ALIAS_TAG_LENGTH: usize = 8;
+
+// A handle such as "glitchyotter47": two words from the English list and two
+// digits, for sites where the username shouldn't link back to you.
+pub fn generate_handle() -> Result<String, FerropassError> {
+    entropy::ensure()?;
+    generate_handle_with(&mut OsRng)
+}
+
+pub fn generate_handle_with<R: Rng + CryptoRng>(rng: &mut R) -> Result<String, FerropassError> {
+    let words: Vec<String> = Language::English.words()
+        .into_iter()
+        .map(|word| word.chars().filter(char::is_ascii_alphabetic).collect())
+        .filter(|word: &String| !word.is_empty())
+        .collect();
+
+    let mut handle = String::new();
+    for _ in 0..2 {
+        let word = words.choose(rng).ok_or_else(|| FerropassError::Validation("The word list for handles is empty".to_string()))?;
+        handle.push_str(word);
+    }
+    handle.push_str(&format!("{:02}", rng.gen_range(0..100)));
+
+    Ok(handle)
+}
+
+// A plus-addressed alias of `base`, such as "me+k3f9x2ab@example.com", which
+// arrives in the same mailbox but is unique to one site. Leaking or selling it
+// shows which site did.
+pub fn generate_alias(base: &str) -> Result<String, FerropassError> {
+    entropy::ensure()?;
+    generate_alias_with(base, &mut OsRng)
+}
+
+pub fn generate_alias_with<R: Rng + CryptoRng>(base: &str, rng: &mut R) -> Result<String, FerropassError> {
+    let Some((local, domain)) = base.rsplit_once('@').filter(|(local, domain)| !local.is_empty() && !domain.is_empty()) else {
+        return Err(FerropassError::Validation(format!("alias_base '{}' in the config is not an email address", base)));
+    };
+
+    let tag = (0..ALIAS_TAG_LENGTH)
+        .map(|_| ALIAS_TAG_CHARS.choose(rng).map(|&c| char::from(c)))
+        .collect::<Option<String>>()
+        .ok_or_else(|| FerropassError::Validation("There are no characters to draw an alias tag from".to_string()))?;
+
+    // An address that already has a tag keeps it in front of the new one.
+    Ok(format!("{}+{}@{}", local, tag, domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn handles_are_two_words_and_two_digits() {
+        let words: HashSet<String> = Language::English.words()
+            .into_iter()
+            .map(|word| word.chars().filter(char::is_ascii_alphabetic).collect())
+            .collect();
+
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..200 {
+            let handle = generate_handle_with(&mut rng).unwrap();
+            let (letters, number) = handle.split_at(handle.len() - 2);
+            assert!(number.bytes().all(|c| c.is_ascii_digit()), "{}", handle);
+            assert!(letters.bytes().all(|c| c.is_ascii_lowercase()), "{}", handle);
+            assert!(
+                (1..letters.len()).any(|split| words.contains(&letters[..split]) && words.contains(&letters[split..])),
+                "{} isn't two words", handle,
+            );
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_handle_and_alias() {
+        for seed in 0..10 {
+            assert_eq!(generate_handle_with(&mut StdRng::seed_from_u64(seed)).unwrap(), generate_handle_with(&mut StdRng::seed_from_u64(seed)).unwrap());
+            assert_eq!(
+                generate_alias_with("me@example.com", &mut StdRng::seed_from_u64(seed)).unwrap(),
+                generate_alias_with("me@example.com", &mut StdRng::seed_from_u64(seed)).unwrap(),
+            );
+        }
+
+        let handles: HashSet<String> = (0..10).map(|seed| generate_handle_with(&mut StdRng::seed_from_u64(seed)).unwrap()).collect();
+        assert_eq!(handles.len(), 10);
+    }
+
+    #[test]
+    fn aliases_tag_the_local_part() {
+        let mut rng = StdRng::seed_from_u64(2);
+
+        let alias = generate_alias_with("me@example.com", &mut rng).unwrap();
+        let (local, domain) = alias.split_once('@').unwrap();
+        assert_eq!(domain, "example.com");
+        let tag = local.strip_prefix("me+").unwrap();
+        assert_eq!(tag.len(), ALIAS_TAG_LENGTH);
+        assert!(tag.bytes().all(|c| ALIAS_TAG_CHARS.contains(&c)));
+
+        // An existing tag is kept, and only the last @ splits the address.
+        let alias = generate_alias_with("\"a@b\"+news@mail.example.com", &mut rng).unwrap();
+        assert!(alias.starts_with("\"a@b\"+news+") && alias.ends_with("@mail.example.com"));
+    }
+
+    #[test]
+    fn aliases_need_an_email_address() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for base in ["", "me", "@example.com", "me@", "@"] {
+            assert!(matches!(generate_alias_with(base, &mut rng), Err(FerropassError::Validation(_))), "{:?}", base);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::cli::Cli;
+use crate::fingerprint;
+
+use ferropass_core::encryption::{key_fingerprint, EncryptedData, CURRENT_FORMAT_VERSION};
+use ferropass_core::error::FerropassError;
+use ferropass_core::models::Database;
+use ferropass_core::signing;
+use ferropass_core::storage::{FileStorage, VaultStorage};
+use ferropass_core::throttle;
+
+use std::path::Path;
+
+pub fn run(filepath: &Path, passkey_file: Option<&Path>) -> Result<(), FerropassError> {
+    println!("Verifying {}", filepath.display());
+
+    let encrypted_data = check(filepath, "Header is readable and well-formed", EncryptedData::read(&FileStorage, filepath))?;
+    println!("  Format version {}", encrypted_data.version());
+    println!("  Revision {}", encrypted_data.revision());
+    check(filepath, "Key derivation parameters are supported", encrypted_data.kdf_params())?;
+    check(filepath, "Cipher is supported", encrypted_data.cipher())?;
+    check(filepath, "Salt is valid", encrypted_data.salt())?;
+    check(filepath, "Nonce has the expected length", encrypted_data.nonce())?;
+    check(filepath, "Ciphertext decodes and includes an authentication tag", encrypted_data.ciphertext())?;
+
+    // A bad signature doesn't stop the remaining checks, so a file that was
+    // only replaced or re-saved elsewhere can still be shown to decrypt.
+    let signature_matches = match encrypted_data.get_signing_key() {
+        Some(public_key) => {
+            println!("  Signed with key {}", signing::short_id(public_key));
+            let file_content = FileStorage.read_text(filepath)?;
+            match encrypted_data.check_signature(&FileStorage, filepath, &file_content) {
+                Ok(()) => {
+                    println!("  [ OK ] Signature matches the file");
+                    true
+                },
+                Err(e) => {
+                    println!("  [FAIL] Signature matches the file: {}", e);
+                    false
+                }
+            }
+        },
+        None => {
+            println!("  Not signed");
+            true
+        }
+    };
+
+    let passkey = Cli::read_passkey(passkey_file, "Enter database passkey: ")?;
+
+    let derived_key = check(filepath, "Key derivation succeeds", encrypted_data.derive_key(&passkey))?;
+    throttle::wait(filepath)?;
+    let unwrapped = encrypted_data.unwrap_key(&derived_key);
+    let authenticated = unwrapped.clone().and_then(|key| encrypted_data.decrypt_base(&key));
+    throttle::record(filepath, &authenticated)?;
+
+    let key = check(filepath, "Data key unwraps with the passkey", unwrapped)?;
+    check(filepath, "Ciphertext authenticates with the passkey", authenticated)?;
+
+    println!("  Journal holds {} record(s)", encrypted_data.journal_len());
+    check(filepath, "Journal records authenticate in sequence", encrypted_data.decrypt_journal(&key))?;
+
+    let database: Database = check(filepath, "Decrypted contents form a valid database", encrypted_data.open(&key))?;
+
+    let entry_key = encrypted_data.entry_key(&key);
+    let passwords = database.get_accounts().iter().try_for_each(|account| {
+        entry_key.open(account.get_password())
+            .and_then(|_| account.get_totp().map_or(Ok(()), |totp| entry_key.open(totp).map(|_| ())))
+            .and_then(|_| account.get_ssh_key().map_or(Ok(()), |ssh_key| entry_key.open(ssh_key).map(|_| ())))
+            .map_err(|e| e.context(format!("account {}", account.get_id())))
+    });
+    check(filepath, "Each password, TOTP secret and SSH key opens on its own", passwords)?;
+
+    println!("  Fingerprint {}", key_fingerprint(&key));
+    if encrypted_data.version() == CURRENT_FORMAT_VERSION && let Some(previous) = fingerprint::check(filepath, &key_fingerprint(&key))? {
+        println!("  Warning: this machine last saw {} at this path, a different vault", previous);
+    }
+    if !signature_matches {
+        return Err(FerropassError::Format(format!("{} decrypts, but its signature doesn't match", filepath.display())));
+    }
+    println!("{} is intact ({} accounts).", filepath.display(), database.get_accounts().len());
+    Ok(())
+}
+
+fn check<T>(filepath: &Path, label: &str, result: Result<T, FerropassError>) -> Result<T, FerropassError> {
+    match result {
+        Ok(value) => {
+            println!("  [ OK ] {}", label);
+            Ok(value)
+        },
+        Err(e) => {
+            println!("  [FAIL] {}: {}", label, e);
+            Err(FerropassError::Format(format!("{} failed verification", filepath.display())))
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use ferropass_core::storage::FileStorage;
+use ferropass_core::vault::Vault;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::sync::Arc;
+
+const PASSKEY: &str = "correct horse battery staple";
+
+// A browser export with two identical rows and one that matches the entry
+// already in the vault.
+const CSV: &str = "name,url,username,password,note\n\
+    Forum,https://forum.example.com,bob,hunter2,\n\
+    Forum,https://forum.example.com,bob,hunter2,\n\
+    Mail,,alice,\"new, quoted\npassword\",\n";
+
+// A vault with one entry, its passkey file and the export, in a directory of
+// their own that also stands in for the config and state directories.
+fn vault(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ferropass-import-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("vault.fp");
+    let mut vault = Vault::create(Arc::new(FileStorage), &path, PASSKEY).unwrap();
+    vault.add_entry("alice", Some("Mail"), "s3cret-Passw0rd-for-alice").unwrap();
+    vault.save().unwrap();
+    fs::write(dir.join("passkey"), PASSKEY).unwrap();
+    fs::write(dir.join("export.csv"), CSV).unwrap();
+    path
+}
+
+// Runs the import, typing `input` at its prompts.
+fn run_import(path: &Path, on_conflict: &str, input: &str) -> Output {
+    let dir = path.parent().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ferropass"))
+        .env("XDG_CONFIG_HOME", dir)
+        .env("XDG_STATE_HOME", dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .arg("import")
+        .arg(path)
+        .arg(dir.join("export.csv"))
+        .args(["--on-conflict", on_conflict, "--passkey-file"])
+        .arg(dir.join("passkey"))
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn import(path: &Path, on_conflict: &str, input: &str) {
+    let output = run_import(path, on_conflict, input);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+fn passwords(path: &Path, username: &str) -> Vec<String> {
+    let vault = Vault::open(Arc::new(FileStorage), path, PASSKEY).unwrap();
+    vault.entries().iter()
+        .filter(|account| account.get_username_or_email() == username)
+        .map(|account| vault.password(account).unwrap())
+        .collect()
+}
+
+#[test]
+fn duplicates_are_skipped() {
+    let path = vault("skip");
+
+    import(&path, "skip", "");
+    assert_eq!(passwords(&path, "bob"), ["hunter2"]);
+    assert_eq!(passwords(&path, "alice"), ["s3cret-Passw0rd-for-alice"]);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn duplicates_are_kept_when_asked() {
+    let path = vault("duplicate");
+
+    import(&path, "duplicate", "");
+    assert_eq!(passwords(&path, "bob"), ["hunter2", "hunter2"]);
+    assert_eq!(passwords(&path, "alice"), ["s3cret-Passw0rd-for-alice", "new, quoted\npassword"]);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn matching_entries_are_overwritten_when_asked() {
+    let path = vault("overwrite");
+
+    import(&path, "overwrite", "vault\n");
+    assert_eq!(passwords(&path, "bob"), ["hunter2"]);
+    assert_eq!(passwords(&path, "alice"), ["new, quoted\npassword"]);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn overwriting_merge_needs_the_vault_name() {
+    let path = vault("overwrite-refused");
+
+    let output = run_import(&path, "overwrite", "personal\n");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Type the vault name 'vault' to continue"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Import cancelled"), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(passwords(&path, "alice"), ["s3cret-Passw0rd-for-alice"]);
+    assert!(passwords(&path, "bob").is_empty());
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
@@ -0,0 +1,151 @@
+use ferropass_core::storage::FileStorage;
+use ferropass_core::vault::Vault;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::Arc;
+
+const PASSKEY: &str = "correct horse battery staple";
+
+// A vault with one entry and its passkey file, in a directory of its own
+// that also stands in for the config and state directories.
+fn vault(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ferropass-summary-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("vault.fp");
+    let mut vault = Vault::create(Arc::new(FileStorage), &path, PASSKEY).unwrap();
+    vault.add_entry("alice", None, "s3cret-Passw0rd-for-alice").unwrap();
+    vault.save().unwrap();
+    fs::write(dir.join("passkey"), PASSKEY).unwrap();
+    path
+}
+
+// ferropass with its config and state next to the vault, and any agent's
+// socket there too.
+fn ferropass(path: &Path) -> Command {
+    let dir = path.parent().unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_ferropass"));
+    command.env("XDG_CONFIG_HOME", dir)
+        .env("XDG_STATE_HOME", dir)
+        .env_remove("XDG_RUNTIME_DIR")
+        .stdin(Stdio::null());
+    command
+}
+
+fn summary(path: &Path, args: &[&str]) -> Output {
+    ferropass(path)
+        .arg("summary")
+        .arg(path)
+        .arg("--passkey-file")
+        .arg(path.with_file_name("passkey"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+// Stops the agent however the test ends.
+#[cfg(unix)]
+struct Agent<'a>(&'a Path);
+
+#[cfg(unix)]
+impl Drop for Agent<'_> {
+    fn drop(&mut self) {
+        let _ = ferropass(self.0).args(["agent", "--stop"]).output();
+    }
+}
+
+#[test]
+fn summary_covers_a_very_long_period() {
+    let path = vault("long");
+
+    let output = summary(&path, &["--since", "999999999999d", "--max-age", "999999999999d"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let digest = String::from_utf8_lossy(&output.stdout);
+    assert!(digest.contains("Added (1):"));
+    assert!(digest.contains("max password age 999999999999 days"));
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn periods_past_the_end_of_time_are_refused() {
+    let path = vault("overflow");
+
+    for args in [["--since", "213503982334600d"], ["--max-age", "213503982334600d"]] {
+        let output = summary(&path, &args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1), "{}", stderr);
+        assert!(stderr.contains("is too long"), "{}", stderr);
+    }
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn audit_changes_are_reported_against_the_last_summary() {
+    let path = vault("audit");
+
+    let output = summary(&path, &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let digest = String::from_utf8_lossy(&output.stdout);
+    assert!(digest.contains("Audit of the whole vault (the next summary reports what changed):\n  Weak passwords: 0\n  Reused passwords: 0\n"), "{}", digest);
+
+    let mut vault = Vault::open(Arc::new(FileStorage), &path, PASSKEY).unwrap();
+    vault.add_entry("bob", None, "hunter2").unwrap();
+    vault.add_entry("carol", None, "s3cret-Passw0rd-for-alice").unwrap();
+    vault.save().unwrap();
+    drop(vault);
+
+    let output = summary(&path, &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let digest = String::from_utf8_lossy(&output.stdout);
+    assert!(digest.contains("Audit changes since the last summary ("), "{}", digest);
+    assert!(digest.contains("  Weak passwords: 1 (was 0; 1 new, 0 resolved)\n  "), "{}", digest);
+    assert!(digest.contains("  Reused passwords: 2 (was 0; 2 new, 0 resolved)\n"), "{}", digest);
+
+    let output = summary(&path, &[]);
+    let digest = String::from_utf8_lossy(&output.stdout);
+    assert!(digest.contains("  Weak passwords: 1 (was 1; 0 new, 0 resolved)\n"), "{}", digest);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn line_breaks_never_reach_the_mail_headers() {
+    let path = vault("mail");
+    let renamed = path.with_file_name("vault\r\nBcc: eve@example.com.fp");
+    fs::rename(&path, &renamed).unwrap();
+    let message = path.with_file_name("message");
+    let sendmail = format!("tee {}", message.display());
+
+    let output = summary(&renamed, &["--mail", "alice@example.com\nBcc: eve@example.com", "--sendmail", &sendmail]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("line breaks"), "{}", stderr);
+    assert!(!message.exists());
+
+    let output = summary(&renamed, &["--mail", "alice@example.com", "--sendmail", &sendmail]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let sent = fs::read_to_string(&message).unwrap();
+    let (headers, _) = sent.split_once("\n\n").unwrap();
+    assert_eq!(headers, "To: alice@example.com\nSubject: FerroPass summary for vault  Bcc: eve@example.com.fp");
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn agent_builds_the_summary_without_a_passkey() {
+    let path = vault("agent");
+
+    let started = ferropass(&path).arg("agent").output().unwrap();
+    assert!(started.status.success(), "{}", String::from_utf8_lossy(&started.stderr));
+    let agent = Agent(&path);
+    let unlocked = ferropass(&path).arg("unlock").arg(&path).arg("--passkey-file").arg(path.with_file_name("passkey")).output().unwrap();
+    assert!(unlocked.status.success(), "{}", String::from_utf8_lossy(&unlocked.stderr));
+
+    let output = ferropass(&path).arg("summary").arg(&path).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Added (1):"));
+    drop(agent);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}